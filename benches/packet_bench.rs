@@ -0,0 +1,78 @@
+//! 数据包热路径的基准测试
+//! 工作线程统计：所有工作线程逐包更新共享的StatsCounter，与线程本地StatsCounter累加后定期合并两种方式
+//!
+//! 运行：cargo bench --bench packet_bench
+
+use std::sync::Arc;
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use dns_spider::core::stats::StatsCounter;
+
+/// 每个工作线程处理的包数
+const PACKETS_PER_WORKER: u64 = 100_000;
+/// 线程本地统计的合并间隔（包数）
+const MERGE_EVERY: u64 = 1_000;
+
+const KEYS: [&str; 4] = ["packet.processed", "dns.udp.parsed", "dns.udp.bytes", "dns.type.A"];
+
+fn record(stats: &StatsCounter, i: u64) {
+    for key in KEYS {
+        stats.add(key, i & 0xFF);
+    }
+}
+
+/// 每个包都更新共享计数器
+fn shared_counter(workers: usize) {
+    let global = Arc::new(StatsCounter::new());
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let global = Arc::clone(&global);
+            thread::spawn(move || {
+                for i in 0..PACKETS_PER_WORKER {
+                    record(&global, i);
+                }
+            })
+        })
+        .collect();
+    handles.into_iter().for_each(|h| h.join().unwrap());
+}
+
+/// 线程本地累加，定期合并到全局
+fn thread_local_merge(workers: usize) {
+    let global = Arc::new(StatsCounter::new());
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let global = Arc::clone(&global);
+            thread::spawn(move || {
+                let local = StatsCounter::new();
+                for i in 0..PACKETS_PER_WORKER {
+                    record(&local, i);
+                    if i % MERGE_EVERY == MERGE_EVERY - 1 {
+                        global.merge(&local.take());
+                    }
+                }
+                global.merge(&local);
+            })
+        })
+        .collect();
+    handles.into_iter().for_each(|h| h.join().unwrap());
+}
+
+fn worker_stats(c: &mut Criterion) {
+    let mut group = c.benchmark_group("worker_stats");
+    for workers in [1usize, 4, 8] {
+        group.throughput(Throughput::Elements(PACKETS_PER_WORKER * workers as u64));
+        group.bench_with_input(BenchmarkId::new("shared_counter", workers), &workers, |b, &w| {
+            b.iter(|| shared_counter(w))
+        });
+        group.bench_with_input(BenchmarkId::new("thread_local_merge", workers), &workers, |b, &w| {
+            b.iter(|| thread_local_merge(w))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, worker_stats);
+criterion_main!(benches);
@@ -1,9 +1,9 @@
 //! DPDK捕获模块实现
 //! 基于DPDK的高性能数据包捕获
 
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use super::{CaptureConfig, CaptureStats, PacketCapture};
+use super::{CaptureConfig, CaptureStats, CapturedPacket, LinkType, PacketCapture};
 use crate::core::dpdk::{DpdkConfig, DpdkInstance};
 use crate::core::stats::StatsCounter;
 use crate::error;
@@ -50,7 +50,7 @@ pub struct DpdkCapture {
     /// DPDK实例
     dpdk: Option<DpdkInstance>,
     /// 统计计数器
-    stats: Arc<Mutex<StatsCounter>>,
+    stats: Arc<StatsCounter>,
     /// 是否正在捕获
     is_capturing: bool,
     /// 当前使用的端口ID
@@ -66,7 +66,7 @@ impl DpdkCapture {
     pub fn new(
         config: CaptureConfig,
         dpdk_config: DpdkCaptureConfig,
-        stats: Arc<Mutex<StatsCounter>>,
+        stats: Arc<StatsCounter>,
     ) -> Self {
         DpdkCapture {
             config,
@@ -132,7 +132,7 @@ impl PacketCapture for DpdkCapture {
         self.is_capturing = false;
     }
 
-    fn receive_packets(&mut self, max_packets: usize) -> Vec<Vec<u8>> {
+    fn receive_packets(&mut self, max_packets: usize) -> Vec<CapturedPacket> {
         if !self.is_capturing || self.dpdk.is_none() {
             return Vec::new();
         }
@@ -156,7 +156,16 @@ impl PacketCapture for DpdkCapture {
             self.capture_stats.rx_bytes += packet.len() as u64;
         }
 
+        let source: Arc<str> = Arc::from(format!("dpdk:{}", self.current_port));
         packets
+            .into_iter()
+            .map(|data| CapturedPacket {
+                data,
+                source: Arc::clone(&source),
+                timestamp: None,
+                link_type: LinkType::Ethernet,
+            })
+            .collect()
     }
 
     fn send_packets(&mut self, packets: &[Vec<u8>]) -> usize {
@@ -0,0 +1,226 @@
+//! 离线文件捕获模块实现
+//! 从保存的pcap/pcapng文件读取数据包，用于测试和取证回放
+
+use std::sync::Arc;
+
+use super::{CaptureConfig, CaptureStats, CapturedPacket, LinkType, PacketCapture};
+use crate::core::stats::StatsCounter;
+
+#[cfg(feature = "pcap")]
+use super::pcap::timeval_micros;
+#[cfg(feature = "pcap")]
+use pcap::{Capture, Offline};
+
+/// 离线文件捕获实现，`CaptureConfig::interface`为文件路径
+pub struct FileCapture {
+    /// 捕获配置
+    config: CaptureConfig,
+    /// pcap文件读取器
+    #[cfg(feature = "pcap")]
+    capture: Option<Capture<Offline>>,
+    /// 统计计数器
+    stats: Arc<StatsCounter>,
+    /// 是否正在捕获
+    is_capturing: bool,
+    /// 是否已读到文件末尾
+    finished: bool,
+    /// 文件的链路层类型
+    link_type: LinkType,
+    /// 捕获统计信息
+    capture_stats: CaptureStats,
+    /// 数据包来源标识
+    source: Arc<str>,
+}
+
+impl FileCapture {
+    /// 创建新的离线文件捕获实例
+    pub fn new(config: CaptureConfig, stats: Arc<StatsCounter>) -> Self {
+        let source = Arc::from(config.interface.as_str());
+        FileCapture {
+            config,
+            #[cfg(feature = "pcap")]
+            capture: None,
+            stats,
+            is_capturing: false,
+            finished: false,
+            link_type: LinkType::Ethernet,
+            capture_stats: CaptureStats::default(),
+            source,
+        }
+    }
+}
+
+impl PacketCapture for FileCapture {
+    fn initialize(&mut self) -> crate::error::Result<()> {
+        #[cfg(feature = "pcap")]
+        {
+            let mut capture = Capture::from_file(&self.config.interface).map_err(|e| {
+                crate::error::Error::Capture(format!(
+                    "打开抓包文件失败 {}: {}",
+                    self.config.interface, e
+                ))
+            })?;
+
+            let filter = self.config.effective_filter();
+            if !filter.is_empty() {
+                capture.filter(&filter, true).map_err(|e| {
+                    crate::error::Error::Capture(format!("设置过滤器失败: {}", e))
+                })?;
+            }
+
+            self.link_type = LinkType::from_dlt(capture.get_datalink().0);
+            self.capture = Some(capture);
+            self.finished = false;
+            Ok(())
+        }
+
+        #[cfg(not(feature = "pcap"))]
+        {
+            Err(crate::error::Error::Capture(
+                "libpcap功能未启用，请在Cargo.toml中启用pcap特性".to_string(),
+            ))
+        }
+    }
+
+    fn start_capture(&mut self) -> crate::error::Result<()> {
+        #[cfg(feature = "pcap")]
+        {
+            if self.capture.is_none() {
+                return Err(crate::error::Error::Capture("捕获器未初始化".to_string()));
+            }
+
+            self.is_capturing = true;
+            Ok(())
+        }
+
+        #[cfg(not(feature = "pcap"))]
+        {
+            Err(crate::error::Error::Capture(
+                "libpcap功能未启用".to_string(),
+            ))
+        }
+    }
+
+    fn stop_capture(&mut self) {
+        self.is_capturing = false;
+    }
+
+    fn receive_packets(&mut self, max_packets: usize) -> Vec<CapturedPacket> {
+        let mut packets = Vec::new();
+
+        #[cfg(feature = "pcap")]
+        {
+            if !self.is_capturing || self.finished {
+                return packets;
+            }
+            let capture = match self.capture.as_mut() {
+                Some(capture) => capture,
+                None => return packets,
+            };
+
+            for _ in 0..max_packets {
+                match capture.next_packet() {
+                    Ok(packet) => {
+                        let data = packet.data.to_vec();
+                        self.capture_stats.rx_packets += 1;
+                        self.capture_stats.rx_bytes += data.len() as u64;
+                        packets.push(CapturedPacket {
+                            data,
+                            source: Arc::clone(&self.source),
+                            timestamp: Some(timeval_micros(
+                                packet.header.ts.tv_sec as i64,
+                                packet.header.ts.tv_usec as i64,
+                            )),
+                            link_type: self.link_type,
+                        });
+                    }
+                    Err(pcap::Error::NoMorePackets) => {
+                        self.finished = true;
+                        break;
+                    }
+                    Err(e) => {
+                        // 文件损坏时同样视为结束，避免反复读取
+                        eprintln!("Failed to read capture file {}: {}", self.config.interface, e);
+                        self.finished = true;
+                        break;
+                    }
+                }
+            }
+
+            self.stats.add("file.rx_packets", packets.len() as u64);
+        }
+
+        packets
+    }
+
+    fn send_packets(&mut self, _packets: &[Vec<u8>]) -> usize {
+        // 离线文件不支持发送
+        0
+    }
+
+    fn get_stats(&self) -> CaptureStats {
+        self.capture_stats.clone()
+    }
+
+    fn shutdown(&mut self) {
+        #[cfg(feature = "pcap")]
+        {
+            self.capture = None;
+        }
+
+        self.is_capturing = false;
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(all(test, feature = "pcap"))]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// 写入只含一个数据包的pcap文件
+    fn write_pcap(path: &std::path::Path, linktype: u32, packet: &[u8]) {
+        let mut file = std::fs::File::create(path).unwrap();
+        file.write_all(&0xA1B2_C3D4u32.to_le_bytes()).unwrap();
+        file.write_all(&2u16.to_le_bytes()).unwrap();
+        file.write_all(&4u16.to_le_bytes()).unwrap();
+        file.write_all(&[0; 8]).unwrap();
+        file.write_all(&65535u32.to_le_bytes()).unwrap();
+        file.write_all(&linktype.to_le_bytes()).unwrap();
+        file.write_all(&1_700_000_000u32.to_le_bytes()).unwrap();
+        file.write_all(&42u32.to_le_bytes()).unwrap();
+        file.write_all(&(packet.len() as u32).to_le_bytes()).unwrap();
+        file.write_all(&(packet.len() as u32).to_le_bytes()).unwrap();
+        file.write_all(packet).unwrap();
+    }
+
+    #[test]
+    fn test_read_until_eof() {
+        let path = std::env::temp_dir().join(format!("dns_spider_file_capture_{}.pcap", std::process::id()));
+        // LINKTYPE_RAW：数据包直接以IP头开始
+        write_pcap(&path, 101, &[0x45; 28]);
+
+        let config = CaptureConfig {
+            mode: super::super::CaptureMode::File,
+            interface: path.to_str().unwrap().to_string(),
+            filter: String::new(),
+            ..CaptureConfig::default()
+        };
+        let mut capture = FileCapture::new(config, Arc::new(StatsCounter::new()));
+        capture.initialize().unwrap();
+        capture.start_capture().unwrap();
+
+        let packets = capture.receive_packets(10);
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data, vec![0x45; 28]);
+        assert_eq!(packets[0].timestamp, Some(1_700_000_000_000_042));
+        assert_eq!(packets[0].link_type, LinkType::RawIp);
+        assert!(capture.is_finished());
+        assert!(capture.receive_packets(10).is_empty());
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
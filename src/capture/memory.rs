@@ -0,0 +1,99 @@
+//! 内存捕获模块实现
+//! 从预先准备好的数据包队列中读取，用于测试和回放
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use super::{CaptureStats, CapturedPacket, LinkType, PacketCapture};
+
+/// 内存捕获实现
+pub struct MemoryCapture {
+    /// 待读取的数据包
+    packets: VecDeque<Vec<u8>>,
+    /// 数据包来源标识
+    source: Arc<str>,
+    /// 是否正在捕获
+    is_capturing: bool,
+    /// 捕获统计信息
+    capture_stats: CaptureStats,
+}
+
+impl MemoryCapture {
+    /// 创建新的内存捕获实例
+    pub fn new(source: &str, packets: Vec<Vec<u8>>) -> Self {
+        MemoryCapture {
+            packets: packets.into(),
+            source: Arc::from(source),
+            is_capturing: false,
+            capture_stats: CaptureStats::default(),
+        }
+    }
+
+    /// 追加待读取的数据包
+    pub fn push(&mut self, packet: Vec<u8>) {
+        self.packets.push_back(packet);
+    }
+
+    /// 剩余未读取的数据包数量
+    pub fn remaining(&self) -> usize {
+        self.packets.len()
+    }
+}
+
+impl PacketCapture for MemoryCapture {
+    fn initialize(&mut self) -> crate::error::Result<()> {
+        Ok(())
+    }
+
+    fn start_capture(&mut self) -> crate::error::Result<()> {
+        self.is_capturing = true;
+        Ok(())
+    }
+
+    fn stop_capture(&mut self) {
+        self.is_capturing = false;
+    }
+
+    fn receive_packets(&mut self, max_packets: usize) -> Vec<CapturedPacket> {
+        let mut packets = Vec::new();
+
+        if !self.is_capturing {
+            return packets;
+        }
+
+        while packets.len() < max_packets {
+            match self.packets.pop_front() {
+                Some(data) => {
+                    self.capture_stats.rx_packets += 1;
+                    self.capture_stats.rx_bytes += data.len() as u64;
+                    packets.push(CapturedPacket {
+                        data,
+                        source: Arc::clone(&self.source),
+                        timestamp: None,
+                        link_type: LinkType::Ethernet,
+                    });
+                }
+                None => break,
+            }
+        }
+
+        packets
+    }
+
+    fn send_packets(&mut self, packets: &[Vec<u8>]) -> usize {
+        self.capture_stats.tx_packets += packets.len() as u64;
+        for packet in packets {
+            self.capture_stats.tx_bytes += packet.len() as u64;
+        }
+        packets.len()
+    }
+
+    fn get_stats(&self) -> CaptureStats {
+        self.capture_stats.clone()
+    }
+
+    fn shutdown(&mut self) {
+        self.packets.clear();
+        self.is_capturing = false;
+    }
+}
@@ -2,14 +2,22 @@
 //! 提供统一的数据包捕获接口，支持多种捕获方式
 
 use std::fmt;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
 use crate::core::stats::StatsCounter;
+use crate::protocols::detect::ProtocolDetector;
 
 pub mod dpdk;
+pub mod file;
+pub mod memory;
+pub mod multi;
 pub mod pcap;
 pub mod xdp;
 
+pub use file::FileCapture;
+pub use memory::MemoryCapture;
+pub use multi::MultiCapture;
+
 /// 捕获方式枚举
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CaptureMode {
@@ -19,6 +27,8 @@ pub enum CaptureMode {
     Pcap,
     /// 使用XDP捕获
     Xdp,
+    /// 读取pcap/pcapng文件离线回放
+    File,
 }
 
 impl fmt::Display for CaptureMode {
@@ -27,6 +37,7 @@ impl fmt::Display for CaptureMode {
             CaptureMode::Dpdk => write!(f, "dpdk"),
             CaptureMode::Pcap => write!(f, "pcap"),
             CaptureMode::Xdp => write!(f, "xdp"),
+            CaptureMode::File => write!(f, "file"),
         }
     }
 }
@@ -36,6 +47,7 @@ impl From<&str> for CaptureMode {
         match s.to_lowercase().as_str() {
             "dpdk" => CaptureMode::Dpdk,
             "xdp" => CaptureMode::Xdp,
+            "file" => CaptureMode::File,
             _ => CaptureMode::Pcap, // 默认使用pcap
         }
     }
@@ -45,7 +57,7 @@ impl From<&str> for CaptureMode {
 pub struct CaptureConfig {
     /// 捕获模式
     pub mode: CaptureMode,
-    /// 网络接口名称
+    /// 网络接口名称，pcap模式下可用逗号分隔多个接口同时捕获，File模式下为抓包文件路径
     pub interface: String,
     /// BPF过滤器
     pub filter: String,
@@ -61,6 +73,12 @@ pub struct CaptureConfig {
     pub dpdk_config: Option<dpdk::DpdkCaptureConfig>,
     /// XDP特定配置
     pub xdp_config: Option<xdp::XdpCaptureConfig>,
+    /// 内核层采样率N，过滤器追加采样条件后只有约1/N的数据包进入用户态
+    ///
+    /// 按源、目的端口异或取模采样，同一条流的查询和响应同时保留或同时丢弃，查询关联不受影响。
+    /// 仅对libpcap后端（pcap、file）生效。之后的所有统计和输出都只覆盖采样后的流量，
+    /// 估算总量时需乘以N；用户态若再做采样，实际采样率为两者之积。
+    pub bpf_sample_rate: Option<u32>,
 }
 
 impl Clone for CaptureConfig {
@@ -75,6 +93,7 @@ impl Clone for CaptureConfig {
             buffer_size: self.buffer_size,
             dpdk_config: self.dpdk_config.clone(),
             xdp_config: self.xdp_config.clone(),
+            bpf_sample_rate: self.bpf_sample_rate,
         }
     }
 }
@@ -84,17 +103,55 @@ impl Default for CaptureConfig {
         CaptureConfig {
             mode: CaptureMode::Pcap,
             interface: "eth0".to_string(),
-            filter: "udp port 53 or tcp port 53".to_string(),
+            filter: ProtocolDetector::new().bpf_filter(),
             promiscuous: true,
             snaplen: 65535,
             timeout_ms: 1000,
             buffer_size: 16777216, // 16MB
             dpdk_config: None,
             xdp_config: None,
+            bpf_sample_rate: None,
+        }
+    }
+}
+
+impl CaptureConfig {
+    /// 逗号分隔的接口列表，忽略空白和空项
+    pub fn interfaces(&self) -> Vec<String> {
+        self.interface
+            .split(',')
+            .map(str::trim)
+            .filter(|interface| !interface.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// 实际下发的BPF过滤器：配置的过滤器加上内核层采样条件
+    pub fn effective_filter(&self) -> String {
+        let clause = match self.bpf_sample_rate {
+            Some(rate) if rate > 1 => bpf_sample_clause(rate),
+            _ => return self.filter.clone(),
+        };
+        if self.filter.is_empty() {
+            clause
+        } else {
+            format!("({}) and {}", self.filter, clause)
         }
     }
 }
 
+/// 采样条件：传输层端口异或后对N取模为0
+///
+/// 传输层下标表达式只适用于IPv4，IPv6按固定偏移读取端口，带扩展头的IPv6包不会被采样保留。
+fn bpf_sample_clause(rate: u32) -> String {
+    format!(
+        "((udp and (udp[0:2] ^ udp[2:2]) % {rate} = 0) \
+         or (tcp and (tcp[0:2] ^ tcp[2:2]) % {rate} = 0) \
+         or (ip6 and (ip6[6] = 17 or ip6[6] = 6) and (ip6[40:2] ^ ip6[42:2]) % {rate} = 0))",
+        rate = rate
+    )
+}
+
 /// 数据包捕获接口
 pub trait PacketCapture: Send {
     /// 初始化捕获器
@@ -107,7 +164,7 @@ pub trait PacketCapture: Send {
     fn stop_capture(&mut self);
 
     /// 接收数据包
-    fn receive_packets(&mut self, max_packets: usize) -> Vec<Vec<u8>>;
+    fn receive_packets(&mut self, max_packets: usize) -> Vec<CapturedPacket>;
 
     /// 发送数据包
     fn send_packets(&mut self, packets: &[Vec<u8>]) -> usize;
@@ -117,6 +174,47 @@ pub trait PacketCapture: Send {
 
     /// 关闭捕获器
     fn shutdown(&mut self);
+
+    /// 数据源是否已耗尽，只有离线文件等有限数据源会返回true
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+/// 链路层类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkType {
+    /// 以太网帧
+    Ethernet,
+    /// 无链路层头，直接以IPv4/IPv6头开始
+    RawIp,
+    /// 其他链路层类型（DLT值）
+    Other(i32),
+}
+
+impl LinkType {
+    /// 由libpcap的DLT值转换
+    pub fn from_dlt(dlt: i32) -> Self {
+        match dlt {
+            1 => LinkType::Ethernet,
+            // DLT_RAW在不同平台取值为12或14，文件中为LINKTYPE_RAW(101)；228/229为IPv4/IPv6
+            12 | 14 | 101 | 228 | 229 => LinkType::RawIp,
+            other => LinkType::Other(other),
+        }
+    }
+}
+
+/// 捕获到的数据包
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    /// 数据包内容
+    pub data: Vec<u8>,
+    /// 来源标识（接口名或捕获源名称）
+    pub source: Arc<str>,
+    /// 抓包时间戳（微秒），捕获后端不提供时为None，由处理方取当前时间
+    pub timestamp: Option<u64>,
+    /// 链路层类型，决定解码从哪一层开始
+    pub link_type: LinkType,
 }
 
 /// 捕获统计信息
@@ -126,8 +224,10 @@ pub struct CaptureStats {
     pub rx_packets: u64,
     /// 发送的数据包数量
     pub tx_packets: u64,
-    /// 丢弃的数据包数量
+    /// 丢弃的数据包数量（含接口丢包）
     pub dropped_packets: u64,
+    /// 其中由网卡/驱动丢弃的数据包数量，后端不区分时为0
+    pub if_dropped_packets: u64,
     /// 接收的字节数
     pub rx_bytes: u64,
     /// 发送的字节数
@@ -137,7 +237,7 @@ pub struct CaptureStats {
 /// 创建捕获器
 pub fn create_capture(
     config: CaptureConfig,
-    stats: Arc<Mutex<StatsCounter>>,
+    stats: Arc<StatsCounter>,
 ) -> Box<dyn PacketCapture> {
     match config.mode {
         CaptureMode::Dpdk => {
@@ -145,7 +245,26 @@ pub fn create_capture(
             let dpdk_config = config.dpdk_config.unwrap_or_default();
             Box::new(dpdk::DpdkCapture::new(cap_config, dpdk_config, stats))
         }
-        CaptureMode::Pcap => Box::new(pcap::PcapCapture::new(config, stats)),
+        CaptureMode::Pcap => {
+            let interfaces = config.interfaces();
+            if interfaces.len() <= 1 {
+                return Box::new(pcap::PcapCapture::new(config, stats));
+            }
+
+            // 每个接口一个捕获器，数据包以接口名为来源标识合并
+            let sources = interfaces
+                .into_iter()
+                .map(|interface| {
+                    let config = CaptureConfig {
+                        interface,
+                        ..config.clone()
+                    };
+                    Box::new(pcap::PcapCapture::new(config, Arc::clone(&stats))) as Box<dyn PacketCapture>
+                })
+                .collect();
+            Box::new(multi::MultiCapture::new(sources))
+        }
+        CaptureMode::File => Box::new(file::FileCapture::new(config, stats)),
         CaptureMode::Xdp => {
             let cap_config = config.clone();
             let xdp_config = config.xdp_config.unwrap_or_default();
@@ -153,3 +272,48 @@ pub fn create_capture(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interface_list() {
+        let config = CaptureConfig {
+            interface: "eth0, eth1,,bond0 ".to_string(),
+            ..CaptureConfig::default()
+        };
+        assert_eq!(config.interfaces(), vec!["eth0", "eth1", "bond0"]);
+
+        let stats = Arc::new(StatsCounter::new());
+        let capture = create_capture(config, stats);
+        assert_eq!(capture.get_stats().rx_packets, 0);
+    }
+
+    #[test]
+    fn test_effective_filter_appends_sampling_clause() {
+        let config = CaptureConfig {
+            filter: "udp port 53".to_string(),
+            bpf_sample_rate: Some(16),
+            ..CaptureConfig::default()
+        };
+        let filter = config.effective_filter();
+        assert!(filter.starts_with("(udp port 53) and (("));
+        assert!(filter.contains("(udp[0:2] ^ udp[2:2]) % 16 = 0"));
+        assert!(filter.contains("(ip6[40:2] ^ ip6[42:2]) % 16 = 0"));
+
+        // 无过滤器时只有采样条件，采样率为1时不采样
+        let config = CaptureConfig {
+            filter: String::new(),
+            ..config
+        };
+        assert!(config.effective_filter().starts_with("((udp and"));
+        let config = CaptureConfig {
+            filter: "udp port 53".to_string(),
+            bpf_sample_rate: Some(1),
+            ..config
+        };
+        assert_eq!(config.effective_filter(), "udp port 53");
+    }
+
+}
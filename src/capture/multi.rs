@@ -0,0 +1,194 @@
+//! 组合捕获模块实现
+//! 将多个捕获源合并为一条数据包流
+
+use super::{CaptureStats, CapturedPacket, PacketCapture};
+
+/// 组合捕获实现
+pub struct MultiCapture {
+    /// 子捕获源
+    sources: Vec<Box<dyn PacketCapture>>,
+    /// 下一轮优先读取的捕获源，避免单个繁忙源饿死其他源
+    next_source: usize,
+}
+
+impl MultiCapture {
+    /// 创建新的组合捕获实例
+    pub fn new(sources: Vec<Box<dyn PacketCapture>>) -> Self {
+        MultiCapture {
+            sources,
+            next_source: 0,
+        }
+    }
+
+    /// 子捕获源数量
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// 是否没有任何捕获源
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+impl PacketCapture for MultiCapture {
+    fn initialize(&mut self) -> crate::error::Result<()> {
+        for source in &mut self.sources {
+            source.initialize()?;
+        }
+        Ok(())
+    }
+
+    fn start_capture(&mut self) -> crate::error::Result<()> {
+        for source in &mut self.sources {
+            source.start_capture()?;
+        }
+        Ok(())
+    }
+
+    fn stop_capture(&mut self) {
+        for source in &mut self.sources {
+            source.stop_capture();
+        }
+    }
+
+    fn receive_packets(&mut self, max_packets: usize) -> Vec<CapturedPacket> {
+        let mut packets = Vec::new();
+        let count = self.sources.len();
+
+        if count == 0 {
+            return packets;
+        }
+
+        // 每个源分得公平的配额，从上次之后的源开始轮询
+        let quota = (max_packets / count).max(1);
+        for i in 0..count {
+            if packets.len() >= max_packets {
+                break;
+            }
+
+            let index = (self.next_source + i) % count;
+            let budget = quota.min(max_packets - packets.len());
+            packets.extend(self.sources[index].receive_packets(budget));
+        }
+        self.next_source = (self.next_source + 1) % count;
+
+        packets
+    }
+
+    fn send_packets(&mut self, packets: &[Vec<u8>]) -> usize {
+        // 发送只走第一个捕获源
+        match self.sources.first_mut() {
+            Some(source) => source.send_packets(packets),
+            None => 0,
+        }
+    }
+
+    fn get_stats(&self) -> CaptureStats {
+        let mut total = CaptureStats::default();
+        for source in &self.sources {
+            let stats = source.get_stats();
+            total.rx_packets += stats.rx_packets;
+            total.tx_packets += stats.tx_packets;
+            total.dropped_packets += stats.dropped_packets;
+            total.if_dropped_packets += stats.if_dropped_packets;
+            total.rx_bytes += stats.rx_bytes;
+            total.tx_bytes += stats.tx_bytes;
+        }
+        total
+    }
+
+    fn shutdown(&mut self) {
+        for source in &mut self.sources {
+            source.shutdown();
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        !self.sources.is_empty() && self.sources.iter().all(|source| source.is_finished())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{CaptureConfig, CaptureMode, FileCapture, MemoryCapture};
+    use crate::core::stats::StatsCounter;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    /// 写入LINKTYPE_RAW的pcap文件
+    fn write_pcap(path: &std::path::Path, packets: &[Vec<u8>]) {
+        let mut file = std::fs::File::create(path).unwrap();
+        file.write_all(&0xA1B2_C3D4u32.to_le_bytes()).unwrap();
+        file.write_all(&2u16.to_le_bytes()).unwrap();
+        file.write_all(&4u16.to_le_bytes()).unwrap();
+        file.write_all(&[0; 8]).unwrap();
+        file.write_all(&65535u32.to_le_bytes()).unwrap();
+        file.write_all(&101u32.to_le_bytes()).unwrap();
+        for packet in packets {
+            file.write_all(&[0; 8]).unwrap();
+            file.write_all(&(packet.len() as u32).to_le_bytes()).unwrap();
+            file.write_all(&(packet.len() as u32).to_le_bytes()).unwrap();
+            file.write_all(packet).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_merge_sources_with_tags() {
+        let path = std::env::temp_dir().join(format!("dns_spider_multi_capture_{}.pcap", std::process::id()));
+        write_pcap(&path, &[vec![10; 20], vec![20; 20]]);
+        let replay_source = path.to_str().unwrap().to_string();
+
+        let live = MemoryCapture::new("eth0", vec![vec![1], vec![2], vec![3]]);
+        let replay = FileCapture::new(
+            CaptureConfig {
+                mode: CaptureMode::File,
+                interface: replay_source.clone(),
+                filter: String::new(),
+                ..CaptureConfig::default()
+            },
+            Arc::new(StatsCounter::new()),
+        );
+
+        let mut capture = MultiCapture::new(vec![Box::new(live), Box::new(replay)]);
+        capture.initialize().unwrap();
+        capture.start_capture().unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..10 {
+            received.extend(capture.receive_packets(4));
+        }
+
+        assert_eq!(received.len(), 5);
+        let from_live: Vec<u8> = received
+            .iter()
+            .filter(|p| &*p.source == "eth0")
+            .map(|p| p.data[0])
+            .collect();
+        let from_replay: Vec<u8> = received
+            .iter()
+            .filter(|p| *p.source == *replay_source)
+            .map(|p| p.data[0])
+            .collect();
+        assert_eq!(from_live, vec![1, 2, 3]);
+        assert_eq!(from_replay, vec![10, 20]);
+
+        let stats = capture.get_stats();
+        assert_eq!(stats.rx_packets, 5);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_busy_source_does_not_starve_others() {
+        let busy = MemoryCapture::new("busy", vec![vec![0]; 100]);
+        let quiet = MemoryCapture::new("quiet", vec![vec![1]]);
+
+        let mut capture = MultiCapture::new(vec![Box::new(busy), Box::new(quiet)]);
+        capture.start_capture().unwrap();
+
+        let packets = capture.receive_packets(10);
+        assert!(packets.iter().any(|p| &*p.source == "quiet"));
+    }
+}
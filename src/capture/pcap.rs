@@ -1,9 +1,9 @@
 //! libpcap捕获模块实现
 //! 基于libpcap的数据包捕获
 
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use super::{CaptureConfig, CaptureStats, PacketCapture};
+use super::{CaptureConfig, CaptureStats, CapturedPacket, LinkType, PacketCapture};
 use crate::core::stats::StatsCounter;
 
 #[cfg(feature = "pcap")]
@@ -17,18 +17,23 @@ pub struct PcapCapture {
     #[cfg(feature = "pcap")]
     capture: Option<Capture<Active>>,
     /// 统计计数器
-    stats: Arc<Mutex<StatsCounter>>,
+    stats: Arc<StatsCounter>,
     /// 是否正在捕获
     is_capturing: bool,
     /// 捕获统计信息
     capture_stats: CaptureStats,
-    /// 上次统计时间
+    /// 上次读取libpcap统计的时间
     last_stats_time: std::time::Instant,
+    /// 数据包来源标识
+    source: Arc<str>,
+    /// 接口的链路层类型
+    link_type: LinkType,
 }
 
 impl PcapCapture {
     /// 创建新的libpcap捕获实例
-    pub fn new(config: CaptureConfig, stats: Arc<Mutex<StatsCounter>>) -> Self {
+    pub fn new(config: CaptureConfig, stats: Arc<StatsCounter>) -> Self {
+        let source = Arc::from(config.interface.as_str());
         PcapCapture {
             config,
             #[cfg(feature = "pcap")]
@@ -37,6 +42,8 @@ impl PcapCapture {
             is_capturing: false,
             capture_stats: CaptureStats::default(),
             last_stats_time: std::time::Instant::now(),
+            source,
+            link_type: LinkType::Ethernet,
         }
     }
 }
@@ -45,6 +52,12 @@ impl PacketCapture for PcapCapture {
     fn initialize(&mut self) -> crate::error::Result<()> {
         #[cfg(feature = "pcap")]
         {
+            // 激活前先编译过滤器，语法错误时直接报告配置问题
+            let filter = self.config.effective_filter();
+            if !filter.is_empty() {
+                validate_filter(&filter)?;
+            }
+
             // 查找设备
             let device = match Device::list() {
                 Ok(devices) => {
@@ -52,7 +65,7 @@ impl PacketCapture for PcapCapture {
                         d.name == self.config.interface
                             || d.desc
                                 .as_ref()
-                                .map_or(false, |desc| desc.contains(&self.config.interface))
+                                .is_some_and(|desc| desc.contains(&self.config.interface))
                     });
 
                     match device {
@@ -106,8 +119,8 @@ impl PacketCapture for PcapCapture {
             };
 
             // 设置过滤器（在Active上）
-            if !self.config.filter.is_empty() {
-                if let Err(e) = active_capture.filter(&self.config.filter, true) {
+            if !filter.is_empty() {
+                if let Err(e) = active_capture.filter(&filter, true) {
                     return Err(crate::error::Error::Capture(format!(
                         "设置过滤器失败: {}",
                         e
@@ -126,6 +139,16 @@ impl PacketCapture for PcapCapture {
                 }
             };
 
+            // 部分平台或权限下混杂模式会静默失效，激活后核对实际状态
+            if let Some(message) = promisc_status_message(
+                &self.config.interface,
+                self.config.promiscuous,
+                read_promisc_flag(&self.config.interface),
+            ) {
+                eprintln!("{}", message);
+            }
+
+            self.link_type = LinkType::from_dlt(active_capture.get_datalink().0);
             self.capture = Some(active_capture);
             Ok(())
         }
@@ -162,7 +185,7 @@ impl PacketCapture for PcapCapture {
         self.is_capturing = false;
     }
 
-    fn receive_packets(&mut self, max_packets: usize) -> Vec<Vec<u8>> {
+    fn receive_packets(&mut self, max_packets: usize) -> Vec<CapturedPacket> {
         let mut packets = Vec::new();
 
         #[cfg(feature = "pcap")]
@@ -180,17 +203,39 @@ impl PacketCapture for PcapCapture {
                         let data = packet.data.to_vec();
                         self.capture_stats.rx_packets += 1;
                         self.capture_stats.rx_bytes += data.len() as u64;
-                        packets.push(data);
+                        packets.push(CapturedPacket {
+                            data,
+                            source: Arc::clone(&self.source),
+                            timestamp: Some(timeval_micros(
+                                packet.header.ts.tv_sec as i64,
+                                packet.header.ts.tv_usec as i64,
+                            )),
+                            link_type: self.link_type,
+                        });
                     }
                     Err(pcap::Error::TimeoutExpired) => break,
                     Err(_) => break,
                 }
             }
 
-            // 更新统计信息
-            if let Ok(mut stats) = self.stats.lock() {
-                stats.add("pcap.rx_packets", packets.len() as u64);
+            // 定期读取libpcap的丢包统计：ps_drop为缓冲区满丢弃，ps_ifdrop为网卡/驱动丢弃
+            if self.last_stats_time.elapsed() >= PCAP_STATS_INTERVAL {
+                self.last_stats_time = std::time::Instant::now();
+                if let Ok(stat) = capture.stats() {
+                    self.capture_stats.dropped_packets = stat.dropped as u64 + stat.if_dropped as u64;
+                    self.capture_stats.if_dropped_packets = stat.if_dropped as u64;
+                }
             }
+
+            // 更新统计信息
+            let stats = &self.stats;
+            let ps_drop = self.capture_stats.dropped_packets - self.capture_stats.if_dropped_packets;
+            stats.add("pcap.rx_packets", packets.len() as u64);
+            // 丢包数只按接口上报，多接口捕获时各接口不会互相覆盖；
+            // 所有接口的丢包合计由MultiCapture::get_stats汇总为capture.dropped_packets
+            stats.add(&format!("{}.pcap.rx_packets", self.source), packets.len() as u64);
+            stats.set_gauge(&format!("{}.pcap.ps_drop", self.source), ps_drop);
+            stats.set_gauge(&format!("{}.pcap.ps_ifdrop", self.source), self.capture_stats.if_dropped_packets);
         }
 
         packets
@@ -217,9 +262,7 @@ impl PacketCapture for PcapCapture {
 
             // 更新统计信息
             if sent > 0 {
-                if let Ok(mut stats) = self.stats.lock() {
-                    stats.add("pcap.tx_packets", sent as u64);
-                }
+                self.stats.add("pcap.tx_packets", sent as u64);
             }
 
             sent
@@ -245,8 +288,178 @@ impl PacketCapture for PcapCapture {
     }
 }
 
+/// 读取libpcap统计的间隔
+#[cfg(feature = "pcap")]
+const PCAP_STATS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// libpcap头部时间（秒+微秒）转换为微秒时间戳，早于纪元的时间截断为0
+#[cfg(feature = "pcap")]
+pub(super) fn timeval_micros(sec: i64, usec: i64) -> u64 {
+    (sec.max(0) as u64) * 1_000_000 + (usec.clamp(0, 999_999) as u64)
+}
+
+/// 在dead捕获器上编译BPF过滤器，不需要抓包权限和可用接口
+#[cfg(feature = "pcap")]
+fn validate_filter(filter: &str) -> crate::error::Result<()> {
+    let mut dead = Capture::dead(pcap::Linktype::ETHERNET)
+        .map_err(|e| crate::error::Error::Capture(format!("创建捕获器失败: {}", e)))?;
+    dead.compile(filter, true).map(|_| ()).map_err(|e| {
+        crate::error::Error::Config(format!("无效的BPF过滤器 \"{}\": {}", filter, e))
+    })
+}
+
+/// 接口flags中的IFF_PROMISC位
+#[cfg(all(feature = "pcap", target_os = "linux"))]
+const IFF_PROMISC: u32 = 0x100;
+
+/// 读取接口当前是否处于混杂模式，平台不支持或读取失败时返回None
+#[cfg(feature = "pcap")]
+fn read_promisc_flag(interface: &str) -> Option<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        let flags = std::fs::read_to_string(format!("/sys/class/net/{}/flags", interface)).ok()?;
+        let flags = u32::from_str_radix(flags.trim().trim_start_matches("0x"), 16).ok()?;
+        Some(flags & IFF_PROMISC != 0)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = interface;
+        None
+    }
+}
+
+/// 比较请求的和实际的混杂模式状态，需要提示时返回日志内容
+#[cfg(feature = "pcap")]
+fn promisc_status_message(interface: &str, requested: bool, effective: Option<bool>) -> Option<String> {
+    match (requested, effective) {
+        (true, Some(false)) => Some(format!(
+            "Warning: promiscuous mode requested on {} but the interface reports it off; \
+             only traffic to or from this host will be captured (check capture privileges)",
+            interface
+        )),
+        (true, None) => Some(format!(
+            "Promiscuous mode requested on {}; effective state could not be verified",
+            interface
+        )),
+        _ => None,
+    }
+}
+
 impl Drop for PcapCapture {
     fn drop(&mut self) {
         self.shutdown();
     }
 }
+
+/// 端到端测试：在回环接口抓取测试自身发送的DNS查询，经解码、解析后投递到内存输出。
+/// 没有抓包权限或找不到回环接口时跳过而不是失败。
+#[cfg(all(test, feature = "pcap", any(target_os = "linux", target_os = "macos")))]
+mod tests {
+    use super::*;
+
+    /// Linux回环接口的链路层为以太网，macOS回环的DLT_NULL驱动不解码，端到端测试只在Linux上运行
+    #[cfg(target_os = "linux")]
+    const LOOPBACK: &str = "lo";
+
+    #[cfg(target_os = "linux")]
+    fn build_query(transaction_id: u16) -> Vec<u8> {
+        let mut query = transaction_id.to_be_bytes().to_vec();
+        query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        query.extend_from_slice(b"\x04e2et\x07example\x00");
+        query.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        query
+    }
+
+    #[test]
+    fn test_invalid_filter_is_config_error() {
+        assert!(validate_filter("udp port 53").is_ok());
+        // 内核层采样条件能被libpcap编译
+        let sampled = CaptureConfig {
+            bpf_sample_rate: Some(8),
+            ..CaptureConfig::default()
+        };
+        assert!(validate_filter(&sampled.effective_filter()).is_ok());
+
+        match validate_filter("udp prot 53") {
+            Err(crate::error::Error::Config(message)) => assert!(message.contains("udp prot 53")),
+            other => panic!("expected config error, got {:?}", other),
+        }
+
+        // 初始化时在查找接口之前报告过滤器错误
+        let config = CaptureConfig {
+            interface: "dns-spider-no-such-if".to_string(),
+            filter: "udp prot 53".to_string(),
+            ..CaptureConfig::default()
+        };
+        let mut capture = PcapCapture::new(config, Arc::new(StatsCounter::new()));
+        assert!(matches!(capture.initialize(), Err(crate::error::Error::Config(_))));
+    }
+
+    #[test]
+    fn test_promisc_not_engaged_warns() {
+        let message = promisc_status_message("eth0", true, Some(false)).unwrap();
+        assert!(message.starts_with("Warning: promiscuous mode requested on eth0"));
+
+        assert!(promisc_status_message("eth0", true, None).unwrap().contains("could not be verified"));
+        assert_eq!(promisc_status_message("eth0", true, Some(true)), None);
+        assert_eq!(promisc_status_message("eth0", false, Some(false)), None);
+        assert_eq!(read_promisc_flag("dns-spider-no-such-if"), None);
+    }
+
+    #[test]
+    fn test_timeval_micros() {
+        assert_eq!(timeval_micros(1_700_000_000, 123_456), 1_700_000_000_123_456);
+        assert_eq!(timeval_micros(-1, 5), 5);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_loopback_capture_through_driver() {
+        use crate::core::config_builder::DriverConfigBuilder;
+        use crate::core::driver::Driver;
+        use crate::output::MemoryOutput;
+        use crate::protocols::dns::DnsRecordType;
+        use crate::utils::time::current_time_micros;
+        use std::net::UdpSocket;
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = receiver.local_addr().unwrap().port();
+
+        let config = DriverConfigBuilder::new()
+            .interface(LOOPBACK)
+            .filter(format!("udp dst port {}", port))
+            .workers(1)
+            .build()
+            .unwrap();
+        let output = MemoryOutput::new();
+        let messages = output.messages();
+        let mut driver = Driver::new(config).with_output(Box::new(output));
+        let shutdown = driver.shutdown_handle();
+        let handle = thread::spawn(move || driver.start());
+
+        // 捕获启动前发出的查询抓不到，重复发送直到输出收到或驱动因权限等原因退出
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sent_at = current_time_micros();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while messages.lock().unwrap().is_empty() && !handle.is_finished() && Instant::now() < deadline {
+            sender.send_to(&build_query(0x5EED), ("127.0.0.1", port)).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+        shutdown.shutdown();
+        if let Err(e) = handle.join().unwrap() {
+            eprintln!("skipping loopback capture test: {}", e);
+            return;
+        }
+
+        let messages = messages.lock().unwrap();
+        assert!(!messages.is_empty());
+        assert_eq!(messages[0].transaction_id, 0x5EED);
+        assert_eq!(messages[0].questions[0].name, "e2et.example");
+        assert_eq!(messages[0].questions[0].record_type, DnsRecordType::A);
+        // 抓包时间戳来自libpcap头部，与发送时间相差不超过数秒
+        assert!(messages[0].timestamp.abs_diff(sent_at) < 5_000_000);
+    }
+}
@@ -1,9 +1,9 @@
 //! XDP捕获模块实现
 //! 基于XDP(eXpress Data Path)的高性能数据包捕获
 
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use super::{CaptureConfig, CaptureStats, PacketCapture};
+use super::{CaptureConfig, CaptureStats, CapturedPacket, LinkType, PacketCapture};
 use crate::core::stats::StatsCounter;
 
 #[cfg(feature = "xdp")]
@@ -28,6 +28,10 @@ pub struct XdpCaptureConfig {
     pub fill_size: u32,
     /// 完成大小
     pub comp_size: u32,
+    /// 内核过滤端口所在的eBPF映射名称
+    pub filter_map_name: String,
+    /// 内核中放行的端口，程序加载后写入映射；为空时保留程序内置配置
+    pub filter_ports: Vec<u16>,
 }
 
 impl Default for XdpCaptureConfig {
@@ -41,10 +45,44 @@ impl Default for XdpCaptureConfig {
             frame_count: 8192,
             fill_size: 4096,
             comp_size: 4096,
+            filter_map_name: "dns_ports".to_string(),
+            filter_ports: vec![53, 443, 853, 8853],
         }
     }
 }
 
+/// eBPF映射写入接口，便于在没有内核环境时测试映射填充逻辑
+pub trait FilterMapWriter {
+    /// 写入一个键值对
+    fn update(&mut self, key: &[u8], value: &[u8]) -> Result<(), String>;
+}
+
+#[cfg(feature = "xdp")]
+impl FilterMapWriter for Map {
+    fn update(&mut self, key: &[u8], value: &[u8]) -> Result<(), String> {
+        Map::update(self, key, value, 0).map_err(|e| e.to_string())
+    }
+}
+
+/// 将端口列表写入过滤映射
+///
+/// 键为网络字节序的端口号，值为1表示放行，与dns_filter.o中的查找方式一致。
+/// 返回写入的端口数，重复端口只写一次。
+pub fn populate_port_map(map: &mut dyn FilterMapWriter, ports: &[u16]) -> Result<usize, String> {
+    let mut written: Vec<u16> = Vec::with_capacity(ports.len());
+
+    for &port in ports {
+        if written.contains(&port) {
+            continue;
+        }
+        map.update(&port.to_be_bytes(), &[1u8])
+            .map_err(|e| format!("写入端口{}失败: {}", port, e))?;
+        written.push(port);
+    }
+
+    Ok(written.len())
+}
+
 /// XDP捕获实现
 pub struct XdpCapture {
     /// 捕获配置
@@ -61,11 +99,13 @@ pub struct XdpCapture {
     #[cfg(feature = "xdp")]
     socket: Option<Socket>,
     /// 统计计数器
-    stats: Arc<Mutex<StatsCounter>>,
+    stats: Arc<StatsCounter>,
     /// 是否正在捕获
     is_capturing: bool,
     /// 捕获统计信息
     capture_stats: CaptureStats,
+    /// 数据包来源标识
+    source: Arc<str>,
 }
 
 impl XdpCapture {
@@ -73,8 +113,9 @@ impl XdpCapture {
     pub fn new(
         config: CaptureConfig,
         xdp_config: XdpCaptureConfig,
-        stats: Arc<Mutex<StatsCounter>>,
+        stats: Arc<StatsCounter>,
     ) -> Self {
+        let source = Arc::from(config.interface.as_str());
         XdpCapture {
             config,
             xdp_config,
@@ -87,6 +128,7 @@ impl XdpCapture {
             stats,
             is_capturing: false,
             capture_stats: CaptureStats::default(),
+            source,
         }
     }
 }
@@ -104,6 +146,22 @@ impl PacketCapture for XdpCapture {
                 Err(e) => return Err(crate::error::Error::Xdp(format!("加载XDP程序失败: {}", e))),
             };
 
+            // 写入内核过滤端口，无需重新编译eBPF程序
+            if !self.xdp_config.filter_ports.is_empty() {
+                let mut map = match program.map(&self.xdp_config.filter_map_name) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        return Err(crate::error::Error::Xdp(format!(
+                            "找不到过滤映射{}: {}",
+                            self.xdp_config.filter_map_name, e
+                        )))
+                    }
+                };
+                if let Err(e) = populate_port_map(&mut map, &self.xdp_config.filter_ports) {
+                    return Err(crate::error::Error::Xdp(format!("配置过滤映射失败: {}", e)));
+                }
+            }
+
             // 获取网络接口
             let interface = match Interface::from_name(&self.config.interface) {
                 Ok(i) => i,
@@ -177,7 +235,7 @@ impl PacketCapture for XdpCapture {
         self.is_capturing = false;
     }
 
-    fn receive_packets(&mut self, max_packets: usize) -> Vec<Vec<u8>> {
+    fn receive_packets(&mut self, max_packets: usize) -> Vec<CapturedPacket> {
         let mut packets = Vec::new();
 
         #[cfg(feature = "xdp")]
@@ -195,16 +253,19 @@ impl PacketCapture for XdpCapture {
                         let packet = data.to_vec();
                         self.capture_stats.rx_packets += 1;
                         self.capture_stats.rx_bytes += packet.len() as u64;
-                        packets.push(packet);
+                        packets.push(CapturedPacket {
+                            data: packet,
+                            source: Arc::clone(&self.source),
+                            timestamp: None,
+                            link_type: LinkType::Ethernet,
+                        });
                     }
                     Err(_) => break,
                 }
             }
 
             // 更新统计信息
-            if let Ok(mut stats) = self.stats.lock() {
-                stats.add("xdp.rx_packets", packets.len() as u64);
-            }
+            self.stats.add("xdp.rx_packets", packets.len() as u64);
         }
 
         packets
@@ -231,9 +292,7 @@ impl PacketCapture for XdpCapture {
 
             // 更新统计信息
             if sent > 0 {
-                if let Ok(mut stats) = self.stats.lock() {
-                    stats.add("xdp.tx_packets", sent as u64);
-                }
+                self.stats.add("xdp.tx_packets", sent as u64);
             }
 
             sent
@@ -284,3 +343,46 @@ impl Drop for XdpCapture {
         self.shutdown();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 记录写入内容的模拟映射
+    #[derive(Default)]
+    struct MockMap {
+        entries: Vec<(Vec<u8>, Vec<u8>)>,
+        fail_on: Option<u16>,
+    }
+
+    impl FilterMapWriter for MockMap {
+        fn update(&mut self, key: &[u8], value: &[u8]) -> Result<(), String> {
+            if self.fail_on.map(|p| p.to_be_bytes().to_vec()) == Some(key.to_vec()) {
+                return Err("map full".to_string());
+            }
+            self.entries.push((key.to_vec(), value.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_populate_port_map() {
+        let mut map = MockMap::default();
+        let written = populate_port_map(&mut map, &[53, 853, 53, 5353]).unwrap();
+
+        assert_eq!(written, 3);
+        let keys: Vec<Vec<u8>> = map.entries.iter().map(|(k, _)| k.clone()).collect();
+        assert_eq!(keys, vec![vec![0x00, 0x35], vec![0x03, 0x55], vec![0x14, 0xE9]]);
+        assert!(map.entries.iter().all(|(_, v)| v == &vec![1u8]));
+    }
+
+    #[test]
+    fn test_populate_port_map_error() {
+        let mut map = MockMap {
+            fail_on: Some(853),
+            ..MockMap::default()
+        };
+        let err = populate_port_map(&mut map, &[53, 853]).unwrap_err();
+        assert!(err.contains("853"));
+    }
+}
@@ -0,0 +1,427 @@
+//! 异常触发的原始报文转储
+//! 正常运行只输出解析摘要，检测标记触发后把前后一段时间的原始帧写入pcap文件，
+//! 便于事后分析水刑攻击、放大攻击、伪造响应、DNS隧道等异常
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::capture::LinkType;
+use crate::core::stats::StatsCounter;
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsProtocol};
+
+/// pcap文件魔数（微秒精度）
+const PCAP_MAGIC: u32 = 0xA1B2_C3D4;
+/// pcap链路类型：以太网
+pub(crate) const PCAP_LINKTYPE_ETHERNET: u32 = 1;
+/// pcap链路类型：无链路层头的IP包
+pub(crate) const PCAP_LINKTYPE_RAW: u32 = 101;
+/// pcap快照长度
+const PCAP_SNAPLEN: u32 = 65535;
+/// 水刑检测的计数窗口
+const WATER_TORTURE_WINDOW: Duration = Duration::from_secs(1);
+/// 水刑检测同时跟踪的最大域名数，超出后新域名不计数
+const MAX_WATER_TORTURE_ZONES: usize = 10_000;
+/// NXDOMAIN响应码
+const RCODE_NXDOMAIN: u16 = 3;
+/// DNS头部长度
+const DNS_HEADER_LEN: usize = 12;
+
+/// 异常转储配置
+#[derive(Debug, Clone)]
+pub struct AnomalyDumpConfig {
+    /// pcap文件输出目录
+    pub output_dir: PathBuf,
+    /// 触发后持续转储的时长，窗口内再次触发会顺延
+    pub window: Duration,
+    /// 触发前保留的最近帧数，触发时一并写入
+    pub pre_trigger_packets: usize,
+    /// 同一域名每秒的NXDOMAIN响应数达到该值时视为水刑攻击，为0时不检测
+    pub water_torture_nxdomain_per_sec: u32,
+    /// UDP响应至少达到该字节数才视为放大攻击
+    pub amplification_min_bytes: usize,
+    /// UDP响应与对应查询的估算长度之比达到该值时视为放大攻击，为0时不检测
+    pub amplification_min_ratio: usize,
+}
+
+impl Default for AnomalyDumpConfig {
+    fn default() -> Self {
+        AnomalyDumpConfig {
+            output_dir: PathBuf::from("./logs"),
+            window: Duration::from_secs(10),
+            pre_trigger_packets: 1000,
+            water_torture_nxdomain_per_sec: 100,
+            amplification_min_bytes: 1500,
+            amplification_min_ratio: 10,
+        }
+    }
+}
+
+/// 正在进行的转储
+struct ActiveDump {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    until: Instant,
+    /// 文件头的链路类型，链路类型不同的帧不写入
+    linktype: u32,
+}
+
+/// 异常转储器
+pub struct AnomalyDump {
+    config: AnomalyDumpConfig,
+    /// 最近的帧 (时间戳微秒, pcap链路类型, 帧数据)
+    recent: VecDeque<(u64, u32, Vec<u8>)>,
+    active: Option<ActiveDump>,
+    /// 当前水刑检测窗口的开始时间和各域名的NXDOMAIN数
+    nxdomain_window: Option<(Instant, HashMap<String, u32>)>,
+}
+
+impl AnomalyDump {
+    /// 创建新的异常转储器
+    pub fn new(config: AnomalyDumpConfig) -> Self {
+        AnomalyDump {
+            recent: VecDeque::with_capacity(config.pre_trigger_packets),
+            config,
+            active: None,
+            nxdomain_window: None,
+        }
+    }
+
+    /// 是否正在转储
+    pub fn is_dumping(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// 当前转储文件路径
+    pub fn current_path(&self) -> Option<&PathBuf> {
+        self.active.as_ref().map(|active| &active.path)
+    }
+
+    /// 记录一帧，转储进行中直接写入，否则放入触发前缓冲
+    pub fn record(
+        &mut self,
+        timestamp: u64,
+        frame: &[u8],
+        link_type: LinkType,
+        now: Instant,
+        stats: &StatsCounter,
+    ) {
+        if self.active.as_ref().is_some_and(|active| now >= active.until) {
+            self.finish();
+        }
+
+        let linktype = pcap_linktype(link_type);
+        if let Some(active) = &mut self.active {
+            if active.linktype != linktype {
+                stats.increment("anomaly_dump.linktype_mismatch");
+                return;
+            }
+            match write_record(&mut active.writer, timestamp, frame) {
+                Ok(()) => stats.increment("anomaly_dump.packets"),
+                Err(e) => {
+                    eprintln!("Failed to write anomaly dump {}: {}", active.path.display(), e);
+                    stats.increment("anomaly_dump.write_failed");
+                    self.active = None;
+                }
+            }
+            return;
+        }
+
+        if self.config.pre_trigger_packets == 0 {
+            return;
+        }
+        if self.recent.len() == self.config.pre_trigger_packets {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((timestamp, linktype, frame.to_vec()));
+    }
+
+    /// 检查消息的检测标记，命中时以对应原因触发转储
+    ///
+    /// `payload_len`为消息所在报文的DNS负载长度，用于判断放大攻击。
+    pub fn inspect(&mut self, message: &DnsMessage, payload_len: usize, now: Instant, stats: &StatsCounter) {
+        // 水刑计数需要看到每条NXDOMAIN响应，先于其他判断执行
+        let water_torture = self.is_water_torture(message, now);
+        let reason = if message.unsolicited {
+            "unsolicited"
+        } else if message.tunneling_suspected {
+            "tunneling"
+        } else if self.is_amplification(message, payload_len) {
+            stats.increment("anomaly.amplification");
+            "amplification"
+        } else if water_torture {
+            stats.increment("anomaly.water_torture");
+            "water_torture"
+        } else {
+            return;
+        };
+        self.trigger(reason, message.timestamp, now, stats);
+    }
+
+    /// 同一域名的NXDOMAIN响应在窗口内恰好达到阈值时返回true，每个窗口每个域名只触发一次
+    fn is_water_torture(&mut self, message: &DnsMessage, now: Instant) -> bool {
+        let threshold = self.config.water_torture_nxdomain_per_sec;
+        if threshold == 0 || message.message_type != DnsMessageType::Response || message.rcode != RCODE_NXDOMAIN {
+            return false;
+        }
+        let Some(question) = message.questions.first() else {
+            return false;
+        };
+
+        let counts = match &mut self.nxdomain_window {
+            Some((start, counts)) if now.duration_since(*start) < WATER_TORTURE_WINDOW => counts,
+            window => &mut window.insert((now, HashMap::new())).1,
+        };
+        // 随机子域名攻击针对的是上级域名，按最后两个标签计数
+        let zone = zone_of(&question.name);
+        if counts.len() >= MAX_WATER_TORTURE_ZONES && !counts.contains_key(&zone) {
+            return false;
+        }
+        let count = counts.entry(zone).or_insert(0);
+        *count += 1;
+        *count == threshold
+    }
+
+    /// UDP响应远大于查询时可能被用于反射放大
+    fn is_amplification(&self, message: &DnsMessage, payload_len: usize) -> bool {
+        let ratio = self.config.amplification_min_ratio;
+        if ratio == 0
+            || message.message_type != DnsMessageType::Response
+            || !matches!(message.protocol, DnsProtocol::Udp)
+            || payload_len < self.config.amplification_min_bytes
+        {
+            return false;
+        }
+
+        // 查询长度按头部加问题估算，每个问题为名称编码加类型和类别
+        let query_len = DNS_HEADER_LEN
+            + message
+                .questions
+                .iter()
+                .map(|question| question.name.trim_end_matches('.').len() + 2 + 4)
+                .sum::<usize>();
+        payload_len >= query_len * ratio
+    }
+
+    /// 检测标记触发转储，已在转储时顺延窗口
+    pub fn trigger(&mut self, reason: &str, timestamp: u64, now: Instant, stats: &StatsCounter) {
+        stats.increment("anomaly_dump.triggered");
+        let until = now + self.config.window;
+
+        if let Some(active) = &mut self.active {
+            active.until = until;
+            return;
+        }
+
+        let path = self
+            .config
+            .output_dir
+            .join(format!("anomaly-{}-{}.pcap", reason, timestamp));
+        // 文件链路类型取触发前最后一帧，通常就是触发检测的那一帧
+        let linktype = self
+            .recent
+            .back()
+            .map_or(PCAP_LINKTYPE_ETHERNET, |(_, linktype, _)| *linktype);
+        match self.open(&path, linktype) {
+            Ok(mut writer) => {
+                let mut written = 0;
+                for (ts, frame_linktype, frame) in self.recent.drain(..) {
+                    if frame_linktype != linktype {
+                        stats.increment("anomaly_dump.linktype_mismatch");
+                        continue;
+                    }
+                    if write_record(&mut writer, ts, &frame).is_err() {
+                        stats.increment("anomaly_dump.write_failed");
+                        return;
+                    }
+                    written += 1;
+                }
+                stats.add("anomaly_dump.packets", written);
+                self.active = Some(ActiveDump {
+                    writer,
+                    path,
+                    until,
+                    linktype,
+                });
+            }
+            Err(e) => {
+                eprintln!("Failed to create anomaly dump {}: {}", path.display(), e);
+                stats.increment("anomaly_dump.write_failed");
+            }
+        }
+    }
+
+    /// 结束当前转储
+    pub fn finish(&mut self) {
+        if let Some(mut active) = self.active.take() {
+            if let Err(e) = active.writer.flush() {
+                eprintln!("Failed to flush anomaly dump {}: {}", active.path.display(), e);
+            }
+        }
+    }
+
+    /// 创建pcap文件并写入文件头
+    fn open(&self, path: &PathBuf, linktype: u32) -> std::io::Result<BufWriter<File>> {
+        fs::create_dir_all(&self.config.output_dir)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer, linktype)?;
+        Ok(writer)
+    }
+}
+
+impl Drop for AnomalyDump {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// 链路层类型对应的pcap文件链路类型
+pub(crate) fn pcap_linktype(link_type: LinkType) -> u32 {
+    match link_type {
+        LinkType::Ethernet => PCAP_LINKTYPE_ETHERNET,
+        LinkType::RawIp => PCAP_LINKTYPE_RAW,
+        LinkType::Other(dlt) => dlt as u32,
+    }
+}
+
+/// 域名的最后两个标签，不足两个时为整个域名
+fn zone_of(name: &str) -> String {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    match name.rmatch_indices('.').nth(1) {
+        Some((index, _)) => name[index + 1..].to_string(),
+        None => name,
+    }
+}
+
+/// 写入pcap文件头
+pub(crate) fn write_header<W: Write>(writer: &mut W, linktype: u32) -> std::io::Result<()> {
+    writer.write_all(&PCAP_MAGIC.to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&4u16.to_le_bytes())?;
+    writer.write_all(&0i32.to_le_bytes())?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&PCAP_SNAPLEN.to_le_bytes())?;
+    writer.write_all(&linktype.to_le_bytes())
+}
+
+/// 写入一条pcap记录
+pub(crate) fn write_record<W: Write>(writer: &mut W, timestamp: u64, frame: &[u8]) -> std::io::Result<()> {
+    let captured = frame.len().min(PCAP_SNAPLEN as usize);
+    writer.write_all(&((timestamp / 1_000_000) as u32).to_le_bytes())?;
+    writer.write_all(&((timestamp % 1_000_000) as u32).to_le_bytes())?;
+    writer.write_all(&(captured as u32).to_le_bytes())?;
+    writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+    writer.write_all(&frame[..captured])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsClass, DnsQuestion, DnsRecordType};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_trigger_starts_dump_with_surrounding_packets() {
+        let dir = std::env::temp_dir().join(format!("dns_spider_anomaly_{}", std::process::id()));
+        let mut dump = AnomalyDump::new(AnomalyDumpConfig {
+            output_dir: dir.clone(),
+            window: Duration::from_secs(5),
+            pre_trigger_packets: 2,
+            ..AnomalyDumpConfig::default()
+        });
+        let stats = StatsCounter::new();
+        let start = Instant::now();
+
+        // 触发前只保留最近2帧
+        for i in 0..3u8 {
+            dump.record(1_000_000 + i as u64, &[i; 20], LinkType::Ethernet, start, &stats);
+        }
+        assert!(!dump.is_dumping());
+        assert_eq!(stats.get("anomaly_dump.packets"), 0);
+
+        dump.trigger("unsolicited", 1_000_003, start, &stats);
+        assert!(dump.is_dumping());
+        let path = dump.current_path().unwrap().clone();
+
+        // 窗口内的帧写入，窗口结束后停止
+        dump.record(2_000_000, &[9; 30], LinkType::Ethernet, start + Duration::from_secs(1), &stats);
+        dump.record(9_000_000, &[7; 30], LinkType::Ethernet, start + Duration::from_secs(6), &stats);
+        assert!(!dump.is_dumping());
+        assert_eq!(stats.get("anomaly_dump.triggered"), 1);
+        assert_eq!(stats.get("anomaly_dump.packets"), 3);
+
+        let data = fs::read(&path).unwrap();
+        assert_eq!(&data[..4], &PCAP_MAGIC.to_le_bytes());
+        // 24字节文件头 + 2条20字节帧 + 1条30字节帧，每条16字节记录头
+        assert_eq!(data.len(), 24 + 2 * (16 + 20) + (16 + 30));
+        assert_eq!(data[24 + 16], 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_detection_triggers_use_frame_linktype() {
+        let dir = std::env::temp_dir().join(format!("dns_spider_anomaly_detect_{}", std::process::id()));
+        let config = AnomalyDumpConfig {
+            output_dir: dir.clone(),
+            water_torture_nxdomain_per_sec: 3,
+            ..AnomalyDumpConfig::default()
+        };
+        let stats = StatsCounter::new();
+        let start = Instant::now();
+        let response = |name: &str, rcode: u16| DnsMessage {
+            transaction_id: 1,
+            message_type: DnsMessageType::Response,
+            questions: vec![DnsQuestion {
+                name: name.to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            timestamp: 1_000_000,
+            src_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            src_port: 53,
+            dst_port: 40000,
+            rcode,
+            recursion_desired: true,
+            recursion_available: true,
+            ..Default::default()
+        };
+
+        // 同一上级域名的随机子域名NXDOMAIN达到阈值时触发，文件链路类型跟随原始帧
+        let mut dump = AnomalyDump::new(config.clone());
+        for i in 0..3 {
+            dump.record(1_000_000, &[0x45; 28], LinkType::RawIp, start, &stats);
+            assert!(!dump.is_dumping());
+            dump.inspect(&response(&format!("x{}.Victim.example.", i), 3), 40, start, &stats);
+        }
+        assert!(dump.is_dumping());
+        assert_eq!(stats.get("anomaly.water_torture"), 1);
+        let path = dump.current_path().unwrap().clone();
+        assert!(path.to_string_lossy().contains("water_torture"));
+        dump.finish();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(&data[20..24], &PCAP_LINKTYPE_RAW.to_le_bytes());
+
+        // 小查询对应的大UDP响应
+        let mut dump = AnomalyDump::new(config.clone());
+        dump.inspect(&response("example.com", 0), 1000, start, &stats);
+        assert!(!dump.is_dumping());
+        dump.inspect(&response("example.com", 0), 4000, start, &stats);
+        assert!(dump.is_dumping());
+        assert_eq!(stats.get("anomaly.amplification"), 1);
+
+        // 疑似隧道
+        let mut dump = AnomalyDump::new(config);
+        let mut message = response("example.com", 0);
+        message.tunneling_suspected = true;
+        dump.inspect(&message, 100, start, &stats);
+        assert!(dump.current_path().unwrap().to_string_lossy().contains("tunneling"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
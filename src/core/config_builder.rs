@@ -0,0 +1,288 @@
+//! 驱动配置构建器
+//! 从合理的默认值出发，只需设置关心的字段，构建时统一校验
+
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use crate::capture::{CaptureConfig, CaptureMode};
+use crate::core::driver::DriverConfig;
+use crate::core::enrichment::ReverseDnsConfig;
+use crate::core::packet_queue::BackpressurePolicy;
+use crate::core::tunneling::TunnelingConfig;
+use crate::error::{Error, Result};
+use crate::output::{ConsoleConfig, ErrorOutputConfig, OutputConfig, OutputEncoding, TopicTemplate};
+use crate::protocols::dns::DnsRecordType;
+
+/// 驱动配置构建器
+///
+/// 默认使用libpcap捕获DNS相关端口、4个工作线程、每10秒输出统计，所有输出均关闭。
+pub struct DriverConfigBuilder {
+    config: DriverConfig,
+}
+
+impl Default for DriverConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DriverConfigBuilder {
+    /// 创建带默认值的构建器
+    pub fn new() -> Self {
+        DriverConfigBuilder {
+            config: DriverConfig {
+                capture: CaptureConfig::default(),
+                output: OutputConfig::default(),
+                stats_interval: 10,
+                worker_threads: 4,
+                stats_state_path: None,
+                ttl_histograms: false,
+                heartbeat_interval: 0,
+                anomaly_dump: None,
+                backpressure: BackpressurePolicy::DropNewest,
+                pcap_tee: None,
+                interface_stats: false,
+                tunneling: None,
+                tcp_handshake_tracking: true,
+                error_output: None,
+                resolver_ips: Vec::new(),
+                metrics_addr: None,
+                reverse_dns: None,
+            },
+        }
+    }
+
+    /// 捕获接口，pcap模式下可用逗号分隔多个接口，File模式下为抓包文件路径
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.config.capture.interface = interface.into();
+        self
+    }
+
+    /// BPF过滤器，替换默认的DNS端口过滤器
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.config.capture.filter = filter.into();
+        self
+    }
+
+    /// 捕获方式
+    pub fn capture_mode(mut self, mode: CaptureMode) -> Self {
+        self.config.capture.mode = mode;
+        self
+    }
+
+    /// 是否启用混杂模式
+    pub fn promiscuous(mut self, enabled: bool) -> Self {
+        self.config.capture.promiscuous = enabled;
+        self
+    }
+
+    /// 内核层采样率，只有约1/N的数据包进入用户态
+    pub fn bpf_sample_rate(mut self, rate: u32) -> Self {
+        self.config.capture.bpf_sample_rate = Some(rate);
+        self
+    }
+
+    /// 工作线程数
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.config.worker_threads = workers;
+        self
+    }
+
+    /// 统计输出间隔（秒）
+    pub fn stats_interval(mut self, secs: u64) -> Self {
+        self.config.stats_interval = secs;
+        self
+    }
+
+    /// 累计计数器状态文件
+    pub fn stats_state_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.stats_state_path = Some(path.into());
+        self
+    }
+
+    /// 是否按记录类型统计应答TTL直方图
+    pub fn ttl_histograms(mut self, enabled: bool) -> Self {
+        self.config.ttl_histograms = enabled;
+        self
+    }
+
+    /// 心跳间隔（秒），为0时不发送
+    pub fn heartbeat_interval(mut self, secs: u64) -> Self {
+        self.config.heartbeat_interval = secs;
+        self
+    }
+
+    /// 工作线程队列满时的背压策略
+    pub fn backpressure(mut self, policy: BackpressurePolicy) -> Self {
+        self.config.backpressure = policy;
+        self
+    }
+
+    /// 启用DNS隧道检测
+    pub fn tunneling(mut self, config: TunnelingConfig) -> Self {
+        self.config.tunneling = Some(config);
+        self
+    }
+
+    /// 将解析失败的报文写成限频的JSON错误事件
+    pub fn error_output(mut self, config: ErrorOutputConfig) -> Self {
+        self.config.error_output = Some(config);
+        self
+    }
+
+    /// 递归解析器地址，设置后为每条消息标记所属的解析链路
+    pub fn resolver_ips(mut self, resolver_ips: Vec<IpAddr>) -> Self {
+        self.config.resolver_ips = resolver_ips;
+        self
+    }
+
+    /// 在指定地址的`/metrics`导出Prometheus指标
+    pub fn metrics_addr(mut self, addr: SocketAddr) -> Self {
+        self.config.metrics_addr = Some(addr);
+        self
+    }
+
+    /// 启用客户端地址的反向DNS富化
+    pub fn reverse_dns(mut self, config: ReverseDnsConfig) -> Self {
+        self.config.reverse_dns = Some(config);
+        self
+    }
+
+    /// 是否启用控制台输出
+    pub fn enable_console(mut self, enabled: bool) -> Self {
+        self.config.output.enable_console = enabled;
+        self
+    }
+
+    /// 启用控制台输出并使用指定配置
+    pub fn console(mut self, config: ConsoleConfig) -> Self {
+        self.config.output.enable_console = true;
+        self.config.output.console_config = config;
+        self
+    }
+
+    /// 启用文件输出，写入指定目录
+    pub fn file(mut self, output_dir: impl Into<String>) -> Self {
+        self.config.output.enable_file = true;
+        self.config.output.file_config.output_dir = output_dir.into();
+        self
+    }
+
+    /// 启用CSV输出，写入指定文件
+    pub fn csv(mut self, path: impl Into<String>) -> Self {
+        self.config.output.enable_csv = true;
+        self.config.output.csv_config.path = path.into();
+        self
+    }
+
+    /// 启用Kafka输出，主题可以是模板
+    pub fn kafka(mut self, brokers: impl Into<String>, topic: impl Into<String>) -> Self {
+        self.config.output.enable_kafka = true;
+        self.config.output.kafka_config.brokers = brokers.into();
+        self.config.output.kafka_config.topic = topic.into();
+        self
+    }
+
+    /// 启用Statsd输出
+    pub fn statsd(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.config.output.enable_statsd = true;
+        self.config.output.statsd_config.host = host.into();
+        self.config.output.statsd_config.port = port;
+        self
+    }
+
+    /// 启用Syslog输出
+    pub fn syslog(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.config.output.enable_syslog = true;
+        self.config.output.syslog_config.host = host.into();
+        self.config.output.syslog_config.port = port;
+        self
+    }
+
+    /// 只输出问题类型在列表中的消息
+    pub fn record_type_filter(mut self, record_types: Vec<DnsRecordType>) -> Self {
+        self.config.output.record_type_filter = record_types;
+        self
+    }
+
+    /// 文件和Kafka输出的记录编码
+    pub fn encoding(mut self, encoding: OutputEncoding) -> Self {
+        self.config.output.file_config.encoding = encoding;
+        self.config.output.kafka_config.encoding = encoding;
+        self
+    }
+
+    /// 校验并生成驱动配置
+    pub fn build(self) -> Result<DriverConfig> {
+        let config = self.config;
+        if config.worker_threads == 0 {
+            return Err(Error::Config("worker_threads must be at least 1".to_string()));
+        }
+        if config.stats_interval == 0 {
+            return Err(Error::Config("stats_interval must be at least 1 second".to_string()));
+        }
+        if config.capture.mode != CaptureMode::Dpdk && config.capture.interfaces().is_empty() {
+            return Err(Error::Config("capture interface is empty".to_string()));
+        }
+        if config.capture.bpf_sample_rate == Some(0) {
+            return Err(Error::Config("bpf_sample_rate must be at least 1".to_string()));
+        }
+
+        let output = &config.output;
+        if output.enable_file || output.enable_kafka {
+            let encoding = if output.enable_file {
+                output.file_config.encoding
+            } else {
+                output.kafka_config.encoding
+            };
+            encoding.check_supported().map_err(Error::Config)?;
+        }
+        if output.enable_kafka {
+            if output.kafka_config.brokers.trim().is_empty() {
+                return Err(Error::Config("Kafka brokers are empty".to_string()));
+            }
+            TopicTemplate::parse(&output.kafka_config.topic).map_err(Error::Config)?;
+        }
+        if output.enable_file && output.file_config.output_dir.trim().is_empty() {
+            return Err(Error::Config("file output directory is empty".to_string()));
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_sets_fields_and_validates() {
+        let config = DriverConfigBuilder::new()
+            .interface("eth1")
+            .filter("udp port 5353")
+            .enable_console(true)
+            .kafka("kafka-1:9092,kafka-2:9092", "dns-{message_type}")
+            .workers(8)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.capture.interface, "eth1");
+        assert_eq!(config.capture.filter, "udp port 5353");
+        assert_eq!(config.worker_threads, 8);
+        assert_eq!(config.stats_interval, 10);
+        assert!(config.output.enable_console);
+        assert!(config.output.enable_kafka);
+        assert_eq!(config.output.kafka_config.brokers, "kafka-1:9092,kafka-2:9092");
+        assert_eq!(config.output.kafka_config.topic, "dns-{message_type}");
+        assert!(!config.output.enable_file);
+
+        for builder in [
+            DriverConfigBuilder::new().workers(0),
+            DriverConfigBuilder::new().interface(" , "),
+            DriverConfigBuilder::new().kafka("localhost:9092", "dns events"),
+            DriverConfigBuilder::new().bpf_sample_rate(0),
+        ] {
+            assert!(matches!(builder.build(), Err(Error::Config(_))));
+        }
+    }
+}
@@ -0,0 +1,367 @@
+//! 查询/响应关联
+//! 将响应与其对应的查询配对并计算时延
+//!
+//! 限制：时延依赖抓包时间戳。多队列抓包或时钟调整可能导致响应时间戳早于查询，
+//! 此时时延被截断为0并计入`correlation.negative_latency`；批内排序只能修正
+//! 同一批次内的乱序，跨批次的乱序无法修正。
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+
+use crate::core::stats::StatsCounter;
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsProtocol};
+
+/// 流标识 (src_ip, dst_ip, src_port, dst_port)
+pub type FlowKey = (IpAddr, IpAddr, u16, u16);
+
+/// 关联键
+///
+/// 事务ID只有16位，同一客户端的并发查询可能重复，
+/// 因此键中同时包含问题名和查询类型。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CorrelationKey {
+    client_ip: IpAddr,
+    server_ip: IpAddr,
+    client_port: u16,
+    server_port: u16,
+    transaction_id: u16,
+    name: String,
+    record_type: u16,
+}
+
+impl CorrelationKey {
+    /// 从消息构造关联键，响应的流方向需要翻转
+    fn new(flow: FlowKey, message: &DnsMessage) -> Self {
+        let (src_ip, dst_ip, src_port, dst_port) = flow;
+        let (client_ip, server_ip, client_port, server_port) = match message.message_type {
+            DnsMessageType::Query => (src_ip, dst_ip, src_port, dst_port),
+            DnsMessageType::Response => (dst_ip, src_ip, dst_port, src_port),
+        };
+
+        // 名称统一小写，兼容0x20大小写随机化
+        let (name, record_type) = match message.questions.first() {
+            Some(q) => (q.name.to_ascii_lowercase(), u16::from(q.record_type)),
+            None => (String::new(), 0),
+        };
+
+        CorrelationKey {
+            client_ip,
+            server_ip,
+            client_port,
+            server_port,
+            transaction_id: message.transaction_id,
+            name,
+            record_type,
+        }
+    }
+}
+
+/// 关联结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correlation {
+    /// 事务ID
+    pub transaction_id: u16,
+    /// 问题名
+    pub name: String,
+    /// 查询时间戳（微秒）
+    pub query_timestamp: u64,
+    /// 响应时间戳（微秒）
+    pub response_timestamp: u64,
+    /// 时延（微秒），时间戳乱序时截断为0
+    pub latency_us: u64,
+}
+
+/// 查询关联器
+pub struct QueryCorrelator {
+    /// 未应答的查询，同键的重传按到达顺序排队
+    pending: HashMap<CorrelationKey, VecDeque<u64>>,
+    /// 未应答查询总数
+    pending_count: usize,
+    /// 最大未应答查询数
+    max_pending: usize,
+    /// 查询超时时间（微秒）
+    timeout_us: u64,
+    /// 批量处理前是否按时间戳排序
+    sort_batches: bool,
+}
+
+impl QueryCorrelator {
+    /// 创建新的查询关联器
+    pub fn new(max_pending: usize, timeout_us: u64) -> Self {
+        QueryCorrelator {
+            pending: HashMap::new(),
+            pending_count: 0,
+            max_pending,
+            timeout_us,
+            sort_batches: false,
+        }
+    }
+
+    /// 批量处理前按时间戳排序，缓解多队列抓包带来的小范围乱序
+    pub fn with_batch_sorting(mut self, enabled: bool) -> Self {
+        self.sort_batches = enabled;
+        self
+    }
+
+    /// 批量处理消息，返回所有匹配成功的关联结果
+    pub fn observe_batch(
+        &mut self,
+        batch: &mut [(FlowKey, DnsMessage)],
+        stats: &StatsCounter,
+    ) -> Vec<Correlation> {
+        if self.sort_batches {
+            batch.sort_by_key(|(_, message)| message.timestamp);
+        }
+
+        batch
+            .iter()
+            .filter_map(|(flow, message)| self.observe(*flow, message, stats))
+            .collect()
+    }
+
+    /// 处理一条消息，响应匹配成功时返回关联结果
+    pub fn observe(
+        &mut self,
+        flow: FlowKey,
+        message: &DnsMessage,
+        stats: &StatsCounter,
+    ) -> Option<Correlation> {
+        let key = CorrelationKey::new(flow, message);
+
+        match message.message_type {
+            DnsMessageType::Query => {
+                if self.pending_count >= self.max_pending {
+                    stats.increment("correlation.pending_overflow");
+                    return None;
+                }
+
+                self.pending
+                    .entry(key)
+                    .or_insert_with(VecDeque::new)
+                    .push_back(message.timestamp);
+                self.pending_count += 1;
+                None
+            }
+            DnsMessageType::Response => {
+                let query_timestamp = match self.pending.get_mut(&key) {
+                    Some(queue) => {
+                        let timestamp = queue.pop_front();
+                        if queue.is_empty() {
+                            self.pending.remove(&key);
+                        }
+                        timestamp
+                    }
+                    None => None,
+                };
+
+                match query_timestamp {
+                    Some(query_timestamp) => {
+                        self.pending_count -= 1;
+                        stats.increment("correlation.matched");
+
+                        // 响应早于查询说明时间戳乱序，截断为0
+                        let latency_us = match message.timestamp.checked_sub(query_timestamp) {
+                            Some(latency) => latency,
+                            None => {
+                                stats.increment("correlation.negative_latency");
+                                0
+                            }
+                        };
+
+                        Some(Correlation {
+                            transaction_id: key.transaction_id,
+                            name: key.name,
+                            query_timestamp,
+                            response_timestamp: message.timestamp,
+                            latency_us,
+                        })
+                    }
+                    None => {
+                        stats.increment("correlation.unmatched_response");
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// 处理一条消息，未匹配到查询的响应标记为`unsolicited`
+    ///
+    /// 单向镜像或主动推送的响应仍会完整输出，标记用于告知分析人员未观察到查询。
+    pub fn observe_and_mark(
+        &mut self,
+        flow: FlowKey,
+        message: &mut DnsMessage,
+        stats: &StatsCounter,
+    ) -> Option<Correlation> {
+        let correlation = self.observe(flow, message, stats);
+
+        if message.message_type == DnsMessageType::Response && correlation.is_none() {
+            message.unsolicited = true;
+            if let DnsProtocol::Udp = message.protocol {
+                stats.increment("dns.udp.unsolicited_response");
+            }
+        }
+
+        correlation
+    }
+
+    /// 清理超时未应答的查询
+    pub fn expire(&mut self, now_us: u64, stats: &StatsCounter) {
+        let deadline = now_us.saturating_sub(self.timeout_us);
+        let mut expired = 0;
+
+        self.pending.retain(|_, queue| {
+            let before = queue.len();
+            queue.retain(|&timestamp| timestamp > deadline);
+            expired += before - queue.len();
+            !queue.is_empty()
+        });
+
+        if expired > 0 {
+            self.pending_count -= expired;
+            stats.add("correlation.expired", expired as u64);
+        }
+    }
+
+    /// 未应答查询数
+    pub fn pending(&self) -> usize {
+        self.pending_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{MemoryOutput, OutputConfig, OutputManager};
+    use crate::protocols::dns::{DnsClass, DnsQuestion, DnsRecordType};
+    use std::net::Ipv4Addr;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53));
+
+    fn message(message_type: DnsMessageType, id: u16, name: &str, timestamp: u64) -> DnsMessage {
+        DnsMessage {
+            transaction_id: id,
+            message_type,
+            questions: vec![DnsQuestion {
+                name: name.to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            timestamp,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_same_id_queries_pair_by_name() {
+        let mut correlator = QueryCorrelator::new(1024, 5_000_000);
+        let stats = StatsCounter::new();
+        let query_flow = (CLIENT, SERVER, 40000, 53);
+        let response_flow = (SERVER, CLIENT, 53, 40000);
+
+        // 两个并发查询使用相同的事务ID
+        correlator.observe(query_flow, &message(DnsMessageType::Query, 7, "a.example", 100), &stats);
+        correlator.observe(query_flow, &message(DnsMessageType::Query, 7, "b.example", 200), &stats);
+        assert_eq!(correlator.pending(), 2);
+
+        // 响应乱序到达
+        let b = correlator
+            .observe(response_flow, &message(DnsMessageType::Response, 7, "B.example", 1200), &stats)
+            .unwrap();
+        let a = correlator
+            .observe(response_flow, &message(DnsMessageType::Response, 7, "a.example", 1500), &stats)
+            .unwrap();
+
+        assert_eq!(b.name, "b.example");
+        assert_eq!(b.latency_us, 1000);
+        assert_eq!(a.name, "a.example");
+        assert_eq!(a.latency_us, 1400);
+        assert_eq!(correlator.pending(), 0);
+        assert_eq!(stats.get("correlation.matched"), 2);
+    }
+
+    #[test]
+    fn test_negative_latency_clamped() {
+        let mut correlator = QueryCorrelator::new(1024, 5_000_000);
+        let stats = StatsCounter::new();
+
+        correlator.observe((CLIENT, SERVER, 1234, 53), &message(DnsMessageType::Query, 9, "x.example", 5_000), &stats);
+        let correlation = correlator
+            .observe((SERVER, CLIENT, 53, 1234), &message(DnsMessageType::Response, 9, "x.example", 4_000), &stats)
+            .unwrap();
+
+        assert_eq!(correlation.latency_us, 0);
+        assert_eq!(stats.get("correlation.negative_latency"), 1);
+    }
+
+    #[test]
+    fn test_batch_sorting_restores_order() {
+        let mut correlator = QueryCorrelator::new(1024, 5_000_000).with_batch_sorting(true);
+        let stats = StatsCounter::new();
+
+        // 同一批次内响应先于查询到达
+        let mut batch = vec![
+            ((SERVER, CLIENT, 53, 1234), message(DnsMessageType::Response, 3, "x.example", 2_000)),
+            ((CLIENT, SERVER, 1234, 53), message(DnsMessageType::Query, 3, "x.example", 1_000)),
+        ];
+        let correlations = correlator.observe_batch(&mut batch, &stats);
+
+        assert_eq!(correlations.len(), 1);
+        assert_eq!(correlations[0].latency_us, 1_000);
+        assert_eq!(stats.get("correlation.unmatched_response"), 0);
+    }
+
+    #[test]
+    fn test_lone_response_marked_unsolicited() {
+        let mut correlator = QueryCorrelator::new(1024, 5_000_000);
+        let stats = StatsCounter::new();
+
+        let output = MemoryOutput::new();
+        let messages = output.messages();
+        let mut manager = OutputManager::new(OutputConfig::default());
+        manager.add_output(Box::new(output));
+
+        // 只有响应方向
+        let mut response = message(DnsMessageType::Response, 5, "x.example", 1_000);
+        assert!(correlator
+            .observe_and_mark((SERVER, CLIENT, 53, 1234), &mut response, &stats)
+            .is_none());
+        manager.output(&response).unwrap();
+
+        // 有查询的响应不标记
+        let mut query = message(DnsMessageType::Query, 6, "y.example", 2_000);
+        correlator.observe_and_mark((CLIENT, SERVER, 1234, 53), &mut query, &stats);
+        let mut answered = message(DnsMessageType::Response, 6, "y.example", 2_500);
+        assert!(correlator
+            .observe_and_mark((SERVER, CLIENT, 53, 1234), &mut answered, &stats)
+            .is_some());
+        manager.output(&answered).unwrap();
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].unsolicited);
+        assert!(!messages[1].unsolicited);
+        assert!(!query.unsolicited);
+        assert_eq!(stats.get("dns.udp.unsolicited_response"), 1);
+    }
+
+    #[test]
+    fn test_unmatched_and_expired() {
+        let mut correlator = QueryCorrelator::new(1024, 1_000);
+        let stats = StatsCounter::new();
+
+        let response = message(DnsMessageType::Response, 1, "x.example", 10);
+        assert!(correlator.observe((SERVER, CLIENT, 53, 1234), &response, &stats).is_none());
+        assert_eq!(stats.get("correlation.unmatched_response"), 1);
+
+        let query = message(DnsMessageType::Query, 2, "y.example", 10);
+        correlator.observe((CLIENT, SERVER, 1234, 53), &query, &stats);
+        correlator.expire(5_000, &stats);
+        assert_eq!(correlator.pending(), 0);
+        assert_eq!(stats.get("correlation.expired"), 1);
+    }
+}
@@ -2,7 +2,7 @@
 //! 提供高性能网络数据包处理功能
 
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::time::Instant;
 
 #[cfg(feature = "dpdk")]
@@ -53,14 +53,14 @@ pub struct DpdkInstance {
     #[cfg(feature = "dpdk")]
     ports: HashMap<u16, Port>,
     /// 统计计数器
-    stats: Arc<Mutex<StatsCounter>>,
+    stats: Arc<StatsCounter>,
     /// 是否已初始化
     initialized: bool,
 }
 
 impl DpdkInstance {
     /// 创建新的DPDK实例
-    pub fn new(config: DpdkConfig, stats: Arc<Mutex<StatsCounter>>) -> Self {
+    pub fn new(config: DpdkConfig, stats: Arc<StatsCounter>) -> Self {
         DpdkInstance {
             config,
             #[cfg(feature = "dpdk")]
@@ -202,9 +202,7 @@ impl DpdkInstance {
 
                 if rx_count > 0 {
                     // 更新统计信息
-                    if let Ok(mut stats) = self.stats.lock() {
-                        stats.add("dpdk.rx_packets", rx_count as u64);
-                    }
+                    self.stats.add("dpdk.rx_packets", rx_count as u64);
 
                     // 处理接收到的数据包
                     for mbuf in mbufs.iter().take(rx_count) {
@@ -246,9 +244,7 @@ impl DpdkInstance {
 
                 // 更新统计信息
                 if tx_count > 0 {
-                    if let Ok(mut stats) = self.stats.lock() {
-                        stats.add("dpdk.tx_packets", tx_count as u64);
-                    }
+                    self.stats.add("dpdk.tx_packets", tx_count as u64);
                 }
 
                 return tx_count;
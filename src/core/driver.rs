@@ -1,15 +1,55 @@
 //! 抓包主驱动逻辑
 //! 负责协调捕获、解析和输出模块
 
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::capture::{CaptureConfig, create_capture};
+use crate::capture::{CaptureConfig, CapturedPacket, LinkType, MultiCapture, PacketCapture, create_capture};
+use crate::core::anomaly_dump::{AnomalyDump, AnomalyDumpConfig};
+use crate::core::correlation::QueryCorrelator;
+use crate::core::drop_monitor::DropMonitor;
+use crate::core::enrichment::{ReverseDnsConfig, ReverseDnsEnricher};
+use crate::core::flow_partition::{FlowHashPartitioner, WorkerPartitioner};
+use crate::core::interface_stats::InterfaceStatsReporter;
+use crate::core::metrics::MetricsExporter;
+use crate::core::packet_queue::{BackpressurePolicy, PacketQueue};
+use crate::core::pcap_tee::{PcapTee, PcapTeeConfig};
+use crate::core::resolver_role::ResolverRoles;
+use crate::core::sessions::WorkerSessions;
 use crate::core::stats::StatsCounter;
-use crate::output::{OutputConfig, OutputManager};
-use crate::protocols::detect::ProtocolDetector;
-use crate::protocols::dns::{DnsParser, UdpDnsParser};
+use crate::core::tunneling::{TunnelingConfig, TunnelingDetector};
+use crate::output::{
+    ChannelOutput, ErrorOutput, ErrorOutputConfig, Heartbeat, HeartbeatTimer, Output, OutputConfig, OutputManager,
+};
+use crate::protocols::decode::{
+    decode_ethernet_with_max_len, decode_raw_ip, Transport, DEFAULT_MAX_FRAME_LEN,
+};
+use crate::protocols::detect::{ProtocolDetectResult, ProtocolDetector};
+use crate::protocols::dns::{DnsMessage, DnsParser, DnsProtocol, UdpDnsParser};
+use crate::utils::time::current_time_micros;
+
+/// 最大未应答查询数
+const MAX_PENDING_QUERIES: usize = 65536;
+/// 查询超时时间（微秒）
+const QUERY_TIMEOUT_US: u64 = 5_000_000;
+/// 读线程到工作线程的队列总容量，按工作线程数平分
+const PACKET_QUEUE_CAPACITY: usize = 65536;
+/// 捕获丢包率告警阈值（百万分比，即0.1%）
+const DROP_WARNING_THRESHOLD_PPM: u64 = 1_000;
+/// 丢包告警最小间隔
+const DROP_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+/// 每个工作线程的最大TCP会话数
+const MAX_SESSIONS_PER_WORKER: usize = 4096;
+/// TCP会话超时时间（毫秒）
+const SESSION_TIMEOUT_MS: u64 = 30_000;
+/// 单个TCP会话的缓冲上限
+const MAX_TCP_MESSAGE_BUFFER: usize = 65535;
+/// 每个工作线程输出解析失败原因的最小间隔
+const PARSE_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(1);
 
 /// 驱动配置
 pub struct DriverConfig {
@@ -21,13 +61,71 @@ pub struct DriverConfig {
     pub stats_interval: u64,
     /// 工作线程数
     pub worker_threads: usize,
+    /// 累计计数器状态文件，设置后计数器跨重启保持单调递增
+    pub stats_state_path: Option<PathBuf>,
+    /// 是否按记录类型统计应答TTL直方图
+    pub ttl_histograms: bool,
+    /// 心跳间隔（秒），为0时不发送心跳
+    pub heartbeat_interval: u64,
+    /// 异常触发的原始报文转储，为空时不转储
+    pub anomaly_dump: Option<AnomalyDumpConfig>,
+    /// 工作线程队列满时的背压策略
+    pub backpressure: BackpressurePolicy,
+    /// 将所有捕获到的帧另存为轮转的pcap文件，为空时不保存
+    pub pcap_tee: Option<PcapTeeConfig>,
+    /// 每个统计周期单独输出捕获层计数（接收、丢包、字节数、接收速率）
+    pub interface_stats: bool,
+    /// DNS隧道检测阈值，为空时不检测
+    pub tunneling: Option<TunnelingConfig>,
+    /// 按TCP握手和挥手建立、结束会话，关闭时仅按空闲超时回收
+    pub tcp_handshake_tracking: bool,
+    /// 解析错误事件输出，为空时解析失败只计数
+    pub error_output: Option<ErrorOutputConfig>,
+    /// 递归解析器地址，用于区分客户端到解析器与解析器到上游的流量，为空时不划分
+    pub resolver_ips: Vec<IpAddr>,
+    /// Prometheus指标监听地址，为空时不导出
+    pub metrics_addr: Option<SocketAddr>,
+    /// 客户端地址的反向DNS富化，为空时不查询
+    pub reverse_dns: Option<ReverseDnsConfig>,
+}
+
+/// 关闭句柄
+///
+/// SIGINT、SIGTERM等停止信号统一通过该句柄触发优雅关闭，
+/// `Driver::start`返回前会停止捕获并关闭所有输出，确保日志落盘、Kafka发送完毕。
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    running: Arc<AtomicBool>,
+}
+
+impl ShutdownHandle {
+    /// 请求关闭，可在信号处理器等任意线程中调用
+    pub fn shutdown(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    /// 驱动是否仍在运行
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
 }
 
 /// 抓包驱动
 pub struct Driver {
     config: DriverConfig,
-    stats: Arc<Mutex<StatsCounter>>,
-    running: Arc<Mutex<bool>>,
+    stats: Arc<StatsCounter>,
+    /// 持久化的累计计数：从状态文件恢复的历史计数加上本次运行已结束周期的计数，
+    /// 由指标端点导出并写入状态文件
+    cumulative: Arc<StatsCounter>,
+    /// 本次运行已结束周期的计数，不含恢复的历史计数，心跳只按它统计
+    run_totals: Arc<StatsCounter>,
+    running: Arc<AtomicBool>,
+    /// 外部提供的捕获源，为空时按配置创建
+    captures: Vec<Box<dyn PacketCapture>>,
+    /// 数据包到工作线程的分配策略
+    partitioner: Arc<dyn WorkerPartitioner>,
+    /// 外部提供的输出，启动时与配置启用的输出一起注册
+    outputs: Vec<Box<dyn Output + Send>>,
 }
 
 impl Driver {
@@ -35,168 +133,766 @@ impl Driver {
     pub fn new(config: DriverConfig) -> Self {
         Driver {
             config,
-            stats: Arc::new(Mutex::new(StatsCounter::new())),
-            running: Arc::new(Mutex::new(false)),
+            stats: Arc::new(StatsCounter::new()),
+            cumulative: Arc::new(StatsCounter::new()),
+            run_totals: Arc::new(StatsCounter::new()),
+            running: Arc::new(AtomicBool::new(false)),
+            captures: Vec::new(),
+            partitioner: Arc::new(FlowHashPartitioner),
+            outputs: Vec::new(),
         }
     }
 
+    /// 使用多个捕获源创建驱动，所有源的数据包合并进同一处理流水线
+    pub fn with_captures(config: DriverConfig, captures: Vec<Box<dyn PacketCapture>>) -> Self {
+        let mut driver = Driver::new(config);
+        driver.captures = captures;
+        driver
+    }
+
+    /// 设置工作线程分配策略，默认按五元组哈希分配
+    pub fn with_partitioner(mut self, partitioner: Arc<dyn WorkerPartitioner>) -> Self {
+        self.partitioner = partitioner;
+        self
+    }
+
+    /// 添加自定义输出，与配置启用的输出一起接收消息
+    pub fn with_output(mut self, output: Box<dyn Output + Send>) -> Self {
+        self.outputs.push(output);
+        self
+    }
+
+    /// 把每条消息发送到通道，嵌入调用的程序可在自己的线程中接收
+    pub fn with_channel(self, sender: crossbeam::channel::Sender<DnsMessage>) -> Self {
+        self.with_output(Box::new(ChannelOutput::new(sender)))
+    }
+
     /// 启动抓包
     pub fn start(&mut self) -> crate::error::Result<()> {
+        // 从状态文件恢复累计计数器，失败时不进入运行状态
+        let stats_state_path = self.config.stats_state_path.clone();
+
+        if let Some(path) = stats_state_path.as_ref().filter(|path| path.exists()) {
+            self.cumulative = Arc::new(StatsCounter::load_state(path)?);
+        }
+        self.run_totals = Arc::new(StatsCounter::new());
+
+        // 先绑定指标端口，地址被占用时不进入运行状态
+        let metrics_exporter = match self.config.metrics_addr {
+            Some(addr) => Some(MetricsExporter::bind(addr).map_err(|e| {
+                crate::error::Error::Output(format!("Failed to bind metrics listener on {}: {}", addr, e))
+            })?),
+            None => None,
+        };
+
         // 设置运行状态
-        {
-            let mut running = self.running.lock().unwrap();
-            if *running {
-                return Err(crate::error::Error::Other(
-                    "Driver already running".to_string(),
-                ));
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err(crate::error::Error::Other(
+                "Driver already running".to_string(),
+            ));
+        }
+
+        // 创建并启动捕获实例，在启动任何线程、打开任何输出之前完成，
+        // 失败时只需退出运行状态，指标端口随未启动的监听器一起释放
+        let mut capture: Box<dyn PacketCapture> = if self.captures.is_empty() {
+            create_capture(self.config.capture.clone(), Arc::clone(&self.stats))
+        } else {
+            Box::new(MultiCapture::new(std::mem::take(&mut self.captures)))
+        };
+        if let Err(e) = capture.initialize() {
+            self.running.store(false, Ordering::SeqCst);
+            // 配置错误原样返回，便于提示用户修正配置
+            if let crate::error::Error::Config(_) = e {
+                return Err(e);
             }
-            *running = true;
+            return Err(crate::error::Error::Capture(format!(
+                "Failed to initialize capture: {}", e
+            )));
         }
+        if let Err(e) = capture.start_capture() {
+            self.running.store(false, Ordering::SeqCst);
+            return Err(crate::error::Error::Capture(format!(
+                "Failed to start capture: {}", e
+            )));
+        }
+
+        // 导出累计统计，每个统计周期结束时更新
+        let metrics_handle = metrics_exporter.map(|exporter| {
+            if let Ok(addr) = exporter.local_addr() {
+                println!("Prometheus指标地址: http://{}/metrics", addr);
+            }
+            exporter.spawn(Arc::clone(&self.cumulative), Arc::clone(&self.running))
+        });
 
         // 创建协议检测器
-        let detector = Arc::new(Mutex::new(ProtocolDetector::new()));
+        let detector = Arc::new(ProtocolDetector::new());
+
+        // DNS解析器只有配置没有状态，每个工作线程各建一个，解析时无需加锁
+        // dnstap和原始报文模式的Kafka需要原始报文
+        let keep_raw = self.config.output.enable_dnstap
+            || (self.config.output.enable_kafka && self.config.output.kafka_config.raw_payload);
+        let ttl_histograms = self.config.ttl_histograms;
+        let tcp_handshake_tracking = self.config.tcp_handshake_tracking;
+
+        // 创建查询关联器
+        let correlator = Arc::new(Mutex::new(QueryCorrelator::new(
+            MAX_PENDING_QUERIES,
+            QUERY_TIMEOUT_US,
+        )));
+
+        // 反向DNS查询在独立的有界任务池中执行，所有工作线程共享缓存和在途上限
+        let reverse_dns = self.config.reverse_dns.clone().map(|config| Arc::new(ReverseDnsEnricher::new(config)));
 
-        // 创建DNS解析器
-        let dns_parser = Arc::new(Mutex::new(UdpDnsParser::new(65535)));
+        // 创建异常转储器
+        let anomaly_dump = self
+            .config
+            .anomaly_dump
+            .clone()
+            .map(|config| Arc::new(Mutex::new(AnomalyDump::new(config))));
+
+        // 创建解析错误输出，打开失败时只计数
+        let anonymize = self.config.output.anonymize_client_ip.clone();
+        let error_output = self.config.error_output.clone().and_then(|config| match ErrorOutput::new(config) {
+            Ok(output) => Some(Arc::new(Mutex::new(output.with_anonymization(anonymize.clone())))),
+            Err(e) => {
+                eprintln!("Failed to initialize error output: {}", e);
+                None
+            }
+        });
+
+        // 创建原始报文旁路写入器
+        let pcap_tee = self
+            .config
+            .pcap_tee
+            .clone()
+            .map(|config| Arc::new(Mutex::new(PcapTee::new(config))));
 
         // 创建输出管理器
-        let output_manager = Arc::new(Mutex::new(OutputManager::new(self.config.output.clone())));
+        let mut output_manager = OutputManager::new(self.config.output.clone());
+        for output in std::mem::take(&mut self.outputs) {
+            output_manager.add_output(output);
+        }
+        let output_manager = Arc::new(Mutex::new(output_manager));
 
-        // 创建捕获实例
-        let capture = create_capture(self.config.capture.clone(), Arc::clone(&self.stats));
+        // 只有读线程收包，统计线程每秒读取一次捕获统计，工作线程不接触capture
+        let capture = Arc::new(Mutex::new(capture));
+
+        // 每个工作线程一个队列，读线程按流分配，同一条流只由一个工作线程按序处理
+        let worker_count = self.config.worker_threads.max(1);
+        let queues: Vec<Arc<PacketQueue<CapturedPacket>>> = (0..worker_count)
+            .map(|_| {
+                Arc::new(PacketQueue::new(
+                    PACKET_QUEUE_CAPACITY / worker_count,
+                    self.config.backpressure,
+                ))
+            })
+            .collect();
+        let reader_handle = {
+            let queues = queues.clone();
+            let partitioner = Arc::clone(&self.partitioner);
+            let capture = Arc::clone(&capture);
+            let stats = Arc::clone(&self.stats);
+            let running = Arc::clone(&self.running);
+
+            thread::spawn(move || {
+                while running.load(Ordering::Relaxed) {
+                    let (packets, finished) = {
+                        let mut capture = capture.lock().unwrap();
+                        let packets = capture.receive_packets(64);
+                        let finished = packets.is_empty() && capture.is_finished();
+                        (packets, finished)
+                    };
+                    // 离线文件读完后关闭队列，工作线程处理完剩余数据包后退出
+                    if finished {
+                        queues.iter().for_each(|queue| queue.close());
+                        break;
+                    }
+                    if packets.is_empty() {
+                        thread::sleep(Duration::from_millis(1));
+                        continue;
+                    }
+
+                    for packet in packets {
+                        let worker = partitioner.worker_for(&packet, queues.len());
+                        if let Some(counter) = queues[worker].push(packet).counter() {
+                            stats.increment(counter);
+                        }
+                    }
+                }
+            })
+        };
 
         // 创建统计线程
         let stats_clone = Arc::clone(&self.stats);
+        let cumulative = Arc::clone(&self.cumulative);
+        let run_totals = Arc::clone(&self.run_totals);
         let running_clone = Arc::clone(&self.running);
         let stats_interval = self.config.stats_interval;
+        let stats_output = Arc::clone(&output_manager);
+        let stats_capture = Arc::clone(&capture);
+        let heartbeat_interval = self.config.heartbeat_interval;
+        let stats_correlator = Arc::clone(&correlator);
+        let mut drop_monitor = DropMonitor::new(
+            DROP_WARNING_THRESHOLD_PPM,
+            DROP_WARNING_INTERVAL,
+            self.config.capture.buffer_size,
+            self.config.worker_threads,
+        );
+        let mut interface_stats = if self.config.interface_stats {
+            Some(InterfaceStatsReporter::new())
+        } else {
+            None
+        };
 
-        thread::spawn(move || {
+        let stats_handle = thread::spawn(move || {
             let mut last_stats = Instant::now();
-
-            while *running_clone.lock().unwrap() {
+            let mut heartbeat_timer = if heartbeat_interval > 0 {
+                Some(HeartbeatTimer::new(Duration::from_secs(heartbeat_interval), last_stats))
+            } else {
+                None
+            };
+            while running_clone.load(Ordering::Relaxed) {
                 thread::sleep(Duration::from_secs(1));
 
                 let now = Instant::now();
+
+                // 清理超时未应答的查询
+                stats_correlator.lock().unwrap().expire(current_time_micros(), &stats_clone);
+
+                // 捕获丢包率作为瞬时值上报，持续丢包时限频告警
+                let capture_stats = stats_capture.lock().unwrap().get_stats();
+                let drops = drop_monitor.update(capture_stats.rx_packets, capture_stats.dropped_packets, now);
+                stats_clone.set_gauge("capture.drop_rate_ppm", drops.rate_ppm);
+                stats_clone.set_gauge("capture.dropped_packets", capture_stats.dropped_packets);
+                stats_clone.set_gauge("capture.if_dropped_packets", capture_stats.if_dropped_packets);
+                if let Some(warning) = drops.warning {
+                    eprintln!("{}", warning);
+                }
+
+                // 输出按时间落盘、发送不依赖新消息到达
+                stats_output.lock().unwrap().tick();
+
+                // 没有流量时也发送心跳
+                if let Some(uptime) = heartbeat_timer.as_mut().and_then(|timer| timer.poll(now)) {
+                    // 只统计本次运行，恢复的历史计数不计入
+                    let processed = run_totals.get("packet.processed") + stats_clone.get("packet.processed");
+                    let heartbeat = Heartbeat {
+                        timestamp: current_time_micros(),
+                        uptime_secs: uptime.as_secs(),
+                        packets_processed: processed,
+                        capture_rx_packets: capture_stats.rx_packets,
+                        capture_dropped_packets: capture_stats.dropped_packets,
+                    };
+                    let _ = stats_output.lock().unwrap().heartbeat(&heartbeat);
+                }
+
                 if now.duration_since(last_stats).as_secs() >= stats_interval {
-                    let mut stats = stats_clone.lock().unwrap();
+                    let (sinks, output_stats) = {
+                        let mut output = stats_output.lock().unwrap();
+                        (output.sink_stats(), output.take_stats())
+                    };
+                    // 取出本周期的统计后再汇总，工作线程同时继续计入下一个周期
+                    let stats = stats_clone.take();
+                    stats.merge(&output_stats);
+                    for sink in sinks {
+                        stats.set_gauge(&format!("output.{}.queue_depth", sink.name), sink.queue_depth as u64);
+                        stats.set_gauge(&format!("output.{}.dropped", sink.name), sink.dropped);
+                    }
+                    if let Some(reporter) = interface_stats.as_mut() {
+                        println!("{}", reporter.report(&capture_stats, now, &stats));
+                    }
+
+                    // 计数累加，队列深度、丢包数等瞬时值取最新值，状态文件只保存计数
+                    run_totals.merge(&stats);
+                    cumulative.merge(&stats);
+                    if let Some(path) = &stats_state_path {
+                        if let Err(e) = cumulative.save_state(path) {
+                            eprintln!("Failed to save stats state: {}", e);
+                        }
+                    }
+
                     stats.print_and_reset();
                     last_stats = now;
                 }
             }
+
+            // 关闭时合并最后一个统计周期的计数，避免重启后计数回退
+            run_totals.merge(&stats_clone);
+            cumulative.merge(&stats_clone);
+            if let Some(path) = &stats_state_path {
+                if let Err(e) = cumulative.save_state(path) {
+                    eprintln!("Failed to save stats state: {}", e);
+                }
+            }
         });
 
+        // 帧长度上限跟随快照长度，未设置时使用默认值
+        let max_frame_len = match self.config.capture.snaplen {
+            snaplen if snaplen > 0 => snaplen as usize,
+            _ => DEFAULT_MAX_FRAME_LEN,
+        };
+
         // 创建工作线程
         let mut worker_handles = Vec::new();
 
-        // 将capture包装在Arc<Mutex<>>中以便多线程共享
-        let capture = Arc::new(Mutex::new(capture));
-
-        for _ in 0..self.config.worker_threads {
+        for queue in &queues {
             let detector_clone = Arc::clone(&detector);
-            let dns_parser_clone = Arc::clone(&dns_parser);
             let output_clone = Arc::clone(&output_manager);
-            let stats_clone = Arc::clone(&self.stats);
+            let correlator_clone = Arc::clone(&correlator);
+            // 工作线程直接更新共享计数器，计数器分片加锁，无需本地累加后合并
+            let stats = Arc::clone(&self.stats);
             let running_clone = Arc::clone(&self.running);
-            let capture_clone = Arc::clone(&capture);
+            let queue_clone = Arc::clone(queue);
+            let anomaly_dump_clone = anomaly_dump.clone();
+            let pcap_tee_clone = pcap_tee.clone();
+            let error_output_clone = error_output.clone();
+            let anonymize_clone = anonymize.clone();
+            let reverse_dns_clone = reverse_dns.clone();
+            let tunneling = self.config.tunneling.clone().map(TunnelingDetector::new);
+            let resolver_roles =
+                (!self.config.resolver_ips.is_empty()).then(|| ResolverRoles::new(&self.config.resolver_ips));
 
             let handle = thread::spawn(move || {
-                while *running_clone.lock().unwrap() {
-                    // 从捕获器获取数据包
-                    let packets = {
-                        let mut capture = capture_clone.lock().unwrap();
-                        capture.receive_packets(10)
-                    };
+                let mut last_parse_error: Option<Instant> = None;
+                let mut parser = UdpDnsParser::new(65535)
+                    .with_keep_raw(keep_raw)
+                    .with_ttl_histograms(ttl_histograms);
+                let mut mdns_parser = UdpDnsParser::new(65535)
+                    .with_protocol(DnsProtocol::Mdns)
+                    .with_keep_raw(keep_raw)
+                    .with_ttl_histograms(ttl_histograms);
+                // 本线程独占的流会话表，同一条流的数据包只会分到这里
+                let mut sessions =
+                    WorkerSessions::new(MAX_TCP_MESSAGE_BUFFER, MAX_SESSIONS_PER_WORKER, SESSION_TIMEOUT_MS)
+                        .with_handshake_tracking(tcp_handshake_tracking);
 
-                    for packet_data in packets {
-                        // 检测协议
-                        let result = {
-                            let detector = detector_clone.lock().unwrap();
-                            detector.detect(&packet_data, 53, 53) // 简化：假设都是DNS端口
-                        };
+                while running_clone.load(Ordering::Relaxed) {
+                    // 从读线程队列获取数据包
+                    let packets = queue_clone.pop_batch(10, Duration::from_millis(10));
+                    if packets.is_empty() && queue_clone.is_closed() {
+                        break;
+                    }
 
-                        // 处理检测结果
-                        match result {
-                            crate::protocols::detect::ProtocolDetectResult::Dns(protocol) => {
-                                // 解析DNS消息
-                                let dns_message = {
-                                    let mut parser = dns_parser_clone.lock().unwrap();
-                                    let mut stats = stats_clone.lock().unwrap();
-                                    parser.parse(&packet_data, &mut stats)
-                                };
+                    for packet in packets {
+                        // 优先使用捕获后端提供的抓包时间
+                        let timestamp = packet.timestamp.unwrap_or_else(current_time_micros);
 
-                                if let Some(message) = dns_message {
-                                    // 更新统计
-                                    {
-                                        let mut stats = stats_clone.lock().unwrap();
-                                        stats.increment("packet.processed");
-                                    }
+                        // 保留原始帧，异常触发时写入pcap
+                        if let Some(dump) = &anomaly_dump_clone {
+                            dump.lock().unwrap().record(
+                                timestamp,
+                                &packet.data,
+                                packet.link_type,
+                                Instant::now(),
+                                &stats,
+                            );
+                        }
+
+                        // 旁路保存原始帧
+                        if let Some(tee) = &pcap_tee_clone {
+                            tee.lock().unwrap().record(
+                                timestamp,
+                                &packet.data,
+                                packet.link_type,
+                                Instant::now(),
+                                &stats,
+                            );
+                        }
 
-                                    // 输出结果
-                                    {
-                                        let mut output = output_clone.lock().unwrap();
-                                        let _ = output.output(&message);
+                        // 按链路层类型解码网络层和传输层头部
+                        let decoded = match packet.link_type {
+                            LinkType::Ethernet => {
+                                decode_ethernet_with_max_len(&packet.data, max_frame_len, &stats)
+                            }
+                            LinkType::RawIp => decode_raw_ip(&packet.data, max_frame_len, &stats),
+                            LinkType::Other(_) => {
+                                stats.increment("decode.unsupported_link_type");
+                                None
+                            }
+                        };
+                        let decoded = match decoded {
+                            Some(decoded) => decoded,
+                            None => continue,
+                        };
+
+                        // 检测协议，按检测结果分发：普通UDP DNS和mDNS直接解析，
+                        // TCP/DoT/DoH/DoQ交给本线程独占的会话表重组
+                        let result = detector_clone.detect(
+                            decoded.payload,
+                            decoded.src_port,
+                            decoded.dst_port,
+                            decoded.transport,
+                        );
+
+                        let messages = match result {
+                            ProtocolDetectResult::Dns(protocol @ (DnsProtocol::Udp | DnsProtocol::Mdns)) => {
+                                // 解析DNS消息；mDNS格式相同，一个报文可能带多个问题，
+                                // 查询的应答区是已知应答抑制记录，class最高位由mDNS解析器拆为单独的标志
+                                let parser = match protocol {
+                                    DnsProtocol::Mdns => &mut mdns_parser,
+                                    _ => &mut parser,
+                                };
+                                match parser.try_parse(decoded.payload, &stats) {
+                                    Ok(message) => vec![message],
+                                    Err(e) => {
+                                        // 解析失败原因限频输出，计数由解析器负责
+                                        if last_parse_error.is_none_or(|last| last.elapsed() >= PARSE_ERROR_LOG_INTERVAL) {
+                                            eprintln!(
+                                                "DNS parse error from {}: {}",
+                                                SocketAddr::new(anonymize_clone.apply(decoded.src_ip), decoded.src_port),
+                                                e
+                                            );
+                                            last_parse_error = Some(Instant::now());
+                                        }
+                                        if let Some(errors) = &error_output_clone {
+                                            errors.lock().unwrap().record(
+                                                timestamp,
+                                                &e.to_string(),
+                                                (decoded.src_ip, decoded.dst_ip, decoded.src_port, decoded.dst_port),
+                                                decoded.payload,
+                                                Instant::now(),
+                                                &stats,
+                                            );
+                                        }
+                                        continue;
                                     }
                                 }
                             }
-                            crate::protocols::detect::ProtocolDetectResult::NeedMoreData => {
+                            ProtocolDetectResult::Dns(protocol) => {
+                                sessions.process(protocol, &decoded, timestamp / 1_000, &stats)
+                            }
+                            ProtocolDetectResult::NeedMoreData if sessions.has_doh_session(&decoded) => {
+                                sessions.process(DnsProtocol::Doh, &decoded, timestamp / 1_000, &stats)
+                            }
+                            ProtocolDetectResult::NeedMoreData => {
                                 // 需要更多数据，暂时跳过
-                                let mut stats = stats_clone.lock().unwrap();
                                 stats.increment("packet.need_more_data");
+                                continue;
+                            }
+                            ProtocolDetectResult::Unknown if decoded.transport == Transport::Tcp => {
+                                // 非DNS端口的TCP流量
+                                stats.increment("packet.tcp_skipped");
+                                continue;
                             }
-                            crate::protocols::detect::ProtocolDetectResult::Unknown => {
+                            ProtocolDetectResult::Unknown => {
                                 // 未知协议，丢弃
-                                let mut stats = stats_clone.lock().unwrap();
                                 stats.increment("packet.unknown");
+                                continue;
+                            }
+                        };
+
+                        for mut message in messages {
+                            message.timestamp = timestamp;
+                            message.src_ip = decoded.src_ip;
+                            message.dst_ip = decoded.dst_ip;
+                            message.src_port = decoded.src_port;
+                            message.dst_port = decoded.dst_port;
+                            message.unreachable = decoded.unreachable;
+                            message.interface = Some(Arc::clone(&packet.source));
+
+                            if let Some(detector) = &tunneling {
+                                detector.inspect(&mut message, &stats);
+                            }
+                            if let Some(roles) = &resolver_roles {
+                                roles.tag(&mut message);
+                            }
+                            if let Some(enricher) = &reverse_dns_clone {
+                                enricher.enrich(&mut message, &stats);
+                            }
+
+                            // 更新统计并关联查询，未见查询的响应会被标记
+                            {
+                                stats.increment("packet.processed");
+                                // ICMP内嵌的查询已在原始数据报中关联过，不重复关联；
+                                // mDNS响应发往组播地址且常无对应查询，不参与关联
+                                if !message.unreachable && !matches!(message.protocol, DnsProtocol::Mdns) {
+                                    let flow = (decoded.src_ip, decoded.dst_ip, decoded.src_port, decoded.dst_port);
+                                    correlator_clone
+                                        .lock()
+                                        .unwrap()
+                                        .observe_and_mark(flow, &mut message, &stats);
+                                }
+
+                                // 伪造响应、隧道、放大、水刑等异常触发原始报文转储
+                                if let Some(dump) = &anomaly_dump_clone {
+                                    dump.lock().unwrap().inspect(
+                                        &message,
+                                        decoded.payload.len(),
+                                        Instant::now(),
+                                        &stats,
+                                    );
+                                }
+                            }
+
+                            // 输出结果
+                            {
+                                let mut output = output_clone.lock().unwrap();
+                                let _ = output.output(&message);
                             }
                         }
                     }
-
-                    // 短暂休眠避免CPU占用过高
-                    thread::sleep(Duration::from_millis(1));
                 }
             });
 
             worker_handles.push(handle);
         }
 
-        // 启动捕获
-        if let Err(e) = {
-            let mut capture = capture.lock().unwrap();
-            capture.initialize()
-        } {
-            let mut running = self.running.lock().unwrap();
-            *running = false;
-            return Err(crate::error::Error::Capture(format!(
-                "Failed to initialize capture: {}", e
-            )));
+        // 等待所有工作线程完成，关闭队列以唤醒可能阻塞的读线程
+        for handle in worker_handles {
+            let _ = handle.join();
         }
-        if let Err(e) = {
-            let mut capture = capture.lock().unwrap();
-            capture.start_capture()
-        } {
-            let mut running = self.running.lock().unwrap();
-            *running = false;
-            return Err(crate::error::Error::Capture(format!(
-                "Failed to start capture: {}", e
-            )));
+        queues.iter().for_each(|queue| queue.close());
+        let _ = reader_handle.join();
+        // 离线文件读完时工作线程自行退出，同时停止统计线程，等待其保存最终状态
+        self.running.store(false, Ordering::SeqCst);
+        let _ = stats_handle.join();
+        if let Some(handle) = metrics_handle {
+            let _ = handle.join();
         }
 
-        // 等待所有工作线程完成
-        for handle in worker_handles {
-            let _ = handle.join();
+        // 停止捕获后关闭输出，异步队列中的剩余消息会在关闭时处理完
+        capture.lock().unwrap().stop_capture();
+        if let Some(dump) = &anomaly_dump {
+            dump.lock().unwrap().finish();
         }
+        if let Some(tee) = &pcap_tee {
+            tee.lock().unwrap().finish();
+        }
+        if let Some(errors) = &error_output {
+            errors.lock().unwrap().flush();
+        }
+        output_manager.lock().unwrap().close()?;
 
         Ok(())
     }
 
     /// 停止抓包
     pub fn stop(&mut self) {
-        let mut running = self.running.lock().unwrap();
-        *running = false;
+        self.shutdown_handle().shutdown();
+    }
+
+    /// 获取关闭句柄，需在`start`阻塞前获取
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            running: Arc::clone(&self.running),
+        }
     }
 
     /// 获取统计信息
     pub fn get_stats(&self) -> StatsCounter {
-        self.stats.lock().unwrap().clone()
+        StatsCounter::clone(&self.stats)
+    }
+
+    /// 获取累计统计信息，包含从状态文件恢复的计数、已结束周期的计数和最新的瞬时值
+    pub fn get_cumulative_stats(&self) -> StatsCounter {
+        StatsCounter::clone(&self.cumulative)
+    }
+
+    /// 获取本次运行已结束周期的统计信息，不含从状态文件恢复的计数
+    pub fn get_run_stats(&self) -> StatsCounter {
+        StatsCounter::clone(&self.run_totals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::{FileCapture, MemoryCapture};
+    use crate::output::MemoryOutput;
+    use crate::protocols::dns::DnsMessageType;
+
+    fn config() -> DriverConfig {
+        DriverConfig {
+            capture: CaptureConfig {
+                mode: crate::capture::CaptureMode::Pcap,
+                interface: "test".to_string(),
+                filter: String::new(),
+                promiscuous: false,
+                snaplen: 65535,
+                timeout_ms: 1000,
+                buffer_size: 0,
+                dpdk_config: None,
+                xdp_config: None,
+                bpf_sample_rate: None,
+            },
+            output: OutputConfig::default(),
+            stats_interval: 10,
+            worker_threads: 2,
+            stats_state_path: None,
+            ttl_histograms: false,
+            heartbeat_interval: 0,
+            anomaly_dump: None,
+            pcap_tee: None,
+            backpressure: BackpressurePolicy::DropNewest,
+            interface_stats: false,
+            tunneling: None,
+            tcp_handshake_tracking: false,
+            error_output: None,
+            resolver_ips: Vec::new(),
+            metrics_addr: None,
+            reverse_dns: None,
+        }
+    }
+
+    #[test]
+    fn test_shutdown_handle_stops_running_driver() {
+        let capture: Box<dyn PacketCapture> = Box::new(MemoryCapture::new("test", Vec::new()));
+        let mut driver = Driver::with_captures(config(), vec![capture]);
+        let shutdown = driver.shutdown_handle();
+
+        let handle = thread::spawn(move || driver.start());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !shutdown.is_running() {
+            assert!(Instant::now() < deadline, "driver did not start");
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // 信号处理器调用的同一关闭流程
+        shutdown.shutdown();
+
+        assert!(handle.join().unwrap().is_ok());
+        assert!(!shutdown.is_running());
+    }
+
+    #[test]
+    fn test_capture_failure_starts_no_threads() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let missing = std::env::temp_dir().join(format!("dns_spider_missing_{}.pcap", std::process::id()));
+        let capture: Box<dyn PacketCapture> = Box::new(FileCapture::new(
+            CaptureConfig {
+                interface: missing.display().to_string(),
+                ..CaptureConfig::default()
+            },
+            Arc::new(StatsCounter::new()),
+        ));
+        let mut driver = Driver::with_captures(
+            DriverConfig {
+                metrics_addr: Some(addr),
+                ..config()
+            },
+            vec![capture],
+        );
+
+        assert!(matches!(driver.start(), Err(crate::error::Error::Capture(_))));
+        assert!(!driver.shutdown_handle().is_running());
+        // 没有指标线程持有监听器，端口立即可用
+        assert!(std::net::TcpListener::bind(addr).is_ok());
+    }
+
+    /// 构造以太网+IPv4+UDP帧
+    fn udp_frame(src: [u8; 4], src_port: u16, dst: [u8; 4], dst_port: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0x00]);
+        frame.extend_from_slice(&(28 + payload.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, 17, 0x00, 0x00]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&src_port.to_be_bytes());
+        frame.extend_from_slice(&dst_port.to_be_bytes());
+        frame.extend_from_slice(&(8 + payload.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// 构造example.com的A查询或空应答
+    fn dns_message(transaction_id: u16, flags: u16) -> Vec<u8> {
+        let mut message = transaction_id.to_be_bytes().to_vec();
+        message.extend_from_slice(&flags.to_be_bytes());
+        message.extend_from_slice(&[0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        message.extend_from_slice(b"\x07example\x03com\x00");
+        message.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        message
+    }
+
+    #[test]
+    fn test_unsolicited_response_flag_emitted() {
+        let client = [192, 0, 2, 10];
+        let server = [10, 0, 0, 53];
+        let capture: Box<dyn PacketCapture> = Box::new(MemoryCapture::new(
+            "test",
+            vec![
+                udp_frame(client, 40000, server, 53, &dns_message(0x1111, 0x0100)),
+                udp_frame(server, 53, client, 40000, &dns_message(0x1111, 0x8180)),
+                // 只抓到响应方向，没有对应的查询
+                udp_frame(server, 53, client, 40001, &dns_message(0x2222, 0x8180)),
+            ],
+        ));
+
+        let output = MemoryOutput::new();
+        let messages = output.messages();
+        let mut driver = Driver::with_captures(config(), vec![capture]).with_output(Box::new(output));
+        let shutdown = driver.shutdown_handle();
+        let handle = thread::spawn(move || driver.start());
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while messages.lock().unwrap().len() < 3 {
+            assert!(Instant::now() < deadline, "driver did not emit all messages");
+            thread::sleep(Duration::from_millis(1));
+        }
+        shutdown.shutdown();
+        assert!(handle.join().unwrap().is_ok());
+
+        let messages = messages.lock().unwrap();
+        let response = |transaction_id: u16| {
+            messages
+                .iter()
+                .find(|m| m.transaction_id == transaction_id && m.message_type == DnsMessageType::Response)
+                .unwrap()
+        };
+        assert!(response(0x2222).unsolicited);
+        assert!(!response(0x1111).unsolicited);
+    }
+
+    #[test]
+    fn test_stats_state_saved_on_shutdown() {
+        let path = std::env::temp_dir().join(format!("dns_spider_driver_state_{}", std::process::id()));
+        std::fs::write(&path, "packet.processed 7\n").unwrap();
+
+        let capture: Box<dyn PacketCapture> = Box::new(MemoryCapture::new("test", Vec::new()));
+        let output = MemoryOutput::new();
+        let heartbeats = output.heartbeats();
+        let mut driver = Driver::with_captures(
+            DriverConfig {
+                stats_state_path: Some(path.clone()),
+                heartbeat_interval: 1,
+                ..config()
+            },
+            vec![capture],
+        )
+        .with_output(Box::new(output));
+        let shutdown = driver.shutdown_handle();
+        let handle = thread::spawn(move || {
+            let result = driver.start();
+            (driver, result)
+        });
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !shutdown.is_running() {
+            assert!(Instant::now() < deadline, "driver did not start");
+            thread::sleep(Duration::from_millis(1));
+        }
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while heartbeats.lock().unwrap().is_empty() {
+            assert!(Instant::now() < deadline, "no heartbeat");
+            thread::sleep(Duration::from_millis(10));
+        }
+        shutdown.shutdown();
+
+        // 心跳只统计本次运行，恢复的计数不计入
+        assert_eq!(heartbeats.lock().unwrap()[0].packets_processed, 0);
+
+        // start返回前统计线程已退出并写入最终状态
+        let (driver, result) = handle.join().unwrap();
+        assert!(result.is_ok());
+        assert!(path.exists());
+
+        // 累计统计从状态文件恢复，本次运行的统计与之分开
+        assert_eq!(driver.get_cumulative_stats().get("packet.processed"), 7);
+        assert_eq!(driver.get_run_stats().get("packet.processed"), 0);
+
+        let _ = std::fs::remove_file(&path);
     }
 }
@@ -0,0 +1,115 @@
+//! 捕获丢包监测
+//! 内核缓冲区溢出时捕获后端只累计丢包数，这里按采样周期计算丢包率，
+//! 超过阈值时输出限频告警，提示调大缓冲区或增加工作线程
+
+use std::time::{Duration, Instant};
+
+/// 一次采样结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DropSample {
+    /// 本周期丢包率（百万分比）
+    pub rate_ppm: u64,
+    /// 需要输出的告警，限频期内为None
+    pub warning: Option<String>,
+}
+
+/// 丢包率监测器
+pub struct DropMonitor {
+    /// 告警阈值（百万分比）
+    threshold_ppm: u64,
+    /// 两次告警的最小间隔
+    warn_interval: Duration,
+    /// 当前捕获缓冲区大小，用于告警提示
+    buffer_size: i32,
+    /// 当前工作线程数，用于告警提示
+    worker_threads: usize,
+    /// 上次采样的(接收数, 丢包数)
+    last: Option<(u64, u64)>,
+    last_warning: Option<Instant>,
+}
+
+impl DropMonitor {
+    /// 创建新的监测器
+    pub fn new(threshold_ppm: u64, warn_interval: Duration, buffer_size: i32, worker_threads: usize) -> Self {
+        DropMonitor {
+            threshold_ppm,
+            warn_interval,
+            buffer_size,
+            worker_threads,
+            last: None,
+            last_warning: None,
+        }
+    }
+
+    /// 用捕获后端的累计计数采样，计数回绕或重置时以本次为新基线
+    pub fn update(&mut self, rx_packets: u64, dropped_packets: u64, now: Instant) -> DropSample {
+        let (last_rx, last_dropped) = self.last.unwrap_or((rx_packets, dropped_packets));
+        self.last = Some((rx_packets, dropped_packets));
+
+        let rx = rx_packets.saturating_sub(last_rx);
+        let dropped = dropped_packets.saturating_sub(last_dropped);
+        let total = rx + dropped;
+        let rate_ppm = if total == 0 { 0 } else { dropped * 1_000_000 / total };
+
+        let throttled = self
+            .last_warning
+            .is_some_and(|last| now.duration_since(last) < self.warn_interval);
+        let warning = if rate_ppm > self.threshold_ppm && !throttled {
+            self.last_warning = Some(now);
+            Some(format!(
+                "Capture is dropping packets: {} of {} ({:.2}%) since last sample; \
+                 consider increasing capture buffer_size (currently {}) or worker_threads (currently {})",
+                dropped,
+                total,
+                rate_ppm as f64 / 10_000.0,
+                self.buffer_size,
+                self.worker_threads
+            ))
+        } else {
+            None
+        };
+
+        DropSample { rate_ppm, warning }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_drop_warning_throttled() {
+        let mut monitor = DropMonitor::new(1_000, Duration::from_secs(60), 0, 4);
+        let start = Instant::now();
+
+        // 首次采样只建立基线
+        let sample = monitor.update(1_000, 0, start);
+        assert_eq!(sample.rate_ppm, 0);
+        assert!(sample.warning.is_none());
+
+        // 低于阈值不告警：0.05%
+        let sample = monitor.update(20_990, 10, start + Duration::from_secs(1));
+        assert_eq!(sample.rate_ppm, 500);
+        assert!(sample.warning.is_none());
+
+        // 超过阈值告警：5%
+        let sample = monitor.update(30_490, 510, start + Duration::from_secs(2));
+        assert_eq!(sample.rate_ppm, 50_000);
+        let warning = sample.warning.unwrap();
+        assert!(warning.contains("buffer_size"));
+        assert!(warning.contains("5.00%"));
+
+        // 限频期内只更新丢包率
+        let sample = monitor.update(39_490, 1_510, start + Duration::from_secs(3));
+        assert_eq!(sample.rate_ppm, 100_000);
+        assert!(sample.warning.is_none());
+
+        // 限频期过后再次告警
+        let sample = monitor.update(48_490, 2_510, start + Duration::from_secs(63));
+        assert!(sample.warning.is_some());
+
+        // 计数重置后重新建立基线
+        let sample = monitor.update(10, 0, start + Duration::from_secs(64));
+        assert_eq!(sample.rate_ppm, 0);
+    }
+}
@@ -0,0 +1,346 @@
+//! 富化任务池
+//! 反向DNS等富化查询在固定数量的工作线程上执行，
+//! 超过在途上限时直接跳过，绝不阻塞抓包路径
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::core::stats::StatsCounter;
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsParser, DnsRecordType, UdpDnsParser};
+use crate::utils::time::current_time_micros;
+
+/// 富化任务
+pub type EnrichmentJob = Box<dyn FnOnce() + Send>;
+
+/// 富化任务池
+pub struct EnrichmentPool {
+    /// 任务发送端，关闭时置空以通知工作线程退出
+    sender: Option<SyncSender<EnrichmentJob>>,
+    /// 工作线程
+    workers: Vec<JoinHandle<()>>,
+    /// 在途任务数（排队中和执行中）
+    in_flight: Arc<AtomicUsize>,
+    /// 最大在途任务数
+    max_in_flight: usize,
+}
+
+impl EnrichmentPool {
+    /// 创建新的富化任务池
+    pub fn new(workers: usize, max_in_flight: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<EnrichmentJob>(max_in_flight);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let in_flight = Arc::clone(&in_flight);
+                thread::spawn(move || loop {
+                    let job = match receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    job();
+                    in_flight.fetch_sub(1, Ordering::AcqRel);
+                })
+            })
+            .collect();
+
+        EnrichmentPool {
+            sender: Some(sender),
+            workers,
+            in_flight,
+            max_in_flight,
+        }
+    }
+
+    /// 提交富化任务，达到在途上限时跳过并计入`enrichment.skipped_overload`
+    pub fn try_submit(&self, job: EnrichmentJob, stats: &StatsCounter) -> bool {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return false,
+        };
+
+        // 先占用名额，超出上限立即放弃
+        if self.in_flight.fetch_add(1, Ordering::AcqRel) >= self.max_in_flight {
+            self.in_flight.fetch_sub(1, Ordering::AcqRel);
+            stats.increment("enrichment.skipped_overload");
+            return false;
+        }
+
+        match sender.try_send(job) {
+            Ok(()) => {
+                stats.increment("enrichment.submitted");
+                true
+            }
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                self.in_flight.fetch_sub(1, Ordering::AcqRel);
+                stats.increment("enrichment.skipped_overload");
+                false
+            }
+        }
+    }
+
+    /// 当前在途任务数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// 关闭任务池，等待已接受的任务执行完毕
+    pub fn shutdown(&mut self) {
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for EnrichmentPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// 反向DNS富化配置
+#[derive(Debug, Clone)]
+pub struct ReverseDnsConfig {
+    /// 发送PTR查询的解析器地址
+    pub resolver: SocketAddr,
+    /// 单次查询超时
+    pub timeout: Duration,
+    /// 查询线程数
+    pub workers: usize,
+    /// 最大在途查询数，超出时跳过
+    pub max_in_flight: usize,
+    /// 缓存的客户端地址数上限，写满时整体清空
+    pub cache_size: usize,
+}
+
+impl ReverseDnsConfig {
+    /// 使用默认参数创建配置
+    pub fn new(resolver: SocketAddr) -> Self {
+        ReverseDnsConfig {
+            resolver,
+            timeout: Duration::from_secs(2),
+            workers: 4,
+            max_in_flight: 256,
+            cache_size: 65536,
+        }
+    }
+}
+
+/// 客户端地址到主机名的缓存，None表示查询中或无PTR记录
+type HostnameCache = Arc<Mutex<HashMap<IpAddr, Option<Arc<str>>>>>;
+
+/// 反向DNS富化：为消息标记客户端地址的PTR主机名
+///
+/// 查询在任务池中异步执行，结果写入缓存；首次出现的客户端不等待查询结果，
+/// 由查询完成后的消息带上主机名。
+pub struct ReverseDnsEnricher {
+    config: ReverseDnsConfig,
+    pool: EnrichmentPool,
+    cache: HostnameCache,
+}
+
+impl ReverseDnsEnricher {
+    /// 创建新的反向DNS富化器
+    pub fn new(config: ReverseDnsConfig) -> Self {
+        let pool = EnrichmentPool::new(config.workers, config.max_in_flight);
+        ReverseDnsEnricher {
+            config,
+            pool,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 从缓存标记客户端主机名，未缓存时提交查询，任务池满时跳过，下次出现再查
+    pub fn enrich(&self, message: &mut DnsMessage, stats: &StatsCounter) {
+        let client = match message.message_type {
+            DnsMessageType::Query => message.src_ip,
+            DnsMessageType::Response => message.dst_ip,
+        };
+        if client.is_unspecified() {
+            return;
+        }
+
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(hostname) = cache.get(&client) {
+                message.client_hostname = hostname.clone();
+                return;
+            }
+            if cache.len() >= self.config.cache_size {
+                cache.clear();
+            }
+            cache.insert(client, None);
+        }
+
+        let cache = Arc::clone(&self.cache);
+        let resolver = self.config.resolver;
+        let timeout = self.config.timeout;
+        let job: EnrichmentJob = Box::new(move || {
+            if let Some(hostname) = lookup_ptr(resolver, client, timeout) {
+                cache.lock().unwrap().insert(client, Some(Arc::from(hostname)));
+            }
+        });
+        if !self.pool.try_submit(job, stats) {
+            self.cache.lock().unwrap().remove(&client);
+        }
+    }
+}
+
+/// 地址对应的反向解析域名
+fn ptr_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+        }
+        IpAddr::V6(ip) => {
+            let mut name = String::with_capacity(72);
+            for byte in ip.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0x0F, byte >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
+
+/// 构造期望递归的PTR查询
+fn ptr_query(transaction_id: u16, name: &str) -> Vec<u8> {
+    let mut query = transaction_id.to_be_bytes().to_vec();
+    query.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&u16::from(DnsRecordType::PTR).to_be_bytes());
+    query.extend_from_slice(&[0x00, 0x01]);
+    query
+}
+
+/// 向解析器查询地址的PTR记录，失败或超时返回None
+fn lookup_ptr(resolver: SocketAddr, ip: IpAddr, timeout: Duration) -> Option<String> {
+    let local: SocketAddr = if resolver.is_ipv4() {
+        ([0, 0, 0, 0], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = UdpSocket::bind(local).ok()?;
+    socket.set_read_timeout(Some(timeout)).ok()?;
+    socket.connect(resolver).ok()?;
+
+    let transaction_id = current_time_micros() as u16;
+    socket.send(&ptr_query(transaction_id, &ptr_name(ip))).ok()?;
+    let mut buffer = [0u8; 512];
+    let len = socket.recv(&mut buffer).ok()?;
+
+    let response = UdpDnsParser::new(buffer.len()).parse(&buffer[..len], &StatsCounter::new())?;
+    if response.transaction_id != transaction_id {
+        return None;
+    }
+    response
+        .answers
+        .into_iter()
+        .find(|answer| answer.record_type == DnsRecordType::PTR)
+        .map(|answer| answer.data_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+    use std::time::Instant;
+
+    #[test]
+    fn test_saturated_pool_skips_instead_of_queuing() {
+        let mut pool = EnrichmentPool::new(2, 4);
+        let stats = StatsCounter::new();
+
+        // 所有查询阻塞直到放行，模拟缓慢的反向DNS
+        let (release, gate) = mpsc::channel::<()>();
+        let gate: Arc<Mutex<Receiver<()>>> = Arc::new(Mutex::new(gate));
+        let done = Arc::new(AtomicUsize::new(0));
+
+        let mut accepted = 0;
+        for _ in 0..100 {
+            let gate = Arc::clone(&gate);
+            let done = Arc::clone(&done);
+            let job: EnrichmentJob = Box::new(move || {
+                let _ = gate.lock().unwrap().recv();
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+            if pool.try_submit(job, &stats) {
+                accepted += 1;
+            }
+        }
+
+        assert_eq!(accepted, 4);
+        assert!(pool.in_flight() <= 4);
+        assert_eq!(stats.get("enrichment.skipped_overload"), 96);
+
+        for _ in 0..accepted {
+            release.send(()).unwrap();
+        }
+        pool.shutdown();
+        assert_eq!(done.load(Ordering::SeqCst), 4);
+        assert_eq!(pool.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_reverse_dns_hostname_from_resolver() {
+        // 本地模拟解析器：回显查询并附上一条指向host.example的PTR应答
+        let resolver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let resolver_addr = resolver.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            let (len, peer) = resolver.recv_from(&mut buffer).unwrap();
+            let mut response = buffer[..len].to_vec();
+            response[2] = 0x81;
+            response[3] = 0x80;
+            response[7] = 1;
+            response.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x0C, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x0E]);
+            response.extend_from_slice(b"\x04host\x07example\x00");
+            resolver.send_to(&response, peer).unwrap();
+        });
+
+        let enricher = ReverseDnsEnricher::new(ReverseDnsConfig::new(resolver_addr));
+        let stats = StatsCounter::new();
+        let query = || DnsMessage {
+            src_ip: "192.0.2.7".parse().unwrap(),
+            ..Default::default()
+        };
+
+        // 首条消息只触发查询，不等待结果
+        let mut first = query();
+        enricher.enrich(&mut first, &stats);
+        assert_eq!(first.client_hostname, None);
+        server.join().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut later = query();
+        while later.client_hostname.is_none() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            later = query();
+            enricher.enrich(&mut later, &stats);
+        }
+        assert_eq!(later.client_hostname.as_deref(), Some("host.example"));
+        assert_eq!(stats.get("enrichment.submitted"), 1);
+    }
+
+    #[test]
+    fn test_ptr_names() {
+        assert_eq!(ptr_name("192.0.2.7".parse().unwrap()), "7.2.0.192.in-addr.arpa");
+        assert_eq!(
+            ptr_name("2001:db8::1".parse().unwrap()),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! 工作线程分配
+//! 读线程按流把数据包分给固定的工作线程，同一条流（含双向）的数据包始终由同一个
+//! 工作线程按到达顺序处理，TCP重组不会因跨线程乱序而出错
+
+use crate::capture::{CapturedPacket, LinkType};
+use crate::protocols::decode::{
+    ethernet_payload, ipv6_upper_layer, ETHERTYPE_IPV4, ETHERTYPE_IPV6, IPPROTO_TCP, IPPROTO_UDP,
+};
+use crate::utils::hash::{fnv1a, FNV_OFFSET_BASIS};
+
+/// 工作线程分配策略
+pub trait WorkerPartitioner: Send + Sync {
+    /// 返回处理该数据包的工作线程序号，取值范围为`0..workers`
+    fn worker_for(&self, packet: &CapturedPacket, workers: usize) -> usize;
+}
+
+/// 按五元组哈希分配，两个方向的数据包落在同一工作线程
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlowHashPartitioner;
+
+impl WorkerPartitioner for FlowHashPartitioner {
+    fn worker_for(&self, packet: &CapturedPacket, workers: usize) -> usize {
+        if workers <= 1 {
+            return 0;
+        }
+        (flow_hash(&packet.data, packet.link_type) % workers as u64) as usize
+    }
+}
+
+/// 计算数据包的对称流哈希，只读取头部，不做完整校验
+///
+/// 两端按(地址, 端口)排序后参与哈希，因此查询和响应哈希相同；
+/// 分片或无法识别的传输层只使用地址，非IP数据包哈希为0。
+pub fn flow_hash(frame: &[u8], link_type: LinkType) -> u64 {
+    let ip = match link_type {
+        LinkType::Ethernet => match ethernet_payload(frame) {
+            Some((ETHERTYPE_IPV4 | ETHERTYPE_IPV6, ip)) => ip,
+            _ => return 0,
+        },
+        LinkType::RawIp => frame,
+        LinkType::Other(_) => return 0,
+    };
+
+    let (src, dst, protocol, transport): (&[u8], &[u8], u8, Option<&[u8]>) =
+        match ip.first().map(|b| b >> 4) {
+            Some(4) if ip.len() >= 20 => {
+                let header_len = ((ip[0] & 0x0F) as usize) * 4;
+                let fragment = u16::from_be_bytes([ip[6], ip[7]]) & 0x3FFF != 0;
+                let transport = if fragment { None } else { ip.get(header_len..) };
+                (&ip[12..16], &ip[16..20], ip[9], transport)
+            }
+            Some(6) if ip.len() >= 40 => match ipv6_upper_layer(ip) {
+                Ok((protocol, offset)) => (&ip[8..24], &ip[24..40], protocol, ip.get(offset..)),
+                Err(_) => (&ip[8..24], &ip[24..40], ip[6], None),
+            },
+            _ => return 0,
+        };
+
+    let ports = match (protocol, transport) {
+        (IPPROTO_TCP | IPPROTO_UDP, Some(t)) if t.len() >= 4 => {
+            Some((u16::from_be_bytes([t[0], t[1]]), u16::from_be_bytes([t[2], t[3]])))
+        }
+        _ => None,
+    };
+    let (src_port, dst_port) = ports.unwrap_or((0, 0));
+
+    let mut a = (src, src_port);
+    let mut b = (dst, dst_port);
+    if a > b {
+        std::mem::swap(&mut a, &mut b);
+    }
+
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, &[protocol]);
+    hash = fnv1a(hash, a.0);
+    hash = fnv1a(hash, &a.1.to_be_bytes());
+    hash = fnv1a(hash, b.0);
+    fnv1a(hash, &b.1.to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    /// 构造以太网+IPv4+TCP帧头
+    fn tcp_packet(src: [u8; 4], src_port: u16, dst: [u8; 4], dst_port: u16) -> CapturedPacket {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0x00, 0x00, 40, 0x00, 0x00, 0x40, 0x00, 0x40, IPPROTO_TCP, 0x00, 0x00]);
+        frame.extend_from_slice(&src);
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&src_port.to_be_bytes());
+        frame.extend_from_slice(&dst_port.to_be_bytes());
+        frame.extend_from_slice(&[0; 16]);
+        CapturedPacket {
+            data: frame,
+            source: Arc::from("test"),
+            timestamp: None,
+            link_type: LinkType::Ethernet,
+        }
+    }
+
+    #[test]
+    fn test_same_flow_routes_to_same_worker() {
+        let partitioner = FlowHashPartitioner;
+        let client = [192, 0, 2, 10];
+        let server = [10, 0, 0, 53];
+
+        let query = tcp_packet(client, 40000, server, 53);
+        let worker = partitioner.worker_for(&query, 8);
+        assert!(worker < 8);
+        // 同一条流的后续分段和反方向的响应分到同一工作线程
+        for _ in 0..3 {
+            assert_eq!(partitioner.worker_for(&tcp_packet(client, 40000, server, 53), 8), worker);
+        }
+        assert_eq!(partitioner.worker_for(&tcp_packet(server, 53, client, 40000), 8), worker);
+
+        // 不同的流分散到多个工作线程
+        let workers: HashSet<usize> = (0..64)
+            .map(|port| partitioner.worker_for(&tcp_packet(client, 40000 + port, server, 53), 8))
+            .collect();
+        assert!(workers.len() > 1);
+
+        assert_eq!(partitioner.worker_for(&query, 1), 0);
+    }
+
+    /// 构造无链路层头的IPv6+UDP包，可选在UDP前插入一个逐跳选项头
+    fn udp6_packet(src: [u8; 16], src_port: u16, dst: [u8; 16], dst_port: u16, hop_by_hop: bool) -> Vec<u8> {
+        let next_header = if hop_by_hop { 0 } else { IPPROTO_UDP };
+        let payload_len: u16 = if hop_by_hop { 16 } else { 8 };
+        let mut packet = vec![0x60, 0, 0, 0];
+        packet.extend_from_slice(&payload_len.to_be_bytes());
+        packet.extend_from_slice(&[next_header, 64]);
+        packet.extend_from_slice(&src);
+        packet.extend_from_slice(&dst);
+        if hop_by_hop {
+            packet.extend_from_slice(&[IPPROTO_UDP, 0, 1, 4, 0, 0, 0, 0]);
+        }
+        packet.extend_from_slice(&src_port.to_be_bytes());
+        packet.extend_from_slice(&dst_port.to_be_bytes());
+        packet.extend_from_slice(&[0, 8, 0, 0]);
+        packet
+    }
+
+    #[test]
+    fn test_ipv6_extension_headers_are_skipped() {
+        let client = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10];
+        let server = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x53];
+
+        // 扩展头之后的端口参与哈希，结果与不带扩展头的反向包相同
+        let with_ext = flow_hash(&udp6_packet(client, 40000, server, 53, true), LinkType::RawIp);
+        assert_eq!(with_ext, flow_hash(&udp6_packet(server, 53, client, 40000, false), LinkType::RawIp));
+        assert_ne!(with_ext, flow_hash(&udp6_packet(client, 40001, server, 53, true), LinkType::RawIp));
+    }
+}
@@ -0,0 +1,94 @@
+//! 捕获层健康报告
+//! 每个统计周期汇总捕获后端的累计计数（libpcap的pcap_stats、DPDK端口统计、
+//! XDP套接字统计），与DNS统计分开输出，并按周期差值计算接收速率
+
+use std::time::Instant;
+
+use crate::capture::CaptureStats;
+use crate::core::stats::StatsCounter;
+
+/// 捕获统计报告器
+#[derive(Default)]
+pub struct InterfaceStatsReporter {
+    /// 上次报告时的(累计计数, 时间)
+    last: Option<(CaptureStats, Instant)>,
+}
+
+impl InterfaceStatsReporter {
+    /// 创建新的报告器
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 生成本周期的捕获统计段，并把各项作为瞬时值写入计数器
+    pub fn report(&mut self, capture: &CaptureStats, now: Instant, stats: &StatsCounter) -> String {
+        let (rx_pps, rx_bps) = match &self.last {
+            Some((last, at)) => {
+                let secs = now.duration_since(*at).as_secs_f64();
+                if secs > 0.0 {
+                    (
+                        capture.rx_packets.saturating_sub(last.rx_packets) as f64 / secs,
+                        capture.rx_bytes.saturating_sub(last.rx_bytes) as f64 / secs,
+                    )
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+            None => (0.0, 0.0),
+        };
+        self.last = Some((capture.clone(), now));
+
+        stats.set_gauge("capture.rx_packets", capture.rx_packets);
+        stats.set_gauge("capture.rx_bytes", capture.rx_bytes);
+        stats.set_gauge("capture.rx_pps", rx_pps as u64);
+
+        format!(
+            "=== 捕获统计 ===\n\
+             received: {}\n\
+             dropped: {}\n\
+             if_dropped: {}\n\
+             bytes: {}\n\
+             rx_rate: {:.2}包/秒 ({:.2}字节/秒)\n\
+             ================",
+            capture.rx_packets,
+            capture.dropped_packets,
+            capture.if_dropped_packets,
+            capture.rx_bytes,
+            rx_pps,
+            rx_bps
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_capture_stats_section_has_expected_keys() {
+        let mut reporter = InterfaceStatsReporter::new();
+        let stats = StatsCounter::new();
+        let start = Instant::now();
+
+        let mut capture = CaptureStats {
+            rx_packets: 1_000,
+            dropped_packets: 5,
+            if_dropped_packets: 2,
+            rx_bytes: 100_000,
+            ..Default::default()
+        };
+        reporter.report(&capture, start, &stats);
+
+        capture.rx_packets = 3_000;
+        capture.rx_bytes = 300_000;
+        let report = reporter.report(&capture, start + Duration::from_secs(10), &stats);
+
+        assert!(report.starts_with("=== 捕获统计 ==="));
+        for line in ["received: 3000", "dropped: 5", "if_dropped: 2", "bytes: 300000", "rx_rate: 200.00包/秒"] {
+            assert!(report.contains(line), "missing {:?} in {}", line, report);
+        }
+        assert_eq!(stats.gauge("capture.rx_pps"), Some(200));
+        assert_eq!(stats.gauge("capture.rx_bytes"), Some(300_000));
+    }
+}
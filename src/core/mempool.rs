@@ -2,10 +2,10 @@
 //! 提供高效的内存分配和回收机制
 
 use std::collections::VecDeque;
-use std::sync::{Arc, Mutex};
 
 /// 内存块
-#[derive(Clone)]
+///
+/// 不实现`Clone`：块的所有权在池和使用方之间移动，归还时按值交回
 pub struct MemoryBlock {
     /// 内存数据
     pub data: Vec<u8>,
@@ -57,11 +57,13 @@ impl MemoryBlock {
 }
 
 /// 内存池
+///
+/// `allocate`把块移出空闲队列交给调用方，`free`按值收回，池只记录借出的块数
 pub struct MemoryPool {
     /// 空闲内存块
     free_blocks: VecDeque<MemoryBlock>,
-    /// 已分配内存块
-    allocated_blocks: VecDeque<MemoryBlock>,
+    /// 已借出的内存块数
+    allocated_blocks: usize,
     /// 内存块大小
     block_size: usize,
     /// 内存池大小（块数）
@@ -80,7 +82,7 @@ impl MemoryPool {
 
         MemoryPool {
             free_blocks,
-            allocated_blocks: VecDeque::with_capacity(pool_size),
+            allocated_blocks: 0,
             block_size,
             pool_size,
         }
@@ -90,39 +92,40 @@ impl MemoryPool {
     pub fn allocate(&mut self) -> Option<MemoryBlock> {
         if let Some(mut block) = self.free_blocks.pop_front() {
             block.reset();
-            self.allocated_blocks.push_back(block);
-            return self.allocated_blocks.back().cloned();
+            self.allocated_blocks += 1;
+            return Some(block);
         }
 
-        // 如果没有空闲块，创建新的
-        if self.allocated_blocks.len() < self.pool_size * 2 {
-            let block = MemoryBlock::new(self.block_size);
-            self.allocated_blocks.push_back(block);
-            return self.allocated_blocks.back().cloned();
+        // 如果没有空闲块，创建新的，借出总数不超过池大小的两倍
+        if self.allocated_blocks < self.pool_size * 2 {
+            self.allocated_blocks += 1;
+            return Some(MemoryBlock::new(self.block_size));
         }
 
         None
     }
 
     /// 释放内存块
-    pub fn free(&mut self, block: MemoryBlock) {
-        // 查找并移除已分配块
-        for i in 0..self.allocated_blocks.len() {
-            if std::ptr::eq(self.allocated_blocks[i].data.as_ptr(), block.data.as_ptr()) {
-                let mut block = self.allocated_blocks.remove(i).unwrap();
-                block.reset();
-                self.free_blocks.push_back(block);
-                return;
-            }
+    ///
+    /// 大小不符的块（来自其他池）直接丢弃；空闲队列已满时同样丢弃，池收缩回预分配的大小
+    pub fn free(&mut self, mut block: MemoryBlock) {
+        if block.data.len() != self.block_size || self.allocated_blocks == 0 {
+            return;
+        }
+        self.allocated_blocks -= 1;
+
+        if self.free_blocks.len() < self.pool_size {
+            block.reset();
+            self.free_blocks.push_back(block);
         }
     }
 
     /// 获取统计信息
     pub fn stats(&self) -> MemoryPoolStats {
         MemoryPoolStats {
-            total_blocks: self.free_blocks.len() + self.allocated_blocks.len(),
+            total_blocks: self.free_blocks.len() + self.allocated_blocks,
             free_blocks: self.free_blocks.len(),
-            allocated_blocks: self.allocated_blocks.len(),
+            allocated_blocks: self.allocated_blocks,
             block_size: self.block_size,
         }
     }
@@ -150,3 +153,41 @@ pub struct MemoryPoolStats {
     /// 块大小
     pub block_size: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_freed_blocks_return_to_pool() {
+        let mut pool = MemoryPool::new(4, 128);
+        let initial = pool.stats().free_blocks;
+
+        let mut first = pool.allocate().unwrap();
+        first.write(b"example").unwrap();
+        let second = pool.allocate().unwrap();
+        assert_eq!(pool.stats().free_blocks, initial - 2);
+        assert_eq!(pool.stats().allocated_blocks, 2);
+
+        pool.free(first);
+        pool.free(second);
+        let stats = pool.stats();
+        assert_eq!(stats.free_blocks, initial);
+        assert_eq!(stats.allocated_blocks, 0);
+
+        // 归还的块已重置
+        assert_eq!(pool.allocate().unwrap().used, 0);
+
+        // 超出预分配数量的块归还后不再保留
+        let mut pool = MemoryPool::new(1, 128);
+        let blocks: Vec<_> = (0..2).map(|_| pool.allocate().unwrap()).collect();
+        assert!(pool.allocate().is_none());
+        blocks.into_iter().for_each(|block| pool.free(block));
+        assert_eq!(pool.stats().free_blocks, 1);
+        assert_eq!(pool.stats().total_blocks, 1);
+
+        // 其他大小的块不会混入池中
+        pool.free(MemoryBlock::new(64));
+        assert_eq!(pool.stats().free_blocks, 1);
+    }
+}
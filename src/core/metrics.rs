@@ -0,0 +1,173 @@
+//! Prometheus指标导出
+//! 在HTTP端点`/metrics`以Prometheus文本格式导出累计计数和瞬时值，
+//! 计数器名加`_total`后缀，瞬时值（捕获丢包、输出队列深度等）导出为gauge
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use prometheus::{Encoder, IntCounter, IntGauge, Registry, TextEncoder};
+
+use crate::core::stats::StatsCounter;
+
+/// 指标名前缀
+const METRIC_PREFIX: &str = "dns_spider_";
+/// 没有连接时检查停止标志的间隔
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// 读取请求的超时，避免慢客户端阻塞导出线程
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(1);
+/// 请求头最大读取长度
+const MAX_REQUEST_BYTES: usize = 4096;
+
+/// 将统计键转换为合法的指标名，非字母数字字符替换为下划线
+fn metric_name(key: &str) -> String {
+    let mut name = String::with_capacity(METRIC_PREFIX.len() + key.len());
+    name.push_str(METRIC_PREFIX);
+    name.extend(key.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }));
+    name
+}
+
+/// 按Prometheus文本格式编码计数和瞬时值，转换后重名的指标只保留第一个
+pub fn encode(stats: &StatsCounter) -> String {
+    let registry = Registry::new();
+    for (key, value) in stats.counters() {
+        if let Ok(counter) = IntCounter::new(format!("{}_total", metric_name(&key)), key) {
+            counter.inc_by(value);
+            let _ = registry.register(Box::new(counter));
+        }
+    }
+    for (key, value) in stats.gauges() {
+        if let Ok(gauge) = IntGauge::new(metric_name(&key), key) {
+            gauge.set(value.min(i64::MAX as u64) as i64);
+            let _ = registry.register(Box::new(gauge));
+        }
+    }
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&registry.gather(), &mut buffer) {
+        eprintln!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
+
+/// Prometheus指标导出器
+pub struct MetricsExporter {
+    listener: TcpListener,
+}
+
+impl MetricsExporter {
+    /// 绑定监听地址，端口为0时由系统分配
+    pub fn bind(addr: SocketAddr) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        // 非阻塞接受连接，以便及时响应停止信号
+        listener.set_nonblocking(true)?;
+        Ok(MetricsExporter { listener })
+    }
+
+    /// 实际监听的地址
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 启动导出线程，运行标志清除后退出
+    pub fn spawn(self, stats: Arc<StatsCounter>, running: Arc<AtomicBool>) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while running.load(Ordering::Relaxed) {
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = serve(stream, &stats) {
+                            eprintln!("Failed to serve metrics request: {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_INTERVAL),
+                    Err(e) => {
+                        eprintln!("Failed to accept metrics connection: {}", e);
+                        thread::sleep(ACCEPT_POLL_INTERVAL);
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// 处理一个HTTP请求，只响应`GET /metrics`
+fn serve(mut stream: TcpStream, stats: &StatsCounter) -> std::io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_READ_TIMEOUT))?;
+
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while request.len() < MAX_REQUEST_BYTES && !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => ("200 OK", TextEncoder::new().format_type().to_string(), encode(stats)),
+        _ => ("404 Not Found", "text/plain".to_string(), "not found\n".to_string()),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_encode_counters_and_gauges() {
+        let stats = StatsCounter::new();
+        stats.add("packet.processed", 3);
+        stats.set_gauge("capture.dropped_packets", 2);
+        stats.set_gauge("eth0-1.pcap.ps_drop", 1);
+
+        let text = encode(&stats);
+        assert!(text.contains("# TYPE dns_spider_packet_processed_total counter"));
+        assert!(text.contains("dns_spider_packet_processed_total 3"));
+        assert!(text.contains("# TYPE dns_spider_capture_dropped_packets gauge"));
+        assert!(text.contains("dns_spider_capture_dropped_packets 2"));
+        assert!(text.contains("dns_spider_eth0_1_pcap_ps_drop 1"));
+    }
+
+    #[test]
+    fn test_serve_metrics_endpoint() {
+        let exporter = MetricsExporter::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = exporter.local_addr().unwrap();
+        let stats = Arc::new(StatsCounter::new());
+        stats.add("dns.udp.parsed", 5);
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = exporter.spawn(Arc::clone(&stats), Arc::clone(&running));
+
+        let response = get(addr, "/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("dns_spider_dns_udp_parsed_total 5"));
+        assert!(get(addr, "/").starts_with("HTTP/1.1 404"));
+
+        running.store(false, Ordering::Relaxed);
+        handle.join().unwrap();
+    }
+}
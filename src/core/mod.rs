@@ -1,5 +1,18 @@
-pub(crate) mod dpdk;
-pub(crate) mod driver;
-pub(crate) mod mempool;
-pub(crate) mod stats;
-pub(crate) mod xdp;
+pub mod anomaly_dump;
+pub mod config_builder;
+pub mod correlation;
+pub mod dpdk;
+pub mod driver;
+pub mod drop_monitor;
+pub mod enrichment;
+pub mod flow_partition;
+pub mod interface_stats;
+pub mod mempool;
+pub mod metrics;
+pub mod packet_queue;
+pub mod pcap_tee;
+pub mod resolver_role;
+pub mod sessions;
+pub mod stats;
+pub mod tunneling;
+pub mod xdp;
\ No newline at end of file
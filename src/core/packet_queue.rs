@@ -0,0 +1,228 @@
+//! 读线程到工作线程的数据包队列
+//! 队列满时的处理方式由背压策略决定：丢弃新包、丢弃最旧的包，或阻塞读线程
+//! （阻塞时丢包转移到捕获层，由内核/网卡计数）
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{bounded, Receiver, RecvTimeoutError, SendTimeoutError, Sender, TrySendError};
+
+/// 阻塞入队时检查队列是否关闭的间隔
+const BLOCK_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// 背压策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BackpressurePolicy {
+    /// 丢弃新到达的包
+    #[default]
+    DropNewest,
+    /// 丢弃队列中最旧的包，为新包腾出空间
+    DropOldest,
+    /// 阻塞读线程直到有空间，捕获层可能因此丢包
+    BlockReader,
+}
+
+/// 入队结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PushOutcome {
+    /// 直接入队
+    Queued,
+    /// 队列满，新包被丢弃
+    DroppedNewest,
+    /// 队列满，最旧的包被丢弃
+    DroppedOldest,
+    /// 队列满，读线程等待后入队
+    Blocked,
+    /// 队列已关闭，包被丢弃
+    Closed,
+}
+
+impl PushOutcome {
+    /// 对应的统计计数器名，直接入队时为None
+    pub fn counter(&self) -> Option<&'static str> {
+        match self {
+            PushOutcome::Queued => None,
+            PushOutcome::DroppedNewest => Some("queue.dropped_newest"),
+            PushOutcome::DroppedOldest => Some("queue.dropped_oldest"),
+            PushOutcome::Blocked => Some("queue.reader_blocked"),
+            PushOutcome::Closed => Some("queue.dropped_closed"),
+        }
+    }
+}
+
+/// 有界数据包队列，基于无锁的crossbeam通道，读线程入队和工作线程出队互不加锁
+pub struct PacketQueue<T> {
+    sender: Sender<T>,
+    /// 工作线程出队；DropOldest策略下读线程也用它丢弃最旧的包
+    receiver: Receiver<T>,
+    closed: AtomicBool,
+    policy: BackpressurePolicy,
+}
+
+impl<T> PacketQueue<T> {
+    /// 创建新的队列，容量至少为1
+    pub fn new(capacity: usize, policy: BackpressurePolicy) -> Self {
+        let (sender, receiver) = bounded(capacity.max(1));
+        PacketQueue {
+            sender,
+            receiver,
+            closed: AtomicBool::new(false),
+            policy,
+        }
+    }
+
+    /// 按背压策略入队
+    pub fn push(&self, item: T) -> PushOutcome {
+        if self.is_closed() {
+            return PushOutcome::Closed;
+        }
+
+        let item = match self.sender.try_send(item) {
+            Ok(()) => return PushOutcome::Queued,
+            Err(TrySendError::Full(item)) => item,
+            Err(TrySendError::Disconnected(_)) => return PushOutcome::Closed,
+        };
+
+        match self.policy {
+            BackpressurePolicy::DropNewest => PushOutcome::DroppedNewest,
+            BackpressurePolicy::DropOldest => {
+                // 工作线程可能同时取走数据，腾出空间后重试
+                let mut item = item;
+                loop {
+                    let _ = self.receiver.try_recv();
+                    match self.sender.try_send(item) {
+                        Ok(()) => return PushOutcome::DroppedOldest,
+                        Err(TrySendError::Full(rejected)) => item = rejected,
+                        Err(TrySendError::Disconnected(_)) => return PushOutcome::Closed,
+                    }
+                }
+            }
+            BackpressurePolicy::BlockReader => {
+                // 分段等待，以便队列关闭时及时返回
+                let mut item = item;
+                loop {
+                    if self.is_closed() {
+                        return PushOutcome::Closed;
+                    }
+                    match self.sender.send_timeout(item, BLOCK_POLL_INTERVAL) {
+                        Ok(()) => return PushOutcome::Blocked,
+                        Err(SendTimeoutError::Timeout(rejected)) => item = rejected,
+                        Err(SendTimeoutError::Disconnected(_)) => return PushOutcome::Closed,
+                    }
+                }
+            }
+        }
+    }
+
+    /// 取出最多`max`个包，队列为空时最多等待`timeout`
+    pub fn pop_batch(&self, max: usize, timeout: Duration) -> Vec<T> {
+        let mut batch = Vec::new();
+        if max == 0 {
+            return batch;
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(item) => {
+                    batch.push(item);
+                    break;
+                }
+                // 已关闭且为空时立即返回，工作线程据此退出
+                Err(_) if self.is_closed() => return batch,
+                Err(_) => {}
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return batch;
+            }
+            match self.receiver.recv_timeout(remaining.min(BLOCK_POLL_INTERVAL)) {
+                Ok(item) => {
+                    batch.push(item);
+                    break;
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return batch,
+            }
+        }
+
+        batch.extend(self.receiver.try_iter().take(max - 1));
+        batch
+    }
+
+    /// 队列中的包数
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    /// 队列是否为空
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
+
+    /// 队列是否已关闭
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// 关闭队列，阻塞的读线程和等待的工作线程在一个检查间隔内返回
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    /// 容量为2且已填满的队列
+    fn saturated(policy: BackpressurePolicy) -> PacketQueue<u32> {
+        let queue = PacketQueue::new(2, policy);
+        assert_eq!(queue.push(1), PushOutcome::Queued);
+        assert_eq!(queue.push(2), PushOutcome::Queued);
+        queue
+    }
+
+    #[test]
+    fn test_drop_newest_when_saturated() {
+        let queue = saturated(BackpressurePolicy::DropNewest);
+        assert_eq!(queue.push(3), PushOutcome::DroppedNewest);
+        assert_eq!(queue.pop_batch(10, Duration::ZERO), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_drop_oldest_when_saturated() {
+        let queue = saturated(BackpressurePolicy::DropOldest);
+        assert_eq!(queue.push(3), PushOutcome::DroppedOldest);
+        assert_eq!(queue.push(4), PushOutcome::DroppedOldest);
+        assert_eq!(queue.pop_batch(10, Duration::ZERO), vec![3, 4]);
+        assert_eq!(PushOutcome::DroppedOldest.counter(), Some("queue.dropped_oldest"));
+    }
+
+    #[test]
+    fn test_block_reader_when_saturated() {
+        let queue = Arc::new(saturated(BackpressurePolicy::BlockReader));
+
+        let reader_queue = Arc::clone(&queue);
+        let reader = thread::spawn(move || reader_queue.push(3));
+
+        // 读线程阻塞，直到工作线程取走数据
+        thread::sleep(Duration::from_millis(50));
+        assert!(!reader.is_finished());
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.pop_batch(1, Duration::ZERO), vec![1]);
+        assert_eq!(reader.join().unwrap(), PushOutcome::Blocked);
+        assert_eq!(queue.pop_batch(10, Duration::ZERO), vec![2, 3]);
+
+        // 关闭时唤醒阻塞的读线程
+        let queue = Arc::new(saturated(BackpressurePolicy::BlockReader));
+        let reader_queue = Arc::clone(&queue);
+        let reader = thread::spawn(move || reader_queue.push(3));
+        thread::sleep(Duration::from_millis(20));
+        queue.close();
+        assert_eq!(reader.join().unwrap(), PushOutcome::Closed);
+    }
+}
@@ -0,0 +1,177 @@
+//! 原始报文旁路保存
+//! 在解析的同时把每个捕获到的帧写入按时间轮转的pcap文件，
+//! 记录头使用原始抓包时间，便于和解析结果对照
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::capture::LinkType;
+use crate::core::anomaly_dump::{pcap_linktype, write_header, write_record};
+use crate::core::stats::StatsCounter;
+
+/// 旁路保存配置
+#[derive(Debug, Clone)]
+pub struct PcapTeeConfig {
+    /// pcap文件输出目录
+    pub output_dir: PathBuf,
+    /// 文件名前缀
+    pub file_prefix: String,
+    /// 轮转间隔（秒），与文件输出的轮转间隔含义相同
+    pub rotation_interval: u64,
+}
+
+impl Default for PcapTeeConfig {
+    fn default() -> Self {
+        PcapTeeConfig {
+            output_dir: PathBuf::from("./logs"),
+            file_prefix: "raw-".to_string(),
+            rotation_interval: 3600,
+        }
+    }
+}
+
+/// 当前写入的文件
+struct TeeFile {
+    writer: BufWriter<File>,
+    path: PathBuf,
+    opened_at: Instant,
+    linktype: u32,
+}
+
+/// 原始报文旁路写入器
+pub struct PcapTee {
+    config: PcapTeeConfig,
+    current: Option<TeeFile>,
+}
+
+impl PcapTee {
+    /// 创建新的旁路写入器
+    pub fn new(config: PcapTeeConfig) -> Self {
+        PcapTee { config, current: None }
+    }
+
+    /// 当前文件路径
+    pub fn current_path(&self) -> Option<&PathBuf> {
+        self.current.as_ref().map(|file| &file.path)
+    }
+
+    /// 写入一帧，到达轮转间隔或链路层类型变化时换新文件
+    pub fn record(
+        &mut self,
+        timestamp: u64,
+        frame: &[u8],
+        link_type: LinkType,
+        now: Instant,
+        stats: &StatsCounter,
+    ) {
+        let linktype = pcap_linktype(link_type);
+        let rotation_interval = Duration::from_secs(self.config.rotation_interval);
+        let rotate = self.current.as_ref().is_some_and(|file| {
+            file.linktype != linktype || now.duration_since(file.opened_at) >= rotation_interval
+        });
+        if rotate {
+            self.finish();
+            stats.increment("pcap_tee.rotated");
+        }
+
+        if self.current.is_none() {
+            let path = self
+                .config
+                .output_dir
+                .join(format!("{}{}.pcap", self.config.file_prefix, timestamp));
+            match self.open(&path, linktype) {
+                Ok(writer) => {
+                    self.current = Some(TeeFile {
+                        writer,
+                        path,
+                        opened_at: now,
+                        linktype,
+                    })
+                }
+                Err(e) => {
+                    eprintln!("Failed to create pcap file {}: {}", path.display(), e);
+                    stats.increment("pcap_tee.write_failed");
+                    return;
+                }
+            }
+        }
+
+        if let Some(file) = &mut self.current {
+            match write_record(&mut file.writer, timestamp, frame) {
+                Ok(()) => stats.increment("pcap_tee.packets"),
+                Err(e) => {
+                    eprintln!("Failed to write pcap file {}: {}", file.path.display(), e);
+                    stats.increment("pcap_tee.write_failed");
+                    self.current = None;
+                }
+            }
+        }
+    }
+
+    /// 刷新并关闭当前文件
+    pub fn finish(&mut self) {
+        if let Some(mut file) = self.current.take() {
+            if let Err(e) = file.writer.flush() {
+                eprintln!("Failed to flush pcap file {}: {}", file.path.display(), e);
+            }
+        }
+    }
+
+    /// 创建pcap文件并写入文件头
+    fn open(&self, path: &PathBuf, linktype: u32) -> std::io::Result<BufWriter<File>> {
+        fs::create_dir_all(&self.config.output_dir)?;
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer, linktype)?;
+        Ok(writer)
+    }
+}
+
+impl Drop for PcapTee {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::anomaly_dump::PCAP_LINKTYPE_ETHERNET;
+
+    #[test]
+    fn test_tee_rotates_and_keeps_timestamps() {
+        let dir = std::env::temp_dir().join(format!("dns_spider_pcap_tee_{}", std::process::id()));
+        let mut tee = PcapTee::new(PcapTeeConfig {
+            output_dir: dir.clone(),
+            file_prefix: "raw-".to_string(),
+            rotation_interval: 60,
+        });
+        let stats = StatsCounter::new();
+        let start = Instant::now();
+
+        tee.record(1_700_000_000_000_042, &[1; 20], LinkType::Ethernet, start, &stats);
+        tee.record(1_700_000_001_000_000, &[2; 30], LinkType::Ethernet, start + Duration::from_secs(1), &stats);
+        let first = tee.current_path().unwrap().clone();
+
+        // 超过轮转间隔后写入新文件
+        tee.record(1_700_000_061_000_000, &[3; 20], LinkType::Ethernet, start + Duration::from_secs(61), &stats);
+        let second = tee.current_path().unwrap().clone();
+        tee.finish();
+
+        assert_ne!(first, second);
+        assert_eq!(stats.get("pcap_tee.packets"), 3);
+        assert_eq!(stats.get("pcap_tee.rotated"), 1);
+
+        let data = fs::read(&first).unwrap();
+        // 24字节文件头 + 20字节帧 + 30字节帧，每条16字节记录头
+        assert_eq!(data.len(), 24 + (16 + 20) + (16 + 30));
+        assert_eq!(&data[20..24], &PCAP_LINKTYPE_ETHERNET.to_le_bytes());
+        // 记录头保留原始抓包时间
+        assert_eq!(&data[24..28], &1_700_000_000u32.to_le_bytes());
+        assert_eq!(&data[28..32], &42u32.to_le_bytes());
+        assert_eq!(fs::read(&second).unwrap().len(), 24 + 16 + 20);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,87 @@
+//! 递归解析器流量划分
+//! 用户声明递归解析器地址后，按消息的客户端和服务端地址判断其位于
+//! 客户端到解析器一侧，还是解析器到权威服务器一侧
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsRole};
+
+/// 递归解析器地址表
+#[derive(Debug, Clone)]
+pub struct ResolverRoles {
+    resolvers: HashSet<IpAddr>,
+}
+
+impl ResolverRoles {
+    /// 从配置的解析器地址创建
+    pub fn new(resolver_ips: &[IpAddr]) -> Self {
+        ResolverRoles {
+            resolvers: resolver_ips.iter().copied().collect(),
+        }
+    }
+
+    /// 判断消息所属的解析链路
+    ///
+    /// 查询的发起方与响应的接收方为客户端一侧。客户端本身是解析器时
+    /// 视为解析器向上游发起的流量，这样转发链上的每一跳都归入上游一侧。
+    pub fn classify(&self, message: &DnsMessage) -> DnsRole {
+        let (client, server) = match message.message_type {
+            DnsMessageType::Query => (message.src_ip, message.dst_ip),
+            DnsMessageType::Response => (message.dst_ip, message.src_ip),
+        };
+
+        if self.resolvers.contains(&client) {
+            DnsRole::ResolverToAuthority
+        } else if self.resolvers.contains(&server) {
+            DnsRole::StubToResolver
+        } else {
+            DnsRole::Unknown
+        }
+    }
+
+    /// 为消息设置角色
+    pub fn tag(&self, message: &mut DnsMessage) {
+        message.role = self.classify(message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stats::StatsCounter;
+    use crate::protocols::dns::{DnsParser, UdpDnsParser};
+
+    #[test]
+    fn test_query_to_configured_resolver_is_stub_to_resolver() {
+        // example.com A查询
+        let packet = [
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, b'e', b'x', b'a', b'm',
+            b'p', b'l', b'e', 0x03, b'c', b'o', b'm', 0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+        let mut message = UdpDnsParser::new(65535).parse(&packet, &StatsCounter::new()).unwrap();
+        let resolver: IpAddr = "10.0.0.53".parse().unwrap();
+        let stub: IpAddr = "10.0.1.20".parse().unwrap();
+        let authority: IpAddr = "192.0.2.1".parse().unwrap();
+        let roles = ResolverRoles::new(&[resolver]);
+
+        message.src_ip = stub;
+        message.dst_ip = resolver;
+        roles.tag(&mut message);
+        assert_eq!(message.role, DnsRole::StubToResolver);
+
+        message.src_ip = resolver;
+        message.dst_ip = authority;
+        assert_eq!(roles.classify(&message), DnsRole::ResolverToAuthority);
+
+        // 权威服务器返回给解析器的响应同属上游一侧
+        message.message_type = DnsMessageType::Response;
+        message.src_ip = authority;
+        message.dst_ip = resolver;
+        assert_eq!(roles.classify(&message), DnsRole::ResolverToAuthority);
+
+        message.src_ip = stub;
+        message.dst_ip = authority;
+        assert_eq!(roles.classify(&message), DnsRole::Unknown);
+    }
+}
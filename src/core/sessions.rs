@@ -0,0 +1,287 @@
+//! 工作线程私有的流会话表
+//! 读线程按流分配数据包，同一条流只会到达一个工作线程，
+//! 因此流重组状态由各工作线程独占，不需要加锁
+
+use crate::core::stats::StatsCounter;
+use crate::protocols::decode::{DecodedPacket, TCP_FLAG_FIN, TCP_FLAG_RST, TCP_FLAG_SYN};
+use crate::protocols::dns::{DnsMessage, DnsProtocol, DohParser, DoqParser, DotParser, TcpDnsParser};
+
+/// 会话表清理间隔（毫秒）
+const CLEANUP_INTERVAL_MS: u64 = 1_000;
+
+/// 单个工作线程的流解析器集合
+pub struct WorkerSessions {
+    /// TCP DNS流重组
+    tcp: TcpDnsParser,
+    /// DNS over TLS
+    dot: DotParser,
+    /// DNS over HTTPS
+    doh: DohParser,
+    /// DNS over QUIC
+    doq: DoqParser,
+    /// 上次清理过期会话的时间（毫秒）
+    last_cleanup_ms: u64,
+    /// 按SYN/FIN/RST建立和结束TCP DNS会话，关闭时仅依赖空闲超时
+    track_handshake: bool,
+}
+
+impl WorkerSessions {
+    /// 创建新的会话表
+    pub fn new(max_packet_size: usize, max_sessions: usize, session_timeout_ms: u64) -> Self {
+        WorkerSessions {
+            tcp: TcpDnsParser::new(max_packet_size, max_sessions, session_timeout_ms),
+            dot: DotParser::new(max_packet_size, max_sessions, session_timeout_ms),
+            doh: DohParser::new(max_packet_size, max_sessions, session_timeout_ms),
+            doq: DoqParser::new(max_packet_size, max_sessions, session_timeout_ms),
+            last_cleanup_ms: 0,
+            track_handshake: false,
+        }
+    }
+
+    /// 设置是否跟踪TCP握手和挥手
+    ///
+    /// 开启后SYN初始化会话缓冲，FIN/RST立即结束并移除会话，比等待超时更早释放内存。
+    pub fn with_handshake_tracking(mut self, enabled: bool) -> Self {
+        self.track_handshake = enabled;
+        self
+    }
+
+    /// 处理一个TCP段，返回本段中完成重组的DNS消息
+    ///
+    /// `now_ms`为抓包时间，会话表按该时间至多每秒清理一次过期会话。
+    pub fn process_tcp(
+        &mut self,
+        packet: &DecodedPacket<'_>,
+        now_ms: u64,
+        stats: &StatsCounter,
+    ) -> Vec<DnsMessage> {
+        self.process(DnsProtocol::Tcp, packet, now_ms, stats)
+    }
+
+    /// 按协议检测结果把数据交给对应的流解析器，返回本段中完成的DNS消息
+    ///
+    /// 普通UDP DNS和mDNS无需会话状态，由调用方直接解析，传入时返回空。
+    pub fn process(
+        &mut self,
+        protocol: DnsProtocol,
+        packet: &DecodedPacket<'_>,
+        now_ms: u64,
+        stats: &StatsCounter,
+    ) -> Vec<DnsMessage> {
+        if now_ms.saturating_sub(self.last_cleanup_ms) >= CLEANUP_INTERVAL_MS {
+            self.tcp.update_time(now_ms);
+            self.dot.update_time(now_ms);
+            self.doh.update_time(now_ms);
+            self.doq.update_time(now_ms);
+            self.last_cleanup_ms = now_ms;
+        }
+
+        let (src_ip, dst_ip, src_port, dst_port) = (packet.src_ip, packet.dst_ip, packet.src_port, packet.dst_port);
+        let payload = packet.payload;
+        match protocol {
+            DnsProtocol::Tcp if self.track_handshake => {
+                let flags = packet.tcp_flags;
+                if flags & TCP_FLAG_SYN != 0 {
+                    self.tcp.open_session(src_ip, dst_ip, src_port, dst_port, stats);
+                }
+                if flags & TCP_FLAG_RST != 0 {
+                    // 连接被中止，缓冲中未完成的数据不再有意义
+                    self.tcp.close_session(src_ip, dst_ip, src_port, dst_port, true, stats);
+                    return Vec::new();
+                }
+                let messages = self
+                    .tcp
+                    .process_tcp_segment(src_ip, dst_ip, src_port, dst_port, payload, stats);
+                if flags & TCP_FLAG_FIN != 0 {
+                    // FIN段可能携带最后的数据，先处理再结束会话
+                    self.tcp.close_session(src_ip, dst_ip, src_port, dst_port, false, stats);
+                }
+                messages
+            }
+            DnsProtocol::Tcp => self
+                .tcp
+                .process_tcp_segment(src_ip, dst_ip, src_port, dst_port, payload, stats),
+            DnsProtocol::Dot => self
+                .dot
+                .process_tls_data(src_ip, dst_ip, src_port, dst_port, payload, stats),
+            DnsProtocol::Doq => self
+                .doq
+                .process_quic_data(src_ip, dst_ip, src_port, dst_port, payload, stats),
+            DnsProtocol::Doh => {
+                let messages = self
+                    .doh
+                    .process_http_data(src_ip, dst_ip, src_port, dst_port, payload, stats);
+                if self.doh.is_http2(src_ip, dst_ip, src_port, dst_port)
+                    && !self.doh.has_session(dst_ip, src_ip, dst_port, src_port)
+                {
+                    // 服务器方向不发送连接前言，登记后其数据段按帧解析
+                    self.doh.expect_http2(dst_ip, src_ip, dst_port, src_port);
+                }
+                // 只计数，不逐会话打印：打印不限频且会绕过客户端地址匿名化
+                if self.doh.take_authority(src_ip, dst_ip, src_port, dst_port).is_some() {
+                    stats.increment("dns.doh.authority");
+                }
+                messages
+            }
+            DnsProtocol::Udp | DnsProtocol::Mdns => Vec::new(),
+        }
+    }
+
+    /// 数据包是否属于已确认的DoH会话（含HTTP/2连接的服务器方向）
+    ///
+    /// DoH端口上的后续数据段不带HTTP特征，检测器无法确认，需按会话表判断。
+    pub fn has_doh_session(&self, packet: &DecodedPacket<'_>) -> bool {
+        self.doh
+            .has_session(packet.src_ip, packet.dst_ip, packet.src_port, packet.dst_port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::decode::Transport;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    /// 带长度前缀的DNS查询
+    fn framed_query(transaction_id: u16) -> Vec<u8> {
+        let mut msg = transaction_id.to_be_bytes().to_vec();
+        msg.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        msg.extend_from_slice(b"\x07example\x03com\x00\x00\x01\x00\x01");
+        let mut framed = (msg.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&msg);
+        framed
+    }
+
+    fn segment(client: u8, payload: &[u8]) -> DecodedPacket<'_> {
+        DecodedPacket {
+            src_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, client)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)),
+            src_port: 40000,
+            dst_port: 53,
+            transport: Transport::Tcp,
+            payload,
+            tcp_flags: 0,
+            unreachable: false,
+        }
+    }
+
+    #[test]
+    fn test_fin_and_rst_end_sessions_immediately() {
+        let mut sessions = WorkerSessions::new(4096, 16, 30_000).with_handshake_tracking(true);
+        let stats = StatsCounter::new();
+        let query = framed_query(0x5555);
+
+        // SYN建立会话，FIN段携带的完整查询先输出，随后会话立即移除
+        let syn = DecodedPacket { tcp_flags: TCP_FLAG_SYN, ..segment(7, &[]) };
+        assert!(sessions.process_tcp(&syn, 1_000, &stats).is_empty());
+        assert_eq!(sessions.tcp.session_count(), 1);
+        let fin = DecodedPacket { tcp_flags: TCP_FLAG_FIN, ..segment(7, &query) };
+        assert_eq!(sessions.process_tcp(&fin, 1_001, &stats).len(), 1);
+        assert_eq!(sessions.tcp.session_count(), 0);
+        assert_eq!(stats.get("dns.tcp.session_closed"), 1);
+
+        // 传输中途RST：已缓冲的半条消息被丢弃，之后的数据不会与之拼接
+        let (head, tail) = query.split_at(5);
+        assert!(sessions.process_tcp(&segment(8, head), 1_002, &stats).is_empty());
+        assert_eq!(sessions.tcp.session_count(), 1);
+        let rst = DecodedPacket { tcp_flags: TCP_FLAG_RST, ..segment(8, &[]) };
+        assert!(sessions.process_tcp(&rst, 1_003, &stats).is_empty());
+        assert_eq!(sessions.tcp.session_count(), 0);
+        assert_eq!(stats.get("dns.tcp.session_reset"), 1);
+        assert_eq!(stats.get("dns.tcp.discarded_bytes"), 5);
+        assert!(sessions.process_tcp(&segment(8, tail), 1_004, &stats).is_empty());
+    }
+
+    #[test]
+    fn test_dispatch_by_detected_protocol() {
+        let mut sessions = WorkerSessions::new(4096, 16, 30_000);
+        let stats = StatsCounter::new();
+
+        // DoT：首段视为握手，之后的记录按TCP DNS解析
+        let framed = framed_query(0x4444);
+        let tls = DecodedPacket { dst_port: 853, ..segment(4, &framed) };
+        assert!(sessions.process(DnsProtocol::Dot, &tls, 1_000, &stats).is_empty());
+        let messages = sessions.process(DnsProtocol::Dot, &tls, 1_001, &stats);
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].protocol, DnsProtocol::Dot));
+
+        // DoH：POST消息体为DNS报文
+        let body = &framed[2..];
+        let mut request = format!(
+            "POST /dns-query HTTP/1.1\r\nHost: dns.example\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(body);
+        let https = DecodedPacket { dst_port: 443, ..segment(5, &request) };
+        let messages = sessions.process(DnsProtocol::Doh, &https, 1_002, &stats);
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0].protocol, DnsProtocol::Doh));
+        // 后续不带HTTP特征的数据段按会话表归入DoH
+        assert!(sessions.has_doh_session(&https));
+
+        // HTTP/2连接前言同时登记服务器方向
+        let preface = DecodedPacket { dst_port: 443, ..segment(9, b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n") };
+        assert!(sessions.process(DnsProtocol::Doh, &preface, 1_002, &stats).is_empty());
+        let reply = DecodedPacket {
+            src_ip: preface.dst_ip,
+            dst_ip: preface.src_ip,
+            src_port: 443,
+            dst_port: 40000,
+            ..segment(9, &[0, 0, 0, 0x4, 0, 0, 0, 0, 0])
+        };
+        assert!(sessions.has_doh_session(&reply));
+
+        // IPv6流同样按四元组重组
+        let (head, tail) = framed.split_at(5);
+        let v6 = |payload| DecodedPacket {
+            src_ip: IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            dst_ip: IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x53)),
+            ..segment(10, payload)
+        };
+        assert!(sessions.process_tcp(&v6(head), 1_003, &stats).is_empty());
+        assert_eq!(sessions.process_tcp(&v6(tail), 1_003, &stats).len(), 1);
+
+        // 普通UDP DNS由调用方直接解析
+        assert!(sessions.process(DnsProtocol::Udp, &segment(6, body), 1_003, &stats).is_empty());
+        assert_eq!(stats.get("dns.dot.parsed"), 1);
+        assert_eq!(stats.get("dns.doh.parsed"), 1);
+        assert_eq!(stats.get("dns.doh.authority"), 1);
+    }
+
+    #[test]
+    fn test_workers_keep_independent_sessions() {
+        let first = framed_query(0x1111);
+        let second = framed_query(0x2222);
+
+        // 两个工作线程各自独占会话表，并行处理各自的流
+        let handles: Vec<_> = [(1u8, first), (2u8, second)]
+            .into_iter()
+            .map(|(client, query)| {
+                std::thread::spawn(move || {
+                    let mut sessions = WorkerSessions::new(4096, 16, 30_000);
+                    let stats = StatsCounter::new();
+                    let (head, tail) = query.split_at(5);
+
+                    // 前半段不足一条消息，状态保留在本线程的会话表中
+                    assert!(sessions.process_tcp(&segment(client, head), 1_000, &stats).is_empty());
+                    let messages = sessions.process_tcp(&segment(client, tail), 1_010, &stats);
+                    assert_eq!(messages.len(), 1);
+                    messages[0].transaction_id
+                })
+            })
+            .collect();
+
+        let ids: Vec<u16> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(ids, vec![0x1111, 0x2222]);
+
+        // 同一流的后半段落到另一个工作线程时无法拼接
+        let query = framed_query(0x3333);
+        let (head, tail) = query.split_at(5);
+        let mut worker_a = WorkerSessions::new(4096, 16, 30_000);
+        let mut worker_b = WorkerSessions::new(4096, 16, 30_000);
+        let stats = StatsCounter::new();
+        assert!(worker_a.process_tcp(&segment(3, head), 1_000, &stats).is_empty());
+        assert!(worker_b.process_tcp(&segment(3, tail), 1_000, &stats).is_empty());
+    }
+}
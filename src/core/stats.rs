@@ -2,108 +2,503 @@
 //! 用于收集和报告性能指标
 
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock};
 use std::time::{Duration, Instant};
 
+use crate::utils::hash::{fnv1a, FNV_OFFSET_BASIS};
+
+/// 直方图桶数：桶0存放0，桶i存放[2^(i-1), 2^i)
+const HISTOGRAM_BUCKETS: usize = 65;
+
+/// 对数分桶直方图
+///
+/// 按2的幂分桶，百分位数返回所在桶的上界（不超过最大值），精度足够观察分布异常。
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// 各桶计数
+    buckets: [u64; HISTOGRAM_BUCKETS],
+    /// 样本数
+    count: u64,
+    /// 最小值
+    min: u64,
+    /// 最大值
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            buckets: [0; HISTOGRAM_BUCKETS],
+            count: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// 记录一个样本
+    pub fn record(&mut self, value: u64) {
+        let bucket = (u64::BITS - value.leading_zeros()) as usize;
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// 样本数
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// 最小值，无样本时返回None
+    pub fn min(&self) -> Option<u64> {
+        if self.count == 0 { None } else { Some(self.min) }
+    }
+
+    /// 最大值，无样本时返回None
+    pub fn max(&self) -> Option<u64> {
+        if self.count == 0 { None } else { Some(self.max) }
+    }
+
+    /// 百分位数（0-100），无样本时返回None
+    pub fn percentile(&self, percentile: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = ((percentile / 100.0) * self.count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                let upper = if bucket == 0 { 0 } else { (1u128 << bucket) as u64 - 1 };
+                return Some(upper.clamp(self.min, self.max));
+            }
+        }
+        Some(self.max)
+    }
+
+    /// 合并另一个直方图
+    pub fn merge(&mut self, other: &Histogram) {
+        for (bucket, count) in self.buckets.iter_mut().zip(other.buckets.iter()) {
+            *bucket += count;
+        }
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+}
+
+/// 计数器分片数，不同键分散到各自的读写锁上
+const COUNTER_SHARDS: usize = 16;
+
+type CounterShard = RwLock<HashMap<String, AtomicU64>>;
+
 /// 统计计数器
-#[derive(Clone)]
+///
+/// 所有方法只需共享引用，可直接放在`Arc`中由多个线程同时计数：计数器按键分片，
+/// 已存在的键只取读锁后原子累加，只有首次出现的键需要短暂获取写锁。
 pub struct StatsCounter {
-    /// 计数器映射
-    counters: HashMap<String, u64>,
+    /// 计数器映射，按键哈希分片
+    counters: [CounterShard; COUNTER_SHARDS],
+    /// 瞬时值（队列深度、丢包率等），合并时取最新值而不累加
+    gauges: Mutex<HashMap<String, u64>>,
     /// 计时器映射
-    timers: HashMap<String, Duration>,
+    timers: Mutex<HashMap<String, Duration>>,
+    /// 直方图映射
+    histograms: Mutex<HashMap<String, Histogram>>,
     /// 开始时间
-    start_time: Instant,
+    start_time: Mutex<Instant>,
+}
+
+/// 锁内的映射在每次操作后都是完整的，持锁线程panic后继续使用
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl Default for StatsCounter {
+    fn default() -> Self {
+        StatsCounter::new()
+    }
 }
 
 impl StatsCounter {
     /// 创建新的统计计数器
     pub fn new() -> Self {
         StatsCounter {
-            counters: HashMap::new(),
-            timers: HashMap::new(),
-            start_time: Instant::now(),
+            counters: std::array::from_fn(|_| RwLock::default()),
+            gauges: Mutex::default(),
+            timers: Mutex::default(),
+            histograms: Mutex::default(),
+            start_time: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// 键所在的分片，FNV-1a哈希
+    fn shard(&self, key: &str) -> &CounterShard {
+        &self.counters[fnv1a(FNV_OFFSET_BASIS, key.as_bytes()) as usize % COUNTER_SHARDS]
+    }
+
+    /// 对计数器执行原子操作，键不存在时先插入0
+    fn update(&self, key: &str, op: impl Fn(&AtomicU64)) {
+        let shard = self.shard(key);
+        if let Some(counter) = shard.read().unwrap_or_else(PoisonError::into_inner).get(key) {
+            op(counter);
+            return;
         }
+        let mut counters = shard.write().unwrap_or_else(PoisonError::into_inner);
+        op(counters.entry(key.to_string()).or_default());
     }
     
     /// 增加计数器值
-    pub fn increment(&mut self, key: &str) {
-        *self.counters.entry(key.to_string()).or_insert(0) += 1;
+    pub fn increment(&self, key: &str) {
+        self.add(key, 1);
     }
     
     /// 增加计数器指定值
-    pub fn add(&mut self, key: &str, value: u64) {
-        *self.counters.entry(key.to_string()).or_insert(0) += value;
+    pub fn add(&self, key: &str, value: u64) {
+        self.update(key, |counter| {
+            counter.fetch_add(value, Ordering::Relaxed);
+        });
     }
     
     /// 设置计数器值
-    pub fn set(&mut self, key: &str, value: u64) {
-        self.counters.insert(key.to_string(), value);
+    pub fn set(&self, key: &str, value: u64) {
+        self.update(key, |counter| counter.store(value, Ordering::Relaxed));
     }
     
     /// 获取计数器值
     pub fn get(&self, key: &str) -> u64 {
-        *self.counters.get(key).unwrap_or(&0)
+        self.shard(key)
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .map_or(0, |counter| counter.load(Ordering::Relaxed))
+    }
+
+    /// 设置瞬时值
+    pub fn set_gauge(&self, key: &str, value: u64) {
+        lock(&self.gauges).insert(key.to_string(), value);
+    }
+
+    /// 获取瞬时值，未设置时返回None
+    pub fn gauge(&self, key: &str) -> Option<u64> {
+        lock(&self.gauges).get(key).copied()
+    }
+
+    /// 按键排序的瞬时值快照
+    pub fn gauges(&self) -> Vec<(String, u64)> {
+        let mut gauges: Vec<(String, u64)> = lock(&self.gauges).iter().map(|(key, value)| (key.clone(), *value)).collect();
+        gauges.sort();
+        gauges
+    }
+
+    /// 按键排序的计数器快照
+    pub fn counters(&self) -> Vec<(String, u64)> {
+        let mut counters: Vec<(String, u64)> = self
+            .counters
+            .iter()
+            .flat_map(|shard| {
+                let shard = shard.read().unwrap_or_else(PoisonError::into_inner);
+                shard
+                    .iter()
+                    .map(|(key, counter)| (key.clone(), counter.load(Ordering::Relaxed)))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        counters.sort();
+        counters
     }
     
+    /// 计算两个计数器的比值（如平均消息大小），分母为0时返回None
+    pub fn average(&self, sum_key: &str, count_key: &str) -> Option<f64> {
+        let count = self.get(count_key);
+        if count == 0 {
+            return None;
+        }
+        Some(self.get(sum_key) as f64 / count as f64)
+    }
+
+    /// 向直方图记录一个样本
+    pub fn record(&self, key: &str, value: u64) {
+        lock(&self.histograms).entry(key.to_string()).or_default().record(value);
+    }
+
+    /// 获取直方图的副本
+    pub fn histogram(&self, key: &str) -> Option<Histogram> {
+        lock(&self.histograms).get(key).cloned()
+    }
+
     /// 开始计时
-    pub fn start_timer(&mut self, key: &str) {
-        self.timers.insert(key.to_string(), Duration::from_secs(0));
+    pub fn start_timer(&self, key: &str) {
+        lock(&self.timers).insert(key.to_string(), Duration::from_secs(0));
     }
     
     /// 停止计时
-    pub fn stop_timer(&mut self, key: &str, start: Instant) {
-        let duration = start.elapsed();
-        if let Some(timer) = self.timers.get_mut(key) {
-            *timer += duration;
-        } else {
-            self.timers.insert(key.to_string(), duration);
-        }
+    pub fn stop_timer(&self, key: &str, start: Instant) {
+        *lock(&self.timers).entry(key.to_string()).or_default() += start.elapsed();
     }
     
     /// 获取计时器值（毫秒）
     pub fn get_timer_ms(&self, key: &str) -> u64 {
-        self.timers.get(key).map_or(0, |d| d.as_millis() as u64)
+        lock(&self.timers).get(key).map_or(0, |d| d.as_millis() as u64)
+    }
+
+    /// 取出当前统计周期的全部统计并重置
+    ///
+    /// 每个分片在写锁内整体取出，并发的累加要么计入返回值，要么计入下一个周期，不会丢失。
+    pub fn take(&self) -> StatsCounter {
+        let taken = StatsCounter::new();
+        for (from, to) in self.counters.iter().zip(taken.counters.iter()) {
+            let counters = std::mem::take(&mut *from.write().unwrap_or_else(PoisonError::into_inner));
+            *to.write().unwrap_or_else(PoisonError::into_inner) = counters;
+        }
+        *lock(&taken.gauges) = std::mem::take(&mut *lock(&self.gauges));
+        *lock(&taken.timers) = std::mem::take(&mut *lock(&self.timers));
+        *lock(&taken.histograms) = std::mem::take(&mut *lock(&self.histograms));
+        *lock(&taken.start_time) = std::mem::replace(&mut *lock(&self.start_time), Instant::now());
+        taken
     }
     
     /// 打印统计信息并重置
-    pub fn print_and_reset(&mut self) {
-        let elapsed = self.start_time.elapsed().as_secs_f64();
+    pub fn print_and_reset(&self) {
+        let stats = self.take();
+        let elapsed = lock(&stats.start_time).elapsed().as_secs_f64();
         
         println!("=== 统计信息 (运行时间: {:.2}秒) ===", elapsed);
         
         // 打印计数器
-        let mut sorted_counters: Vec<_> = self.counters.iter().collect();
-        sorted_counters.sort_by(|a, b| a.0.cmp(b.0));
-        
-        for (key, value) in sorted_counters {
-            let rate = *value as f64 / elapsed;
+        for (key, value) in stats.counters() {
+            let rate = value as f64 / elapsed;
             println!("{}: {} ({:.2}/秒)", key, value, rate);
         }
+
+        // 打印瞬时值
+        for (key, value) in stats.gauges() {
+            println!("{}: {}", key, value);
+        }
+
+        // 打印各协议平均消息大小
+        for protocol in ["udp", "tcp", "dot", "doh", "doq", "mdns"] {
+            let bytes_key = format!("dns.{}.bytes", protocol);
+            let parsed_key = format!("dns.{}.parsed", protocol);
+            if let Some(avg) = stats.average(&bytes_key, &parsed_key) {
+                println!("dns.{}.avg_size: {:.1}字节", protocol, avg);
+            }
+        }
         
         // 打印计时器
-        let mut sorted_timers: Vec<_> = self.timers.iter().collect();
+        let timers = lock(&stats.timers);
+        let mut sorted_timers: Vec<_> = timers.iter().collect();
         sorted_timers.sort_by(|a, b| a.0.cmp(b.0));
         
         for (key, duration) in sorted_timers {
             println!("{}: {:.2}毫秒", key, duration.as_millis());
         }
+
+        // 打印直方图
+        let histograms = lock(&stats.histograms);
+        let mut sorted_histograms: Vec<_> = histograms.iter().collect();
+        sorted_histograms.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (key, histogram) in sorted_histograms {
+            if let (Some(min), Some(p50), Some(p90), Some(p99), Some(max)) = (
+                histogram.min(),
+                histogram.percentile(50.0),
+                histogram.percentile(90.0),
+                histogram.percentile(99.0),
+                histogram.max(),
+            ) {
+                println!(
+                    "{}: count={} min={} p50={} p90={} p99={} max={}",
+                    key, histogram.count(), min, p50, p90, p99, max
+                );
+            }
+        }
         
         println!("===========================");
-        
-        // 重置
-        self.counters.clear();
-        self.timers.clear();
-        self.start_time = Instant::now();
     }
     
     /// 合并另一个计数器的统计信息
-    pub fn merge(&mut self, other: &StatsCounter) {
-        for (key, value) in &other.counters {
-            *self.counters.entry(key.clone()).or_insert(0) += value;
+    pub fn merge(&self, other: &StatsCounter) {
+        for (key, value) in other.counters() {
+            self.add(&key, value);
         }
-        
-        for (key, duration) in &other.timers {
-            *self.timers.entry(key.clone()).or_insert(Duration::from_secs(0)) += *duration;
+
+        // 先复制再加锁合并，两个计数器的锁不会同时持有
+        let gauges = lock(&other.gauges).clone();
+        lock(&self.gauges).extend(gauges);
+
+        let timers = lock(&other.timers).clone();
+        let mut self_timers = lock(&self.timers);
+        for (key, duration) in timers {
+            *self_timers.entry(key).or_default() += duration;
+        }
+        drop(self_timers);
+
+        let histograms = lock(&other.histograms).clone();
+        let mut self_histograms = lock(&self.histograms);
+        for (key, histogram) in histograms {
+            self_histograms.entry(key).or_default().merge(&histogram);
+        }
+    }
+
+    /// 将计数器快照写入状态文件，瞬时值不写入，先写临时文件再重命名，避免写到一半时崩溃损坏状态
+    pub fn save_state(&self, path: &Path) -> crate::error::Result<()> {
+        let mut content = String::new();
+        for (key, value) in self.counters() {
+            content.push_str(&format!("{} {}\n", key, value));
+        }
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// 从状态文件恢复计数器，计时器不持久化
+    pub fn load_state(path: &Path) -> crate::error::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let stats = StatsCounter::new();
+
+        for (line_no, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let parsed = line
+                .rsplit_once(' ')
+                .and_then(|(key, value)| value.parse::<u64>().ok().map(|value| (key, value)));
+            match parsed {
+                Some((key, value)) => stats.set(key, value),
+                None => {
+                    return Err(crate::error::Error::Parse(format!(
+                        "Invalid stats state line {}: {}", line_no + 1, line
+                    )))
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+}
+
+impl Clone for StatsCounter {
+    /// 复制当前的快照
+    fn clone(&self) -> Self {
+        let stats = StatsCounter::new();
+        stats.merge(self);
+        *lock(&stats.start_time) = *lock(&self.start_time);
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_average() {
+        let stats = StatsCounter::new();
+        assert_eq!(stats.average("dns.udp.bytes", "dns.udp.parsed"), None);
+
+        stats.add("dns.udp.bytes", 300);
+        stats.add("dns.udp.parsed", 4);
+        assert_eq!(stats.average("dns.udp.bytes", "dns.udp.parsed"), Some(75.0));
+    }
+
+    #[test]
+    fn test_histogram_percentiles() {
+        let mut histogram = Histogram::default();
+        assert_eq!(histogram.percentile(50.0), None);
+
+        for value in [0, 30, 60, 300, 300, 300, 3600, 86400] {
+            histogram.record(value);
         }
+
+        assert_eq!(histogram.count(), 8);
+        assert_eq!(histogram.min(), Some(0));
+        assert_eq!(histogram.max(), Some(86400));
+        // 300落在[256, 512)桶
+        assert_eq!(histogram.percentile(50.0), Some(511));
+        assert_eq!(histogram.percentile(100.0), Some(86400));
+        assert_eq!(histogram.percentile(1.0), Some(0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_save_and_load_state() {
+        let path = std::env::temp_dir().join(format!("dns_spider_stats_{}.state", std::process::id()));
+
+        let stats = StatsCounter::new();
+        stats.add("dns.udp.parsed", 42);
+        stats.add("packet.processed", 7);
+        stats.save_state(&path).unwrap();
+
+        // 模拟重启后继续累加
+        let restored = StatsCounter::load_state(&path).unwrap();
+        assert_eq!(restored.get("dns.udp.parsed"), 42);
+        assert_eq!(restored.get("packet.processed"), 7);
+        restored.increment("dns.udp.parsed");
+        assert_eq!(restored.get("dns.udp.parsed"), 43);
+
+        // 瞬时值合并时取最新值，不写入状态文件
+        let cumulative = StatsCounter::new();
+        for depth in [5, 3] {
+            let period = StatsCounter::new();
+            period.increment("packet.processed");
+            period.set_gauge("output.file.queue_depth", depth);
+            cumulative.merge(&period);
+        }
+        assert_eq!(cumulative.get("packet.processed"), 2);
+        assert_eq!(cumulative.gauge("output.file.queue_depth"), Some(3));
+        cumulative.save_state(&path).unwrap();
+        let restored = StatsCounter::load_state(&path).unwrap();
+        assert_eq!(restored.counters(), vec![("packet.processed".to_string(), 2)]);
+        assert_eq!(restored.gauge("output.file.queue_depth"), None);
+
+        fs::write(&path, "dns.udp.parsed not-a-number\n").unwrap();
+        assert!(StatsCounter::load_state(&path).is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_concurrent_increments_and_take() {
+        let stats = std::sync::Arc::new(StatsCounter::new());
+        let handles: Vec<_> = (0..4)
+            .map(|worker| {
+                let stats = std::sync::Arc::clone(&stats);
+                std::thread::spawn(move || {
+                    for i in 0..10_000 {
+                        stats.increment("packet.processed");
+                        stats.add(&format!("worker.{}.bytes", worker), i % 2);
+                    }
+                })
+            })
+            .collect();
+
+        // 计数期间反复取出，每次取出的和剩余的加起来不丢失
+        let mut taken = 0;
+        while handles.iter().any(|h| !h.is_finished()) {
+            taken += stats.take().get("packet.processed");
+        }
+        handles.into_iter().for_each(|h| h.join().unwrap());
+        assert_eq!(taken + stats.get("packet.processed"), 40_000);
+
+        let snapshot = StatsCounter::clone(&stats);
+        stats.record("ttl.a", 300);
+        assert!(snapshot.histogram("ttl.a").is_none());
+        assert_eq!(stats.histogram("ttl.a").unwrap().count(), 1);
+
+        let merged = StatsCounter::new();
+        merged.merge(&stats);
+        merged.merge(&stats.take());
+        assert_eq!(merged.get("packet.processed"), 2 * (40_000 - taken));
+        assert_eq!(stats.get("packet.processed"), 0);
+        assert!(stats.counters().is_empty());
+    }
+}
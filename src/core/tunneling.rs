@@ -0,0 +1,137 @@
+//! DNS隧道检测
+//! 隧道工具把数据编码进子域名标签，标签长且字符分布接近随机；
+//! 按问题名子域名部分的最长标签、总长度和字符熵判断是否疑似隧道
+
+use crate::core::stats::StatsCounter;
+use crate::protocols::dns::DnsMessage;
+
+/// 隧道检测阈值
+#[derive(Debug, Clone)]
+pub struct TunnelingConfig {
+    /// 子域名中最长标签达到该长度时视为过长
+    pub min_label_len: usize,
+    /// 子域名总长度（不含点）达到该长度时视为过长
+    pub min_subdomain_len: usize,
+    /// 子域名字符的香农熵阈值（比特/字符），可读主机名通常低于4
+    pub min_entropy: f64,
+}
+
+impl Default for TunnelingConfig {
+    fn default() -> Self {
+        TunnelingConfig {
+            min_label_len: 30,
+            min_subdomain_len: 52,
+            min_entropy: 4.0,
+        }
+    }
+}
+
+/// 一个问题名的统计特征
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TunnelingScore {
+    /// 子域名字符熵（比特/字符）
+    pub entropy: f64,
+    /// 子域名中最长标签的长度
+    pub longest_label: usize,
+    /// 子域名总长度（不含点）
+    pub subdomain_len: usize,
+}
+
+/// 隧道检测器
+#[derive(Debug, Clone, Default)]
+pub struct TunnelingDetector {
+    config: TunnelingConfig,
+}
+
+impl TunnelingDetector {
+    /// 创建新的检测器
+    pub fn new(config: TunnelingConfig) -> Self {
+        TunnelingDetector { config }
+    }
+
+    /// 计算问题名的统计特征
+    ///
+    /// 末尾两个标签视为注册域名不参与计算（不查询公共后缀列表，`co.uk`等后缀下会多计一个标签）。
+    pub fn score(name: &str) -> TunnelingScore {
+        let labels: Vec<&str> = name.trim_end_matches('.').split('.').collect();
+        let subdomain = &labels[..labels.len().saturating_sub(2)];
+
+        let mut counts = [0usize; 256];
+        let mut subdomain_len = 0;
+        let mut longest_label = 0;
+        for label in subdomain {
+            longest_label = longest_label.max(label.len());
+            subdomain_len += label.len();
+            for &b in label.as_bytes() {
+                counts[b.to_ascii_lowercase() as usize] += 1;
+            }
+        }
+
+        let entropy = if subdomain_len == 0 {
+            0.0
+        } else {
+            let total = subdomain_len as f64;
+            counts
+                .iter()
+                .filter(|&&count| count > 0)
+                .map(|&count| {
+                    let p = count as f64 / total;
+                    -p * p.log2()
+                })
+                .sum()
+        };
+
+        TunnelingScore {
+            entropy,
+            longest_label,
+            subdomain_len,
+        }
+    }
+
+    /// 问题名是否疑似隧道：子域名过长且熵值超过阈值
+    pub fn is_suspected(&self, name: &str) -> bool {
+        let score = Self::score(name);
+        let too_long = score.longest_label >= self.config.min_label_len
+            || score.subdomain_len >= self.config.min_subdomain_len;
+        too_long && score.entropy >= self.config.min_entropy
+    }
+
+    /// 检查消息的第一个问题，疑似隧道时标记消息并计入`dns.tunneling_suspected`
+    pub fn inspect(&self, message: &mut DnsMessage, stats: &StatsCounter) {
+        let suspected = message
+            .questions
+            .first()
+            .is_some_and(|question| self.is_suspected(&question.name));
+        if suspected {
+            message.tunneling_suspected = true;
+            stats.increment("dns.tunneling_suspected");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benign_and_base32_tunneled_names() {
+        let detector = TunnelingDetector::default();
+
+        // 普通主机名：标签短或字符分布集中
+        assert!(!detector.is_suspected("www.example.com"));
+        assert!(!detector.is_suspected("mail.static.example.com."));
+        let readable = "this-is-a-long-readable-hostname-for-testing.example.com";
+        assert!(TunnelingDetector::score(readable).longest_label >= 30);
+        assert!(!detector.is_suspected(readable));
+
+        // base32编码的数据："secret-data: user=alice pass=hunter2"
+        let tunneled = "onswg4tfoqwwiylume5ca5ltmvzd2ylmnfrwkidqmfzxgpliovxhizlsgi.t.example.com";
+        let score = TunnelingDetector::score(tunneled);
+        assert_eq!(score.longest_label, 58);
+        assert!(score.entropy > 4.0, "entropy {}", score.entropy);
+        assert!(detector.is_suspected(tunneled));
+
+        // 注册域名本身不参与计算
+        assert_eq!(TunnelingDetector::score("example.com").subdomain_len, 0);
+    }
+}
@@ -0,0 +1,9 @@
+//! DNS Spider
+//! DNS流量抓包、解析和输出库，可执行文件和基准测试都通过这里的公开接口使用
+
+pub mod capture;
+pub mod core;
+pub mod error;
+pub mod output;
+pub mod protocols;
+pub mod utils;
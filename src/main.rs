@@ -3,15 +3,9 @@
 
 use std::process;
 
-use crate::capture::{CaptureConfig, CaptureMode};
-use crate::core::driver::{Driver, DriverConfig};
-use crate::output::{ConsoleConfig, FileConfig, KafkaConfig, OutputConfig, StatsdConfig};
-
-mod capture;
-mod core;
-mod error;
-mod output;
-mod protocols;
+use dns_spider::core::config_builder::DriverConfigBuilder;
+use dns_spider::core::driver::{Driver, DriverConfig};
+use dns_spider::output::{ColorMode, ConsoleConfig};
 
 fn main() {
     println!("启动DNS Spider...");
@@ -28,30 +22,28 @@ fn main() {
 
     println!("配置信息:");
     println!("  接口: {}", config.capture.interface);
-    println!("  过滤器: {}", config.capture.filter);
+    println!("  过滤器: {}", config.capture.effective_filter());
     println!("  混杂模式: {}", config.capture.promiscuous);
     println!("  工作线程: {}", config.worker_threads);
 
     // 创建驱动
     let mut driver = Driver::new(config);
 
-    // 启动抓包
-    match driver.start() {
-        Ok(_) => {
-            println!("DNS Spider已启动，按Ctrl+C停止...");
-            println!("正在监听网络流量...");
+    // SIGINT、SIGTERM（systemd停止服务时发送）走同一关闭流程，非Unix平台为Ctrl+C
+    let shutdown = driver.shutdown_handle();
+    ctrlc::set_handler(move || {
+        println!("接收到停止信号，正在关闭...");
+        shutdown.shutdown();
+    })
+    .expect("设置中断处理器失败");
 
-            // 等待中断信号
-            ctrlc::set_handler(move || {
-                println!("接收到停止信号，正在关闭...");
-                process::exit(0);
-            })
-            .expect("设置中断处理器失败");
+    println!("DNS Spider已启动，按Ctrl+C停止...");
+    println!("正在监听网络流量...");
 
-            // 阻塞主线程
-            loop {
-                std::thread::sleep(std::time::Duration::from_secs(1));
-            }
+    // 启动抓包，阻塞直到收到停止信号并完成输出刷新
+    match driver.start() {
+        Ok(_) => {
+            println!("DNS Spider已停止");
         }
         Err(e) => {
             eprintln!("启动失败: {}", e);
@@ -69,69 +61,28 @@ fn create_config() -> DriverConfig {
     // 自动检测网络接口
     let interface = detect_network_interface();
 
-    // 捕获配置
-    let capture_config = CaptureConfig {
-        interface,
-        filter: "udp or tcp".to_string(), // 更宽松的过滤器，抓取所有UDP和TCP流量
-        promiscuous: true,
-        snaplen: 65535,
-        timeout_ms: 1000,
-        buffer_size: 16_777_216, // 16MB
-        mode: CaptureMode::Pcap,
-        dpdk_config: Default::default(),
-        xdp_config: Default::default(),
-    };
+    // 未设置的字段使用构建器默认值：BPF过滤器由协议检测器的端口配置生成，
+    // Kafka、Statsd、dnstap、被动DNS输出默认关闭
+    let config = DriverConfigBuilder::new()
+        .interface(interface)
+        .workers(4)
+        .stats_interval(10)
+        .ttl_histograms(true)
+        .console(ConsoleConfig {
+            verbose: true,
+            color: ColorMode::Auto,
+        })
+        .file("./logs")
+        .build()
+        .unwrap_or_else(|e| {
+            eprintln!("配置无效: {}", e);
+            process::exit(1);
+        });
 
-    println!("使用BPF过滤器: {}", capture_config.filter);
+    println!("使用BPF过滤器: {}", config.capture.effective_filter());
     println!("注意: 如果仍然抓不到包，请尝试使用 sudo 运行程序");
 
-    // Kafka配置
-    let kafka_config = KafkaConfig {
-        brokers: "localhost:9092".to_string(),
-        topic: "dns-events".to_string(),
-        client_id: "dns-spider".to_string(),
-    };
-
-    // 文件配置
-    let file_config = FileConfig {
-        output_dir: "./logs".to_string(),
-        file_prefix: "dns-".to_string(),
-        file_suffix: "".to_string(),
-        rotation_interval: 3600, // 1小时
-    };
-
-    // Statsd配置
-    let statsd_config = StatsdConfig {
-        host: "localhost".to_string(),
-        port: 8125,
-        prefix: "dns.spider".to_string(),
-    };
-
-    // 控制台配置
-    let console_config = ConsoleConfig {
-        verbose: true,
-        color: true,
-    };
-
-    // 输出配置
-    let output_config = OutputConfig {
-        enable_kafka: false, // 默认禁用Kafka
-        kafka_config,
-        enable_file: true,
-        file_config,
-        enable_statsd: false, // 默认禁用Statsd
-        statsd_config,
-        enable_console: true,
-        console_config,
-    };
-
-    // 驱动配置
-    DriverConfig {
-        capture: capture_config,
-        output: output_config,
-        stats_interval: 10,
-        worker_threads: 4,
-    }
+    config
 }
 
 /// 自动检测网络接口
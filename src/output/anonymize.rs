@@ -0,0 +1,167 @@
+//! 客户端IP匿名化
+//! 在输出前对客户端地址做截断或HMAC假名化，满足隐私合规要求
+
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+
+use crate::protocols::dns::{DnsMessage, DnsMessageType};
+
+/// 客户端IP匿名化方式
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum ClientIpAnonymization {
+    /// 不处理
+    #[default]
+    None,
+    /// 截断低位：IPv4保留/24，IPv6保留/48
+    Truncate,
+    /// 以配置的密钥做HMAC-SHA256，相同输入得到相同的假名地址
+    Hmac(Vec<u8>),
+}
+
+impl std::fmt::Debug for ClientIpAnonymization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ClientIpAnonymization::None => write!(f, "None"),
+            ClientIpAnonymization::Truncate => write!(f, "Truncate"),
+            // 不输出密钥
+            ClientIpAnonymization::Hmac(_) => write!(f, "Hmac(..)"),
+        }
+    }
+}
+
+impl ClientIpAnonymization {
+    /// 对地址做匿名化
+    pub fn apply(&self, ip: IpAddr) -> IpAddr {
+        match self {
+            ClientIpAnonymization::None => ip,
+            ClientIpAnonymization::Truncate => truncate(ip),
+            ClientIpAnonymization::Hmac(key) => match hmac(key, ip) {
+                Some(ip) => ip,
+                // HMAC失败时不能回退为原地址
+                None => unspecified(ip),
+            },
+        }
+    }
+
+    /// 对消息中的客户端地址做匿名化，未启用时不复制消息
+    pub fn apply_message<'a>(&self, message: Cow<'a, DnsMessage>) -> Cow<'a, DnsMessage> {
+        if *self == ClientIpAnonymization::None {
+            return message;
+        }
+
+        let mut message = message;
+        let inner = message.to_mut();
+        match inner.message_type {
+            DnsMessageType::Query => inner.src_ip = self.apply(inner.src_ip),
+            DnsMessageType::Response => inner.dst_ip = self.apply(inner.dst_ip),
+        }
+        message
+    }
+}
+
+/// 截断地址低位
+pub(crate) fn truncate(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let mut octets = v4.octets();
+            octets[3] = 0;
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        IpAddr::V6(v6) => {
+            let mut octets = v6.octets();
+            octets[6..].fill(0);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+    }
+}
+
+/// 用HMAC摘要替换地址，保持地址族不变
+fn hmac(key: &[u8], ip: IpAddr) -> Option<IpAddr> {
+    let pkey = PKey::hmac(key).ok()?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &pkey).ok()?;
+    let digest = match ip {
+        IpAddr::V4(v4) => signer.sign_oneshot_to_vec(&v4.octets()).ok()?,
+        IpAddr::V6(v6) => signer.sign_oneshot_to_vec(&v6.octets()).ok()?,
+    };
+
+    match ip {
+        IpAddr::V4(_) => {
+            let octets: [u8; 4] = digest[..4].try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+        }
+        IpAddr::V6(_) => {
+            let octets: [u8; 16] = digest[..16].try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+    }
+}
+
+/// 同地址族的未指定地址
+fn unspecified(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate() {
+        let policy = ClientIpAnonymization::Truncate;
+        assert_eq!(
+            policy.apply("192.0.2.77".parse().unwrap()),
+            "192.0.2.0".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            policy.apply("2001:db8:abcd:1234:5678:9abc:def0:1".parse().unwrap()),
+            "2001:db8:abcd::".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_hmac_stable() {
+        let policy = ClientIpAnonymization::Hmac(b"secret".to_vec());
+        let client: IpAddr = "192.0.2.77".parse().unwrap();
+
+        let first = policy.apply(client);
+        assert_eq!(first, policy.apply(client));
+        assert_ne!(first, client);
+        assert!(first.is_ipv4());
+
+        // 不同客户端、不同密钥得到不同结果
+        assert_ne!(first, policy.apply("192.0.2.78".parse().unwrap()));
+        let other_key = ClientIpAnonymization::Hmac(b"other".to_vec());
+        assert_ne!(first, other_key.apply(client));
+
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        assert!(policy.apply(v6).is_ipv6());
+        assert_eq!(format!("{:?}", policy), "Hmac(..)");
+    }
+
+    #[test]
+    fn test_apply_message_rewrites_client_only() {
+        let response = DnsMessage {
+            transaction_id: 1,
+            message_type: DnsMessageType::Response,
+            src_ip: "192.0.2.53".parse().unwrap(),
+            dst_ip: "198.51.100.77".parse().unwrap(),
+            src_port: 53,
+            dst_port: 40000,
+            ..Default::default()
+        };
+
+        let kept = ClientIpAnonymization::None.apply_message(Cow::Borrowed(&response));
+        assert!(matches!(kept, Cow::Borrowed(_)));
+
+        let truncated = ClientIpAnonymization::Truncate.apply_message(Cow::Borrowed(&response));
+        assert_eq!(truncated.dst_ip, "198.51.100.0".parse::<IpAddr>().unwrap());
+        assert_eq!(truncated.src_ip, response.src_ip);
+    }
+}
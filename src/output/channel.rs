@@ -0,0 +1,76 @@
+//! 通道输出实现
+//! 把每条DNS消息发送到调用方提供的通道，供嵌入dns_spider的程序在自己的线程中接收
+
+use crossbeam::channel::Sender;
+
+use crate::output::Output;
+use crate::protocols::dns::DnsMessage;
+
+/// 通道输出
+///
+/// 有界通道写满时发送会阻塞工作线程，直到接收方取走消息；不希望反压抓包时使用无界通道。
+pub struct ChannelOutput {
+    sender: Sender<DnsMessage>,
+    /// 接收方已关闭，之后的消息直接丢弃
+    disconnected: bool,
+}
+
+impl ChannelOutput {
+    /// 创建新的通道输出
+    pub fn new(sender: Sender<DnsMessage>) -> Self {
+        ChannelOutput {
+            sender,
+            disconnected: false,
+        }
+    }
+}
+
+impl Output for ChannelOutput {
+    fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        if self.disconnected {
+            return Ok(());
+        }
+        // 接收方关闭只报告一次，避免每条消息都输出错误
+        self.sender.send(message.clone()).map_err(|_| {
+            self.disconnected = true;
+            "Channel receiver disconnected, dropping further messages".to_string()
+        })
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stats::StatsCounter;
+    use crate::protocols::dns::{DnsParser, UdpDnsParser};
+    use crossbeam::channel;
+
+    #[test]
+    fn test_messages_delivered_until_receiver_dropped() {
+        // example.com A查询
+        let packet = [
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, b'e', b'x', b'a', b'm',
+            b'p', b'l', b'e', 0x03, b'c', b'o', b'm', 0x00, 0x00, 0x01, 0x00, 0x01,
+        ];
+        let message = UdpDnsParser::new(65535).parse(&packet, &StatsCounter::new()).unwrap();
+
+        let (sender, receiver) = channel::unbounded();
+        let mut output = ChannelOutput::new(sender);
+        output.output(&message).unwrap();
+        output.output(&message).unwrap();
+
+        let received: Vec<DnsMessage> = receiver.try_iter().collect();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].transaction_id, 0x1234);
+        assert_eq!(received[0].questions[0].name, "example.com");
+
+        // 接收方关闭后只报告一次错误
+        drop(receiver);
+        assert!(output.output(&message).is_err());
+        assert!(output.output(&message).is_ok());
+    }
+}
@@ -1,20 +1,74 @@
 //! 控制台输出实现
 //! 将DNS消息输出到控制台
 
-use crate::output::{ConsoleConfig, Output};
-use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsRecordType};
-use colored::*;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+
+use crate::output::{truncate_text, ColorMode, ConsoleConfig, Heartbeat, Output};
+use crate::protocols::dns::{dnssd_records, is_service_enumeration, rcode_name, DnsMessage, DnsMessageType, DnsRecordType};
+
+/// 查询使用的ANSI前景色（蓝）
+const ANSI_BLUE: &str = "\x1b[34m";
+/// 响应使用的ANSI前景色（绿）
+const ANSI_GREEN: &str = "\x1b[32m";
+/// 恢复默认样式
+const ANSI_RESET: &str = "\x1b[0m";
 
 /// 控制台输出
 pub struct ConsoleOutput {
     /// 配置
     config: ConsoleConfig,
+    /// 实际是否使用彩色输出
+    use_color: bool,
+    /// 单条消息文本的最大字节数，不含颜色控制符
+    max_event_bytes: Option<usize>,
 }
 
 impl ConsoleOutput {
     /// 创建新的控制台输出
     pub fn new(config: ConsoleConfig) -> Result<Self, String> {
-        Ok(ConsoleOutput { config })
+        let use_color = Self::should_colorize(
+            config.color,
+            std::env::var_os("NO_COLOR").is_some(),
+            std::io::stdout().is_terminal(),
+        );
+
+        Ok(ConsoleOutput {
+            config,
+            use_color,
+            max_event_bytes: None,
+        })
+    }
+
+    /// 限制单条消息文本的长度，超出时截断并标记
+    pub fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// 决定是否使用彩色输出：自动模式下设置了NO_COLOR或stdout不是终端时禁用，
+    /// 显式开启或关闭时不受环境影响
+    fn should_colorize(mode: ColorMode, no_color: bool, is_tty: bool) -> bool {
+        match mode {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => !no_color && is_tty,
+        }
+    }
+
+    /// 渲染最终输出文本，颜色只取决于本输出的配置，不依赖进程级的全局开关
+    fn render(&self, message: &DnsMessage) -> String {
+        let formatted = truncate_text(self.format_message(message), self.max_event_bytes);
+
+        if self.use_color {
+            let color = match message.message_type {
+                DnsMessageType::Query => ANSI_BLUE,
+                DnsMessageType::Response => ANSI_GREEN,
+            };
+            format!("{}{}{}", color, formatted, ANSI_RESET)
+        } else {
+            formatted
+        }
     }
 
     /// 格式化DNS消息
@@ -28,20 +82,34 @@ impl ConsoleOutput {
         };
 
         result.push_str(&format!(
-            "[DNS {}] ID: {:04X} | 协议: {:?}\n",
-            msg_type, message.transaction_id, message.protocol
+            "[DNS {}] {}.{:06} | ID: {:04X} | 协议: {:?} | {} -> {}{}{}\n",
+            msg_type,
+            message.timestamp / 1_000_000,
+            message.timestamp % 1_000_000,
+            message.transaction_id,
+            message.protocol,
+            SocketAddr::new(message.src_ip, message.src_port),
+            SocketAddr::new(message.dst_ip, message.dst_port),
+            if message.unsolicited { " | 未见查询" } else { "" },
+            if message.unreachable { " | 端口不可达" } else { "" }
         ));
 
+        // 响应码，便于定位NXDOMAIN/SERVFAIL
+        if message.message_type == DnsMessageType::Response {
+            result.push_str(&format!("响应码: {}\n", rcode_name(message.rcode)));
+        }
+
         // 问题部分
         if !message.questions.is_empty() {
             result.push_str("问题:\n");
             for (i, q) in message.questions.iter().enumerate() {
                 result.push_str(&format!(
-                    "  {}. {} (类型: {:?}, 类: {})\n",
+                    "  {}. {} (类型: {}, 类: {}){}\n",
                     i + 1,
                     q.name,
                     q.record_type,
-                    q.class
+                    q.class,
+                    if is_service_enumeration(&q.name) { " [DNS-SD服务枚举]" } else { "" }
                 ));
             }
         }
@@ -51,7 +119,7 @@ impl ConsoleOutput {
             result.push_str("应答:\n");
             for (i, a) in message.answers.iter().enumerate() {
                 result.push_str(&format!(
-                    "  {}. {} (类型: {:?}, TTL: {}s)\n",
+                    "  {}. {} (类型: {}, TTL: {}s)\n",
                     i + 1,
                     a.name,
                     a.record_type,
@@ -79,25 +147,55 @@ impl ConsoleOutput {
             }
         }
 
+        // 详细模式下显示权威和附加部分
+        if self.config.verbose {
+            for (title, records) in [("权威", &message.authorities), ("附加", &message.additionals)] {
+                if records.is_empty() {
+                    continue;
+                }
+                result.push_str(&format!("{}:\n", title));
+                for (i, r) in records.iter().enumerate() {
+                    result.push_str(&format!(
+                        "  {}. {} (类型: {}, TTL: {}s) {}\n",
+                        i + 1,
+                        r.name,
+                        r.record_type,
+                        r.ttl,
+                        r.data_str
+                    ));
+                }
+            }
+        }
+
+        // 详细模式下列出DNS-SD发现的服务类型和实例
+        if self.config.verbose {
+            let services = dnssd_records(message);
+            if !services.is_empty() {
+                result.push_str("发现服务:\n");
+                for service in services {
+                    result.push_str(&format!("  - {}\n", service));
+                }
+            }
+        }
+
         result
     }
 }
 
 impl Output for ConsoleOutput {
     fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
-        let formatted = self.format_message(message);
-
-        // 根据配置决定是否使用彩色输出
-        if self.config.color {
-            let colored_output = match message.message_type {
-                DnsMessageType::Query => formatted.blue(),
-                DnsMessageType::Response => formatted.green(),
-            };
-            println!("{}", colored_output);
-        } else {
-            println!("{}", formatted);
-        }
+        println!("{}", self.render(message));
+        Ok(())
+    }
 
+    fn heartbeat(&mut self, heartbeat: &Heartbeat) -> Result<(), String> {
+        println!(
+            "[心跳] 运行: {}秒 | 已处理: {} | 接收: {} | 丢弃: {}",
+            heartbeat.uptime_secs,
+            heartbeat.packets_processed,
+            heartbeat.capture_rx_packets,
+            heartbeat.capture_dropped_packets
+        );
         Ok(())
     }
 
@@ -106,3 +204,93 @@ impl Output for ConsoleOutput {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsClass, DnsQuestion};
+
+    #[test]
+    fn test_should_colorize() {
+        assert!(ConsoleOutput::should_colorize(ColorMode::Auto, false, true));
+        assert!(!ConsoleOutput::should_colorize(ColorMode::Never, false, true));
+        assert!(!ConsoleOutput::should_colorize(ColorMode::Auto, true, true));
+        assert!(!ConsoleOutput::should_colorize(ColorMode::Auto, false, false));
+        // 显式开启时重定向到文件或设置了NO_COLOR也保留颜色
+        assert!(ConsoleOutput::should_colorize(ColorMode::Always, true, false));
+    }
+
+    #[test]
+    fn test_color_is_per_output() {
+        let colored = ConsoleOutput::new(ConsoleConfig {
+            verbose: true,
+            color: ColorMode::Always,
+        })
+        .unwrap();
+        let plain = ConsoleOutput::new(ConsoleConfig {
+            verbose: true,
+            color: ColorMode::Never,
+        })
+        .unwrap();
+
+        // 后创建的输出不影响先创建的输出
+        let message = DnsMessage::default();
+        assert!(colored.render(&message).starts_with(ANSI_BLUE));
+        assert!(colored.render(&message).ends_with(ANSI_RESET));
+        assert!(!plain.render(&message).contains('\x1b'));
+    }
+
+    #[test]
+    fn test_no_ansi_codes_when_color_disabled() {
+        let output = ConsoleOutput::new(ConsoleConfig {
+            verbose: true,
+            color: ColorMode::Never,
+        })
+        .unwrap();
+
+        let message = DnsMessage {
+            transaction_id: 1,
+            questions: vec![DnsQuestion {
+                name: "example.com".to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            timestamp: 1_700_000_000_000_042,
+            ..Default::default()
+        };
+
+        let rendered = output.render(&message);
+        assert!(rendered.contains("example.com"));
+        assert!(rendered.contains("1700000000.000042"));
+        assert!(!rendered.contains('\x1b'));
+    }
+
+    #[test]
+    fn test_record_type_display() {
+        assert_eq!(DnsRecordType::Other(65280).to_string(), "TYPE65280");
+        assert_eq!(DnsRecordType::AAAA.to_string(), "AAAA");
+
+        let output = ConsoleOutput::new(ConsoleConfig {
+            verbose: true,
+            color: ColorMode::Never,
+        })
+        .unwrap();
+        let message = DnsMessage {
+            transaction_id: 1,
+            questions: vec![DnsQuestion {
+                name: "example.com".to_string(),
+                record_type: DnsRecordType::Other(65280),
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            ..Default::default()
+        };
+
+        let rendered = output.render(&message);
+        assert!(rendered.contains("类型: TYPE65280"));
+        assert!(!rendered.contains("Other"));
+    }
+}
@@ -0,0 +1,201 @@
+//! CSV输出
+//! 把每个问题展开为`timestamp,src_ip,qname,qtype,rcode,answer`行，便于导入表格或数据库；
+//! 多条应答可以合并到一个单元格，也可以每条应答一行
+
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::output::{truncate_text, CsvAnswerMode, CsvConfig, Output};
+use crate::protocols::dns::{rcode_name, DnsMessage, DnsMessageType};
+use crate::utils::time::utc_timestamp;
+
+/// 表头
+const HEADER: [&str; 6] = ["timestamp", "src_ip", "qname", "qtype", "rcode", "answer"];
+
+/// CSV输出
+pub struct CsvOutput {
+    writer: csv::Writer<Box<dyn Write + Send>>,
+    answer_mode: CsvAnswerMode,
+    answer_separator: String,
+    /// 单行的最大字节数，超出时截断应答列
+    max_event_bytes: Option<usize>,
+}
+
+impl CsvOutput {
+    /// 创建新的CSV输出，追加写入配置的文件，新文件先写表头
+    pub fn new(config: CsvConfig) -> Result<Self, String> {
+        if let Some(dir) = Path::new(&config.path).parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create CSV directory: {}", e))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+            .map_err(|e| format!("Failed to open CSV file {}: {}", config.path, e))?;
+        let is_empty = file
+            .metadata()
+            .map_err(|e| format!("Failed to read CSV file metadata {}: {}", config.path, e))?
+            .len()
+            == 0;
+
+        Self::with_writer(Box::new(BufWriter::new(file)), &config, is_empty)
+    }
+
+    /// 使用指定的写入目标创建输出
+    pub fn with_writer(writer: Box<dyn Write + Send>, config: &CsvConfig, write_header: bool) -> Result<Self, String> {
+        let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(writer);
+        if write_header {
+            writer
+                .write_record(HEADER)
+                .map_err(|e| format!("Failed to write CSV header: {}", e))?;
+        }
+
+        Ok(CsvOutput {
+            writer,
+            answer_mode: config.answer_mode,
+            answer_separator: config.answer_separator.clone(),
+            max_event_bytes: None,
+        })
+    }
+
+    /// 限制单行的长度，超出时截断应答列并标记
+    pub fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// 消息展开后的行，查询的响应码和应答列为空
+    fn rows(&self, message: &DnsMessage) -> Vec<[String; 6]> {
+        let timestamp = utc_timestamp(message.timestamp);
+        let src_ip = message.src_ip.to_string();
+        let rcode = match message.message_type {
+            DnsMessageType::Query => String::new(),
+            DnsMessageType::Response => rcode_name(message.rcode),
+        };
+        let answers: Vec<&str> = message.answers.iter().map(|a| a.data_str.as_str()).collect();
+
+        let mut rows = Vec::new();
+        for question in &message.questions {
+            let row = |answer: String| {
+                // 应答列之外的字段长度有限，超限时只截断应答列，类型、分隔符和换行预留16字节
+                let budget = self.max_event_bytes.map(|max_bytes| {
+                    let fixed = timestamp.len() + src_ip.len() + question.name.len() + rcode.len() + 16;
+                    max_bytes.saturating_sub(fixed)
+                });
+                [
+                    timestamp.clone(),
+                    src_ip.clone(),
+                    question.name.clone(),
+                    question.record_type.to_string(),
+                    rcode.clone(),
+                    truncate_text(answer, budget),
+                ]
+            };
+            match self.answer_mode {
+                CsvAnswerMode::Rows if !answers.is_empty() => {
+                    rows.extend(answers.iter().map(|answer| row(answer.to_string())))
+                }
+                _ => rows.push(row(answers.join(&self.answer_separator))),
+            }
+        }
+        rows
+    }
+}
+
+impl Output for CsvOutput {
+    fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        for row in self.rows(message) {
+            self.writer
+                .write_record(&row)
+                .map_err(|e| format!("Failed to write CSV row: {}", e))?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush CSV file: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsAnswer, DnsClass, DnsQuestion, DnsRecordType};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn response(answers: &[&str]) -> DnsMessage {
+        DnsMessage {
+            transaction_id: 1,
+            message_type: DnsMessageType::Response,
+            questions: vec![DnsQuestion {
+                name: "example.com".to_string(),
+                record_type: DnsRecordType::TXT,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            answers: answers
+                .iter()
+                .map(|data| DnsAnswer {
+                    name: "example.com".to_string(),
+                    record_type: DnsRecordType::TXT,
+                    class: DnsClass::IN,
+                    cache_flush: false,
+                    ttl: 300,
+                    data: Vec::new(),
+                    data_str: data.to_string(),
+                })
+                .collect(),
+            timestamp: 1_700_000_000_000_000,
+            src_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)),
+            src_port: 53,
+            dst_port: 40000,
+            recursion_desired: true,
+            recursion_available: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rows_are_quoted_joined_or_split() {
+        let path = std::env::temp_dir().join(format!("dns_spider_csv_{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let config = CsvConfig {
+            path: path.to_str().unwrap().to_string(),
+            ..CsvConfig::default()
+        };
+
+        let mut output = CsvOutput::new(config.clone()).unwrap();
+        output.output(&response(&["v=spf1 -all", "say \"hi\", world"])).unwrap();
+        output.close().unwrap();
+
+        // 重新打开时不重复写表头，每条应答单独一行
+        let mut output = CsvOutput::new(CsvConfig {
+            answer_mode: CsvAnswerMode::Rows,
+            ..config
+        })
+        .unwrap();
+        output.output(&response(&["v=spf1 -all", "say \"hi\", world"])).unwrap();
+        output.output(&response(&[])).unwrap();
+        output.close().unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "timestamp,src_ip,qname,qtype,rcode,answer",
+                "2023-11-14T22:13:20.000000Z,10.0.0.53,example.com,TXT,NOERROR,\"v=spf1 -all;say \"\"hi\"\", world\"",
+                "2023-11-14T22:13:20.000000Z,10.0.0.53,example.com,TXT,NOERROR,v=spf1 -all",
+                "2023-11-14T22:13:20.000000Z,10.0.0.53,example.com,TXT,NOERROR,\"say \"\"hi\"\", world\"",
+                "2023-11-14T22:13:20.000000Z,10.0.0.53,example.com,TXT,NOERROR,",
+            ]
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,373 @@
+//! dnstap输出实现
+//! 以Frame Streams格式写出dnstap记录，兼容现有dnstap工具链
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::IpAddr;
+
+use prost::Message;
+
+use crate::output::{DnstapConfig, Output};
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsProtocol};
+
+/// Frame Streams内容类型
+pub const CONTENT_TYPE: &str = "protobuf:dnstap.Dnstap";
+
+/// Frame Streams控制帧类型
+const CONTROL_START: u32 = 0x02;
+const CONTROL_STOP: u32 = 0x03;
+/// 控制帧字段：内容类型
+const CONTROL_FIELD_CONTENT_TYPE: u32 = 0x01;
+
+/// dnstap.proto中的类型定义，字段编号与上游schema保持一致
+pub mod schema {
+    /// dnstap顶层记录
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Dnstap {
+        #[prost(bytes = "vec", optional, tag = "1")]
+        pub identity: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "2")]
+        pub version: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "3")]
+        pub extra: Option<Vec<u8>>,
+        #[prost(message, optional, tag = "14")]
+        pub message: Option<Message>,
+        #[prost(enumeration = "DnstapType", required, tag = "15")]
+        pub r#type: i32,
+    }
+
+    /// dnstap记录类型
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum DnstapType {
+        Message = 1,
+    }
+
+    /// 套接字地址族
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum SocketFamily {
+        Inet = 1,
+        Inet6 = 2,
+    }
+
+    /// 传输协议
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum SocketProtocol {
+        Udp = 1,
+        Tcp = 2,
+        Dot = 3,
+        Doh = 4,
+        DnscryptUdp = 5,
+        DnscryptTcp = 6,
+        Doq = 7,
+    }
+
+    /// 消息类型，被动抓包无法区分角色，统一使用TOOL_QUERY/TOOL_RESPONSE
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum MessageType {
+        AuthQuery = 1,
+        AuthResponse = 2,
+        ResolverQuery = 3,
+        ResolverResponse = 4,
+        ClientQuery = 5,
+        ClientResponse = 6,
+        ForwarderQuery = 7,
+        ForwarderResponse = 8,
+        StubQuery = 9,
+        StubResponse = 10,
+        ToolQuery = 11,
+        ToolResponse = 12,
+        UpdateQuery = 13,
+        UpdateResponse = 14,
+    }
+
+    /// dnstap消息
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Message {
+        #[prost(enumeration = "MessageType", required, tag = "1")]
+        pub r#type: i32,
+        #[prost(enumeration = "SocketFamily", optional, tag = "2")]
+        pub socket_family: Option<i32>,
+        #[prost(enumeration = "SocketProtocol", optional, tag = "3")]
+        pub socket_protocol: Option<i32>,
+        #[prost(bytes = "vec", optional, tag = "4")]
+        pub query_address: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "5")]
+        pub response_address: Option<Vec<u8>>,
+        #[prost(uint32, optional, tag = "6")]
+        pub query_port: Option<u32>,
+        #[prost(uint32, optional, tag = "7")]
+        pub response_port: Option<u32>,
+        #[prost(uint64, optional, tag = "8")]
+        pub query_time_sec: Option<u64>,
+        #[prost(fixed32, optional, tag = "9")]
+        pub query_time_nsec: Option<u32>,
+        #[prost(bytes = "vec", optional, tag = "10")]
+        pub query_message: Option<Vec<u8>>,
+        #[prost(bytes = "vec", optional, tag = "11")]
+        pub query_zone: Option<Vec<u8>>,
+        #[prost(uint64, optional, tag = "12")]
+        pub response_time_sec: Option<u64>,
+        #[prost(fixed32, optional, tag = "13")]
+        pub response_time_nsec: Option<u32>,
+        #[prost(bytes = "vec", optional, tag = "14")]
+        pub response_message: Option<Vec<u8>>,
+    }
+}
+
+/// 将DNS消息转换为dnstap记录
+pub fn to_dnstap(message: &DnsMessage, identity: &str) -> schema::Dnstap {
+    let socket_protocol = match message.protocol {
+        // dnstap没有mDNS对应的取值，按其承载的UDP记录
+        DnsProtocol::Udp | DnsProtocol::Mdns => schema::SocketProtocol::Udp,
+        DnsProtocol::Tcp => schema::SocketProtocol::Tcp,
+        DnsProtocol::Dot => schema::SocketProtocol::Dot,
+        DnsProtocol::Doh => schema::SocketProtocol::Doh,
+        DnsProtocol::Doq => schema::SocketProtocol::Doq,
+    };
+
+    // 时间戳为微秒
+    let sec = message.timestamp / 1_000_000;
+    let nsec = ((message.timestamp % 1_000_000) * 1_000) as u32;
+    let wire = message.raw.clone();
+
+    let socket_family = match message.src_ip {
+        IpAddr::V4(_) => schema::SocketFamily::Inet,
+        IpAddr::V6(_) => schema::SocketFamily::Inet6,
+    };
+
+    let mut tap_message = schema::Message {
+        socket_family: Some(socket_family as i32),
+        socket_protocol: Some(socket_protocol as i32),
+        ..Default::default()
+    };
+
+    // dnstap的query_address始终是发起查询的一方
+    let (query_ip, query_port, response_ip, response_port) = match message.message_type {
+        DnsMessageType::Query => (message.src_ip, message.src_port, message.dst_ip, message.dst_port),
+        DnsMessageType::Response => (message.dst_ip, message.dst_port, message.src_ip, message.src_port),
+    };
+    tap_message.query_address = Some(ip_bytes(query_ip));
+    tap_message.query_port = Some(query_port as u32);
+    tap_message.response_address = Some(ip_bytes(response_ip));
+    tap_message.response_port = Some(response_port as u32);
+
+    match message.message_type {
+        DnsMessageType::Query => {
+            tap_message.r#type = schema::MessageType::ToolQuery as i32;
+            tap_message.query_time_sec = Some(sec);
+            tap_message.query_time_nsec = Some(nsec);
+            tap_message.query_message = wire;
+        }
+        DnsMessageType::Response => {
+            tap_message.r#type = schema::MessageType::ToolResponse as i32;
+            tap_message.response_time_sec = Some(sec);
+            tap_message.response_time_nsec = Some(nsec);
+            tap_message.response_message = wire;
+        }
+    }
+
+    schema::Dnstap {
+        identity: Some(identity.as_bytes().to_vec()),
+        version: Some(env!("CARGO_PKG_VERSION").as_bytes().to_vec()),
+        extra: None,
+        message: Some(tap_message),
+        r#type: schema::DnstapType::Message as i32,
+    }
+}
+
+/// IP地址的网络字节序表示
+fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(v4) => v4.octets().to_vec(),
+        IpAddr::V6(v6) => v6.octets().to_vec(),
+    }
+}
+
+/// Frame Streams单向写入器
+pub struct FrameStreamWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> FrameStreamWriter<W> {
+    /// 创建写入器并写出START控制帧
+    pub fn new(mut writer: W) -> std::io::Result<Self> {
+        let content_type = CONTENT_TYPE.as_bytes();
+        let control_len = 4 + 4 + 4 + content_type.len() as u32;
+
+        writer.write_all(&0u32.to_be_bytes())?;
+        writer.write_all(&control_len.to_be_bytes())?;
+        writer.write_all(&CONTROL_START.to_be_bytes())?;
+        writer.write_all(&CONTROL_FIELD_CONTENT_TYPE.to_be_bytes())?;
+        writer.write_all(&(content_type.len() as u32).to_be_bytes())?;
+        writer.write_all(content_type)?;
+
+        Ok(FrameStreamWriter { writer })
+    }
+
+    /// 写出一个数据帧
+    pub fn write_frame(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        self.writer.write_all(&(payload.len() as u32).to_be_bytes())?;
+        self.writer.write_all(payload)
+    }
+
+    /// 写出STOP控制帧并返回底层写入器
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.writer.write_all(&0u32.to_be_bytes())?;
+        self.writer.write_all(&4u32.to_be_bytes())?;
+        self.writer.write_all(&CONTROL_STOP.to_be_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// dnstap输出
+pub struct DnstapOutput {
+    /// 配置
+    config: DnstapConfig,
+    /// Frame Streams写入器
+    writer: Option<FrameStreamWriter<BufWriter<File>>>,
+    /// 单条记录的最大字节数
+    max_event_bytes: Option<usize>,
+}
+
+impl DnstapOutput {
+    /// 创建新的dnstap输出
+    pub fn new(config: DnstapConfig) -> Result<Self, String> {
+        let file = File::create(&config.path)
+            .map_err(|e| format!("Failed to create dnstap file: {}", e))?;
+        let writer = FrameStreamWriter::new(BufWriter::new(file))
+            .map_err(|e| format!("Failed to write dnstap header: {}", e))?;
+
+        Ok(DnstapOutput {
+            config,
+            writer: Some(writer),
+            max_event_bytes: None,
+        })
+    }
+
+    /// 限制单条记录的长度，超出时去掉原始报文，并在extra中标记原始长度
+    pub fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+}
+
+/// 编码dnstap记录，超过`max_bytes`时去掉占绝大部分长度的原始报文
+fn encode_limited(mut record: schema::Dnstap, max_bytes: Option<usize>) -> Vec<u8> {
+    let payload = record.encode_to_vec();
+    match max_bytes {
+        Some(max_bytes) if payload.len() > max_bytes => {
+            if let Some(message) = &mut record.message {
+                message.query_message = None;
+                message.response_message = None;
+            }
+            record.extra = Some(format!("{{\"truncated\": true, \"original_bytes\": {}}}", payload.len()).into_bytes());
+            record.encode_to_vec()
+        }
+        _ => payload,
+    }
+}
+
+impl Output for DnstapOutput {
+    fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        let payload = encode_limited(to_dnstap(message, &self.config.identity), self.max_event_bytes);
+
+        if let Some(writer) = &mut self.writer {
+            writer
+                .write_frame(&payload)
+                .map_err(|e| format!("Failed to write dnstap frame: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        if let Some(writer) = self.writer.take() {
+            writer
+                .finish()
+                .map_err(|e| format!("Failed to finish dnstap stream: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsClass, DnsQuestion, DnsRecordType, DnsRole};
+    use std::net::Ipv4Addr;
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    }
+
+    #[test]
+    fn test_dnstap_frame_round_trip() {
+        let wire = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let message = DnsMessage {
+            transaction_id: 0x1234,
+            questions: vec![DnsQuestion {
+                name: "example.com".to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            timestamp: 1_700_000_000_123_456,
+            raw: Some(wire.clone()),
+            src_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)),
+            src_port: 40000,
+            dst_port: 53,
+            ..Default::default()
+        };
+
+        let mut writer = FrameStreamWriter::new(Vec::new()).unwrap();
+        writer
+            .write_frame(&to_dnstap(&message, "sensor-1").encode_to_vec())
+            .unwrap();
+        let stream = writer.finish().unwrap();
+
+        // START控制帧
+        assert_eq!(read_u32(&stream, 0), 0);
+        let control_len = read_u32(&stream, 4) as usize;
+        assert_eq!(read_u32(&stream, 8), CONTROL_START);
+        assert_eq!(&stream[20..8 + control_len], CONTENT_TYPE.as_bytes());
+
+        // 数据帧
+        let offset = 8 + control_len;
+        let frame_len = read_u32(&stream, offset) as usize;
+        let frame = &stream[offset + 4..offset + 4 + frame_len];
+        let decoded = schema::Dnstap::decode(frame).unwrap();
+
+        assert_eq!(decoded.identity.as_deref(), Some("sensor-1".as_bytes()));
+        let tap = decoded.message.unwrap();
+        assert_eq!(tap.r#type, schema::MessageType::ToolQuery as i32);
+        assert_eq!(tap.socket_protocol, Some(schema::SocketProtocol::Udp as i32));
+        assert_eq!(tap.query_time_sec, Some(1_700_000_000));
+        assert_eq!(tap.query_time_nsec, Some(123_456_000));
+        assert_eq!(tap.query_message, Some(wire));
+        assert_eq!(tap.socket_family, Some(schema::SocketFamily::Inet as i32));
+        assert_eq!(tap.query_address, Some(vec![192, 0, 2, 10]));
+        assert_eq!(tap.query_port, Some(40000));
+        assert_eq!(tap.response_address, Some(vec![192, 0, 2, 53]));
+        assert_eq!(tap.response_port, Some(53));
+
+        // STOP控制帧
+        let stop = offset + 4 + frame_len;
+        assert_eq!(&stream[stop..], &[0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 3]);
+
+        // 超限时去掉原始报文并在extra中标记
+        let full = to_dnstap(&message, "sensor-1").encode_to_vec();
+        assert_eq!(encode_limited(to_dnstap(&message, "sensor-1"), Some(full.len())), full);
+        let limited = encode_limited(to_dnstap(&message, "sensor-1"), Some(full.len() - 1));
+        let decoded = schema::Dnstap::decode(limited.as_slice()).unwrap();
+        assert_eq!(decoded.message.unwrap().query_message, None);
+        let extra = format!("{{\"truncated\": true, \"original_bytes\": {}}}", full.len());
+        assert_eq!(decoded.extra, Some(extra.into_bytes()));
+    }
+}
@@ -0,0 +1,225 @@
+//! 解析错误事件输出
+//! 畸形报文默认只计数，启用后把解析失败的原因、流信息和负载前若干字节写成单行JSON，
+//! 按秒限频，便于排查线上问题又不会在攻击流量下刷爆磁盘
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::core::stats::StatsCounter;
+use crate::output::ClientIpAnonymization;
+
+/// 限频窗口
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// 解析错误输出配置
+#[derive(Debug, Clone)]
+pub struct ErrorOutputConfig {
+    /// 输出文件路径，追加写入
+    pub path: PathBuf,
+    /// 每秒最多写出的事件数，超出部分只计数
+    pub max_events_per_sec: u32,
+    /// 事件中保留的负载前缀字节数（十六进制）
+    pub payload_prefix_bytes: usize,
+}
+
+impl Default for ErrorOutputConfig {
+    fn default() -> Self {
+        ErrorOutputConfig {
+            path: PathBuf::from("./logs/parse-errors.json"),
+            max_events_per_sec: 10,
+            payload_prefix_bytes: 64,
+        }
+    }
+}
+
+/// 解析错误事件
+#[derive(Debug, Serialize)]
+struct ParseErrorEvent<'a> {
+    /// 抓包时间戳（微秒）
+    timestamp: u64,
+    /// 失败原因
+    reason: &'a str,
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    src_port: u16,
+    dst_port: u16,
+    /// 负载总长度
+    payload_len: usize,
+    /// 负载前缀的十六进制
+    payload_hex: String,
+    /// 上一条事件之后因限频未写出的事件数
+    suppressed: u64,
+}
+
+/// 解析错误输出，未指定写入目标时写文件
+pub struct ErrorOutput<W: Write = File> {
+    config: ErrorOutputConfig,
+    writer: W,
+    /// 当前限频窗口的开始时间和已写出数量
+    window: Option<(Instant, u32)>,
+    /// 因限频未写出的事件数
+    suppressed: u64,
+    /// 客户端IP匿名化，畸形报文无法判断方向，两端地址都处理
+    anonymize: ClientIpAnonymization,
+}
+
+impl ErrorOutput<File> {
+    /// 创建解析错误输出，目录不存在时自动创建
+    pub fn new(config: ErrorOutputConfig) -> Result<Self, String> {
+        if let Some(dir) = config.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+            .map_err(|e| format!("Failed to open {}: {}", config.path.display(), e))?;
+        Ok(Self::with_writer(config, file))
+    }
+}
+
+impl<W: Write> ErrorOutput<W> {
+    /// 写入指定目标
+    pub fn with_writer(config: ErrorOutputConfig, writer: W) -> Self {
+        ErrorOutput {
+            config,
+            writer,
+            window: None,
+            suppressed: 0,
+            anonymize: ClientIpAnonymization::None,
+        }
+    }
+
+    /// 写出前按输出配置匿名化地址
+    pub fn with_anonymization(mut self, anonymize: ClientIpAnonymization) -> Self {
+        self.anonymize = anonymize;
+        self
+    }
+
+    /// 记录一次解析失败，超过限频时只计入`error_output.suppressed`
+    pub fn record(
+        &mut self,
+        timestamp: u64,
+        reason: &str,
+        flow: (IpAddr, IpAddr, u16, u16),
+        payload: &[u8],
+        now: Instant,
+        stats: &StatsCounter,
+    ) {
+        let emitted = match &mut self.window {
+            Some((start, emitted)) if now.duration_since(*start) < RATE_WINDOW => emitted,
+            window => &mut window.insert((now, 0)).1,
+        };
+        if *emitted >= self.config.max_events_per_sec {
+            self.suppressed += 1;
+            stats.increment("error_output.suppressed");
+            return;
+        }
+        *emitted += 1;
+
+        let prefix = &payload[..payload.len().min(self.config.payload_prefix_bytes)];
+        let (src_ip, dst_ip, src_port, dst_port) = flow;
+        let event = ParseErrorEvent {
+            timestamp,
+            reason,
+            src_ip: self.anonymize.apply(src_ip),
+            dst_ip: self.anonymize.apply(dst_ip),
+            src_port,
+            dst_port,
+            payload_len: payload.len(),
+            payload_hex: prefix.iter().map(|b| format!("{:02x}", b)).collect(),
+            suppressed: self.suppressed,
+        };
+        // 字段均可序列化为JSON，不会失败
+        let mut line = serde_json::to_string(&event).unwrap_or_default();
+        line.push('\n');
+
+        match self.writer.write_all(line.as_bytes()) {
+            Ok(()) => {
+                self.suppressed = 0;
+                stats.increment("error_output.events");
+            }
+            Err(e) => {
+                eprintln!("Failed to write parse error event: {}", e);
+                stats.increment("error_output.write_failed");
+            }
+        }
+    }
+
+    /// 刷新写入目标
+    pub fn flush(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            eprintln!("Failed to flush parse error events: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsParser, UdpDnsParser};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_malformed_packet_event_and_rate_limit() {
+        let config = ErrorOutputConfig {
+            max_events_per_sec: 3,
+            payload_prefix_bytes: 4,
+            ..ErrorOutputConfig::default()
+        };
+        let mut output = ErrorOutput::with_writer(config, Vec::new());
+        let stats = StatsCounter::new();
+        let flow = (
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)),
+            40000,
+            53,
+        );
+
+        // 声明了一个问题但报文在头部后截断
+        let packet = [0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, b'e'];
+        let error = UdpDnsParser::new(65535).try_parse(&packet, &stats).unwrap_err();
+        let start = Instant::now();
+        output.record(1_000, &error.to_string(), flow, &packet, start, &stats);
+
+        let written = String::from_utf8(output.writer.clone()).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(event["reason"], error.to_string());
+        assert_eq!(event["src_ip"], "10.0.0.1");
+        assert_eq!(event["dst_port"], 53);
+        assert_eq!(event["payload_len"], packet.len());
+        assert_eq!(event["payload_hex"], "12340100");
+        assert_eq!(event["suppressed"], 0);
+
+        // 同一秒内的洪泛只写出配额内的事件
+        for _ in 0..100 {
+            output.record(2_000, "flood", flow, &packet, start, &stats);
+        }
+        assert_eq!(output.writer.iter().filter(|&&b| b == b'\n').count(), 3);
+        assert_eq!(stats.get("error_output.events"), 3);
+        assert_eq!(stats.get("error_output.suppressed"), 98);
+
+        // 下一个窗口的第一条事件带上被抑制的数量
+        output.record(3_000, "later", flow, &packet, start + RATE_WINDOW, &stats);
+        let written = String::from_utf8(output.writer.clone()).unwrap();
+        let last: serde_json::Value = serde_json::from_str(written.lines().last().unwrap()).unwrap();
+        assert_eq!(last["reason"], "later");
+        assert_eq!(last["suppressed"], 98);
+
+        // 启用匿名化时两端地址都截断
+        let mut output = ErrorOutput::with_writer(ErrorOutputConfig::default(), Vec::new())
+            .with_anonymization(ClientIpAnonymization::Truncate);
+        output.record(4_000, "truncated", flow, &packet, start, &stats);
+        let written = String::from_utf8(output.writer).unwrap();
+        let event: serde_json::Value = serde_json::from_str(written.trim_end()).unwrap();
+        assert_eq!(event["src_ip"], "10.0.0.0");
+        assert_eq!(event["dst_ip"], "10.0.0.0");
+    }
+}
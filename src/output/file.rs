@@ -1,29 +1,54 @@
 //! 文件输出实现
 //! 将DNS消息输出到文件
 
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
-use std::path::Path;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use crate::output::{FileConfig, Output};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::output::{
+    format_message_json, format_message_json_compact, truncate_event, FileConfig, FileFormat, FilePartition, Heartbeat,
+    Output, OutputEncoding,
+};
 use crate::protocols::dns::DnsMessage;
+use crate::utils::time::utc_date;
+
+/// 单个分区当前打开的文件
+struct PartitionFile {
+    file: BufWriter<File>,
+    /// 文件路径，关闭后按配置压缩
+    path: PathBuf,
+    /// 打开时间，各分区独立轮转
+    opened_at: SystemTime,
+    /// 最近使用序号，句柄数超限时关闭最久未用的分区
+    last_used: u64,
+}
 
 /// 文件输出
 pub struct FileOutput {
     /// 配置
     config: FileConfig,
-    /// 当前文件
-    current_file: Option<File>,
-    /// 当前文件路径
-    current_path: String,
-    /// 上次轮转时间
-    last_rotation: SystemTime,
+    /// 各分区打开的文件，未分区时只有键为空串的一个
+    files: HashMap<String, PartitionFile>,
+    /// 使用序号
+    use_counter: u64,
+    /// 单条JSON事件的最大字节数
+    max_event_bytes: Option<usize>,
+    /// 上次按时间落盘的时间
+    last_flush: Instant,
+    /// 上次落盘后写入缓冲的字节数
+    pending_bytes: usize,
 }
 
 impl FileOutput {
     /// 创建新的文件输出
     pub fn new(config: FileConfig) -> Result<Self, String> {
+        config.encoding.check_supported()?;
+
         // 确保输出目录存在
         let output_dir = Path::new(&config.output_dir);
         if !output_dir.exists() {
@@ -33,28 +58,74 @@ impl FileOutput {
 
         let mut output = FileOutput {
             config,
-            current_file: None,
-            current_path: String::new(),
-            last_rotation: SystemTime::now(),
+            files: HashMap::new(),
+            use_counter: 0,
+            max_event_bytes: None,
+            last_flush: Instant::now(),
+            pending_bytes: 0,
         };
 
-        // 初始化文件
-        output.rotate_file()?;
+        // 未分区时立即创建文件，分区文件在首条消息到达时创建
+        if output.config.partition == FilePartition::None {
+            output.open_file("")?;
+        }
 
         Ok(output)
     }
 
-    /// 轮转文件
-    fn rotate_file(&mut self) -> Result<(), String> {
+    /// 限制单条JSON事件的长度，超出时截断并标记
+    pub fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// 消息所属分区
+    fn partition_key(&self, message: &DnsMessage) -> String {
+        match self.config.partition {
+            FilePartition::None => String::new(),
+            FilePartition::ByProtocol => format!("{:?}", message.protocol).to_ascii_lowercase(),
+            FilePartition::ByDate => utc_date(message.timestamp / 1_000_000),
+            FilePartition::ByRecordType => match message.questions.first() {
+                Some(question) => question.record_type.to_string().to_ascii_lowercase(),
+                None => "none".to_string(),
+            },
+        }
+    }
+
+    /// 为分区打开新文件（轮转），句柄数达到上限时先关闭最久未用的分区
+    fn open_file(&mut self, key: &str) -> Result<(), String> {
+        if !self.files.contains_key(key) && self.files.len() >= self.config.max_open_files.max(1) {
+            let oldest = self
+                .files
+                .iter()
+                .min_by_key(|(_, file)| file.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                if let Some(evicted) = self.files.remove(&oldest) {
+                    self.close_partition(evicted)?;
+                }
+            }
+        }
+
+        // 轮转：先关闭旧文件，新文件即使与旧文件同名也不会被压缩
+        if let Some(old) = self.files.remove(key) {
+            self.close_partition(old)?;
+        }
+
         // 生成新文件名
         let timestamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map_err(|e| format!("Time error: {}", e))?
             .as_secs();
 
+        let extension = match self.config.encoding {
+            OutputEncoding::Json => "log",
+            OutputEncoding::Protobuf => "pb",
+        };
+        let partition = if key.is_empty() { String::new() } else { format!("{}-", key) };
         let filename = format!(
-            "{}{}{}.{}",
-            self.config.file_prefix, timestamp, self.config.file_suffix, "log"
+            "{}{}{}{}.{}",
+            self.config.file_prefix, partition, timestamp, self.config.file_suffix, extension
         );
 
         let path = Path::new(&self.config.output_dir).join(filename);
@@ -68,112 +139,321 @@ impl FileOutput {
             .open(&path)
             .map_err(|e| format!("Failed to open file: {}", e))?;
 
-        // 更新状态
-        self.current_file = Some(file);
-        self.current_path = path_str.to_string();
-        self.last_rotation = SystemTime::now();
+        self.files.insert(
+            key.to_string(),
+            PartitionFile {
+                file: BufWriter::with_capacity(self.config.flush_bytes.max(1), file),
+                path: path.clone(),
+                opened_at: SystemTime::now(),
+                last_used: self.use_counter,
+            },
+        );
 
         println!("Rotated to new file: {}", path_str);
 
         Ok(())
     }
 
-    /// 检查是否需要轮转文件
-    fn check_rotation(&mut self) -> Result<(), String> {
-        if let Ok(duration) = SystemTime::now().duration_since(self.last_rotation) {
-            if duration.as_secs() >= self.config.rotation_interval {
-                self.rotate_file()?;
+    /// 关闭分区文件：缓冲落盘后关闭，按配置压缩
+    ///
+    /// 压缩失败只记录日志并保留原文件，不影响后续输出。
+    fn close_partition(&self, partition: PartitionFile) -> Result<(), String> {
+        partition
+            .file
+            .into_inner()
+            .map_err(|e| format!("Failed to flush file: {}", e.error()))?;
+
+        if self.config.compress_on_rotate {
+            if let Err(e) = compress_file(&partition.path) {
+                eprintln!("Failed to compress {}: {}", partition.path.display(), e);
             }
         }
-
         Ok(())
     }
 
-    /// 格式化DNS消息为JSON
-    fn format_message_json(&self, message: &DnsMessage) -> String {
-        // 简单实现，实际项目中可能需要更复杂的JSON序列化
-        let mut json = String::new();
-
-        json.push_str("{\n");
-        json.push_str(&format!("  \"timestamp\": {},\n", message.timestamp));
-        json.push_str(&format!(
-            "  \"transaction_id\": {},\n",
-            message.transaction_id
-        ));
-        json.push_str(&format!(
-            "  \"message_type\": \"{:?}\",\n",
-            message.message_type
-        ));
-        json.push_str(&format!("  \"protocol\": \"{:?}\",\n", message.protocol));
-
-        // 问题
-        json.push_str("  \"questions\": [\n");
-        for (i, q) in message.questions.iter().enumerate() {
-            json.push_str("    {\n");
-            json.push_str(&format!("      \"name\": \"{}\",\n", q.name));
-            json.push_str(&format!(
-                "      \"record_type\": \"{:?}\",\n",
-                q.record_type
-            ));
-            json.push_str(&format!("      \"class\": {}\n", q.class));
-            json.push_str("    }");
-            if i < message.questions.len() - 1 {
-                json.push_str(",\n");
-            } else {
-                json.push_str("\n");
-            }
+    /// 获取分区文件，必要时打开或轮转
+    fn file_for(&mut self, key: &str) -> Result<&mut BufWriter<File>, String> {
+        let rotation_interval = Duration::from_secs(self.config.rotation_interval);
+        let expired = match self.files.get(key) {
+            Some(file) => SystemTime::now()
+                .duration_since(file.opened_at)
+                .is_ok_and(|age| age >= rotation_interval),
+            None => true,
+        };
+        if expired {
+            self.open_file(key)?;
         }
-        json.push_str("  ],\n");
-
-        // 应答
-        json.push_str("  \"answers\": [\n");
-        for (i, a) in message.answers.iter().enumerate() {
-            json.push_str("    {\n");
-            json.push_str(&format!("      \"name\": \"{}\",\n", a.name));
-            json.push_str(&format!(
-                "      \"record_type\": \"{:?}\",\n",
-                a.record_type
-            ));
-            json.push_str(&format!("      \"class\": {},\n", a.class));
-            json.push_str(&format!("      \"ttl\": {},\n", a.ttl));
-            json.push_str(&format!("      \"data\": \"{}\"\n", a.data_str));
-            json.push_str("    }");
-            if i < message.answers.len() - 1 {
-                json.push_str(",\n");
-            } else {
-                json.push_str("\n");
-            }
+
+        self.use_counter += 1;
+        let partition = self
+            .files
+            .get_mut(key)
+            .ok_or_else(|| format!("No open file for partition {}", key))?;
+        partition.last_used = self.use_counter;
+        Ok(&mut partition.file)
+    }
+
+    /// 写入分区文件的缓冲，所有分区累计未落盘字节数或距上次落盘时间超过阈值时全部落盘
+    fn write_to(&mut self, key: &str, data: &[u8]) -> Result<(), String> {
+        let file = self.file_for(key)?;
+        file.write_all(data)
+            .map_err(|e| format!("Failed to write to file: {}", e))?;
+
+        self.pending_bytes += data.len();
+        if self.pending_bytes >= self.config.flush_bytes || self.flush_due() {
+            self.flush_all()?;
         }
-        json.push_str("  ]\n");
+        Ok(())
+    }
 
-        json.push_str("}\n");
+    /// 是否到达落盘间隔
+    fn flush_due(&self) -> bool {
+        self.last_flush.elapsed() >= Duration::from_secs(self.config.flush_interval_secs)
+    }
 
-        json
+    /// 把所有分区的缓冲写入磁盘
+    fn flush_all(&mut self) -> Result<(), String> {
+        self.last_flush = Instant::now();
+        self.pending_bytes = 0;
+        for partition in self.files.values_mut() {
+            partition
+                .file
+                .flush()
+                .map_err(|e| format!("Failed to flush file: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// 把已关闭的文件压缩为`<文件名>.gz`并删除原文件
+///
+/// 同名压缩文件已存在时追加为新的gzip成员，解压时各成员依次拼接；
+/// 失败时截掉本次写入的部分，保留原文件。
+fn compress_file(path: &Path) -> io::Result<()> {
+    let mut gz_path = path.as_os_str().to_owned();
+    gz_path.push(".gz");
+    let gz_path = PathBuf::from(gz_path);
+
+    let archive = OpenOptions::new().create(true).append(true).open(&gz_path)?;
+    let original_len = archive.metadata()?.len();
+
+    if let Err(e) = write_gzip_member(path, &archive) {
+        let _ = archive.set_len(original_len);
+        return Err(e);
     }
+
+    std::fs::remove_file(path)
+}
+
+/// 把文件内容压缩为一个gzip成员写入压缩文件
+fn write_gzip_member(path: &Path, archive: &File) -> io::Result<()> {
+    let mut input = File::open(path)?;
+    let mut encoder = GzEncoder::new(BufWriter::new(archive), Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.flush()
 }
 
 impl Output for FileOutput {
     fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
-        // 检查是否需要轮转文件
-        self.check_rotation()?;
+        // 编码消息
+        let formatted = match self.config.encoding {
+            OutputEncoding::Json => {
+                let json = match self.config.format {
+                    FileFormat::Pretty => format_message_json(message),
+                    FileFormat::Ndjson => format_message_json_compact(message) + "\n",
+                };
+                truncate_event(json, self.max_event_bytes).into_bytes()
+            }
+            #[cfg(feature = "protobuf")]
+            OutputEncoding::Protobuf => crate::output::proto::encode_length_delimited(message),
+            #[cfg(not(feature = "protobuf"))]
+            OutputEncoding::Protobuf => return Err("protobuf功能未启用".to_string()),
+        };
 
-        // 格式化消息
-        let formatted = self.format_message_json(message);
+        // 写入所属分区的文件
+        let key = self.partition_key(message);
+        self.write_to(&key, &formatted)
+    }
 
-        // 写入文件
-        if let Some(file) = &mut self.current_file {
-            file.write_all(formatted.as_bytes())
-                .map_err(|e| format!("Failed to write to file: {}", e))?;
-            file.flush()
-                .map_err(|e| format!("Failed to flush file: {}", e))?;
+    fn heartbeat(&mut self, heartbeat: &Heartbeat) -> Result<(), String> {
+        // protobuf文件只包含DNS消息
+        if self.config.encoding != OutputEncoding::Json {
+            return Ok(());
         }
 
+        // 心跳不属于任何分区，写入未分区文件
+        self.write_to("", heartbeat.to_json().as_bytes())
+    }
+
+    fn tick(&mut self) -> Result<(), String> {
+        // 没有新消息时也按间隔落盘，流量停止后缓冲不会一直留在内存中
+        if self.pending_bytes > 0 && self.flush_due() {
+            self.flush_all()?;
+        }
         Ok(())
     }
 
     fn close(&mut self) -> Result<(), String> {
-        // 关闭文件
-        self.current_file = None;
-        Ok(())
+        // 落盘后关闭文件
+        let result = self.flush_all();
+        self.files.clear();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::DnsProtocol;
+
+    fn message(protocol: DnsProtocol) -> DnsMessage {
+        DnsMessage {
+            transaction_id: 1,
+            timestamp: 1_700_000_000_000_000,
+            protocol,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_partition_by_protocol() {
+        let dir = std::env::temp_dir().join(format!("dns_spider_file_partition_{}", std::process::id()));
+        let mut output = FileOutput::new(FileConfig {
+            output_dir: dir.to_str().unwrap().to_string(),
+            partition: FilePartition::ByProtocol,
+            max_open_files: 1,
+            ..FileConfig::default()
+        })
+        .unwrap();
+
+        output.output(&message(DnsProtocol::Udp)).unwrap();
+        output.output(&message(DnsProtocol::Tcp)).unwrap();
+        output.output(&message(DnsProtocol::Udp)).unwrap();
+        // 句柄数上限为1
+        assert_eq!(output.files.len(), 1);
+        output.close().unwrap();
+
+        let mut udp = 0;
+        let mut tcp = 0;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_str().unwrap().to_string();
+            let content = std::fs::read_to_string(&path).unwrap();
+            if name.starts_with("dns-udp-") {
+                udp += content.matches("\"protocol\": \"Udp\"").count();
+            } else if name.starts_with("dns-tcp-") {
+                tcp += content.matches("\"protocol\": \"Tcp\"").count();
+            }
+            assert!(!(content.contains("Udp") && content.contains("Tcp")));
+        }
+        assert_eq!((udp, tcp), (2, 1));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_buffered_until_close() {
+        let dir = std::env::temp_dir().join(format!("dns_spider_file_buffered_{}", std::process::id()));
+        let mut output = FileOutput::new(FileConfig {
+            output_dir: dir.to_str().unwrap().to_string(),
+            flush_interval_secs: 3600,
+            ..FileConfig::default()
+        })
+        .unwrap();
+        let read_all = || -> String {
+            std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+                .collect()
+        };
+
+        // 未到落盘间隔且缓冲未满，消息仍在内存中
+        output.output(&message(DnsProtocol::Udp)).unwrap();
+        output.output(&message(DnsProtocol::Udp)).unwrap();
+        output.tick().unwrap();
+        assert!(read_all().is_empty());
+
+        // 没有新消息时，定时调用到达落盘间隔后落盘
+        output.last_flush = Instant::now() - Duration::from_secs(3600);
+        output.tick().unwrap();
+        assert_eq!(read_all().matches("\"protocol\": \"Udp\"").count(), 2);
+
+        // 关闭时全部落盘
+        output.output(&message(DnsProtocol::Udp)).unwrap();
+        output.close().unwrap();
+        assert_eq!(read_all().matches("\"protocol\": \"Udp\"").count(), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_ndjson_one_compact_object_per_line() {
+        let dir = std::env::temp_dir().join(format!("dns_spider_file_ndjson_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut output = FileOutput::new(FileConfig {
+            output_dir: dir.to_str().unwrap().to_string(),
+            format: FileFormat::Ndjson,
+            ..FileConfig::default()
+        })
+        .unwrap();
+
+        output.output(&message(DnsProtocol::Udp)).unwrap();
+        output.output(&message(DnsProtocol::Tcp)).unwrap();
+        output.close().unwrap();
+
+        let content: String = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        assert!(content.ends_with("}\n"));
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for (line, protocol) in lines.iter().zip(["Udp", "Tcp"]) {
+            let event: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(event["protocol"], protocol);
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rotated_file_is_gzip_compressed() {
+        use flate2::read::MultiGzDecoder;
+        use std::io::Read;
+
+        let dir = std::env::temp_dir().join(format!("dns_spider_file_gzip_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        // 轮转间隔为0，每次写入前都轮转
+        let mut output = FileOutput::new(FileConfig {
+            output_dir: dir.to_str().unwrap().to_string(),
+            rotation_interval: 0,
+            compress_on_rotate: true,
+            ..FileConfig::default()
+        })
+        .unwrap();
+
+        output.output(&message(DnsProtocol::Udp)).unwrap();
+        output.output(&message(DnsProtocol::Tcp)).unwrap();
+        output.close().unwrap();
+
+        let mut compressed = String::new();
+        let mut plain = String::new();
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "gz") {
+                let mut decoder = MultiGzDecoder::new(File::open(&path).unwrap());
+                decoder.read_to_string(&mut compressed).unwrap();
+            } else {
+                plain.push_str(&std::fs::read_to_string(&path).unwrap());
+            }
+        }
+
+        // 轮转出的文件已压缩，当前打开的文件保持明文
+        assert!(compressed.contains("\"protocol\": \"Udp\""));
+        assert!(!compressed.contains("Tcp"));
+        assert!(plain.contains("\"protocol\": \"Tcp\""));
+        assert!(!plain.contains("Udp"));
+
+        let _ = std::fs::remove_dir_all(&dir);
     }
 }
@@ -0,0 +1,102 @@
+//! 心跳事件
+//! 定期向输出发送心跳，使下游能区分“没有DNS流量”和“程序已退出”
+
+use std::time::{Duration, Instant};
+
+/// 心跳事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heartbeat {
+    /// 时间戳（微秒）
+    pub timestamp: u64,
+    /// 运行时长（秒）
+    pub uptime_secs: u64,
+    /// 累计处理的DNS消息数
+    pub packets_processed: u64,
+    /// 捕获接收的数据包数
+    pub capture_rx_packets: u64,
+    /// 捕获丢弃的数据包数
+    pub capture_dropped_packets: u64,
+}
+
+impl Heartbeat {
+    /// 格式化为JSON，`event`字段用于与DNS消息区分
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"event\": \"heartbeat\", \"timestamp\": {}, \"uptime_secs\": {}, \"packets_processed\": {}, \"capture_rx_packets\": {}, \"capture_dropped_packets\": {}}}\n",
+            self.timestamp,
+            self.uptime_secs,
+            self.packets_processed,
+            self.capture_rx_packets,
+            self.capture_dropped_packets
+        )
+    }
+}
+
+/// 心跳计时器
+pub struct HeartbeatTimer {
+    /// 心跳间隔
+    interval: Duration,
+    /// 启动时间
+    started: Instant,
+    /// 上次心跳时间
+    last: Instant,
+}
+
+impl HeartbeatTimer {
+    /// 创建新的心跳计时器
+    pub fn new(interval: Duration, now: Instant) -> Self {
+        HeartbeatTimer {
+            interval,
+            started: now,
+            last: now,
+        }
+    }
+
+    /// 到达间隔时返回运行时长，否则返回None
+    pub fn poll(&mut self, now: Instant) -> Option<Duration> {
+        if now.duration_since(self.last) < self.interval {
+            return None;
+        }
+
+        self.last = now;
+        Some(now.duration_since(self.started))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{MemoryOutput, OutputConfig, OutputManager};
+
+    #[test]
+    fn test_heartbeat_emitted_without_traffic() {
+        let start = Instant::now();
+        let mut timer = HeartbeatTimer::new(Duration::from_secs(5), start);
+
+        let output = MemoryOutput::new();
+        let messages = output.messages();
+        let heartbeats = output.heartbeats();
+        let mut manager = OutputManager::new(OutputConfig::default());
+        manager.add_output(Box::new(output));
+
+        // 模拟统计线程每秒轮询一次，期间没有任何DNS流量
+        for second in 1..=12 {
+            let now = start + Duration::from_secs(second);
+            if let Some(uptime) = timer.poll(now) {
+                let heartbeat = Heartbeat {
+                    timestamp: 0,
+                    uptime_secs: uptime.as_secs(),
+                    packets_processed: 0,
+                    capture_rx_packets: 0,
+                    capture_dropped_packets: 0,
+                };
+                manager.heartbeat(&heartbeat).unwrap();
+            }
+        }
+
+        let uptimes: Vec<u64> = heartbeats.lock().unwrap().iter().map(|h| h.uptime_secs).collect();
+        assert_eq!(uptimes, vec![5, 10]);
+        assert!(messages.lock().unwrap().is_empty());
+        assert!(heartbeats.lock().unwrap()[0].to_json().starts_with("{\"event\": \"heartbeat\""));
+    }
+}
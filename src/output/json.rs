@@ -0,0 +1,93 @@
+//! JSON事件格式
+//! 文件和Kafka输出共用，由serde序列化，域名、TXT等字段中的引号和控制字符会正确转义
+
+use serde::Serialize;
+
+use crate::output::query_hash;
+use crate::protocols::dns::DnsMessage;
+
+/// 输出事件：消息字段加上查询指纹
+#[derive(Serialize)]
+struct JsonEvent<'a> {
+    #[serde(flatten)]
+    message: &'a DnsMessage,
+    query_hash: String,
+}
+
+/// 将DNS消息格式化为JSON事件，以换行结尾
+pub fn format_message_json(message: &DnsMessage) -> String {
+    let event = JsonEvent {
+        message,
+        query_hash: format!("{:016x}", query_hash(message)),
+    };
+    // 字段均可序列化为JSON，不会失败
+    let mut json = serde_json::to_string_pretty(&event).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+/// 将DNS消息格式化为单行紧凑JSON事件，不含换行
+pub fn format_message_json_compact(message: &DnsMessage) -> String {
+    let event = JsonEvent {
+        message,
+        query_hash: format!("{:016x}", query_hash(message)),
+    };
+    serde_json::to_string(&event).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsAnswer, DnsClass, DnsMessageType, DnsRecordType};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn test_escapes_quotes_and_keeps_field_names() {
+        let message = DnsMessage {
+            transaction_id: 7,
+            message_type: DnsMessageType::Response,
+            answers: vec![DnsAnswer {
+                name: "evil\"name\\.example".to_string(),
+                record_type: DnsRecordType::TXT,
+                class: DnsClass::IN,
+                cache_flush: false,
+                ttl: 60,
+                data: vec![0xFF],
+                data_str: "say \"hi\"\n".to_string(),
+            }],
+            timestamp: 1_700_000_000_000_000,
+            raw: Some(vec![1, 2, 3]),
+            src_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)),
+            src_port: 53,
+            dst_port: 40000,
+            rcode: 3,
+            authoritative: true,
+            recursion_desired: true,
+            recursion_available: true,
+            interface: Some("eth0".into()),
+            ..Default::default()
+        };
+
+        let json = format_message_json(&message);
+        assert!(json.ends_with('\n'));
+        assert!(json.contains("\"name\": \"evil\\\"name\\\\.example\""));
+        assert!(json.contains("\"data\": \"say \\\"hi\\\"\\n\""));
+        for field in [
+            "\"rcode\": \"NXDOMAIN\"",
+            "\"aa\": true",
+            "\"protocol\": \"Udp\"",
+            "\"message_type\": \"Response\"",
+            "\"record_type\": \"TXT\"",
+            "\"class\": 1",
+            "\"src_ip\": \"10.0.0.53\"",
+            "\"interface\": \"eth0\"",
+            "\"edns\": null",
+            "\"query_hash\": \"",
+        ] {
+            assert!(json.contains(field), "missing {} in {}", field, json);
+        }
+        // 原始报文不输出
+        assert!(!json.contains("\"raw\""));
+    }
+}
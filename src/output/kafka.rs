@@ -1,118 +1,641 @@
 //! Kafka输出实现
 //! 将DNS消息输出到Kafka
 
-use std::time::Duration;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
-use crate::output::KafkaConfig;
-use crate::output::Output;
-use crate::protocols::dns::DnsMessage;
-use kafka::client::RequiredAcks;
+use crate::output::{KafkaConfig, KafkaPartitionKey, SinkStats};
+use crate::output::{format_message_json, truncate_event, Heartbeat, Output, OutputEncoding};
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsRecordType};
+use kafka::client::{KafkaClient, RequiredAcks};
 use kafka::producer::Record;
 use kafka::producer::{Producer};
 
+/// Kafka主题名最大长度
+const MAX_TOPIC_LEN: usize = 249;
+/// 批次发送的最大尝试次数
+const SEND_ATTEMPTS: u32 = 3;
+/// 批次重试间隔（毫秒）
+const SEND_RETRY_DELAY_MS: u64 = 200;
+/// 发送队列容量（记录数），发送线程跟不上时超出的记录被丢弃
+const SEND_QUEUE_CAPACITY: usize = 10_000;
+
+/// 主题模板占位符及其全部取值，取值集合有限，保证生成的主题数量有上限
+const PLACEHOLDERS: [(&str, &[&str]); 3] = [
+    ("{protocol}", &["udp", "tcp", "dot", "doh", "doq", "mdns"]),
+    ("{message_type}", &["query", "response"]),
+    (
+        "{record_type}",
+        &["a", "aaaa", "cname", "mx", "ns", "ptr", "soa", "srv", "txt", "other"],
+    ),
+];
+
+/// Kafka主题模板
+///
+/// 支持`{protocol}`、`{message_type}`、`{record_type}`占位符，按消息解析出主题，
+/// 如`dns-{message_type}`；不含占位符时即为固定主题。
+#[derive(Debug, Clone)]
+pub struct TopicTemplate {
+    template: String,
+}
+
+impl TopicTemplate {
+    /// 解析模板，未知占位符或生成的主题名不合法时返回错误
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed placeholder in Kafka topic: {}", template))?;
+            let placeholder = &rest[start..start + end + 1];
+            if !PLACEHOLDERS.iter().any(|(name, _)| *name == placeholder) {
+                return Err(format!("Unknown placeholder {} in Kafka topic", placeholder));
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        let template = TopicTemplate {
+            template: template.to_string(),
+        };
+        for topic in template.topics() {
+            validate_topic(&topic)?;
+        }
+        Ok(template)
+    }
+
+    /// 是否为固定主题
+    pub fn is_static(&self) -> bool {
+        !self.template.contains('{')
+    }
+
+    /// 解析消息对应的主题
+    pub fn resolve(&self, message: &DnsMessage) -> String {
+        if self.is_static() {
+            return self.template.clone();
+        }
+
+        let protocol = message.protocol.name();
+        let message_type = match message.message_type {
+            DnsMessageType::Query => "query",
+            DnsMessageType::Response => "response",
+        };
+        // 按第一个问题的类型，未知类型归入other
+        let record_type = match message.questions.first().map(|q| q.record_type) {
+            Some(DnsRecordType::A) => "a",
+            Some(DnsRecordType::AAAA) => "aaaa",
+            Some(DnsRecordType::CNAME) => "cname",
+            Some(DnsRecordType::MX) => "mx",
+            Some(DnsRecordType::NS) => "ns",
+            Some(DnsRecordType::PTR) => "ptr",
+            Some(DnsRecordType::SOA) => "soa",
+            Some(DnsRecordType::SRV) => "srv",
+            Some(DnsRecordType::TXT) => "txt",
+            Some(DnsRecordType::OPT) | Some(DnsRecordType::Other(_)) | None => "other",
+        };
+
+        self.template
+            .replace("{protocol}", protocol)
+            .replace("{message_type}", message_type)
+            .replace("{record_type}", record_type)
+    }
+
+    /// 模板可能生成的全部主题
+    pub fn topics(&self) -> Vec<String> {
+        let mut topics = vec![self.template.clone()];
+        for (name, values) in PLACEHOLDERS {
+            if !self.template.contains(name) {
+                continue;
+            }
+            topics = topics
+                .iter()
+                .flat_map(|topic| values.iter().map(move |value| topic.replace(name, value)))
+                .collect();
+        }
+        topics
+    }
+}
+
+/// 校验Kafka主题名：非空、不超过249字符、只含字母数字和`._-`
+fn validate_topic(topic: &str) -> Result<(), String> {
+    if topic.is_empty() || topic.len() > MAX_TOPIC_LEN {
+        return Err(format!("Invalid Kafka topic length: {:?}", topic));
+    }
+    if topic == "." || topic == ".." {
+        return Err(format!("Invalid Kafka topic: {}", topic));
+    }
+    if !topic
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-')
+    {
+        return Err(format!("Invalid character in Kafka topic: {}", topic));
+    }
+    Ok(())
+}
+
+/// 等待批量发送的记录
+struct PendingRecord {
+    topic: String,
+    /// 消息键，为空时不带键
+    key: Vec<u8>,
+    value: Vec<u8>,
+}
+
+/// 发送线程收到的事件
+enum SendItem {
+    Record(PendingRecord),
+    /// 心跳负载，发送到模板可能生成的每个主题
+    Heartbeat(Vec<u8>),
+}
+
+/// 发送队列计数
+#[derive(Default)]
+struct SendCounters {
+    /// 当前排队记录数
+    depth: AtomicUsize,
+    /// 队列满或发送失败而丢弃的记录数
+    dropped: AtomicU64,
+}
+
+/// 记录发送目标，测试中替换为内存实现
+trait RecordSender {
+    /// 批量发送，发送后只保留未被确认的记录
+    fn send_records(&mut self, records: &mut Vec<PendingRecord>) -> Result<(), String>;
+    /// 发送一条不带键的记录
+    fn send_value(&mut self, topic: &str, value: &[u8]) -> Result<(), String>;
+}
+
+impl RecordSender for Producer {
+    /// 分区由生产者选择，无法对应到单条记录，某主题有分区写入失败时重发该主题的全部记录，
+    /// 下游可能收到重复消息。
+    fn send_records(&mut self, records: &mut Vec<PendingRecord>) -> Result<(), String> {
+        let confirms = {
+            let batch: Vec<Record<&[u8], &[u8]>> = records
+                .iter()
+                .map(|record| Record::from_key_value(&record.topic, record.key.as_slice(), record.value.as_slice()))
+                .collect();
+            self.send_all(&batch).map_err(|e| e.to_string())?
+        };
+
+        let failed: HashSet<String> = confirms
+            .into_iter()
+            .filter(|confirm| confirm.partition_confirms.iter().any(|p| p.offset.is_err()))
+            .map(|confirm| confirm.topic)
+            .collect();
+        records.retain(|record| failed.contains(&record.topic));
+        if records.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("{} records not acknowledged", records.len()))
+        }
+    }
+
+    fn send_value(&mut self, topic: &str, value: &[u8]) -> Result<(), String> {
+        self.send(&Record::from_value(topic, value)).map_err(|e| e.to_string())
+    }
+}
+
+/// 后台发送线程的批次状态
+struct Batcher<S: RecordSender> {
+    sender: S,
+    counters: Arc<SendCounters>,
+    batch_size: usize,
+    linger: Duration,
+    /// 心跳发送的主题
+    heartbeat_topics: Vec<String>,
+    /// 待发送的批次
+    pending: Vec<PendingRecord>,
+    /// 当前批次第一条记录的加入时间
+    batch_started: Option<Instant>,
+}
+
+impl<S: RecordSender> Batcher<S> {
+    /// 接收记录直到发送端关闭，达到批次大小或第一条记录等待超时时发送，关闭前发出剩余批次
+    fn run(mut self, receiver: Receiver<SendItem>) {
+        loop {
+            // 有积压时最多等到批次超时，没有时一直等待新记录
+            let item = match self.batch_started {
+                Some(started) => match receiver.recv_timeout(self.linger.saturating_sub(started.elapsed())) {
+                    Ok(item) => Some(item),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+                None => match receiver.recv() {
+                    Ok(item) => Some(item),
+                    Err(_) => break,
+                },
+            };
+
+            match item {
+                Some(SendItem::Record(record)) => {
+                    self.counters.depth.fetch_sub(1, Ordering::Relaxed);
+                    self.pending.push(record);
+                    let started = *self.batch_started.get_or_insert_with(Instant::now);
+                    if self.pending.len() >= self.batch_size || started.elapsed() >= self.linger {
+                        self.flush();
+                    }
+                }
+                Some(SendItem::Heartbeat(payload)) => {
+                    // 心跳前先发出积压的记录，保证心跳不早于之前的消息
+                    self.flush();
+                    for topic in &self.heartbeat_topics {
+                        if let Err(e) = self.sender.send_value(topic, &payload) {
+                            eprintln!("Failed to send heartbeat to Kafka: {}", e);
+                        }
+                    }
+                }
+                None => self.flush(),
+            }
+        }
+        self.flush();
+    }
+
+    /// 发送当前批次，失败的记录按重试次数重发，最终仍失败时丢弃
+    ///
+    /// 重试只阻塞发送线程，不影响调用`output`的工作线程。
+    fn flush(&mut self) {
+        self.batch_started = None;
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let result = crate::retry!(
+            self.sender.send_records(&mut self.pending),
+            SEND_ATTEMPTS,
+            SEND_RETRY_DELAY_MS
+        );
+        if let Err(e) = result {
+            let dropped = self.pending.len();
+            self.pending.clear();
+            self.counters.dropped.fetch_add(dropped as u64, Ordering::Relaxed);
+            eprintln!("Failed to send {} messages to Kafka: {}", dropped, e);
+        }
+    }
+}
+
+/// 启动后台发送线程
+fn spawn_batcher<S: RecordSender + Send + 'static>(
+    sender: S,
+    config: &KafkaConfig,
+    heartbeat_topics: Vec<String>,
+    counters: Arc<SendCounters>,
+) -> (SyncSender<SendItem>, JoinHandle<()>) {
+    let (queue, receiver) = mpsc::sync_channel(SEND_QUEUE_CAPACITY);
+    let batcher = Batcher {
+        sender,
+        counters,
+        batch_size: config.batch_size.max(1),
+        linger: Duration::from_millis(config.linger_ms),
+        heartbeat_topics,
+        pending: Vec::new(),
+        batch_started: None,
+    };
+    (queue, thread::spawn(move || batcher.run(receiver)))
+}
+
 /// Kafka输出
+///
+/// 记录交给后台发送线程积攒成批，达到批次大小或等待超时后一次发送，
+/// 工作线程不会阻塞在网络往返和重试上；发送队列满时丢弃新记录。
 pub struct KafkaOutput {
     /// 配置
     config: KafkaConfig,
-    /// Kafka生产者
-    producer: Producer,
+    /// 主题模板
+    topic: TopicTemplate,
+    /// 单条JSON事件的最大字节数
+    max_event_bytes: Option<usize>,
+    /// 发送队列，关闭时置空以通知发送线程退出
+    queue: Option<SyncSender<SendItem>>,
+    /// 发送线程
+    handle: Option<JoinHandle<()>>,
+    /// 发送队列计数
+    counters: Arc<SendCounters>,
 }
 
 impl KafkaOutput {
     /// 创建新的Kafka输出
     pub fn new(config: KafkaConfig) -> Result<Self, String> {
+        config.encoding.check_supported()?;
+        let topic = TopicTemplate::parse(&config.topic)?;
+
+        // 预先加载全部可能主题的元数据，开启自动创建的集群会在此时创建主题
+        let mut client = KafkaClient::new(vec![config.brokers.clone()]);
+        client
+            .load_metadata(&topic.topics())
+            .map_err(|e| format!("Failed to load Kafka topic metadata: {}", e))?;
+
         // 创建Kafka生产者
-        let producer: Producer = Producer::from_hosts(vec![config.brokers.clone()])
+        let producer: Producer = Producer::from_client(client)
             .with_ack_timeout(Duration::from_secs(5))
             .with_required_acks(RequiredAcks::One)
             .create()
             .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
 
-        Ok(KafkaOutput { config, producer })
-    }
-
-    /// 格式化DNS消息为JSON
-    fn format_message_json(&self, message: &DnsMessage) -> String {
-        // 简单实现，实际项目中可能需要更复杂的JSON序列化
-        let mut json = String::new();
-
-        json.push_str("{\n");
-        json.push_str(&format!("  \"timestamp\": {},\n", message.timestamp));
-        json.push_str(&format!(
-            "  \"transaction_id\": {},\n",
-            message.transaction_id
-        ));
-        json.push_str(&format!(
-            "  \"message_type\": \"{:?}\",\n",
-            message.message_type
-        ));
-        json.push_str(&format!("  \"protocol\": \"{:?}\",\n", message.protocol));
-
-        // 问题
-        json.push_str("  \"questions\": [\n");
-        for (i, q) in message.questions.iter().enumerate() {
-            json.push_str("    {\n");
-            json.push_str(&format!("      \"name\": \"{}\",\n", q.name));
-            json.push_str(&format!(
-                "      \"record_type\": \"{:?}\",\n",
-                q.record_type
-            ));
-            json.push_str(&format!("      \"class\": {}\n", q.class));
-            json.push_str("    }");
-            if i < message.questions.len() - 1 {
-                json.push_str(",\n");
-            } else {
-                json.push_str("\n");
-            }
+        Ok(Self::with_sender(config, topic, producer))
+    }
+
+    /// 使用指定的发送目标创建输出
+    fn with_sender<S: RecordSender + Send + 'static>(config: KafkaConfig, topic: TopicTemplate, sender: S) -> Self {
+        let counters = Arc::new(SendCounters::default());
+        let (queue, handle) = spawn_batcher(sender, &config, topic.topics(), Arc::clone(&counters));
+        KafkaOutput {
+            config,
+            topic,
+            max_event_bytes: None,
+            queue: Some(queue),
+            handle: Some(handle),
+            counters,
         }
-        json.push_str("  ],\n");
-
-        // 应答
-        json.push_str("  \"answers\": [\n");
-        for (i, a) in message.answers.iter().enumerate() {
-            json.push_str("    {\n");
-            json.push_str(&format!("      \"name\": \"{}\",\n", a.name));
-            json.push_str(&format!(
-                "      \"record_type\": \"{:?}\",\n",
-                a.record_type
-            ));
-            json.push_str(&format!("      \"class\": {},\n", a.class));
-            json.push_str(&format!("      \"ttl\": {},\n", a.ttl));
-            json.push_str(&format!("      \"data\": \"{}\"\n", a.data_str));
-            json.push_str("    }");
-            if i < message.answers.len() - 1 {
-                json.push_str(",\n");
-            } else {
-                json.push_str("\n");
+    }
+
+    /// 限制单条JSON事件的长度，超出时截断并标记
+    pub fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// 交给发送线程，队列满时丢弃并计数
+    fn enqueue(&mut self, record: PendingRecord) -> Result<(), String> {
+        let queue = self
+            .queue
+            .as_ref()
+            .ok_or_else(|| "Kafka output already closed".to_string())?;
+
+        // 先计数再入队，避免发送线程先消费导致计数下溢
+        self.counters.depth.fetch_add(1, Ordering::Relaxed);
+        match queue.try_send(SendItem::Record(record)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.counters.depth.fetch_sub(1, Ordering::Relaxed);
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.counters.depth.fetch_sub(1, Ordering::Relaxed);
+                Err("Kafka sender thread exited".to_string())
             }
         }
-        json.push_str("  ]\n");
-
-        json.push_str("}\n");
+    }
+}
 
-        json
+/// 编码事件的消息键，为空时不带键
+fn partition_key(kind: KafkaPartitionKey, message: &DnsMessage) -> Vec<u8> {
+    match kind {
+        KafkaPartitionKey::TransactionId => message.transaction_id.to_string().into_bytes(),
+        KafkaPartitionKey::SourceIp => message.src_ip.to_string().into_bytes(),
+        KafkaPartitionKey::QueryName => message
+            .questions
+            .first()
+            .map(|question| question.name.to_ascii_lowercase().into_bytes())
+            .unwrap_or_default(),
+        KafkaPartitionKey::None => Vec::new(),
     }
 }
 
+/// 原始报文模式的消息键和值：键为流元数据JSON，值为原始DNS报文
+///
+/// 报文需由解析器保留，未保留时返回错误。
+fn raw_record(message: &DnsMessage) -> Result<(String, &[u8]), String> {
+    let raw = message
+        .raw
+        .as_deref()
+        .ok_or_else(|| "Raw DNS payload not kept by parser".to_string())?;
+    let key = serde_json::json!({
+        "timestamp": message.timestamp,
+        "protocol": message.protocol,
+        "src_ip": message.src_ip,
+        "src_port": message.src_port,
+        "dst_ip": message.dst_ip,
+        "dst_port": message.dst_port,
+        "interface": message.interface,
+    });
+    Ok((key.to_string(), raw))
+}
+
 impl Output for KafkaOutput {
     fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
-        // 格式化消息
-        let formatted = self.format_message_json(message);
-        let key = format!("{}", message.transaction_id);
+        let topic = self.topic.resolve(message);
+
+        // 原始报文模式不经过编码
+        if self.config.raw_payload {
+            let (key, raw) = raw_record(message)?;
+            let record = PendingRecord {
+                topic,
+                key: key.into_bytes(),
+                value: raw.to_vec(),
+            };
+            return self.enqueue(record);
+        }
 
-        let topic = self.config.topic.clone();
-        // 发送到Kafka
-        let record = Record::from_value(&topic, formatted);
+        // 编码消息
+        let formatted = match self.config.encoding {
+            OutputEncoding::Json => {
+                truncate_event(format_message_json(message), self.max_event_bytes).into_bytes()
+            }
+            #[cfg(feature = "protobuf")]
+            OutputEncoding::Protobuf => crate::output::proto::encode(message),
+            #[cfg(not(feature = "protobuf"))]
+            OutputEncoding::Protobuf => return Err("protobuf功能未启用".to_string()),
+        };
+
+        self.enqueue(PendingRecord {
+            topic,
+            key: partition_key(self.config.partition_key, message),
+            value: formatted,
+        })
+    }
+
+    fn heartbeat(&mut self, heartbeat: &Heartbeat) -> Result<(), String> {
+        // protobuf和原始报文主题只包含DNS消息
+        if self.config.encoding != OutputEncoding::Json || self.config.raw_payload {
+            return Ok(());
+        }
 
-        // 异步发送，但这里简单等待结果
-        match self.producer.send(&record) {
-            Ok(_) => Ok(()),
-            Err(e) => Err(format!("Failed to send message to Kafka: {}", e)),
+        // 心跳由发送线程发出，队列满时本次心跳跳过
+        if let Some(queue) = &self.queue {
+            let _ = queue.try_send(SendItem::Heartbeat(heartbeat.to_json().into_bytes()));
         }
+        Ok(())
     }
 
     fn close(&mut self) -> Result<(), String> {
-        // Kafka生产者会在析构时自动关闭
-        Ok(())
+        // 关闭队列后发送线程发出剩余批次再退出，Kafka生产者随线程析构
+        self.queue = None;
+        match self.handle.take() {
+            Some(handle) => handle.join().map_err(|_| "Kafka sender thread panicked".to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn queue_stats(&self) -> Option<SinkStats> {
+        Some(SinkStats {
+            name: "kafka".to_string(),
+            queue_depth: self.counters.depth.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        })
+    }
+}
+
+impl Drop for KafkaOutput {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsClass, DnsQuestion};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn message(message_type: DnsMessageType, record_type: DnsRecordType) -> DnsMessage {
+        DnsMessage {
+            transaction_id: 1,
+            message_type,
+            questions: vec![DnsQuestion {
+                name: "example.com".to_string(),
+                record_type,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_topic_template_routes_by_message_type() {
+        let template = TopicTemplate::parse("dns-{message_type}").unwrap();
+        assert!(!template.is_static());
+        assert_eq!(template.resolve(&message(DnsMessageType::Query, DnsRecordType::A)), "dns-query");
+        assert_eq!(
+            template.resolve(&message(DnsMessageType::Response, DnsRecordType::A)),
+            "dns-response"
+        );
+        assert_eq!(template.topics(), vec!["dns-query", "dns-response"]);
+
+        let template = TopicTemplate::parse("dns-{protocol}.{record_type}").unwrap();
+        assert_eq!(
+            template.resolve(&message(DnsMessageType::Query, DnsRecordType::Other(65))),
+            "dns-udp.other"
+        );
+        assert_eq!(template.topics().len(), 60);
+    }
+
+    #[test]
+    fn test_raw_record_value_is_wire_payload() {
+        use crate::core::stats::StatsCounter;
+        use crate::protocols::dns::{DnsParser, UdpDnsParser};
+
+        let mut payload = vec![0xBE, 0xEF, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        payload.extend_from_slice(b"\x07example\x03com\x00\x00\x01\x00\x01");
+        let mut parser = UdpDnsParser::new(65535).with_keep_raw(true);
+        let mut message = parser.parse(&payload, &StatsCounter::new()).unwrap();
+        message.src_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+        message.src_port = 40000;
+        message.dst_port = 53;
+
+        let (key, value) = raw_record(&message).unwrap();
+        assert_eq!(value, &payload[..]);
+        let key: serde_json::Value = serde_json::from_str(&key).unwrap();
+        assert_eq!(key["src_ip"], "192.0.2.1");
+        assert_eq!(key["src_port"], 40000);
+        assert_eq!(key["protocol"], "Udp");
+
+        // 解析器未保留报文时无法发送
+        message.raw = None;
+        assert!(raw_record(&message).is_err());
+    }
+
+    #[test]
+    fn test_partition_key_by_config() {
+        let mut message = message(DnsMessageType::Query, DnsRecordType::A);
+        message.transaction_id = 0x1234;
+        message.src_ip = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7));
+        message.questions[0].name = "WWW.Example.com".to_string();
+
+        assert_eq!(partition_key(KafkaPartitionKey::default(), &message), b"4660");
+        assert_eq!(partition_key(KafkaPartitionKey::SourceIp, &message), b"192.0.2.7");
+        // 域名不区分大小写，同一域名落在同一分区
+        assert_eq!(partition_key(KafkaPartitionKey::QueryName, &message), b"www.example.com");
+        assert!(partition_key(KafkaPartitionKey::None, &message).is_empty());
+
+        message.questions.clear();
+        assert!(partition_key(KafkaPartitionKey::QueryName, &message).is_empty());
+    }
+
+    #[test]
+    fn test_static_topic_and_invalid_templates() {
+        let template = TopicTemplate::parse("dns-events").unwrap();
+        assert!(template.is_static());
+        assert_eq!(template.resolve(&message(DnsMessageType::Response, DnsRecordType::A)), "dns-events");
+
+        assert!(TopicTemplate::parse("dns-{client}").is_err());
+        assert!(TopicTemplate::parse("dns-{protocol").is_err());
+        assert!(TopicTemplate::parse("dns events").is_err());
+        assert!(TopicTemplate::parse("").is_err());
+    }
+
+    /// 记录每次批量发送的内存发送目标
+    struct RecordingSender {
+        batches: Arc<std::sync::Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl RecordSender for RecordingSender {
+        fn send_records(&mut self, records: &mut Vec<PendingRecord>) -> Result<(), String> {
+            let batch = records.drain(..).map(|r| String::from_utf8(r.value).unwrap()).collect();
+            self.batches.lock().unwrap().push(batch);
+            Ok(())
+        }
+
+        fn send_value(&mut self, topic: &str, _value: &[u8]) -> Result<(), String> {
+            self.batches.lock().unwrap().push(vec![format!("heartbeat:{}", topic)]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_background_sender_flushes_on_linger_without_new_records() {
+        let batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let config = KafkaConfig {
+            batch_size: 2,
+            linger_ms: 50,
+            ..KafkaConfig::default()
+        };
+        let topic = TopicTemplate::parse(&config.topic).unwrap();
+        let mut output = KafkaOutput::with_sender(
+            config,
+            topic,
+            RecordingSender {
+                batches: Arc::clone(&batches),
+            },
+        );
+        let record = |value: &str| PendingRecord {
+            topic: "dns-events".to_string(),
+            key: Vec::new(),
+            value: value.as_bytes().to_vec(),
+        };
+
+        // 单条记录在等待超时后由发送线程发出，不依赖后续记录
+        output.enqueue(record("a")).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while batches.lock().unwrap().is_empty() {
+            assert!(Instant::now() < deadline, "linger flush did not happen");
+            thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(*batches.lock().unwrap(), vec![vec!["a".to_string()]]);
+
+        // 达到批次大小立即发送，关闭时发出剩余记录
+        output.enqueue(record("b")).unwrap();
+        output.enqueue(record("c")).unwrap();
+        output.enqueue(record("d")).unwrap();
+        output.close().unwrap();
+        let batches = batches.lock().unwrap();
+        assert_eq!(batches[1], vec!["b".to_string(), "c".to_string()]);
+        assert_eq!(batches[2], vec!["d".to_string()]);
+        assert_eq!(output.queue_stats().unwrap().queue_depth, 0);
     }
 }
@@ -0,0 +1,57 @@
+//! 内存输出实现
+//! 将DNS消息保存在内存中，用于测试和嵌入式调用
+
+use std::sync::{Arc, Mutex};
+
+use crate::output::{Heartbeat, Output};
+use crate::protocols::dns::DnsMessage;
+
+/// 内存输出
+pub struct MemoryOutput {
+    /// 已输出的消息
+    messages: Arc<Mutex<Vec<DnsMessage>>>,
+    /// 已输出的心跳
+    heartbeats: Arc<Mutex<Vec<Heartbeat>>>,
+}
+
+impl MemoryOutput {
+    /// 创建新的内存输出
+    pub fn new() -> Self {
+        MemoryOutput {
+            messages: Arc::new(Mutex::new(Vec::new())),
+            heartbeats: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// 获取消息列表句柄，输出移交给OutputManager后仍可读取
+    pub fn messages(&self) -> Arc<Mutex<Vec<DnsMessage>>> {
+        Arc::clone(&self.messages)
+    }
+
+    /// 获取心跳列表句柄
+    pub fn heartbeats(&self) -> Arc<Mutex<Vec<Heartbeat>>> {
+        Arc::clone(&self.heartbeats)
+    }
+}
+
+impl Default for MemoryOutput {
+    fn default() -> Self {
+        MemoryOutput::new()
+    }
+}
+
+impl Output for MemoryOutput {
+    fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        self.messages.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+
+    fn heartbeat(&mut self, heartbeat: &Heartbeat) -> Result<(), String> {
+        self.heartbeats.lock().unwrap().push(heartbeat.clone());
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -1,17 +1,52 @@
 //! 输出模块
 //! 负责将解析结果输出到不同目标
 
+mod anonymize;
+mod channel;
 mod console;
+mod csv;
+mod error_output;
+#[cfg(feature = "dnstap")]
+pub mod dnstap;
 mod file;
+mod heartbeat;
+mod json;
 mod kafka;
+mod memory;
+mod name_filter;
+mod passive_dns;
+mod query_hash;
+mod queued;
 mod statsd;
+mod syslog;
+mod truncate;
+#[cfg(feature = "protobuf")]
+pub mod proto;
 
+pub use anonymize::ClientIpAnonymization;
+pub use channel::ChannelOutput;
 pub use console::ConsoleOutput;
+pub use csv::CsvOutput;
+pub use error_output::{ErrorOutput, ErrorOutputConfig};
+#[cfg(feature = "dnstap")]
+pub use dnstap::DnstapOutput;
 pub use file::FileOutput;
-pub use kafka::KafkaOutput;
+pub use heartbeat::{Heartbeat, HeartbeatTimer};
+pub use json::{format_message_json, format_message_json_compact};
+pub use kafka::{KafkaOutput, TopicTemplate};
+pub use memory::MemoryOutput;
+pub use name_filter::{NameFilter, NamePatterns};
+pub use passive_dns::{PassiveDnsAggregator, PassiveDnsOutput, PassiveDnsRecord};
+pub use query_hash::query_hash;
+pub use queued::{QueuedOutput, SinkStats};
 pub use statsd::StatsdOutput;
+pub use syslog::SyslogOutput;
+pub use truncate::{truncate_event, truncate_text};
 
-use crate::protocols::dns::DnsMessage;
+use crate::core::stats::StatsCounter;
+use crate::protocols::dns::{DnsMessage, DnsRecordType};
+use std::borrow::Cow;
+use std::ops::RangeInclusive;
 use std::sync::{Arc, Mutex};
 
 /// 输出配置
@@ -29,10 +64,195 @@ pub struct OutputConfig {
     pub enable_statsd: bool,
     /// Statsd配置
     pub statsd_config: StatsdConfig,
+    /// 是否启用Syslog输出
+    pub enable_syslog: bool,
+    /// Syslog配置
+    pub syslog_config: SyslogConfig,
+    /// 是否启用CSV输出
+    pub enable_csv: bool,
+    /// CSV输出配置
+    pub csv_config: CsvConfig,
     /// 是否启用控制台输出
     pub enable_console: bool,
     /// 控制台输出配置
     pub console_config: ConsoleConfig,
+    /// 是否启用dnstap输出
+    pub enable_dnstap: bool,
+    /// dnstap输出配置
+    pub dnstap_config: DnstapConfig,
+    /// 是否启用被动DNS输出
+    pub enable_passive_dns: bool,
+    /// 被动DNS输出配置
+    pub passive_dns_config: PassiveDnsConfig,
+    /// 事务ID调试过滤器
+    pub transaction_id_filter: TransactionIdFilter,
+    /// 问题名过滤器
+    pub query_name_filter: QueryNameFilter,
+    /// 只输出问题类型在列表中的消息，为空时不限制
+    pub record_type_filter: Vec<DnsRecordType>,
+    /// 每个输出独立队列的容量，为0时同步输出
+    pub queue_capacity: usize,
+    /// TTL为0的应答记录处理方式
+    pub ttl_zero_policy: TtlZeroPolicy,
+    /// 客户端IP匿名化方式，在输出前生效
+    pub anonymize_client_ip: ClientIpAnonymization,
+    /// 单条输出事件的最大字节数，超出时截断并标记，为空时不限制
+    ///
+    /// JSON事件（文件、Kafka、Syslog的消息体、被动DNS记录）截断为带标记的JSON对象，
+    /// CSV的应答列和控制台文本直接截断，dnstap记录去掉原始报文。
+    pub max_output_bytes_per_event: Option<usize>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            enable_kafka: false,
+            kafka_config: KafkaConfig::default(),
+            enable_file: false,
+            file_config: FileConfig::default(),
+            enable_statsd: false,
+            statsd_config: StatsdConfig::default(),
+            enable_syslog: false,
+            syslog_config: SyslogConfig::default(),
+            enable_csv: false,
+            csv_config: CsvConfig::default(),
+            enable_console: false,
+            console_config: ConsoleConfig::default(),
+            enable_dnstap: false,
+            dnstap_config: DnstapConfig::default(),
+            enable_passive_dns: false,
+            passive_dns_config: PassiveDnsConfig::default(),
+            transaction_id_filter: TransactionIdFilter::default(),
+            query_name_filter: QueryNameFilter::default(),
+            record_type_filter: Vec::new(),
+            queue_capacity: 0,
+            ttl_zero_policy: TtlZeroPolicy::default(),
+            anonymize_client_ip: ClientIpAnonymization::default(),
+            max_output_bytes_per_event: None,
+        }
+    }
+}
+
+/// TTL为0的应答记录处理方式
+///
+/// 负载均衡/GSLB常返回TTL为0的记录，对缓存分析意义不大，可按记录剔除。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TtlZeroPolicy {
+    /// 保留
+    #[default]
+    Keep,
+    /// 剔除TTL为0的应答记录，消息本身仍然输出
+    Drop,
+}
+
+impl TtlZeroPolicy {
+    /// 按策略处理消息中的应答记录，无需修改时不复制消息
+    pub fn apply<'a>(&self, message: &'a DnsMessage) -> Cow<'a, DnsMessage> {
+        match self {
+            TtlZeroPolicy::Drop if message.answers.iter().any(|a| a.ttl == 0) => {
+                let mut filtered = message.clone();
+                filtered.answers.retain(|a| a.ttl != 0);
+                Cow::Owned(filtered)
+            }
+            _ => Cow::Borrowed(message),
+        }
+    }
+}
+
+/// 问题名过滤配置
+///
+/// 模式可以是域名后缀（`example.com`匹配其本身和子域名）、`*.example.com`（只匹配子域名）
+/// 或任意glob（`mail*.example.org`），不区分大小写。
+#[derive(Clone, Default)]
+pub struct QueryNameFilter {
+    /// 只输出至少一个问题命中这些模式的消息，为空时不限制
+    pub allow: Vec<String>,
+    /// 不输出任一问题命中这些模式的消息，优先于allow
+    pub deny: Vec<String>,
+}
+
+/// 事务ID调试过滤器
+///
+/// 用于定位问题时只查看特定事务ID的消息，在输出之前生效。
+#[derive(Clone, Default)]
+pub struct TransactionIdFilter {
+    /// 只输出这些范围内的事务ID，为空时不限制
+    pub include: Vec<RangeInclusive<u16>>,
+    /// 不输出这些范围内的事务ID，优先于include
+    pub exclude: Vec<RangeInclusive<u16>>,
+}
+
+impl TransactionIdFilter {
+    /// 判断事务ID是否允许输出
+    pub fn matches(&self, transaction_id: u16) -> bool {
+        if self.exclude.iter().any(|r| r.contains(&transaction_id)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|r| r.contains(&transaction_id))
+    }
+}
+
+/// 输出编码格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputEncoding {
+    /// JSON文本
+    #[default]
+    Json,
+    /// protobuf二进制（需启用protobuf特性）
+    Protobuf,
+}
+
+impl OutputEncoding {
+    /// 检查当前构建是否支持该编码
+    pub fn check_supported(&self) -> Result<(), String> {
+        match self {
+            OutputEncoding::Json => Ok(()),
+            #[cfg(feature = "protobuf")]
+            OutputEncoding::Protobuf => Ok(()),
+            #[cfg(not(feature = "protobuf"))]
+            OutputEncoding::Protobuf => {
+                Err("protobuf功能未启用，请在Cargo.toml中启用protobuf特性".to_string())
+            }
+        }
+    }
+}
+
+/// 文件分区方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilePartition {
+    /// 不分区，所有消息写入同一文件
+    #[default]
+    None,
+    /// 按协议（udp/tcp/dot/doh/doq）
+    ByProtocol,
+    /// 按消息时间戳的UTC日期
+    ByDate,
+    /// 按第一个问题的记录类型
+    ByRecordType,
+}
+
+/// JSON文件的排版格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileFormat {
+    /// 缩进的多行JSON，便于人工查看
+    #[default]
+    Pretty,
+    /// 每行一个紧凑JSON对象（NDJSON），推荐用于jq、Logstash等管道处理
+    Ndjson,
+}
+
+/// Kafka消息键，决定记录落到哪个分区
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KafkaPartitionKey {
+    /// 事务ID
+    #[default]
+    TransactionId,
+    /// 源IP，同一客户端的查询落在同一分区，下游可按序处理
+    SourceIp,
+    /// 第一个问题的域名
+    QueryName,
+    /// 不带键，由生产者轮流选择分区
+    None,
 }
 
 /// Kafka配置
@@ -40,10 +260,35 @@ pub struct OutputConfig {
 pub struct KafkaConfig {
     /// Kafka服务器地址
     pub brokers: String,
-    /// 主题
+    /// 主题，可包含{protocol}、{message_type}、{record_type}占位符按消息路由
     pub topic: String,
     /// 客户端ID
     pub client_id: String,
+    /// 消息编码格式
+    pub encoding: OutputEncoding,
+    /// 发送原始DNS报文而非编码后的事件，流元数据以JSON放在消息键中，供自带解析器的下游使用
+    pub raw_payload: bool,
+    /// 每批发送的最大记录数，达到后立即发送
+    pub batch_size: usize,
+    /// 批次中第一条记录的最长等待时间（毫秒），超时后即使未满也发送
+    pub linger_ms: u64,
+    /// 编码事件的消息键，原始报文模式下键固定为流元数据
+    pub partition_key: KafkaPartitionKey,
+}
+
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        KafkaConfig {
+            brokers: "localhost:9092".to_string(),
+            topic: "dns-events".to_string(),
+            client_id: "dns-spider".to_string(),
+            encoding: OutputEncoding::Json,
+            raw_payload: false,
+            batch_size: 500,
+            linger_ms: 100,
+            partition_key: KafkaPartitionKey::TransactionId,
+        }
+    }
 }
 
 /// 文件输出配置
@@ -57,6 +302,79 @@ pub struct FileConfig {
     pub file_suffix: String,
     /// 轮转间隔（秒）
     pub rotation_interval: u64,
+    /// 记录编码格式，protobuf模式下每条记录带长度前缀
+    pub encoding: OutputEncoding,
+    /// JSON编码时的排版格式
+    pub format: FileFormat,
+    /// 分区方式，每个分区写入独立文件并独立轮转
+    pub partition: FilePartition,
+    /// 同时打开的分区文件数上限，超出时关闭最久未用的分区
+    pub max_open_files: usize,
+    /// 缓冲数据落盘间隔（秒），轮转和关闭时也会落盘
+    pub flush_interval_secs: u64,
+    /// 写缓冲大小（字节），单个文件缓冲写满或所有分区累计未落盘数据达到该值时立即落盘
+    pub flush_bytes: usize,
+    /// 轮转后把关闭的文件压缩为`.gz`并删除原文件
+    pub compress_on_rotate: bool,
+}
+
+impl Default for FileConfig {
+    fn default() -> Self {
+        FileConfig {
+            output_dir: "./logs".to_string(),
+            file_prefix: "dns-".to_string(),
+            file_suffix: "".to_string(),
+            rotation_interval: 3600,
+            encoding: OutputEncoding::Json,
+            format: FileFormat::Pretty,
+            partition: FilePartition::None,
+            max_open_files: 64,
+            flush_interval_secs: 5,
+            flush_bytes: 64 * 1024,
+            compress_on_rotate: false,
+        }
+    }
+}
+
+/// CSV中多条应答的展开方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvAnswerMode {
+    /// 每个问题一行，应答用分隔符合并到answer列
+    #[default]
+    Join,
+    /// 每条应答一行，问题列重复；没有应答时仍输出一行
+    Rows,
+}
+
+/// CSV输出配置
+#[derive(Clone)]
+pub struct CsvConfig {
+    /// 输出文件路径，追加写入
+    pub path: String,
+    /// 多条应答的展开方式
+    pub answer_mode: CsvAnswerMode,
+    /// Join模式下应答之间的分隔符
+    pub answer_separator: String,
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        CsvConfig {
+            path: "./logs/dns.csv".to_string(),
+            answer_mode: CsvAnswerMode::Join,
+            answer_separator: ";".to_string(),
+        }
+    }
+}
+
+/// Statsd传输协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatsdProtocol {
+    /// UDP，每个指标一个数据报
+    #[default]
+    Udp,
+    /// TCP长连接，指标按行写入，断开后自动重连
+    Tcp,
 }
 
 /// Statsd配置
@@ -66,8 +384,115 @@ pub struct StatsdConfig {
     pub host: String,
     /// 端口
     pub port: u16,
+    /// 传输协议
+    pub protocol: StatsdProtocol,
     /// 前缀
     pub prefix: String,
+    /// 按查询次数上报的热门域名数量，为0时关闭以避免指标基数膨胀
+    pub top_query_names: usize,
+}
+
+impl Default for StatsdConfig {
+    fn default() -> Self {
+        StatsdConfig {
+            host: "localhost".to_string(),
+            port: 8125,
+            protocol: StatsdProtocol::Udp,
+            prefix: "dns.spider".to_string(),
+            top_query_names: 0,
+        }
+    }
+}
+
+/// Syslog传输协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyslogProtocol {
+    /// UDP，每条消息一个数据报
+    #[default]
+    Udp,
+    /// TCP长连接，按RFC 6587八位组计数分帧
+    Tcp,
+}
+
+/// Syslog配置
+#[derive(Clone)]
+pub struct SyslogConfig {
+    /// Syslog服务器地址
+    pub host: String,
+    /// 端口
+    pub port: u16,
+    /// 传输协议
+    pub protocol: SyslogProtocol,
+    /// 设施（0-23），默认16即local0
+    pub facility: u8,
+    /// HOSTNAME字段，为空时填`-`
+    pub hostname: String,
+    /// APP-NAME字段
+    pub app_name: String,
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        SyslogConfig {
+            host: "localhost".to_string(),
+            port: 514,
+            protocol: SyslogProtocol::Udp,
+            facility: 16,
+            hostname: String::new(),
+            app_name: "dns_spider".to_string(),
+        }
+    }
+}
+
+/// dnstap输出配置
+#[derive(Clone)]
+pub struct DnstapConfig {
+    /// 输出文件路径
+    pub path: String,
+    /// 写入记录的identity字段
+    pub identity: String,
+}
+
+impl Default for DnstapConfig {
+    fn default() -> Self {
+        DnstapConfig {
+            path: "./logs/dns.dnstap".to_string(),
+            identity: "dns-spider".to_string(),
+        }
+    }
+}
+
+/// 被动DNS输出配置
+#[derive(Clone)]
+pub struct PassiveDnsConfig {
+    /// 输出文件路径
+    pub path: String,
+    /// 聚合窗口（秒），窗口结束时写出聚合记录
+    pub window_secs: u64,
+    /// 聚合条目上限，超出时提前写出最久未出现的条目
+    pub max_entries: usize,
+}
+
+impl Default for PassiveDnsConfig {
+    fn default() -> Self {
+        PassiveDnsConfig {
+            path: "./logs/passive-dns.json".to_string(),
+            window_secs: 300,
+            max_entries: 100_000,
+        }
+    }
+}
+
+/// 控制台彩色输出模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    /// stdout是终端且未设置NO_COLOR时启用
+    #[default]
+    Auto,
+    /// 始终启用，输出重定向到文件或管道时也保留颜色
+    Always,
+    /// 始终关闭
+    Never,
 }
 
 /// 控制台输出配置
@@ -75,8 +500,17 @@ pub struct StatsdConfig {
 pub struct ConsoleConfig {
     /// 是否启用详细模式
     pub verbose: bool,
-    /// 是否启用彩色输出
-    pub color: bool,
+    /// 彩色输出模式
+    pub color: ColorMode,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> Self {
+        ConsoleConfig {
+            verbose: true,
+            color: ColorMode::Auto,
+        }
+    }
 }
 
 /// 输出接口
@@ -85,6 +519,18 @@ pub trait Output {
     fn output(&mut self, message: &DnsMessage) -> Result<(), String>;
     /// 关闭输出
     fn close(&mut self) -> Result<(), String>;
+    /// 输出心跳事件，默认忽略
+    fn heartbeat(&mut self, _heartbeat: &Heartbeat) -> Result<(), String> {
+        Ok(())
+    }
+    /// 由统计线程每秒调用一次，用于按时间落盘、发送等周期性工作，默认忽略
+    fn tick(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+    /// 队列统计，仅异步队列输出提供
+    fn queue_stats(&self) -> Option<SinkStats> {
+        None
+    }
 }
 
 /// 输出管理器
@@ -93,14 +539,20 @@ pub struct OutputManager {
     config: OutputConfig,
     /// 输出列表
     outputs: Vec<Box<dyn Output + Send>>,
+    /// 编译后的问题名过滤器
+    name_filter: NameFilter,
+    /// 过滤等输出层统计，由统计线程定期取走
+    stats: StatsCounter,
 }
 
 impl OutputManager {
     /// 创建新的输出管理器
     pub fn new(config: OutputConfig) -> Self {
         let mut manager = OutputManager {
+            name_filter: NameFilter::new(&config.query_name_filter),
             config,
             outputs: Vec::new(),
+            stats: StatsCounter::new(),
         };
 
         manager.init();
@@ -112,7 +564,10 @@ impl OutputManager {
         // 初始化Kafka输出
         if self.config.enable_kafka {
             match KafkaOutput::new(self.config.kafka_config.clone()) {
-                Ok(output) => self.outputs.push(Box::new(output)),
+                Ok(output) => {
+                    let output = output.with_max_event_bytes(self.config.max_output_bytes_per_event);
+                    self.register("kafka", Box::new(output))
+                }
                 Err(e) => eprintln!("Failed to initialize Kafka output: {}", e),
             }
         }
@@ -120,7 +575,10 @@ impl OutputManager {
         // 初始化文件输出
         if self.config.enable_file {
             match FileOutput::new(self.config.file_config.clone()) {
-                Ok(output) => self.outputs.push(Box::new(output)),
+                Ok(output) => {
+                    let output = output.with_max_event_bytes(self.config.max_output_bytes_per_event);
+                    self.register("file", Box::new(output))
+                }
                 Err(e) => eprintln!("Failed to initialize file output: {}", e),
             }
         }
@@ -128,24 +586,119 @@ impl OutputManager {
         // 初始化Statsd输出
         if self.config.enable_statsd {
             match StatsdOutput::new(self.config.statsd_config.clone()) {
-                Ok(output) => self.outputs.push(Box::new(output)),
+                Ok(output) => self.register("statsd", Box::new(output)),
                 Err(e) => eprintln!("Failed to initialize Statsd output: {}", e),
             }
         }
 
+        // 初始化Syslog输出
+        if self.config.enable_syslog {
+            match SyslogOutput::new(self.config.syslog_config.clone()) {
+                Ok(output) => {
+                    let output = output.with_max_event_bytes(self.config.max_output_bytes_per_event);
+                    self.register("syslog", Box::new(output))
+                }
+                Err(e) => eprintln!("Failed to initialize syslog output: {}", e),
+            }
+        }
+
+        // 初始化dnstap输出
+        if self.config.enable_dnstap {
+            #[cfg(feature = "dnstap")]
+            match DnstapOutput::new(self.config.dnstap_config.clone()) {
+                Ok(output) => {
+                    let output = output.with_max_event_bytes(self.config.max_output_bytes_per_event);
+                    self.register("dnstap", Box::new(output))
+                }
+                Err(e) => eprintln!("Failed to initialize dnstap output: {}", e),
+            }
+
+            #[cfg(not(feature = "dnstap"))]
+            eprintln!("dnstap功能未启用，请在Cargo.toml中启用dnstap特性");
+        }
+
+        // 初始化被动DNS输出
+        if self.config.enable_passive_dns {
+            match PassiveDnsOutput::new(self.config.passive_dns_config.clone()) {
+                Ok(output) => {
+                    let output = output.with_max_event_bytes(self.config.max_output_bytes_per_event);
+                    self.register("passive_dns", Box::new(output))
+                }
+                Err(e) => eprintln!("Failed to initialize passive DNS output: {}", e),
+            }
+        }
+
+        // 初始化CSV输出
+        if self.config.enable_csv {
+            match CsvOutput::new(self.config.csv_config.clone()) {
+                Ok(output) => {
+                    let output = output.with_max_event_bytes(self.config.max_output_bytes_per_event);
+                    self.register("csv", Box::new(output))
+                }
+                Err(e) => eprintln!("Failed to initialize CSV output: {}", e),
+            }
+        }
+
         // 初始化控制台输出
         if self.config.enable_console {
             match ConsoleOutput::new(self.config.console_config.clone()) {
-                Ok(output) => self.outputs.push(Box::new(output)),
+                Ok(output) => {
+                    let output = output.with_max_event_bytes(self.config.max_output_bytes_per_event);
+                    self.register("console", Box::new(output))
+                }
                 Err(e) => eprintln!("Failed to initialize console output: {}", e),
             }
         }
     }
 
+    /// 注册输出，配置了队列容量时包装为异步队列输出
+    fn register(&mut self, name: &str, output: Box<dyn Output + Send>) {
+        if self.config.queue_capacity > 0 {
+            self.outputs.push(Box::new(QueuedOutput::new(name, output, self.config.queue_capacity)));
+        } else {
+            self.outputs.push(output);
+        }
+    }
+
+    /// 添加自定义输出
+    pub fn add_output(&mut self, output: Box<dyn Output + Send>) {
+        self.outputs.push(output);
+    }
+
+    /// 各输出的队列统计
+    pub fn sink_stats(&self) -> Vec<SinkStats> {
+        self.outputs.iter().filter_map(|output| output.queue_stats()).collect()
+    }
+
+    /// 取走上次调用以来的输出层统计
+    pub fn take_stats(&mut self) -> StatsCounter {
+        std::mem::take(&mut self.stats)
+    }
+
     /// 输出DNS消息
     pub fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        // 调试过滤
+        if !self.config.transaction_id_filter.matches(message.transaction_id) {
+            return Ok(());
+        }
+        if !self.name_filter.allows(message) {
+            self.stats.increment("output.filtered.query_name");
+            return Ok(());
+        }
+        if !self.config.record_type_filter.is_empty()
+            && !message
+                .questions
+                .iter()
+                .any(|q| self.config.record_type_filter.contains(&q.record_type))
+        {
+            self.stats.increment("output.filtered.record_type");
+            return Ok(());
+        }
+
+        let message = self.config.ttl_zero_policy.apply(message);
+        let message = self.config.anonymize_client_ip.apply_message(message);
         for output in &mut self.outputs {
-            if let Err(e) = output.output(message) {
+            if let Err(e) = output.output(&message) {
                 eprintln!("Output error: {}", e);
             }
         }
@@ -153,6 +706,26 @@ impl OutputManager {
         Ok(())
     }
 
+    /// 向所有输出发送心跳
+    pub fn heartbeat(&mut self, heartbeat: &Heartbeat) -> Result<(), String> {
+        for output in &mut self.outputs {
+            if let Err(e) = output.heartbeat(heartbeat) {
+                eprintln!("Heartbeat output error: {}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 定时调用所有输出
+    pub fn tick(&mut self) {
+        for output in &mut self.outputs {
+            if let Err(e) = output.tick() {
+                eprintln!("Output tick error: {}", e);
+            }
+        }
+    }
+
     /// 关闭所有输出
     pub fn close(&mut self) -> Result<(), String> {
         for output in &mut self.outputs {
@@ -164,3 +737,143 @@ impl OutputManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsAnswer, DnsClass, DnsMessageType, DnsQuestion, DnsRecordType};
+
+    /// 记录收到的事务ID的测试输出
+    struct RecordingOutput {
+        ids: Arc<Mutex<Vec<u16>>>,
+    }
+
+    impl Output for RecordingOutput {
+        fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+            self.ids.lock().unwrap().push(message.transaction_id);
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn message(transaction_id: u16) -> DnsMessage {
+        DnsMessage {
+            transaction_id,
+            message_type: DnsMessageType::Response,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_transaction_id_filter_include_only() {
+        let mut config = OutputConfig::default();
+        config.transaction_id_filter.include = vec![0x1234..=0x1234];
+
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = OutputManager::new(config);
+        manager.add_output(Box::new(RecordingOutput { ids: Arc::clone(&ids) }));
+
+        for id in [0x1233, 0x1234, 0x1235, 0x1234] {
+            manager.output(&message(id)).unwrap();
+        }
+
+        assert_eq!(*ids.lock().unwrap(), vec![0x1234, 0x1234]);
+    }
+
+    #[test]
+    fn test_transaction_id_filter_exclude_range() {
+        let filter = TransactionIdFilter {
+            include: Vec::new(),
+            exclude: vec![100..=200],
+        };
+        assert!(filter.matches(99));
+        assert!(!filter.matches(100));
+        assert!(!filter.matches(150));
+        assert!(filter.matches(201));
+    }
+
+    #[test]
+    fn test_query_name_filter_allow_and_deny() {
+        let mut config = OutputConfig::default();
+        config.query_name_filter = QueryNameFilter {
+            allow: vec!["*.example.com".to_string(), "example.org".to_string()],
+            deny: vec!["ads.example.com".to_string()],
+        };
+
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = OutputManager::new(config);
+        manager.add_output(Box::new(RecordingOutput { ids: Arc::clone(&ids) }));
+
+        let names = ["www.example.com", "example.com", "example.org", "tracker.ads.example.com", "example.net"];
+        for (id, name) in names.iter().enumerate() {
+            let mut query = message(id as u16);
+            query.questions = vec![DnsQuestion {
+                name: name.to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }];
+            manager.output(&query).unwrap();
+        }
+
+        assert_eq!(*ids.lock().unwrap(), vec![0, 2]);
+        assert_eq!(manager.take_stats().get("output.filtered.query_name"), 3);
+        assert_eq!(manager.take_stats().get("output.filtered.query_name"), 0);
+    }
+
+    #[test]
+    fn test_record_type_filter() {
+        let mut config = OutputConfig::default();
+        config.record_type_filter = DnsRecordType::parse_list("a, aaaa,TYPE16,").unwrap();
+        assert_eq!(config.record_type_filter, vec![DnsRecordType::A, DnsRecordType::AAAA, DnsRecordType::TXT]);
+        assert!(DnsRecordType::parse_list("A,BOGUS").is_err());
+        assert_eq!("65".parse::<DnsRecordType>(), Ok(DnsRecordType::Other(65)));
+
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = OutputManager::new(config);
+        manager.add_output(Box::new(RecordingOutput { ids: Arc::clone(&ids) }));
+
+        for (id, record_type) in [DnsRecordType::A, DnsRecordType::MX, DnsRecordType::TXT].into_iter().enumerate() {
+            let mut query = message(id as u16);
+            query.questions = vec![DnsQuestion {
+                name: "example.com".to_string(),
+                record_type,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }];
+            manager.output(&query).unwrap();
+        }
+        // 没有问题的消息同样被过滤
+        manager.output(&message(3)).unwrap();
+
+        assert_eq!(*ids.lock().unwrap(), vec![0, 2]);
+        assert_eq!(manager.take_stats().get("output.filtered.record_type"), 2);
+    }
+
+    #[test]
+    fn test_ttl_zero_answers_dropped() {
+        let answer = |ttl: u32| DnsAnswer {
+            name: "lb.example".to_string(),
+            record_type: DnsRecordType::A,
+            class: DnsClass::IN,
+            cache_flush: false,
+            ttl,
+            data: vec![192, 0, 2, 1],
+            data_str: "192.0.2.1".to_string(),
+        };
+        let mut response = message(1);
+        response.answers = vec![answer(0), answer(300), answer(0), answer(60)];
+
+        let kept = TtlZeroPolicy::Keep.apply(&response);
+        assert_eq!(kept.answers.len(), 4);
+
+        let dropped = TtlZeroPolicy::Drop.apply(&response);
+        let ttls: Vec<u32> = dropped.answers.iter().map(|a| a.ttl).collect();
+        assert_eq!(ttls, vec![300, 60]);
+    }
+}
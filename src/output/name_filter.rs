@@ -0,0 +1,314 @@
+//! 按问题名过滤输出
+//! 后缀模式编译进按标签倒序组织的字典树，每条消息只需从顶级域向下走一遍；
+//! 其余带通配符的模式逐个按glob匹配，不区分大小写
+
+use std::collections::HashMap;
+
+use crate::output::QueryNameFilter;
+use crate::protocols::dns::{DnsMessage, LabelIter};
+
+/// 单个标签的最大长度
+const MAX_LABEL_LEN: usize = 63;
+/// 域名的最大线上长度，也是点分形式长度的上限
+const MAX_NAME_LEN: usize = 255;
+/// 线上长度不超过255字节的域名最多包含的非根标签数
+const MAX_LABELS: usize = MAX_NAME_LEN / 2;
+
+/// 后缀字典树节点，子节点按标签索引
+#[derive(Default)]
+struct SuffixNode {
+    children: HashMap<String, SuffixNode>,
+    /// 该域名本身及其子域名都匹配
+    domain: bool,
+    /// 只匹配子域名
+    subdomains: bool,
+}
+
+/// 编译后的一组域名模式
+///
+/// - `example.com`：匹配该域名及其所有子域名
+/// - `*.example.com`：只匹配子域名
+/// - 其他含`*`、`?`的模式按glob匹配整个域名，`*`可以跨标签
+#[derive(Default)]
+pub struct NamePatterns {
+    suffixes: SuffixNode,
+    globs: Vec<Vec<u8>>,
+}
+
+impl NamePatterns {
+    /// 编译模式列表，空模式忽略
+    pub fn new(patterns: &[String]) -> Self {
+        let mut compiled = NamePatterns::default();
+        for pattern in patterns {
+            let pattern = normalize(pattern);
+            if pattern.is_empty() {
+                continue;
+            }
+
+            let (suffix, subdomains_only) = match pattern.strip_prefix("*.") {
+                Some(rest) => (rest, true),
+                None => (pattern.as_str(), false),
+            };
+            if suffix.contains(['*', '?']) {
+                compiled.globs.push(pattern.into_bytes());
+                continue;
+            }
+
+            let mut node = &mut compiled.suffixes;
+            for label in suffix.rsplit('.') {
+                node = node.children.entry(label.to_string()).or_default();
+            }
+            if subdomains_only {
+                node.subdomains = true;
+            } else {
+                node.domain = true;
+            }
+        }
+        compiled
+    }
+
+    /// 是否没有任何模式
+    pub fn is_empty(&self) -> bool {
+        self.suffixes.children.is_empty() && self.globs.is_empty()
+    }
+
+    /// 域名是否匹配任一模式
+    pub fn matches(&self, name: &str) -> bool {
+        let name = normalize(name);
+        self.matches_suffix(name.rsplit('.').map(str::as_bytes))
+            || self.globs.iter().any(|glob| glob_match(glob, name.as_bytes()))
+    }
+
+    /// 报文中指定偏移处的域名是否匹配任一模式，标签和拼接的域名都放在栈上，不分配内存
+    ///
+    /// 畸形域名以及超过255字节或127个标签的域名不匹配。
+    pub fn matches_wire(&self, data: &[u8], offset: usize) -> bool {
+        let mut labels: [&[u8]; MAX_LABELS] = [&[]; MAX_LABELS];
+        let mut count = 0;
+        let mut iter = LabelIter::new(data, offset);
+        for label in iter.by_ref() {
+            if count == MAX_LABELS {
+                return false;
+            }
+            labels[count] = label;
+            count += 1;
+        }
+        if iter.is_malformed() {
+            return false;
+        }
+        let labels = &labels[..count];
+        if self.matches_suffix(labels.iter().rev().copied()) {
+            return true;
+        }
+        if self.globs.is_empty() {
+            return false;
+        }
+
+        let mut name = [0u8; MAX_NAME_LEN];
+        let mut len = 0;
+        for (i, label) in labels.iter().enumerate() {
+            let needed = label.len() + usize::from(i > 0);
+            if len + needed > MAX_NAME_LEN {
+                return false;
+            }
+            if i > 0 {
+                name[len] = b'.';
+                len += 1;
+            }
+            name[len..len + label.len()].copy_from_slice(label);
+            len += label.len();
+        }
+        let name = &mut name[..len];
+        name.make_ascii_lowercase();
+        self.globs.iter().any(|glob| glob_match(glob, name))
+    }
+
+    /// 从顶级域向下按标签走后缀字典树，标签比较不区分大小写
+    fn matches_suffix<'a>(&self, labels: impl Iterator<Item = &'a [u8]>) -> bool {
+        let mut node = &self.suffixes;
+        let mut labels = labels.peekable();
+        while let Some(label) = labels.next() {
+            if label.len() > MAX_LABEL_LEN {
+                break;
+            }
+            let mut lower = [0u8; MAX_LABEL_LEN];
+            let lower = &mut lower[..label.len()];
+            lower.copy_from_slice(label);
+            lower.make_ascii_lowercase();
+
+            let child = std::str::from_utf8(lower).ok().and_then(|key| node.children.get(key));
+            node = match child {
+                Some(child) => child,
+                None => break,
+            };
+            if node.domain || (node.subdomains && labels.peek().is_some()) {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// 模式和域名统一为小写并去掉末尾的点
+fn normalize(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// glob匹配，`*`匹配任意长度（含空）的字节序列，`?`匹配单个字节
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // 最近一个`*`的位置及其当前匹配到的文本位置，失配时回溯到这里
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// 编译后的问题名过滤器
+#[derive(Default)]
+pub struct NameFilter {
+    allow: NamePatterns,
+    deny: NamePatterns,
+}
+
+impl NameFilter {
+    /// 按配置编译
+    pub fn new(config: &QueryNameFilter) -> Self {
+        NameFilter {
+            allow: NamePatterns::new(&config.allow),
+            deny: NamePatterns::new(&config.deny),
+        }
+    }
+
+    /// 消息是否允许输出：任一问题命中拒绝列表时丢弃，配置了允许列表时至少一个问题需要命中
+    pub fn allows(&self, message: &DnsMessage) -> bool {
+        if !self.deny.is_empty() && Self::any_question_matches(&self.deny, message) {
+            return false;
+        }
+        self.allow.is_empty() || Self::any_question_matches(&self.allow, message)
+    }
+
+    /// 保留了原始报文且知道问题名偏移时直接在报文上匹配，否则匹配解析出的问题名
+    fn any_question_matches(patterns: &NamePatterns, message: &DnsMessage) -> bool {
+        message.questions.iter().any(|question| match (&message.raw, question.offset) {
+            (Some(raw), Some(offset)) => patterns.matches_wire(raw, offset),
+            _ => patterns.matches(&question.name),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stats::StatsCounter;
+    use crate::protocols::dns::{DnsParser, UdpDnsParser};
+
+    fn patterns(patterns: &[&str]) -> NamePatterns {
+        NamePatterns::new(&patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_suffix_and_glob_patterns() {
+        let patterns = patterns(&["example.com", "*.corp.example.net", "mail*.example.org", "cdn-??.*"]);
+
+        assert!(patterns.matches("example.com"));
+        assert!(patterns.matches("WWW.Example.COM."));
+        assert!(!patterns.matches("notexample.com"));
+        assert!(!patterns.matches("com"));
+
+        assert!(patterns.matches("host.corp.example.net"));
+        assert!(patterns.matches("a.b.corp.example.net"));
+        assert!(!patterns.matches("corp.example.net"));
+
+        assert!(patterns.matches("mail.example.org"));
+        assert!(patterns.matches("mail2.eu.example.org"));
+        assert!(!patterns.matches("smtp.example.org"));
+
+        assert!(patterns.matches("cdn-01.akamai.net"));
+        assert!(!patterns.matches("cdn-001.akamai.net"));
+
+        assert!(NamePatterns::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_match_compressed_name_on_wire() {
+        // 问题名www.example.com在偏移12，应答名mail+指针在偏移29
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(b"\x03www\x07Example\x03com\x00");
+        data.extend_from_slice(b"\x04mail\xC0\x10");
+
+        let suffixes = patterns(&["example.com"]);
+        assert!(suffixes.matches_wire(&data, 12));
+        assert!(suffixes.matches_wire(&data, 29));
+        assert!(!patterns(&["ample.com", "a.www.example.com", "example.org"]).matches_wire(&data, 12));
+        assert!(!patterns(&["*.www.example.com"]).matches_wire(&data, 12));
+        assert!(patterns(&["*.example.com"]).matches_wire(&data, 29));
+        assert!(patterns(&["ma?l.*"]).matches_wire(&data, 29));
+
+        // 指针指向自身的畸形域名不匹配
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(b"\x07example\x03com\xC0\x0C");
+        assert!(!suffixes.matches_wire(&data, 12));
+    }
+
+    #[test]
+    fn test_every_question_matched_on_wire() {
+        // 两个问题：www.example.org和指回example.org的ads.example.org
+        let mut data = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        data.extend_from_slice(b"\x03www\x07example\x03org\x00\x00\x01\x00\x01");
+        data.extend_from_slice(b"\x03ads\xC0\x10\x00\x01\x00\x01");
+        let mut message = UdpDnsParser::new(65535)
+            .with_keep_raw(true)
+            .parse(&data, &StatsCounter::new())
+            .unwrap();
+        assert_eq!(message.questions[0].offset, Some(12));
+        assert_eq!(message.questions[1].offset, Some(33));
+
+        // 问题名被改写后结果不变，说明直接在原始报文上匹配
+        for question in &mut message.questions {
+            question.name.clear();
+        }
+        let filter = NameFilter::new(&QueryNameFilter {
+            allow: vec!["example.org".to_string()],
+            deny: vec!["a?s.*".to_string()],
+        });
+        assert!(!filter.allows(&message));
+        message.questions.truncate(1);
+        assert!(filter.allows(&message));
+    }
+
+    #[test]
+    fn test_too_many_labels_on_wire() {
+        let mut data = vec![0u8; 12];
+        for _ in 0..MAX_LABELS + 1 {
+            data.extend_from_slice(b"\x01a");
+        }
+        data.push(0);
+        assert!(!patterns(&["a", "*"]).matches_wire(&data, 12));
+
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&b"\x01a".repeat(MAX_LABELS));
+        data.push(0);
+        assert!(patterns(&["a"]).matches_wire(&data, 12));
+        assert!(!patterns(&["*.b"]).matches_wire(&data, 12));
+    }
+}
@@ -0,0 +1,308 @@
+//! 被动DNS输出
+//! 按(rrname, rrtype, rdata)聚合应答记录，统计首次/末次出现时间和次数，
+//! 每个聚合窗口结束时以Passive DNS通用输出格式（每行一条JSON）写出
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::output::{truncate_event, Output, PassiveDnsConfig};
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsRecordType};
+
+/// 聚合键
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PassiveDnsKey {
+    rrname: String,
+    rrtype: u16,
+    rdata: String,
+}
+
+/// 聚合状态
+struct PassiveDnsEntry {
+    first_seen: u64,
+    last_seen: u64,
+    count: u64,
+    /// 最近使用序号，用于LRU淘汰
+    last_used: u64,
+}
+
+/// 被动DNS记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassiveDnsRecord {
+    pub rrname: String,
+    pub rrtype: DnsRecordType,
+    pub rdata: String,
+    /// 首次出现时间（自纪元起的秒数）
+    pub time_first: u64,
+    /// 末次出现时间（自纪元起的秒数）
+    pub time_last: u64,
+    pub count: u64,
+}
+
+impl PassiveDnsRecord {
+    /// 格式化为单行JSON
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"rrname\": \"{}\", \"rrtype\": \"{}\", \"rdata\": \"{}\", \"time_first\": {}, \"time_last\": {}, \"count\": {}}}\n",
+            self.rrname, self.rrtype, self.rdata, self.time_first, self.time_last, self.count
+        )
+    }
+}
+
+/// 被动DNS聚合器，条目数超过上限时淘汰最久未出现的条目
+pub struct PassiveDnsAggregator {
+    entries: HashMap<PassiveDnsKey, PassiveDnsEntry>,
+    /// 最近使用序号到键的索引
+    lru: BTreeMap<u64, PassiveDnsKey>,
+    next_use: u64,
+    max_entries: usize,
+}
+
+impl PassiveDnsAggregator {
+    /// 创建新的聚合器，上限至少为1
+    pub fn new(max_entries: usize) -> Self {
+        PassiveDnsAggregator {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            next_use: 0,
+            max_entries: max_entries.max(1),
+        }
+    }
+
+    /// 当前条目数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否没有聚合条目
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 记录一条响应中的应答，返回因容量限制被淘汰的记录
+    pub fn observe(&mut self, message: &DnsMessage) -> Vec<PassiveDnsRecord> {
+        let mut evicted = Vec::new();
+        if message.message_type != DnsMessageType::Response {
+            return evicted;
+        }
+
+        let seen = message.timestamp / 1_000_000;
+        for answer in &message.answers {
+            let key = PassiveDnsKey {
+                rrname: answer.name.trim_end_matches('.').to_ascii_lowercase(),
+                rrtype: u16::from(answer.record_type),
+                rdata: answer.data_str.clone(),
+            };
+            let last_used = self.next_use;
+            self.next_use += 1;
+
+            if let Some(entry) = self.entries.get_mut(&key) {
+                self.lru.remove(&entry.last_used);
+                entry.first_seen = entry.first_seen.min(seen);
+                entry.last_seen = entry.last_seen.max(seen);
+                entry.count += 1;
+                entry.last_used = last_used;
+                self.lru.insert(last_used, key);
+                continue;
+            }
+
+            if self.entries.len() >= self.max_entries {
+                if let Some((_, oldest)) = self.lru.pop_first() {
+                    if let Some(entry) = self.entries.remove(&oldest) {
+                        evicted.push(Self::record(oldest, entry));
+                    }
+                }
+            }
+            self.lru.insert(last_used, key.clone());
+            self.entries.insert(
+                key,
+                PassiveDnsEntry {
+                    first_seen: seen,
+                    last_seen: seen,
+                    count: 1,
+                    last_used,
+                },
+            );
+        }
+
+        evicted
+    }
+
+    /// 取出全部聚合记录并清空
+    pub fn drain(&mut self) -> Vec<PassiveDnsRecord> {
+        self.lru.clear();
+        self.entries
+            .drain()
+            .map(|(key, entry)| Self::record(key, entry))
+            .collect()
+    }
+
+    fn record(key: PassiveDnsKey, entry: PassiveDnsEntry) -> PassiveDnsRecord {
+        PassiveDnsRecord {
+            rrname: key.rrname,
+            rrtype: DnsRecordType::from(key.rrtype),
+            rdata: key.rdata,
+            time_first: entry.first_seen,
+            time_last: entry.last_seen,
+            count: entry.count,
+        }
+    }
+}
+
+/// 被动DNS输出
+pub struct PassiveDnsOutput {
+    aggregator: PassiveDnsAggregator,
+    writer: Box<dyn Write + Send>,
+    /// 聚合窗口（秒）
+    window_secs: u64,
+    /// 当前窗口开始时间（秒），尚未收到消息时为None
+    window_start: Option<u64>,
+    /// 单条记录的最大字节数
+    max_event_bytes: Option<usize>,
+}
+
+impl PassiveDnsOutput {
+    /// 创建新的被动DNS输出，记录追加写入配置的文件
+    pub fn new(config: PassiveDnsConfig) -> Result<Self, String> {
+        if let Some(dir) = Path::new(&config.path).parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create passive DNS directory: {}", e))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+            .map_err(|e| format!("Failed to open passive DNS file {}: {}", config.path, e))?;
+
+        Ok(Self::with_writer(
+            Box::new(BufWriter::new(file)),
+            config.window_secs,
+            config.max_entries,
+        ))
+    }
+
+    /// 使用指定的写入目标创建输出
+    pub fn with_writer(writer: Box<dyn Write + Send>, window_secs: u64, max_entries: usize) -> Self {
+        PassiveDnsOutput {
+            aggregator: PassiveDnsAggregator::new(max_entries),
+            writer,
+            window_secs,
+            window_start: None,
+            max_event_bytes: None,
+        }
+    }
+
+    /// 限制单条记录的长度，超出时截断并标记
+    pub fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    fn write_records(&mut self, records: &[PassiveDnsRecord]) -> Result<(), String> {
+        for record in records {
+            self.writer
+                .write_all(truncate_event(record.to_json(), self.max_event_bytes).as_bytes())
+                .map_err(|e| format!("Failed to write passive DNS record: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// 写出当前窗口的全部记录
+    fn flush_window(&mut self) -> Result<(), String> {
+        let records = self.aggregator.drain();
+        self.write_records(&records)?;
+        self.writer
+            .flush()
+            .map_err(|e| format!("Failed to flush passive DNS records: {}", e))
+    }
+}
+
+impl Output for PassiveDnsOutput {
+    fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        // 窗口按抓包时间推进，离线回放时与实时抓包结果一致
+        let now = message.timestamp / 1_000_000;
+        match self.window_start {
+            Some(start) if now.saturating_sub(start) >= self.window_secs => {
+                self.flush_window()?;
+                self.window_start = Some(now);
+            }
+            None => self.window_start = Some(now),
+            _ => {}
+        }
+
+        let evicted = self.aggregator.observe(message);
+        self.write_records(&evicted)
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        self.flush_window()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsAnswer, DnsClass};
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn response(timestamp_secs: u64, answers: &[(&str, &str)]) -> DnsMessage {
+        DnsMessage {
+            transaction_id: 1,
+            message_type: DnsMessageType::Response,
+            answers: answers
+                .iter()
+                .map(|(name, data)| DnsAnswer {
+                    name: name.to_string(),
+                    record_type: DnsRecordType::A,
+                    class: DnsClass::IN,
+                    cache_flush: false,
+                    ttl: 300,
+                    data: Vec::new(),
+                    data_str: data.to_string(),
+                })
+                .collect(),
+            timestamp: timestamp_secs * 1_000_000,
+            src_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)),
+            src_port: 53,
+            dst_port: 40000,
+            recursion_desired: true,
+            recursion_available: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_repeated_observations_aggregate() {
+        let mut aggregator = PassiveDnsAggregator::new(2);
+
+        aggregator.observe(&response(1_000, &[("example.com", "192.0.2.1")]));
+        aggregator.observe(&response(1_060, &[("Example.com.", "192.0.2.1")]));
+        aggregator.observe(&response(1_120, &[("example.com", "192.0.2.1")]));
+        assert_eq!(aggregator.len(), 1);
+
+        // 超过上限时淘汰最久未出现的条目
+        aggregator.observe(&response(1_130, &[("example.org", "192.0.2.2")]));
+        let evicted = aggregator.observe(&response(1_140, &[("example.net", "192.0.2.3")]));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(
+            evicted[0],
+            PassiveDnsRecord {
+                rrname: "example.com".to_string(),
+                rrtype: DnsRecordType::A,
+                rdata: "192.0.2.1".to_string(),
+                time_first: 1_000,
+                time_last: 1_120,
+                count: 3,
+            }
+        );
+
+        let mut records = aggregator.drain();
+        records.sort_by(|a, b| a.rrname.cmp(&b.rrname));
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rrname, "example.net");
+        assert!(aggregator.is_empty());
+        assert!(records[1].to_json().starts_with("{\"rrname\": \"example.org\", \"rrtype\": \"A\""));
+    }
+}
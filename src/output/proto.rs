@@ -0,0 +1,276 @@
+//! protobuf输出编码
+//! 类型定义对应proto/dns_message.proto，保持字段编号一致
+
+use std::net::IpAddr;
+
+use prost::Message;
+
+use crate::output::query_hash;
+use crate::protocols::dns::{self, DnsMessageType, DnsProtocol, DnsRole};
+
+/// DNS消息类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum PbMessageType {
+    Query = 0,
+    Response = 1,
+}
+
+/// DNS协议类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum PbProtocol {
+    Udp = 0,
+    Tcp = 1,
+    Dot = 2,
+    Doh = 3,
+    Doq = 4,
+    Mdns = 5,
+}
+
+/// 消息所属的解析链路
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum PbRole {
+    Unknown = 0,
+    StubToResolver = 1,
+    ResolverToAuthority = 2,
+}
+
+/// DNS问题记录
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PbQuestion {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(uint32, tag = "2")]
+    pub record_type: u32,
+    #[prost(uint32, tag = "3")]
+    pub class: u32,
+}
+
+/// DNS应答记录
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PbAnswer {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(uint32, tag = "2")]
+    pub record_type: u32,
+    #[prost(uint32, tag = "3")]
+    pub class: u32,
+    #[prost(uint32, tag = "4")]
+    pub ttl: u32,
+    #[prost(bytes = "vec", tag = "5")]
+    pub data: Vec<u8>,
+    #[prost(string, tag = "6")]
+    pub data_str: String,
+}
+
+/// DNS消息
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PbMessage {
+    #[prost(uint32, tag = "1")]
+    pub transaction_id: u32,
+    #[prost(enumeration = "PbMessageType", tag = "2")]
+    pub message_type: i32,
+    #[prost(message, repeated, tag = "3")]
+    pub questions: Vec<PbQuestion>,
+    #[prost(message, repeated, tag = "4")]
+    pub answers: Vec<PbAnswer>,
+    #[prost(uint64, tag = "5")]
+    pub timestamp: u64,
+    #[prost(enumeration = "PbProtocol", tag = "6")]
+    pub protocol: i32,
+    #[prost(bool, tag = "7")]
+    pub unsolicited: bool,
+    #[prost(bytes = "vec", tag = "8")]
+    pub src_ip: Vec<u8>,
+    #[prost(bytes = "vec", tag = "9")]
+    pub dst_ip: Vec<u8>,
+    #[prost(uint32, tag = "10")]
+    pub src_port: u32,
+    #[prost(uint32, tag = "11")]
+    pub dst_port: u32,
+    #[prost(uint32, tag = "12")]
+    pub opcode: u32,
+    #[prost(uint32, tag = "13")]
+    pub rcode: u32,
+    #[prost(bool, tag = "14")]
+    pub authoritative: bool,
+    #[prost(bool, tag = "15")]
+    pub truncated: bool,
+    #[prost(bool, tag = "16")]
+    pub recursion_desired: bool,
+    #[prost(bool, tag = "17")]
+    pub recursion_available: bool,
+    #[prost(bool, tag = "18")]
+    pub dnssec_ok: bool,
+    #[prost(message, repeated, tag = "19")]
+    pub authorities: Vec<PbAnswer>,
+    #[prost(message, repeated, tag = "20")]
+    pub additionals: Vec<PbAnswer>,
+    #[prost(bool, tag = "21")]
+    pub unreachable: bool,
+    #[prost(fixed64, tag = "22")]
+    pub query_hash: u64,
+    #[prost(uint32, optional, tag = "23")]
+    pub negative_ttl: Option<u32>,
+    #[prost(string, optional, tag = "24")]
+    pub interface: Option<String>,
+    #[prost(bool, tag = "25")]
+    pub tunneling_suspected: bool,
+    #[prost(enumeration = "PbRole", tag = "26")]
+    pub role: i32,
+    #[prost(string, optional, tag = "27")]
+    pub client_hostname: Option<String>,
+}
+
+/// 转换一组资源记录
+fn records(records: &[dns::DnsAnswer]) -> Vec<PbAnswer> {
+    records
+        .iter()
+        .map(|a| PbAnswer {
+            name: a.name.clone(),
+            record_type: u16::from(a.record_type) as u32,
+            class: u16::from(a.class) as u32,
+            ttl: a.ttl,
+            data: a.data.clone(),
+            data_str: a.data_str.clone(),
+        })
+        .collect()
+}
+
+/// IP地址的网络字节序表示，IPv4为4字节，IPv6为16字节
+fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(v4) => v4.octets().to_vec(),
+        IpAddr::V6(v6) => v6.octets().to_vec(),
+    }
+}
+
+impl From<&dns::DnsMessage> for PbMessage {
+    fn from(message: &dns::DnsMessage) -> Self {
+        let message_type = match message.message_type {
+            DnsMessageType::Query => PbMessageType::Query,
+            DnsMessageType::Response => PbMessageType::Response,
+        };
+        let protocol = match message.protocol {
+            DnsProtocol::Udp => PbProtocol::Udp,
+            DnsProtocol::Tcp => PbProtocol::Tcp,
+            DnsProtocol::Dot => PbProtocol::Dot,
+            DnsProtocol::Doh => PbProtocol::Doh,
+            DnsProtocol::Doq => PbProtocol::Doq,
+            DnsProtocol::Mdns => PbProtocol::Mdns,
+        };
+        let role = match message.role {
+            DnsRole::Unknown => PbRole::Unknown,
+            DnsRole::StubToResolver => PbRole::StubToResolver,
+            DnsRole::ResolverToAuthority => PbRole::ResolverToAuthority,
+        };
+
+        PbMessage {
+            transaction_id: message.transaction_id as u32,
+            message_type: message_type as i32,
+            questions: message
+                .questions
+                .iter()
+                .map(|q| PbQuestion {
+                    name: q.name.clone(),
+                    record_type: u16::from(q.record_type) as u32,
+                    class: u16::from(q.class) as u32,
+                })
+                .collect(),
+            answers: records(&message.answers),
+            timestamp: message.timestamp,
+            protocol: protocol as i32,
+            unsolicited: message.unsolicited,
+            src_ip: ip_bytes(message.src_ip),
+            dst_ip: ip_bytes(message.dst_ip),
+            src_port: message.src_port as u32,
+            dst_port: message.dst_port as u32,
+            opcode: message.opcode as u32,
+            rcode: message.rcode as u32,
+            authoritative: message.authoritative,
+            truncated: message.truncated,
+            recursion_desired: message.recursion_desired,
+            recursion_available: message.recursion_available,
+            dnssec_ok: message.dnssec_ok,
+            authorities: records(&message.authorities),
+            additionals: records(&message.additionals),
+            unreachable: message.unreachable,
+            query_hash: query_hash(message),
+            negative_ttl: message.negative_ttl,
+            interface: message.interface.as_deref().map(str::to_string),
+            tunneling_suspected: message.tunneling_suspected,
+            role: role as i32,
+            client_hostname: message.client_hostname.as_deref().map(str::to_string),
+        }
+    }
+}
+
+/// 将DNS消息编码为带长度前缀的protobuf记录，便于在文件中连续存放
+pub fn encode_length_delimited(message: &dns::DnsMessage) -> Vec<u8> {
+    PbMessage::from(message).encode_length_delimited_to_vec()
+}
+
+/// 将DNS消息编码为单条protobuf记录
+pub fn encode(message: &dns::DnsMessage) -> Vec<u8> {
+    PbMessage::from(message).encode_to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsAnswer, DnsClass, DnsMessage, DnsQuestion, DnsRecordType};
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_protobuf_round_trip() {
+        let message = DnsMessage {
+            transaction_id: 0xBEEF,
+            message_type: DnsMessageType::Response,
+            questions: vec![DnsQuestion {
+                name: "example.com".to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            answers: vec![DnsAnswer {
+                name: "example.com".to_string(),
+                record_type: DnsRecordType::Other(65280),
+                class: DnsClass::IN,
+                cache_flush: false,
+                ttl: 300,
+                data: vec![93, 184, 216, 34],
+                data_str: "93.184.216.34".to_string(),
+            }],
+            timestamp: 1_700_000_000_000_000,
+            protocol: DnsProtocol::Tcp,
+            src_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)),
+            src_port: 53,
+            dst_port: 40000,
+            ..Default::default()
+        };
+
+        let bytes = encode(&message);
+        let decoded = PbMessage::decode(bytes.as_slice()).unwrap();
+
+        assert_eq!(decoded.transaction_id, 0xBEEF);
+        assert_eq!(decoded.message_type(), PbMessageType::Response);
+        assert_eq!(decoded.protocol(), PbProtocol::Tcp);
+        assert_eq!(decoded.timestamp, 1_700_000_000_000_000);
+        assert_eq!(decoded.questions[0].name, "example.com");
+        assert_eq!(decoded.questions[0].record_type, 1);
+        assert_eq!(decoded.answers[0].record_type, 65280);
+        assert_eq!(decoded.answers[0].ttl, 300);
+        assert_eq!(decoded.answers[0].data, vec![93, 184, 216, 34]);
+        assert_eq!(decoded.src_ip, vec![192, 0, 2, 53]);
+        assert_eq!(decoded.dst_port, 40000);
+        assert_eq!(decoded, PbMessage::from(&message));
+
+        let delimited = encode_length_delimited(&message);
+        let decoded = PbMessage::decode_length_delimited(delimited.as_slice()).unwrap();
+        assert_eq!(decoded, PbMessage::from(&message));
+    }
+}
@@ -0,0 +1,78 @@
+//! 查询指纹
+//! 由规范化的问题名、查询类型、查询类和客户端子网计算稳定的64位哈希，
+//! 供下游跨副本对同一逻辑查询去重
+
+use std::net::IpAddr;
+
+use crate::output::anonymize::truncate;
+use crate::protocols::dns::{DnsMessage, DnsMessageType};
+use crate::utils::hash::{fnv1a, FNV_OFFSET_BASIS};
+
+/// 计算查询指纹
+///
+/// 问题名统一小写并去掉末尾的点；客户端地址按IPv4 /24、IPv6 /48取子网，
+/// 查询取源地址，响应取目的地址，因此查询和对应响应的指纹相同。
+pub fn query_hash(message: &DnsMessage) -> u64 {
+    let mut hash = FNV_OFFSET_BASIS;
+
+    if let Some(question) = message.questions.first() {
+        let name = question.name.trim_end_matches('.').to_ascii_lowercase();
+        hash = fnv1a(hash, name.as_bytes());
+        // 分隔符避免名称与后续字段拼接产生歧义
+        hash = fnv1a(hash, &[0]);
+        hash = fnv1a(hash, &u16::from(question.record_type).to_be_bytes());
+        hash = fnv1a(hash, &u16::from(question.class).to_be_bytes());
+    }
+
+    let client = match message.message_type {
+        DnsMessageType::Query => message.src_ip,
+        DnsMessageType::Response => message.dst_ip,
+    };
+    match truncate(client) {
+        IpAddr::V4(v4) => fnv1a(hash, &v4.octets()),
+        IpAddr::V6(v6) => fnv1a(hash, &v6.octets()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsClass, DnsQuestion, DnsRecordType};
+    use std::net::Ipv4Addr;
+
+    fn message(name: &str, record_type: DnsRecordType, client: Ipv4Addr) -> DnsMessage {
+        DnsMessage {
+            transaction_id: 1,
+            questions: vec![DnsQuestion {
+                name: name.to_string(),
+                record_type,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            src_ip: IpAddr::V4(client),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)),
+            src_port: 40000,
+            dst_port: 53,
+            recursion_desired: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_query_hash_stable_and_distinct() {
+        let client = Ipv4Addr::new(192, 0, 2, 10);
+        let hash = query_hash(&message("example.com", DnsRecordType::A, client));
+
+        // 与运行环境无关的固定值
+        assert_eq!(hash, 0xD552_5635_9908_4C98);
+        assert_eq!(hash, query_hash(&message("example.com", DnsRecordType::A, client)));
+        // 大小写、末尾的点和同一子网内的不同主机不影响指纹
+        assert_eq!(hash, query_hash(&message("EXAMPLE.com.", DnsRecordType::A, client)));
+        assert_eq!(hash, query_hash(&message("example.com", DnsRecordType::A, Ipv4Addr::new(192, 0, 2, 99))));
+
+        assert_ne!(hash, query_hash(&message("example.org", DnsRecordType::A, client)));
+        assert_ne!(hash, query_hash(&message("example.com", DnsRecordType::AAAA, client)));
+        assert_ne!(hash, query_hash(&message("example.com", DnsRecordType::A, Ipv4Addr::new(192, 0, 3, 10))));
+    }
+}
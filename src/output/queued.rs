@@ -0,0 +1,231 @@
+//! 异步队列输出
+//! 每个输出拥有独立的有界队列和线程，慢输出不会拖累其他输出
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::output::{Heartbeat, Output};
+use crate::protocols::dns::DnsMessage;
+
+/// 队列中的事件
+enum QueueItem {
+    Message(DnsMessage),
+    Heartbeat(Heartbeat),
+    /// 定时调用，不计入队列深度
+    Tick,
+}
+
+/// 队列计数
+#[derive(Default)]
+struct QueueCounters {
+    /// 当前排队消息数
+    depth: AtomicUsize,
+    /// 队列满时丢弃的消息数
+    dropped: AtomicU64,
+}
+
+/// 单个输出的队列统计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkStats {
+    /// 输出名称
+    pub name: String,
+    /// 当前队列深度
+    pub queue_depth: usize,
+    /// 累计丢弃数
+    pub dropped: u64,
+}
+
+/// 异步队列输出
+pub struct QueuedOutput {
+    /// 输出名称
+    name: String,
+    /// 队列发送端，关闭时置空以通知工作线程退出
+    sender: Option<SyncSender<QueueItem>>,
+    /// 工作线程
+    handle: Option<JoinHandle<Result<(), String>>>,
+    /// 队列计数
+    counters: Arc<QueueCounters>,
+}
+
+impl QueuedOutput {
+    /// 创建新的异步队列输出，队列满时丢弃新消息
+    pub fn new(name: &str, mut inner: Box<dyn Output + Send>, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<QueueItem>(capacity);
+        let counters = Arc::new(QueueCounters::default());
+        let worker_counters = Arc::clone(&counters);
+        let worker_name = name.to_string();
+
+        let handle = thread::spawn(move || {
+            for item in receiver {
+                let result = match &item {
+                    QueueItem::Message(message) => inner.output(message),
+                    QueueItem::Heartbeat(heartbeat) => inner.heartbeat(heartbeat),
+                    QueueItem::Tick => inner.tick(),
+                };
+                if let Err(e) = result {
+                    eprintln!("Output {} error: {}", worker_name, e);
+                }
+                if !matches!(item, QueueItem::Tick) {
+                    worker_counters.depth.fetch_sub(1, Ordering::Relaxed);
+                }
+            }
+
+            inner.close()
+        });
+
+        QueuedOutput {
+            name: name.to_string(),
+            sender: Some(sender),
+            handle: Some(handle),
+            counters,
+        }
+    }
+
+    /// 入队，队列满时丢弃并计数
+    fn enqueue(&mut self, item: QueueItem) -> Result<(), String> {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return Err(format!("Output {} already closed", self.name)),
+        };
+
+        // 先计数再入队，避免工作线程先消费导致计数下溢
+        self.counters.depth.fetch_add(1, Ordering::Relaxed);
+        match sender.try_send(item) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.counters.depth.fetch_sub(1, Ordering::Relaxed);
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                self.counters.depth.fetch_sub(1, Ordering::Relaxed);
+                Err(format!("Output {} worker exited", self.name))
+            }
+        }
+    }
+}
+
+impl Output for QueuedOutput {
+    fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        self.enqueue(QueueItem::Message(message.clone()))
+    }
+
+    fn heartbeat(&mut self, heartbeat: &Heartbeat) -> Result<(), String> {
+        self.enqueue(QueueItem::Heartbeat(heartbeat.clone()))
+    }
+
+    fn tick(&mut self) -> Result<(), String> {
+        // 队列满时说明输出线程正忙，本次定时调用可以跳过
+        if let Some(sender) = &self.sender {
+            let _ = sender.try_send(QueueItem::Tick);
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        // 关闭发送端后工作线程会处理完剩余消息再关闭内部输出
+        self.sender = None;
+
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .map_err(|_| format!("Output {} worker panicked", self.name))?,
+            None => Ok(()),
+        }
+    }
+
+    fn queue_stats(&self) -> Option<SinkStats> {
+        Some(SinkStats {
+            name: self.name.clone(),
+            queue_depth: self.counters.depth.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+        })
+    }
+}
+
+impl Drop for QueuedOutput {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    /// 在收到放行信号前阻塞的慢输出
+    struct GatedOutput {
+        gate: Arc<Mutex<Receiver<()>>>,
+    }
+
+    impl Output for GatedOutput {
+        fn output(&mut self, _message: &DnsMessage) -> Result<(), String> {
+            let _ = self.gate.lock().unwrap().recv();
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    /// 立即返回的快输出
+    struct FastOutput;
+
+    impl Output for FastOutput {
+        fn output(&mut self, _message: &DnsMessage) -> Result<(), String> {
+            Ok(())
+        }
+
+        fn close(&mut self) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn message() -> DnsMessage {
+        DnsMessage {
+            transaction_id: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_slow_sink_backs_up_while_fast_sink_drains() {
+        let (release, gate) = mpsc::channel();
+        let gate = Arc::new(Mutex::new(gate));
+        let mut slow = QueuedOutput::new("slow", Box::new(GatedOutput { gate }), 8);
+        let mut fast = QueuedOutput::new("fast", Box::new(FastOutput), 64);
+
+        for _ in 0..20 {
+            slow.output(&message()).unwrap();
+            fast.output(&message()).unwrap();
+        }
+
+        // 等待快输出排空
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while fast.queue_stats().unwrap().queue_depth > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let slow_stats = slow.queue_stats().unwrap();
+        let fast_stats = fast.queue_stats().unwrap();
+        assert_eq!(fast_stats.queue_depth, 0);
+        assert_eq!(fast_stats.dropped, 0);
+        assert!(slow_stats.queue_depth >= 8);
+        assert!(slow_stats.dropped >= 11);
+
+        // 放行慢输出并关闭
+        for _ in 0..20 {
+            let _ = release.send(());
+        }
+        drop(release);
+        slow.close().unwrap();
+        fast.close().unwrap();
+        assert_eq!(slow.queue_stats().unwrap().queue_depth, 0);
+    }
+}
@@ -1,70 +1,268 @@
 //! Statsd输出实现
 //! 将DNS统计信息输出到Statsd
 
-use std::io::Error;
-use std::net::UdpSocket;
-use std::time::Instant;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
 
-use crate::output::{Output, StatsdConfig};
-use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsRecordType};
+use crate::output::{Output, StatsdConfig, StatsdProtocol};
+use crate::protocols::dns::{rcode_name, DnsMessage, DnsMessageType, DnsRecordType};
+
+/// 热门域名候选数量相对上报数量的倍数，避免高频域名被偶发域名挤出
+const TOP_NAME_CANDIDATE_FACTOR: usize = 10;
+
+/// TCP连接断开期间最多缓存的指标字节数，超出时丢弃最早的指标
+const MAX_PENDING_BYTES: usize = 1024 * 1024;
+/// TCP连接超时
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+/// TCP写超时，避免Statsd无响应时阻塞输出
+const TCP_WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+/// 每次刷新时TCP发送（含重连）的尝试次数
+const TCP_SEND_ATTEMPTS: u32 = 2;
+/// TCP重试间隔（毫秒）
+const TCP_RETRY_DELAY_MS: u64 = 100;
+
+/// Statsd传输方式
+enum Transport {
+    Udp(UdpSocket),
+    /// 连接断开后为None，下次发送时重连
+    Tcp(Option<TcpStream>),
+}
+
+/// 连接TCP服务器，依次尝试解析出的地址，连接设置写超时
+pub(super) fn connect_tcp(addr: &str) -> Result<TcpStream, Error> {
+    let mut last_error = Error::new(ErrorKind::NotFound, format!("no address resolved for {}", addr));
+    for socket_addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_addr, TCP_CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(TCP_WRITE_TIMEOUT))?;
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// 查询域名计数，容量固定，满时淘汰最久未出现的域名
+struct QueryNameCounter {
+    /// 最大候选数量
+    capacity: usize,
+    /// 递增的访问序号，用于判断最近出现时间
+    tick: u64,
+    /// 域名 -> (本周期查询次数, 最近出现序号)
+    names: HashMap<String, (u64, u64)>,
+}
+
+impl QueryNameCounter {
+    fn new(capacity: usize) -> Self {
+        QueryNameCounter {
+            capacity,
+            tick: 0,
+            names: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// 记录一次查询
+    fn record(&mut self, name: &str) {
+        self.tick += 1;
+        if let Some(entry) = self.names.get_mut(name) {
+            entry.0 += 1;
+            entry.1 = self.tick;
+            return;
+        }
+
+        if self.names.len() >= self.capacity {
+            let oldest = self
+                .names
+                .iter()
+                .min_by_key(|(_, (_, last_seen))| *last_seen)
+                .map(|(name, _)| name.clone());
+            if let Some(oldest) = oldest {
+                self.names.remove(&oldest);
+            }
+        }
+        self.names.insert(name.to_string(), (1, self.tick));
+    }
+
+    /// 取出本周期查询次数最多的n个域名并清零计数，候选域名保留以延续淘汰顺序
+    fn take_top(&mut self, n: usize) -> Vec<(String, u64)> {
+        let mut top: Vec<(String, u64)> = self
+            .names
+            .iter()
+            .filter(|(_, (count, _))| *count > 0)
+            .map(|(name, (count, _))| (name.clone(), *count))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(n);
+
+        for (count, _) in self.names.values_mut() {
+            *count = 0;
+        }
+        top
+    }
+}
+
+/// 将域名转换为Statsd指标名片段：点和Statsd保留字符替换为下划线
+fn metric_name_segment(name: &str) -> String {
+    let name = name.trim_end_matches('.');
+    if name.is_empty() {
+        return "root".to_string();
+    }
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
 
 /// Statsd输出
 pub struct StatsdOutput {
     /// 配置
     config: StatsdConfig,
-    /// UDP套接字
-    socket: UdpSocket,
+    /// 传输方式
+    transport: Transport,
+    /// TCP待发送的指标行，连接断开期间在此累积
+    pending: Vec<u8>,
     /// 上次发送时间
     last_send: Instant,
     /// 计数器
-    counters: std::collections::HashMap<String, u64>,
+    counters: HashMap<String, u64>,
+    /// 热门域名计数，未配置top_query_names时为None
+    query_names: Option<QueryNameCounter>,
 }
 
 impl StatsdOutput {
     /// 创建新的Statsd输出
     pub fn new(config: StatsdConfig) -> Result<Self, String> {
-        // 创建UDP套接字
-        let socket = UdpSocket::bind("0.0.0.0:0")
-            .map_err(|e| format!("Failed to bind UDP socket: {}", e))?;
+        let transport = match config.protocol {
+            // 创建UDP套接字
+            StatsdProtocol::Udp => Transport::Udp(
+                UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to bind UDP socket: {}", e))?,
+            ),
+            // 首次发送时再连接，Statsd暂时不可用不影响启动
+            StatsdProtocol::Tcp => Transport::Tcp(None),
+        };
+
+        let query_names = (config.top_query_names > 0)
+            .then(|| QueryNameCounter::new(config.top_query_names * TOP_NAME_CANDIDATE_FACTOR));
 
         Ok(StatsdOutput {
             config,
-            socket,
+            transport,
+            pending: Vec::new(),
             last_send: Instant::now(),
-            counters: std::collections::HashMap::new(),
+            counters: HashMap::new(),
+            query_names,
         })
     }
 
+    /// Statsd服务器地址
+    fn addr(&self) -> String {
+        format!("{}:{}", self.config.host, self.config.port)
+    }
+
+    /// 发送一行指标：UDP直接发出，TCP先写入待发送缓冲区
+    fn send_metric(&mut self, metric: &str) -> Result<(), Error> {
+        if let Transport::Udp(socket) = &self.transport {
+            socket.send_to(metric.as_bytes(), self.addr())?;
+        } else {
+            self.pending.extend_from_slice(metric.as_bytes());
+        }
+        Ok(())
+    }
+
     /// 发送计数器到Statsd
-    fn send_counter(&self, name: &str, value: u64) -> Result<(), Error> {
+    fn send_counter(&mut self, name: &str, value: u64) -> Result<(), Error> {
         let metric = format!("{}.{}:{}|c\n", self.config.prefix, name, value);
-        let addr = format!("{}{}", self.config.host, self.config.port);
-        self.socket.send_to(metric.as_bytes(), addr)?;
-        Ok(())
+        self.send_metric(&metric)
     }
 
     /// 发送计时器到Statsd
-    fn send_timer(&self, name: &str, value_ms: u64) -> Result<(), Error> {
+    fn send_timer(&mut self, name: &str, value_ms: u64) -> Result<(), Error> {
         let metric = format!("{}.{}:{}|ms\n", self.config.prefix, name, value_ms);
-        let addr = format!("{}{}", self.config.host, self.config.port);
-        self.socket.send_to(metric.as_bytes(), addr)?;
+        self.send_metric(&metric)
+    }
+
+    /// 缓冲区超过上限时按行丢弃最早的指标
+    fn trim_pending(&mut self) {
+        if self.pending.len() <= MAX_PENDING_BYTES {
+            return;
+        }
+        let excess = self.pending.len() - MAX_PENDING_BYTES;
+        let cut = self.pending[excess..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(self.pending.len(), |pos| excess + pos + 1);
+        self.pending.drain(..cut);
+        eprintln!("Statsd连接不可用，丢弃{}字节的旧指标", cut);
+    }
+
+    /// 通过TCP写出待发送缓冲区，未连接时先连接，写入失败时断开以便下次重连
+    ///
+    /// 失败时缓冲区整体保留，已部分写出的指标可能被重复发送。
+    fn write_pending(&mut self) -> Result<(), Error> {
+        let addr = self.addr();
+        let stream = match &mut self.transport {
+            Transport::Tcp(stream) => stream,
+            Transport::Udp(_) => return Ok(()),
+        };
+
+        if stream.is_none() {
+            *stream = Some(connect_tcp(&addr)?);
+        }
+        let result = match stream.as_mut() {
+            Some(conn) => conn.write_all(&self.pending).and_then(|_| conn.flush()),
+            None => Ok(()),
+        };
+        if let Err(e) = result {
+            *stream = None;
+            return Err(e);
+        }
+
+        self.pending.clear();
         Ok(())
     }
 
     /// 发送所有统计信息
     fn flush_stats(&mut self) -> Result<(), String> {
-        for (name, value) in &self.counters {
+        self.collect_query_names();
+
+        // 重置计数器
+        let counters = std::mem::take(&mut self.counters);
+        self.last_send = Instant::now();
+
+        for (name, value) in &counters {
             self.send_counter(name, *value)
                 .map_err(|e| format!("Failed to send counter: {}", e))?;
         }
 
-        // 重置计数器
-        self.counters.clear();
-        self.last_send = Instant::now();
+        if matches!(self.transport, Transport::Tcp(_)) && !self.pending.is_empty() {
+            self.trim_pending();
+            crate::retry!(self.write_pending(), TCP_SEND_ATTEMPTS, TCP_RETRY_DELAY_MS).map_err(|e| {
+                format!("Failed to send metrics over TCP, {} bytes buffered: {}", self.pending.len(), e)
+            })?;
+        }
 
         Ok(())
     }
 
+    /// 将本周期的热门域名计数并入计数器
+    fn collect_query_names(&mut self) {
+        if let Some(query_names) = self.query_names.as_mut() {
+            for (name, count) in query_names.take_top(self.config.top_query_names) {
+                let key = format!("qname.{}", metric_name_segment(&name));
+                *self.counters.entry(key).or_insert(0) += count;
+            }
+        }
+    }
+
     /// 更新DNS消息统计信息
     fn update_stats(&mut self, message: &DnsMessage) {
         // 更新总消息计数
@@ -86,6 +284,10 @@ impl StatsdOutput {
                     .counters
                     .entry("messages.response".to_string())
                     .or_insert(0) += 1;
+
+                // 按响应码计数，便于发现NXDOMAIN、SERVFAIL激增
+                let rcode_key = format!("rcode.{}", rcode_name(message.rcode)).to_lowercase();
+                *self.counters.entry(rcode_key).or_insert(0) += 1;
             }
         }
 
@@ -95,10 +297,19 @@ impl StatsdOutput {
 
         // 按记录类型计数
         for question in &message.questions {
-            let record_type_key = format!("record_type.{:?}", question.record_type).to_lowercase();
+            let record_type_key = format!("record_type.{}", question.record_type).to_lowercase();
             *self.counters.entry(record_type_key).or_insert(0) += 1;
         }
 
+        // 热门域名只统计查询，避免同一次解析被计两次
+        if message.message_type == DnsMessageType::Query {
+            if let Some(query_names) = self.query_names.as_mut() {
+                for question in &message.questions {
+                    query_names.record(&question.name.to_lowercase());
+                }
+            }
+        }
+
         // 每分钟刷新一次统计信息
         if self.last_send.elapsed().as_secs() >= 60 {
             if let Err(e) = self.flush_stats() {
@@ -120,3 +331,112 @@ impl Output for StatsdOutput {
         self.flush_stats()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsClass, DnsQuestion};
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    fn message(message_type: DnsMessageType, name: &str, rcode: u16) -> DnsMessage {
+        DnsMessage {
+            transaction_id: 1,
+            message_type,
+            questions: vec![DnsQuestion {
+                name: name.to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            rcode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rcode_and_top_query_name_counters() {
+        let config = StatsdConfig {
+            top_query_names: 2,
+            ..StatsdConfig::default()
+        };
+        let mut output = StatsdOutput::new(config).unwrap();
+
+        for _ in 0..3 {
+            output.update_stats(&message(DnsMessageType::Query, "Example.com", 0));
+        }
+        output.update_stats(&message(DnsMessageType::Query, "mail.example.com", 0));
+        output.update_stats(&message(DnsMessageType::Query, "mail.example.com", 0));
+        output.update_stats(&message(DnsMessageType::Query, "rare.example.org", 0));
+        output.update_stats(&message(DnsMessageType::Response, "missing.example.com", 3));
+        output.update_stats(&message(DnsMessageType::Response, "example.com", 0));
+        output.collect_query_names();
+
+        assert_eq!(output.counters.get("rcode.nxdomain"), Some(&1));
+        assert_eq!(output.counters.get("rcode.noerror"), Some(&1));
+        assert_eq!(output.counters.get("qname.example_com"), Some(&3));
+        assert_eq!(output.counters.get("qname.mail_example_com"), Some(&2));
+        assert!(!output.counters.contains_key("qname.rare_example_org"));
+
+        // 候选域名数量固定，满时淘汰最久未出现的域名
+        let mut names = QueryNameCounter::new(3);
+        for i in 0..100 {
+            names.record(&format!("host{}.example.com", i));
+            names.record("busy.example.com");
+        }
+        assert_eq!(names.names.len(), 3);
+        assert_eq!(names.take_top(1), vec![("busy.example.com".to_string(), 100)]);
+        assert!(names.take_top(1).is_empty());
+        assert_eq!(metric_name_segment("a:b|c@d."), "a_b_c_d");
+    }
+
+    /// 从连接读取数据，直到收到所有期望的指标行
+    fn read_metrics(stream: &mut TcpStream, expected: &[&str]) -> String {
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut received = String::new();
+        let mut buf = [0u8; 4096];
+        while !expected.iter().all(|metric| received.lines().any(|line| line == *metric)) {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "连接提前关闭，已收到: {:?}", received);
+            received.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+        }
+        received
+    }
+
+    #[test]
+    fn test_tcp_buffers_and_reconnects() {
+        // 先占用一个端口再释放，使首次发送时连接失败
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let config = StatsdConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            protocol: StatsdProtocol::Tcp,
+            ..StatsdConfig::default()
+        };
+        let mut output = StatsdOutput::new(config).unwrap();
+
+        output.update_stats(&message(DnsMessageType::Query, "example.com", 0));
+        assert!(output.flush_stats().is_err());
+        assert!(!output.pending.is_empty());
+
+        // 服务端可用后补发缓冲的指标
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        output.update_stats(&message(DnsMessageType::Response, "example.com", 3));
+        output.flush_stats().unwrap();
+        assert!(output.pending.is_empty());
+        let (mut conn, _) = listener.accept().unwrap();
+        read_metrics(&mut conn, &["dns.spider.messages.query:1|c", "dns.spider.rcode.nxdomain:1|c"]);
+
+        // 服务端断开连接后不会panic，后续刷新重新连接
+        drop(conn);
+        std::thread::sleep(Duration::from_millis(50));
+        for _ in 0..3 {
+            output.update_stats(&message(DnsMessageType::Query, "example.com", 0));
+            let _ = output.flush_stats();
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        let (mut conn, _) = listener.accept().unwrap();
+        read_metrics(&mut conn, &["dns.spider.messages.query:1|c"]);
+    }
+}
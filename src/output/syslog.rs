@@ -0,0 +1,350 @@
+//! Syslog输出实现
+//! 按RFC 5424格式化DNS消息：结构化数据携带常用检索字段，MSG为JSON事件；
+//! UDP每条消息一个数据报，TCP按RFC 6587八位组计数分帧
+
+use std::io::{Error, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::output::statsd::connect_tcp;
+use crate::output::{format_message_json_compact, truncate_event, Output, SyslogConfig, SyslogProtocol};
+use crate::protocols::dns::{rcode_name, DnsMessage, DnsMessageType};
+use crate::utils::time::utc_timestamp;
+
+/// 结构化数据ID，32473为文档示例用的私有企业号（RFC 5612）
+const SD_ID: &str = "dns@32473";
+/// 严重级别：informational
+const SEVERITY_INFO: u8 = 6;
+/// 最大设施值（local7）
+const MAX_FACILITY: u8 = 23;
+/// TCP发送（含重连）的尝试次数
+const TCP_SEND_ATTEMPTS: u32 = 2;
+/// TCP重试间隔（毫秒）
+const TCP_RETRY_DELAY_MS: u64 = 100;
+/// TCP连接失败后的退避时间，期间不尝试连接，消息直接丢弃
+const TCP_RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Syslog传输方式
+enum Transport {
+    Udp(UdpSocket),
+    /// 连接断开后为None，下次发送时重连
+    Tcp(Option<TcpStream>),
+}
+
+/// Syslog输出
+pub struct SyslogOutput {
+    /// 配置
+    config: SyslogConfig,
+    /// 传输方式
+    transport: Transport,
+    /// 进程ID，写入PROCID字段
+    pid: u32,
+    /// 退避结束时间，之前不尝试重连
+    backoff_until: Option<Instant>,
+    /// 退避期间丢弃的消息数，重连成功后报告
+    dropped: u64,
+    /// 消息体JSON事件的最大字节数
+    max_event_bytes: Option<usize>,
+}
+
+impl SyslogOutput {
+    /// 创建新的Syslog输出
+    pub fn new(config: SyslogConfig) -> Result<Self, String> {
+        if config.facility > MAX_FACILITY {
+            return Err(format!("Invalid syslog facility {}, expected 0-{}", config.facility, MAX_FACILITY));
+        }
+
+        let transport = match config.protocol {
+            SyslogProtocol::Udp => Transport::Udp(
+                UdpSocket::bind("0.0.0.0:0").map_err(|e| format!("Failed to bind UDP socket: {}", e))?,
+            ),
+            // 首次发送时再连接，Syslog服务器暂时不可用不影响启动
+            SyslogProtocol::Tcp => Transport::Tcp(None),
+        };
+
+        Ok(SyslogOutput {
+            config,
+            transport,
+            pid: std::process::id(),
+            backoff_until: None,
+            dropped: 0,
+            max_event_bytes: None,
+        })
+    }
+
+    /// 限制消息体JSON事件的长度，超出时截断并标记
+    pub fn with_max_event_bytes(mut self, max_event_bytes: Option<usize>) -> Self {
+        self.max_event_bytes = max_event_bytes;
+        self
+    }
+
+    /// Syslog服务器地址
+    fn addr(&self) -> String {
+        format!("{}:{}", self.config.host, self.config.port)
+    }
+
+    /// 格式化为RFC 5424消息：`<PRI>1 时间 主机 应用 进程ID 消息类型 [结构化数据] JSON`
+    fn format(&self, message: &DnsMessage) -> String {
+        let pri = self.config.facility as u16 * 8 + SEVERITY_INFO as u16;
+        let msgid = match message.message_type {
+            DnsMessageType::Query => "query",
+            DnsMessageType::Response => "response",
+        };
+
+        let mut sd = format!("[{}", SD_ID);
+        sd_param(&mut sd, "id", &message.transaction_id.to_string());
+        if let Some(question) = message.questions.first() {
+            sd_param(&mut sd, "qname", &question.name);
+            sd_param(&mut sd, "qtype", &question.record_type.to_string());
+        }
+        if message.message_type == DnsMessageType::Response {
+            sd_param(&mut sd, "rcode", &rcode_name(message.rcode));
+        }
+        sd_param(&mut sd, "src", &message.src_ip.to_string());
+        sd_param(&mut sd, "dst", &message.dst_ip.to_string());
+        sd.push(']');
+
+        format!(
+            "<{}>1 {} {} {} {} {} {} {}",
+            pri,
+            utc_timestamp(message.timestamp),
+            header_field(&self.config.hostname),
+            header_field(&self.config.app_name),
+            self.pid,
+            msgid,
+            sd,
+            // 截断后的事件以换行结尾，Syslog消息体保持单行
+            truncate_event(format_message_json_compact(message), self.max_event_bytes).trim_end()
+        )
+    }
+
+    /// 通过TCP发送一帧，未连接时先连接，写入失败时断开以便下次重连
+    fn write_tcp(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let addr = self.addr();
+        let stream = match &mut self.transport {
+            Transport::Tcp(stream) => stream,
+            Transport::Udp(_) => return Ok(()),
+        };
+
+        if stream.is_none() {
+            *stream = Some(connect_tcp(&addr)?);
+        }
+        let result = match stream.as_mut() {
+            Some(conn) => conn.write_all(frame),
+            None => Ok(()),
+        };
+        if result.is_err() {
+            *stream = None;
+        }
+        result
+    }
+
+    /// 发送一条Syslog消息
+    fn send(&mut self, line: &str) -> Result<(), Error> {
+        if let Transport::Udp(socket) = &self.transport {
+            socket.send_to(line.as_bytes(), self.addr())?;
+            return Ok(());
+        }
+
+        // 退避期内不连接，避免服务器不可用时每条消息都阻塞在连接超时上
+        if matches!(self.transport, Transport::Tcp(None))
+            && self.backoff_until.is_some_and(|until| Instant::now() < until)
+        {
+            self.dropped += 1;
+            return Ok(());
+        }
+
+        // 八位组计数分帧：`长度 消息`，消息内容无需转义换行
+        let frame = format!("{} {}", line.len(), line);
+        let result = crate::retry!(self.write_tcp(frame.as_bytes()), TCP_SEND_ATTEMPTS, TCP_RETRY_DELAY_MS);
+        match &result {
+            Ok(()) => {
+                if self.dropped > 0 {
+                    eprintln!("Syslog connection restored, dropped {} messages while reconnecting", self.dropped);
+                    self.dropped = 0;
+                }
+                self.backoff_until = None;
+            }
+            Err(_) => self.backoff_until = Some(Instant::now() + TCP_RECONNECT_BACKOFF),
+        }
+        result
+    }
+}
+
+/// 头部字段只允许可打印ASCII且不含空格，为空时使用NILVALUE
+fn header_field(value: &str) -> String {
+    let field: String = value.chars().filter(|c| c.is_ascii_graphic()).collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+/// 追加结构化数据参数，值中的`"`、`\`、`]`需要转义
+fn sd_param(sd: &mut String, name: &str, value: &str) {
+    sd.push(' ');
+    sd.push_str(name);
+    sd.push_str("=\"");
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            sd.push('\\');
+        }
+        sd.push(c);
+    }
+    sd.push('"');
+}
+
+impl Output for SyslogOutput {
+    fn output(&mut self, message: &DnsMessage) -> Result<(), String> {
+        let line = self.format(message);
+        self.send(&line)
+            .map_err(|e| format!("Failed to send syslog message: {}", e))
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        if let Transport::Tcp(Some(stream)) = &mut self.transport {
+            stream
+                .flush()
+                .map_err(|e| format!("Failed to flush syslog connection: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::dns::{DnsAnswer, DnsClass, DnsQuestion, DnsRecordType};
+    use std::io::Read;
+    use std::net::{IpAddr, Ipv4Addr, TcpListener};
+
+    #[test]
+    fn test_rfc5424_message_over_udp() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let config = SyslogConfig {
+            host: "127.0.0.1".to_string(),
+            port: receiver.local_addr().unwrap().port(),
+            hostname: "sensor 1".to_string(),
+            ..SyslogConfig::default()
+        };
+        let mut output = SyslogOutput::new(config).unwrap();
+
+        let message = DnsMessage {
+            transaction_id: 0x1234,
+            message_type: DnsMessageType::Response,
+            questions: vec![DnsQuestion {
+                name: "a]\"b.example.com".to_string(),
+                record_type: DnsRecordType::A,
+                class: DnsClass::IN,
+                unicast_response: false,
+                offset: None,
+            }],
+            timestamp: 1_700_000_000_123_456,
+            src_ip: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 53)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            src_port: 53,
+            dst_port: 40000,
+            rcode: 3,
+            recursion_desired: true,
+            recursion_available: true,
+            ..Default::default()
+        };
+        output.output(&message).unwrap();
+
+        let mut buf = [0u8; 4096];
+        let n = receiver.recv(&mut buf).unwrap();
+        let line = std::str::from_utf8(&buf[..n]).unwrap();
+
+        // local0.info = 16 * 8 + 6
+        let expected_header = format!(
+            "<134>1 2023-11-14T22:13:20.123456Z sensor1 dns_spider {} response \
+             [dns@32473 id=\"4660\" qname=\"a\\]\\\"b.example.com\" qtype=\"A\" rcode=\"NXDOMAIN\" \
+             src=\"192.0.2.53\" dst=\"10.0.0.1\"] ",
+            std::process::id()
+        );
+        assert!(line.starts_with(&expected_header), "{}", line);
+
+        let body: serde_json::Value = serde_json::from_str(&line[expected_header.len()..]).unwrap();
+        assert_eq!(body["transaction_id"], 0x1234);
+        assert_eq!(body["rcode"], "NXDOMAIN");
+
+        let invalid = SyslogConfig {
+            facility: 24,
+            ..SyslogConfig::default()
+        };
+        assert!(SyslogOutput::new(invalid).is_err());
+    }
+
+    #[test]
+    fn test_oversized_event_truncated() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let config = SyslogConfig {
+            host: "127.0.0.1".to_string(),
+            port: receiver.local_addr().unwrap().port(),
+            ..SyslogConfig::default()
+        };
+        let mut output = SyslogOutput::new(config).unwrap().with_max_event_bytes(Some(256));
+
+        let message = DnsMessage {
+            message_type: DnsMessageType::Response,
+            answers: vec![DnsAnswer {
+                name: "txt.example.com".to_string(),
+                record_type: DnsRecordType::TXT,
+                class: DnsClass::IN,
+                cache_flush: false,
+                ttl: 300,
+                data: Vec::new(),
+                data_str: "x".repeat(4000),
+            }],
+            ..Default::default()
+        };
+        output.output(&message).unwrap();
+
+        let mut buf = [0u8; 8192];
+        let n = receiver.recv(&mut buf).unwrap();
+        let line = std::str::from_utf8(&buf[..n]).unwrap();
+        let body = &line[line.find("] ").unwrap() + 2..];
+        assert!(body.len() <= 256, "{}", body);
+        assert!(!body.ends_with('\n'));
+
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["truncated"], true);
+        assert!(body["event"].as_str().unwrap().ends_with("...[truncated]"));
+    }
+
+    #[test]
+    fn test_tcp_backoff_drops_until_reconnect() {
+        // 绑定后立即关闭，连接该端口会被拒绝
+        let closed_port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let config = SyslogConfig {
+            host: "127.0.0.1".to_string(),
+            port: closed_port,
+            protocol: SyslogProtocol::Tcp,
+            ..SyslogConfig::default()
+        };
+        let mut output = SyslogOutput::new(config).unwrap();
+
+        assert!(output.send("first").is_err());
+        // 退避期内不再连接，消息直接丢弃
+        assert!(output.send("second").is_ok());
+        assert!(output.send("third").is_ok());
+        assert_eq!(output.dropped, 2);
+
+        // 退避结束后重连成功，丢弃计数清零
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        output.config.port = listener.local_addr().unwrap().port();
+        output.backoff_until = Some(Instant::now());
+        output.send("fourth").unwrap();
+        assert_eq!(output.dropped, 0);
+        assert!(output.backoff_until.is_none());
+
+        drop(output);
+        let (mut conn, _) = listener.accept().unwrap();
+        let mut received = String::new();
+        conn.read_to_string(&mut received).unwrap();
+        assert_eq!(received, "6 fourth");
+    }
+}
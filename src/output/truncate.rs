@@ -0,0 +1,97 @@
+//! 事件长度限制
+//! 超长TXT记录或应答列表会产生数KB的单条事件，超过日志采集的单行上限，
+//! 超限的JSON事件截断为带标记的JSON对象，CSV、控制台等文本直接截断并附加标记
+
+/// 截断标记，附加在保留的前缀之后
+pub const TRUNCATION_MARKER: &str = "...[truncated]";
+
+/// 将序列化后的事件限制在`max_bytes`字节内
+///
+/// 未超限时原样返回；超限时返回`{"truncated": true, "original_bytes": N, "event": "<前缀>...[truncated]"}`，
+/// 前缀按JSON字符串转义，保证结果仍是合法JSON且不超过限制（限制小于外壳本身时只保留外壳）。
+pub fn truncate_event(serialized: String, max_bytes: Option<usize>) -> String {
+    let max_bytes = match max_bytes {
+        Some(max_bytes) if serialized.len() > max_bytes => max_bytes,
+        _ => return serialized,
+    };
+
+    let head = format!(
+        "{{\"truncated\": true, \"original_bytes\": {}, \"event\": \"",
+        serialized.len()
+    );
+    let tail = format!("{}\"}}\n", TRUNCATION_MARKER);
+    let budget = max_bytes.saturating_sub(head.len() + tail.len());
+
+    let mut event = String::with_capacity(budget);
+    let mut escaped = String::new();
+    for c in serialized.chars() {
+        escaped.clear();
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+        if event.len() + escaped.len() > budget {
+            break;
+        }
+        event.push_str(&escaped);
+    }
+
+    format!("{}{}{}", head, event, tail)
+}
+
+/// 将非JSON文本限制在`max_bytes`字节内，超限时在字符边界截断并附加截断标记
+///
+/// 限制小于标记本身时只保留标记。
+pub fn truncate_text(mut text: String, max_bytes: Option<usize>) -> String {
+    let max_bytes = match max_bytes {
+        Some(max_bytes) if text.len() > max_bytes => max_bytes,
+        _ => return text,
+    };
+
+    let mut end = max_bytes.saturating_sub(TRUNCATION_MARKER.len());
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text.push_str(TRUNCATION_MARKER);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_over_limit_truncated() {
+        let txt = "x".repeat(4000);
+        let event = format!("{{\n  \"data\": \"{}\"\n}}\n", txt);
+
+        assert_eq!(truncate_event(event.clone(), None), event);
+        assert_eq!(truncate_event(event.clone(), Some(8192)), event);
+
+        let truncated = truncate_event(event.clone(), Some(256));
+        assert!(truncated.len() <= 256);
+        assert!(truncated.starts_with("{\"truncated\": true, \"original_bytes\": 4017,"));
+        assert!(truncated.contains("{\\n  \\\"data\\\": \\\"xxx"));
+        assert!(truncated.ends_with("...[truncated]\"}\n"));
+
+        // 限制小于外壳时只保留外壳
+        let truncated = truncate_event(event, Some(10));
+        assert!(truncated.contains("\"event\": \"...[truncated]\""));
+    }
+
+    #[test]
+    fn test_text_over_limit_truncated() {
+        assert_eq!(truncate_text("abc".to_string(), Some(3)), "abc");
+
+        // 不在多字节字符中间截断
+        let truncated = truncate_text(format!("ab{}", "域".repeat(10)), Some(20));
+        assert_eq!(truncated, "ab域...[truncated]");
+        assert!(truncate_text("x".repeat(100), Some(40)).len() <= 40);
+    }
+}
@@ -0,0 +1,616 @@
+//! 链路层解码
+//! 从以太网帧中剥离链路层、网络层和传输层头部，取出DNS负载
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::core::stats::StatsCounter;
+
+/// 以太网头长度
+const ETHERNET_HEADER_LEN: usize = 14;
+/// 802.1Q标签长度
+const VLAN_TAG_LEN: usize = 4;
+/// UDP头长度
+const UDP_HEADER_LEN: usize = 8;
+/// TCP最小头长度
+const TCP_MIN_HEADER_LEN: usize = 20;
+/// IPv6固定头长度
+const IPV6_HEADER_LEN: usize = 40;
+/// IPv6分片扩展头长度
+const IPV6_FRAGMENT_HEADER_LEN: usize = 8;
+/// 默认最大帧长度，与libpcap的最大快照长度一致，可容纳巨型帧和GRO/LRO合并后的段
+pub const DEFAULT_MAX_FRAME_LEN: usize = 262_144;
+/// ICMP/ICMPv6头长度（类型、代码、校验和及4字节未用字段）
+const ICMP_HEADER_LEN: usize = 8;
+
+pub(crate) const ETHERTYPE_IPV4: u16 = 0x0800;
+pub(crate) const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88A8;
+
+const IPPROTO_ICMP: u8 = 1;
+pub(crate) const IPPROTO_TCP: u8 = 6;
+pub(crate) const IPPROTO_UDP: u8 = 17;
+const IPPROTO_ICMPV6: u8 = 58;
+
+// 目的不可达类型
+const ICMP_DEST_UNREACHABLE: u8 = 3;
+const ICMPV6_DEST_UNREACHABLE: u8 = 1;
+
+// IPv6扩展头
+const IPV6_EXT_HOP_BY_HOP: u8 = 0;
+const IPV6_EXT_ROUTING: u8 = 43;
+const IPV6_EXT_FRAGMENT: u8 = 44;
+const IPV6_EXT_DEST_OPTS: u8 = 60;
+
+/// TCP标志位：FIN
+pub const TCP_FLAG_FIN: u8 = 0x01;
+/// TCP标志位：SYN
+pub const TCP_FLAG_SYN: u8 = 0x02;
+/// TCP标志位：RST
+pub const TCP_FLAG_RST: u8 = 0x04;
+
+/// 传输层协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+/// IPv6扩展头无法跳过的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Ipv6ExtError {
+    /// 扩展头被截断
+    Truncated,
+    /// 非原子分片，上层头部无法还原
+    Fragment,
+}
+
+/// 解码后的数据包
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedPacket<'a> {
+    /// 源IP地址
+    pub src_ip: IpAddr,
+    /// 目的IP地址
+    pub dst_ip: IpAddr,
+    /// 源端口
+    pub src_port: u16,
+    /// 目的端口
+    pub dst_port: u16,
+    /// 传输层协议
+    pub transport: Transport,
+    /// 传输层负载
+    pub payload: &'a [u8],
+    /// TCP标志位，UDP为0
+    pub tcp_flags: u8,
+    /// 取自ICMP目的不可达报文内嵌的原始数据报，地址和端口为原始数据报的方向
+    pub unreachable: bool,
+}
+
+/// 解码以太网帧，无法解码时返回None并计数
+pub fn decode_ethernet<'a>(frame: &'a [u8], stats: &StatsCounter) -> Option<DecodedPacket<'a>> {
+    decode_ethernet_with_max_len(frame, DEFAULT_MAX_FRAME_LEN, stats)
+}
+
+/// 解码以太网帧，超过`max_frame_len`的帧视为异常直接丢弃
+///
+/// 帧长度不受MTU限制，网络层按IP头中的长度字段截取，巨型帧和合并后的段均可正常解码。
+pub fn decode_ethernet_with_max_len<'a>(
+    frame: &'a [u8],
+    max_frame_len: usize,
+    stats: &StatsCounter,
+) -> Option<DecodedPacket<'a>> {
+    if frame.len() > max_frame_len {
+        stats.increment("decode.oversized_frame");
+        return None;
+    }
+
+    match ethernet_payload(frame) {
+        Some((ETHERTYPE_IPV4, packet)) => decode_ipv4(packet, stats),
+        Some((ETHERTYPE_IPV6, packet)) => decode_ipv6(packet, stats),
+        Some(_) => {
+            stats.increment("decode.unsupported_ethertype");
+            None
+        }
+        None => {
+            stats.increment("decode.truncated");
+            None
+        }
+    }
+}
+
+/// 跳过以太网头和VLAN标签，返回以太网类型和网络层数据，帧被截断时返回None
+pub(crate) fn ethernet_payload(frame: &[u8]) -> Option<(u16, &[u8])> {
+    if frame.len() < ETHERNET_HEADER_LEN {
+        return None;
+    }
+
+    let mut offset = 12;
+    let mut ethertype = u16::from_be_bytes([frame[offset], frame[offset + 1]]);
+    while ethertype == ETHERTYPE_VLAN || ethertype == ETHERTYPE_QINQ {
+        offset += VLAN_TAG_LEN;
+        if frame.len() < offset + 2 {
+            return None;
+        }
+        ethertype = u16::from_be_bytes([frame[offset], frame[offset + 1]]);
+    }
+    Some((ethertype, &frame[offset + 2..]))
+}
+
+/// 解码无链路层头的IP包（DLT_RAW等），按版本号区分IPv4/IPv6
+pub fn decode_raw_ip<'a>(
+    packet: &'a [u8],
+    max_frame_len: usize,
+    stats: &StatsCounter,
+) -> Option<DecodedPacket<'a>> {
+    if packet.len() > max_frame_len {
+        stats.increment("decode.oversized_frame");
+        return None;
+    }
+    match packet.first().map(|b| b >> 4) {
+        Some(4) => decode_ipv4(packet, stats),
+        Some(6) => decode_ipv6(packet, stats),
+        Some(_) => {
+            stats.increment("decode.unsupported_ip_version");
+            None
+        }
+        None => {
+            stats.increment("decode.truncated");
+            None
+        }
+    }
+}
+
+/// 解码IPv4包
+fn decode_ipv4<'a>(packet: &'a [u8], stats: &StatsCounter) -> Option<DecodedPacket<'a>> {
+    if packet.len() < 20 || packet[0] >> 4 != 4 {
+        stats.increment("decode.truncated");
+        return None;
+    }
+
+    let header_len = ((packet[0] & 0x0F) as usize) * 4;
+    let mut total_len = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+    // TSO发送路径上抓到的段总长度可能为0，此时以实际捕获长度为准
+    if total_len == 0 {
+        stats.increment("decode.ip_length_zero");
+        total_len = packet.len();
+    }
+    if header_len < 20 || total_len < header_len || total_len > packet.len() {
+        stats.increment("decode.truncated");
+        return None;
+    }
+
+    // 分片只处理首片之外无法还原，直接跳过
+    let fragment = u16::from_be_bytes([packet[6], packet[7]]);
+    if fragment & 0x3FFF != 0 {
+        stats.increment("decode.ip_fragment");
+        return None;
+    }
+
+    let src_ip = IpAddr::V4(Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]));
+    let dst_ip = IpAddr::V4(Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]));
+    // 按IP总长度截断，去掉以太网填充
+    let segment = &packet[header_len..total_len];
+
+    decode_transport(packet[9], segment, src_ip, dst_ip, stats)
+}
+
+/// 解码IPv6包，逐个跳过扩展头直到UDP/TCP
+fn decode_ipv6<'a>(packet: &'a [u8], stats: &StatsCounter) -> Option<DecodedPacket<'a>> {
+    if packet.len() < IPV6_HEADER_LEN || packet[0] >> 4 != 6 {
+        stats.increment("decode.truncated");
+        return None;
+    }
+
+    // 负载长度为0表示巨型帧（RFC 2675），不支持
+    let payload_len = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+    let total_len = IPV6_HEADER_LEN + payload_len;
+    if payload_len == 0 || total_len > packet.len() {
+        stats.increment("decode.truncated");
+        return None;
+    }
+
+    let mut src = [0u8; 16];
+    let mut dst = [0u8; 16];
+    src.copy_from_slice(&packet[8..24]);
+    dst.copy_from_slice(&packet[24..40]);
+    let src_ip = IpAddr::V6(Ipv6Addr::from(src));
+    let dst_ip = IpAddr::V6(Ipv6Addr::from(dst));
+
+    // 按负载长度截断，去掉以太网填充
+    let packet = &packet[..total_len];
+    let (next_header, offset) = match ipv6_upper_layer(packet) {
+        Ok(upper) => upper,
+        Err(Ipv6ExtError::Truncated) => {
+            stats.increment("decode.truncated");
+            return None;
+        }
+        Err(Ipv6ExtError::Fragment) => {
+            // 与IPv4一致，分片无法还原，直接跳过
+            stats.increment("decode.ip_fragment");
+            return None;
+        }
+    };
+
+    decode_transport(next_header, &packet[offset..], src_ip, dst_ip, stats)
+}
+
+/// 逐个跳过IPv6扩展头，返回上层协议号及其在包中的偏移，调用方需保证包含完整的固定头
+pub(crate) fn ipv6_upper_layer(packet: &[u8]) -> Result<(u8, usize), Ipv6ExtError> {
+    let mut next_header = packet[6];
+    let mut offset = IPV6_HEADER_LEN;
+
+    loop {
+        match next_header {
+            IPV6_EXT_HOP_BY_HOP | IPV6_EXT_ROUTING | IPV6_EXT_DEST_OPTS => {
+                if packet.len() < offset + 2 {
+                    return Err(Ipv6ExtError::Truncated);
+                }
+                let ext_len = (packet[offset + 1] as usize + 1) * 8;
+                if packet.len() < offset + ext_len {
+                    return Err(Ipv6ExtError::Truncated);
+                }
+                next_header = packet[offset];
+                offset += ext_len;
+            }
+            IPV6_EXT_FRAGMENT => {
+                if packet.len() < offset + IPV6_FRAGMENT_HEADER_LEN {
+                    return Err(Ipv6ExtError::Truncated);
+                }
+                let fragment = u16::from_be_bytes([packet[offset + 2], packet[offset + 3]]);
+                if fragment & 0xFFF9 != 0 {
+                    return Err(Ipv6ExtError::Fragment);
+                }
+                next_header = packet[offset];
+                offset += IPV6_FRAGMENT_HEADER_LEN;
+            }
+            _ => return Ok((next_header, offset)),
+        }
+    }
+}
+
+/// 按协议号解码传输层
+fn decode_transport<'a>(
+    protocol: u8,
+    segment: &'a [u8],
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    stats: &StatsCounter,
+) -> Option<DecodedPacket<'a>> {
+    match protocol {
+        IPPROTO_UDP => decode_udp(segment, src_ip, dst_ip, stats),
+        IPPROTO_TCP => decode_tcp(segment, src_ip, dst_ip, stats),
+        IPPROTO_ICMP => decode_icmp(segment, ICMP_DEST_UNREACHABLE, stats),
+        IPPROTO_ICMPV6 => decode_icmp(segment, ICMPV6_DEST_UNREACHABLE, stats),
+        _ => {
+            stats.increment("decode.unsupported_protocol");
+            None
+        }
+    }
+}
+
+/// 解码ICMP/ICMPv6目的不可达报文中内嵌的原始UDP数据报
+///
+/// 服务器不可用时客户端会收到端口不可达，内嵌数据报即原始DNS查询。
+/// 其他ICMP类型以及内嵌的非UDP数据报不处理。
+fn decode_icmp<'a>(
+    segment: &'a [u8],
+    unreachable_type: u8,
+    stats: &StatsCounter,
+) -> Option<DecodedPacket<'a>> {
+    if segment.len() < ICMP_HEADER_LEN {
+        stats.increment("decode.truncated");
+        return None;
+    }
+    if segment[0] != unreachable_type {
+        stats.increment("decode.icmp_ignored");
+        return None;
+    }
+
+    let embedded = &segment[ICMP_HEADER_LEN..];
+    let decoded = match embedded.first().map(|b| b >> 4) {
+        Some(4) => decode_ipv4(embedded, stats),
+        Some(6) => decode_ipv6(embedded, stats),
+        _ => {
+            stats.increment("decode.truncated");
+            None
+        }
+    }?;
+
+    if decoded.transport != Transport::Udp || decoded.unreachable {
+        stats.increment("decode.icmp_ignored");
+        return None;
+    }
+
+    stats.increment("decode.icmp_unreachable");
+    Some(DecodedPacket {
+        unreachable: true,
+        ..decoded
+    })
+}
+
+/// 解码UDP数据报
+///
+/// UDP长度字段大于实际可用字节时拒绝，避免解析器读到不属于该数据报的数据；
+/// 小于可用字节时按长度字段截断。
+fn decode_udp<'a>(
+    segment: &'a [u8],
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    stats: &StatsCounter,
+) -> Option<DecodedPacket<'a>> {
+    if segment.len() < UDP_HEADER_LEN {
+        stats.increment("decode.truncated");
+        return None;
+    }
+
+    let udp_len = u16::from_be_bytes([segment[4], segment[5]]) as usize;
+    if udp_len < UDP_HEADER_LEN || udp_len > segment.len() {
+        stats.increment("decode.udp_length_mismatch");
+        return None;
+    }
+
+    Some(DecodedPacket {
+        src_ip,
+        dst_ip,
+        src_port: u16::from_be_bytes([segment[0], segment[1]]),
+        dst_port: u16::from_be_bytes([segment[2], segment[3]]),
+        transport: Transport::Udp,
+        payload: &segment[UDP_HEADER_LEN..udp_len],
+        tcp_flags: 0,
+        unreachable: false,
+    })
+}
+
+/// 解码TCP段
+fn decode_tcp<'a>(
+    segment: &'a [u8],
+    src_ip: IpAddr,
+    dst_ip: IpAddr,
+    stats: &StatsCounter,
+) -> Option<DecodedPacket<'a>> {
+    if segment.len() < TCP_MIN_HEADER_LEN {
+        stats.increment("decode.truncated");
+        return None;
+    }
+
+    let header_len = ((segment[12] >> 4) as usize) * 4;
+    if header_len < TCP_MIN_HEADER_LEN || header_len > segment.len() {
+        stats.increment("decode.truncated");
+        return None;
+    }
+
+    Some(DecodedPacket {
+        src_ip,
+        dst_ip,
+        src_port: u16::from_be_bytes([segment[0], segment[1]]),
+        dst_port: u16::from_be_bytes([segment[2], segment[3]]),
+        transport: Transport::Tcp,
+        payload: &segment[header_len..],
+        tcp_flags: segment[13],
+        unreachable: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造以太网+IPv4+UDP帧，udp_len为UDP头中声明的长度
+    fn build_udp_frame(payload: &[u8], udp_len: u16, padding: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+
+        let total_len = (20 + UDP_HEADER_LEN + payload.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0x00]);
+        frame.extend_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, IPPROTO_UDP, 0x00, 0x00]);
+        frame.extend_from_slice(&[10, 0, 0, 1]);
+        frame.extend_from_slice(&[10, 0, 0, 53]);
+
+        frame.extend_from_slice(&40000u16.to_be_bytes());
+        frame.extend_from_slice(&53u16.to_be_bytes());
+        frame.extend_from_slice(&udp_len.to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00]);
+        frame.extend_from_slice(payload);
+        frame.extend(std::iter::repeat(0).take(padding));
+        frame
+    }
+
+    #[test]
+    fn test_decode_udp() {
+        let payload = [0xAB; 20];
+        // 以太网最小帧填充不应进入负载
+        let frame = build_udp_frame(&payload, 28, 6);
+        let stats = StatsCounter::new();
+
+        let packet = decode_ethernet(&frame, &stats).unwrap();
+        assert_eq!(packet.src_ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(packet.dst_ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)));
+        assert_eq!((packet.src_port, packet.dst_port), (40000, 53));
+        assert_eq!(packet.transport, Transport::Udp);
+        assert_eq!(packet.payload, &payload[..]);
+    }
+
+    #[test]
+    fn test_decode_raw_ip() {
+        let payload = [0xAB; 20];
+        let frame = build_udp_frame(&payload, 28, 0);
+        let stats = StatsCounter::new();
+
+        // 去掉以太网头即为DLT_RAW的数据包
+        let packet = decode_raw_ip(&frame[ETHERNET_HEADER_LEN..], DEFAULT_MAX_FRAME_LEN, &stats).unwrap();
+        assert_eq!(packet.dst_ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)));
+        assert_eq!(packet.payload, &payload[..]);
+
+        assert!(decode_raw_ip(&[0x50; 28], DEFAULT_MAX_FRAME_LEN, &stats).is_none());
+        assert_eq!(stats.get("decode.unsupported_ip_version"), 1);
+    }
+
+    /// 构造以太网+IPv6+扩展头+UDP帧，extensions为(扩展头类型, 扩展头内容)
+    fn build_ipv6_udp_frame(payload: &[u8], extensions: &[(u8, Vec<u8>)]) -> Vec<u8> {
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+
+        let ext_len: usize = extensions.iter().map(|(_, ext)| ext.len()).sum();
+        let payload_len = (ext_len + UDP_HEADER_LEN + payload.len()) as u16;
+        let first_header = extensions.first().map(|(kind, _)| *kind).unwrap_or(IPPROTO_UDP);
+        frame.extend_from_slice(&[0x60, 0x00, 0x00, 0x00]);
+        frame.extend_from_slice(&payload_len.to_be_bytes());
+        frame.extend_from_slice(&[first_header, 64]);
+        frame.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        frame.extend_from_slice(&"2001:db8::53".parse::<Ipv6Addr>().unwrap().octets());
+
+        for (i, (_, ext)) in extensions.iter().enumerate() {
+            let next = extensions.get(i + 1).map(|(kind, _)| *kind).unwrap_or(IPPROTO_UDP);
+            frame.push(next);
+            frame.extend_from_slice(&ext[1..]);
+        }
+
+        let udp_len = (UDP_HEADER_LEN + payload.len()) as u16;
+        frame.extend_from_slice(&40000u16.to_be_bytes());
+        frame.extend_from_slice(&53u16.to_be_bytes());
+        frame.extend_from_slice(&udp_len.to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00]);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn test_decode_ipv6_udp_with_extension_headers() {
+        let payload = [0xCD; 12];
+        // 逐跳选项头（8字节）、目的选项头（16字节）、非分片的分片头
+        let extensions = vec![
+            (IPV6_EXT_HOP_BY_HOP, vec![0, 0, 1, 4, 0, 0, 0, 0]),
+            (IPV6_EXT_DEST_OPTS, {
+                let mut ext = vec![0, 1, 1, 12];
+                ext.extend_from_slice(&[0; 12]);
+                ext
+            }),
+            (IPV6_EXT_FRAGMENT, vec![0, 0, 0, 0, 0, 0, 0, 1]),
+        ];
+        let frame = build_ipv6_udp_frame(&payload, &extensions);
+        let stats = StatsCounter::new();
+
+        let packet = decode_ethernet(&frame, &stats).unwrap();
+        assert_eq!(packet.src_ip, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(packet.dst_ip, "2001:db8::53".parse::<IpAddr>().unwrap());
+        assert_eq!((packet.src_port, packet.dst_port), (40000, 53));
+        assert_eq!(packet.transport, Transport::Udp);
+        assert_eq!(packet.payload, &payload[..]);
+
+        let plain = build_ipv6_udp_frame(&payload, &[]);
+        assert_eq!(decode_ethernet(&plain, &stats).unwrap().payload, &payload[..]);
+    }
+
+    #[test]
+    fn test_ipv6_fragment_skipped() {
+        // 分片偏移非0
+        let extensions = vec![(IPV6_EXT_FRAGMENT, vec![0, 0, 0x00, 0x08, 0, 0, 0, 1])];
+        let frame = build_ipv6_udp_frame(&[0xCD; 12], &extensions);
+        let stats = StatsCounter::new();
+
+        assert!(decode_ethernet(&frame, &stats).is_none());
+        assert_eq!(stats.get("decode.ip_fragment"), 1);
+
+        // 扩展头长度超出包长
+        let mut frame = build_ipv6_udp_frame(&[0xCD; 12], &[(IPV6_EXT_ROUTING, vec![0, 0, 0, 0, 0, 0, 0, 0])]);
+        frame[14 + IPV6_HEADER_LEN + 1] = 10;
+        assert!(decode_ethernet(&frame, &stats).is_none());
+        assert_eq!(stats.get("decode.truncated"), 1);
+    }
+
+    #[test]
+    fn test_icmp_port_unreachable_carries_dns_query() {
+        // 原始查询：10.0.0.1:40000 -> 10.0.0.53:53
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        query.extend_from_slice(b"\x07example\x03com\x00\x00\x01\x00\x01");
+        let original = build_udp_frame(&query, (UDP_HEADER_LEN + query.len()) as u16, 0);
+
+        // 10.0.0.53 -> 10.0.0.1 ICMP类型3代码3，内嵌原始IP数据报
+        let embedded = &original[ETHERNET_HEADER_LEN..];
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        let total_len = (20 + ICMP_HEADER_LEN + embedded.len()) as u16;
+        frame.extend_from_slice(&[0x45, 0x00]);
+        frame.extend_from_slice(&total_len.to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, IPPROTO_ICMP, 0x00, 0x00]);
+        frame.extend_from_slice(&[10, 0, 0, 53]);
+        frame.extend_from_slice(&[10, 0, 0, 1]);
+        frame.extend_from_slice(&[ICMP_DEST_UNREACHABLE, 3, 0, 0, 0, 0, 0, 0]);
+        frame.extend_from_slice(embedded);
+        let stats = StatsCounter::new();
+
+        let packet = decode_ethernet(&frame, &stats).unwrap();
+        assert!(packet.unreachable);
+        assert_eq!(packet.src_ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(packet.dst_ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)));
+        assert_eq!((packet.src_port, packet.dst_port), (40000, 53));
+        assert_eq!(packet.payload, &query[..]);
+        assert_eq!(stats.get("decode.icmp_unreachable"), 1);
+
+        // 其他ICMP类型（回显请求）忽略
+        frame[14 + 20] = 8;
+        assert!(decode_ethernet(&frame, &stats).is_none());
+        assert_eq!(stats.get("decode.icmp_ignored"), 1);
+    }
+
+    #[test]
+    fn test_jumbo_frame_with_large_tcp_segment() {
+        // 9000字节巨型帧：以太网+IPv4+TCP，负载为带长度前缀的DNS报文
+        let frame_len = 9000;
+        let payload_len = frame_len - ETHERNET_HEADER_LEN - 20 - TCP_MIN_HEADER_LEN;
+        let mut payload = ((payload_len - 2) as u16).to_be_bytes().to_vec();
+        payload.extend_from_slice(&[0x12, 0x34, 0x81, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        payload.resize(payload_len, 0xAB);
+
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend_from_slice(&[0x45, 0x00]);
+        frame.extend_from_slice(&((frame_len - ETHERNET_HEADER_LEN) as u16).to_be_bytes());
+        frame.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, IPPROTO_TCP, 0x00, 0x00]);
+        frame.extend_from_slice(&[10, 0, 0, 53]);
+        frame.extend_from_slice(&[10, 0, 0, 1]);
+        frame.extend_from_slice(&53u16.to_be_bytes());
+        frame.extend_from_slice(&40000u16.to_be_bytes());
+        frame.extend_from_slice(&[0; 8]);
+        frame.extend_from_slice(&[0x50, 0x18, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00]);
+        frame.extend_from_slice(&payload);
+        assert_eq!(frame.len(), frame_len);
+        let stats = StatsCounter::new();
+
+        let packet = decode_ethernet(&frame, &stats).unwrap();
+        assert_eq!(packet.transport, Transport::Tcp);
+        assert_eq!(packet.payload, &payload[..]);
+        assert_eq!(packet.tcp_flags, 0x18);
+
+        // TSO段的IP总长度为0时按捕获长度解码
+        let mut tso = frame.clone();
+        tso[16] = 0;
+        tso[17] = 0;
+        assert_eq!(decode_ethernet(&tso, &stats).unwrap().payload.len(), payload_len);
+        assert_eq!(stats.get("decode.ip_length_zero"), 1);
+
+        // 超过配置的最大帧长度时丢弃
+        assert!(decode_ethernet_with_max_len(&frame, 1514, &stats).is_none());
+        assert_eq!(stats.get("decode.oversized_frame"), 1);
+    }
+
+    #[test]
+    fn test_udp_length_exceeds_capture() {
+        let frame = build_udp_frame(&[0xAB; 20], 200, 0);
+        let stats = StatsCounter::new();
+
+        assert!(decode_ethernet(&frame, &stats).is_none());
+        assert_eq!(stats.get("decode.udp_length_mismatch"), 1);
+
+        // 长度字段小于UDP头同样拒绝
+        let frame = build_udp_frame(&[0xAB; 20], 4, 0);
+        assert!(decode_ethernet(&frame, &stats).is_none());
+        assert_eq!(stats.get("decode.udp_length_mismatch"), 2);
+
+        // 长度字段小于可用字节时截断
+        let frame = build_udp_frame(&[0xAB; 20], 18, 0);
+        let packet = decode_ethernet(&frame, &stats).unwrap();
+        assert_eq!(packet.payload.len(), 10);
+    }
+}
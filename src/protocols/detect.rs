@@ -1,7 +1,9 @@
 //! 协议检测器
 //! 用于识别不同类型的DNS协议
 
-use crate::protocols::dns::{DnsParser, DnsProtocol};
+use crate::protocols::decode::Transport;
+use crate::protocols::dns::{looks_like_doh, DnsParser, DnsProtocol};
+use crate::protocols::tls::is_tls_record;
 
 /// 协议检测结果
 pub enum ProtocolDetectResult {
@@ -17,6 +19,13 @@ pub struct ProtocolDetector {
     dot_ports: Vec<u16>,
     doh_ports: Vec<u16>,
     doq_ports: Vec<u16>,
+    mdns_ports: Vec<u16>,
+}
+
+impl Default for ProtocolDetector {
+    fn default() -> Self {
+        ProtocolDetector::new()
+    }
 }
 
 impl ProtocolDetector {
@@ -27,6 +36,7 @@ impl ProtocolDetector {
             dot_ports: vec![853],
             doh_ports: vec![443],
             doq_ports: vec![853, 8853],
+            mdns_ports: vec![5353],
         }
     }
 
@@ -54,6 +64,12 @@ impl ProtocolDetector {
         self
     }
 
+    /// 自定义mDNS端口
+    pub fn with_mdns_ports(mut self, ports: Vec<u16>) -> Self {
+        self.mdns_ports = ports;
+        self
+    }
+
     /// 检测数据包所属的协议类型
     /// 
     /// # 参数
@@ -61,43 +77,99 @@ impl ProtocolDetector {
     /// * `data` - 数据包内容
     /// * `src_port` - 源端口
     /// * `dst_port` - 目标端口
+    /// * `transport` - 传输层协议，DoT与DoQ共用853端口，需按传输层区分
     /// 
     /// # 返回值
     /// 
     /// 返回检测结果，可能是已知协议、未知协议或需要更多数据
-    pub fn detect(&self, data: &[u8], src_port: u16, dst_port: u16) -> ProtocolDetectResult {
-        // 检查是否是标准DNS协议
-        if self.dns_ports.contains(&src_port) || self.dns_ports.contains(&dst_port) {
-            return ProtocolDetectResult::Dns(DnsProtocol::Udp); // 或其它合适的类型
+    pub fn detect(&self, data: &[u8], src_port: u16, dst_port: u16, transport: Transport) -> ProtocolDetectResult {
+        let matches = |ports: &[u16]| ports.contains(&src_port) || ports.contains(&dst_port);
+
+        // mDNS与DNS报文格式相同，单独标记以便输出区分服务发现流量
+        if transport == Transport::Udp && matches(&self.mdns_ports) {
+            return ProtocolDetectResult::Dns(DnsProtocol::Mdns);
         }
 
-        // 检查是否是DoT协议
-        if self.dot_ports.contains(&src_port) || self.dot_ports.contains(&dst_port) {
-            // DoT协议检测逻辑
-            // 由于DoT是基于TLS的，这里需要更复杂的TLS解析
-            // 简单实现可以先返回需要更多数据
-            return ProtocolDetectResult::NeedMoreData;
+        // 检查是否是标准DNS协议
+        if matches(&self.dns_ports) {
+            return match transport {
+                Transport::Udp => ProtocolDetectResult::Dns(DnsProtocol::Udp),
+                Transport::Tcp => ProtocolDetectResult::Dns(DnsProtocol::Tcp),
+            };
         }
 
-        // 检查是否是DoH协议
-        if self.doh_ports.contains(&src_port) || self.doh_ports.contains(&dst_port) {
-            // DoH协议检测逻辑
-            // 由于DoH是基于HTTP的，这里需要HTTP解析
-            // 简单实现可以先返回需要更多数据
-            return ProtocolDetectResult::NeedMoreData;
+        match transport {
+            Transport::Tcp => {
+                // DoT端口上只有以TLS记录开始的数据才确认为DoT，其他协议复用该端口时不交给DoT解析器
+                if matches(&self.dot_ports) {
+                    return if is_tls_record(data) {
+                        ProtocolDetectResult::Dns(DnsProtocol::Dot)
+                    } else if data.is_empty() {
+                        ProtocolDetectResult::NeedMoreData
+                    } else {
+                        ProtocolDetectResult::Unknown
+                    };
+                }
+
+                // DoH与普通HTTPS共用端口，只有看到HTTP/2连接前言或DoH请求、响应特征才确认；
+                // 其余数据段是否属于已确认的DoH会话由调用方按会话表判断
+                if matches(&self.doh_ports) {
+                    return if looks_like_doh(data) {
+                        ProtocolDetectResult::Dns(DnsProtocol::Doh)
+                    } else {
+                        ProtocolDetectResult::NeedMoreData
+                    };
+                }
+
+                // TCP流没有DNS长度前缀特征，非DNS端口不做猜测
+                ProtocolDetectResult::Unknown
+            }
+            Transport::Udp => {
+                // DoQ使用专用端口，按端口交给DoQ解析器
+                if matches(&self.doq_ports) {
+                    return ProtocolDetectResult::Dns(DnsProtocol::Doq);
+                }
+
+                // 非DNS端口只有内容像DNS报文时才交给解析器
+                if looks_like_dns(data) {
+                    ProtocolDetectResult::Dns(DnsProtocol::Udp)
+                } else {
+                    ProtocolDetectResult::Unknown
+                }
+            }
         }
+    }
+
+    /// 根据端口配置生成BPF过滤表达式，使抓包只接收DNS相关流量
+    ///
+    /// 标准DNS同时监听UDP和TCP，DoT/DoH走TCP，DoQ和mDNS走UDP。
+    pub fn bpf_filter(&self) -> String {
+        let mut clauses: Vec<String> = Vec::new();
+        let mut push = |proto: &str, port: u16| {
+            let clause = format!("{} port {}", proto, port);
+            if !clauses.contains(&clause) {
+                clauses.push(clause);
+            }
+        };
 
-        // 检查是否是DoQ协议
-        if self.doq_ports.contains(&src_port) || self.doq_ports.contains(&dst_port) {
-            // DoQ协议检测逻辑
-            // 由于DoQ是基于QUIC的，这里需要QUIC解析
-            // 简单实现可以先返回需要更多数据
-            return ProtocolDetectResult::NeedMoreData;
+        for &port in &self.dns_ports {
+            push("udp", port);
+            push("tcp", port);
+        }
+        for &port in &self.dot_ports {
+            push("tcp", port);
+        }
+        for &port in &self.doh_ports {
+            push("tcp", port);
+        }
+        for &port in &self.doq_ports {
+            push("udp", port);
+        }
+        for &port in &self.mdns_ports {
+            push("udp", port);
         }
 
-        // 尝试通用DNS检测
-        // 直接返回 ProtocolDetectResult::Dns(DnsProtocol::Udp) 即可
-        ProtocolDetectResult::Dns(DnsProtocol::Udp)
+        clauses.join(" or ")
     }
 
     /// 判断端口是否为DNS相关端口
@@ -105,8 +177,59 @@ impl ProtocolDetector {
         self.dns_ports.contains(&port) || 
         self.dot_ports.contains(&port) || 
         self.doh_ports.contains(&port) || 
-        self.doq_ports.contains(&port)
+        self.doq_ports.contains(&port) || 
+        self.mdns_ports.contains(&port)
+    }
+}
+
+/// 按DNS头部和第一个问题名做合理性检查
+///
+/// 操作码须为已分配的值，查询的响应码须为0，各部分记录数不能超过报文长度所能容纳的数量，
+/// 有问题时第一个问题名须能按标签格式走到结尾并留有类型和类。
+fn looks_like_dns(data: &[u8]) -> bool {
+    if data.len() < 12 {
+        return false;
+    }
+
+    let flags = u16::from_be_bytes([data[2], data[3]]);
+    let is_response = flags & 0x8000 != 0;
+    let opcode = (flags >> 11) & 0x0F;
+    // QUERY、IQUERY、STATUS、NOTIFY、UPDATE、DSO
+    if !matches!(opcode, 0 | 1 | 2 | 4 | 5 | 6) {
+        return false;
+    }
+    if !is_response && flags & 0x000F != 0 {
+        return false;
     }
+
+    let count = |at: usize| u16::from_be_bytes([data[at], data[at + 1]]) as usize;
+    let questions = count(4);
+    let records = count(6) + count(8) + count(10);
+    // 问题至少5字节（根域名加类型和类），记录至少11字节
+    if questions * 5 + records * 11 > data.len() - 12 {
+        return false;
+    }
+    if questions == 0 {
+        return records > 0 || is_response;
+    }
+
+    // 第一个问题名：标签长度不超过63，以0结尾或以压缩指针结束
+    let mut pos = 12;
+    loop {
+        match data.get(pos) {
+            Some(0) => {
+                pos += 1;
+                break;
+            }
+            Some(&len) if len & 0xC0 == 0xC0 => {
+                pos += 2;
+                break;
+            }
+            Some(&len) if len <= 63 => pos += 1 + len as usize,
+            _ => return false,
+        }
+    }
+    pos + 4 <= data.len()
 }
 
 #[cfg(test)]
@@ -120,6 +243,7 @@ mod tests {
         assert!(detector.dot_ports.contains(&853));
         assert!(detector.doh_ports.contains(&443));
         assert!(detector.doq_ports.contains(&853));
+        assert!(detector.mdns_ports.contains(&5353));
     }
 
     #[test]
@@ -128,12 +252,125 @@ mod tests {
             .with_dns_ports(vec![5353])
             .with_dot_ports(vec![8853])
             .with_doh_ports(vec![8443])
-            .with_doq_ports(vec![8853, 9853]);
+            .with_doq_ports(vec![8853, 9853])
+            .with_mdns_ports(vec![5354]);
         
         assert!(detector.dns_ports.contains(&5353));
         assert!(detector.dot_ports.contains(&8853));
         assert!(detector.doh_ports.contains(&8443));
         assert!(detector.doq_ports.contains(&9853));
+        assert!(detector.mdns_ports.contains(&5354));
+    }
+
+    #[test]
+    fn test_bpf_filter() {
+        let detector = ProtocolDetector::new();
+        assert_eq!(
+            detector.bpf_filter(),
+            "udp port 53 or tcp port 53 or tcp port 853 or tcp port 443 or udp port 853 or udp port 8853 or udp port 5353"
+        );
+
+        let detector = ProtocolDetector::new()
+            .with_dns_ports(vec![53, 5353])
+            .with_dot_ports(vec![])
+            .with_doh_ports(vec![8443])
+            .with_doq_ports(vec![]);
+        assert_eq!(
+            detector.bpf_filter(),
+            "udp port 53 or tcp port 53 or udp port 5353 or tcp port 5353 or tcp port 8443"
+        );
+    }
+
+    #[test]
+    fn test_detect_by_transport() {
+        let detector = ProtocolDetector::new();
+        assert!(matches!(
+            detector.detect(&[], 40000, 53, Transport::Udp),
+            ProtocolDetectResult::Dns(DnsProtocol::Udp)
+        ));
+        assert!(matches!(
+            detector.detect(&[], 53, 40000, Transport::Tcp),
+            ProtocolDetectResult::Dns(DnsProtocol::Tcp)
+        ));
+        // 853端口按传输层区分DoT和DoQ，TCP上须是TLS记录
+        let client_hello = [0x16, 0x03, 0x01, 0x00, 0x20, 0x01, 0x00, 0x00, 0x1C];
+        assert!(matches!(
+            detector.detect(&client_hello, 40000, 853, Transport::Tcp),
+            ProtocolDetectResult::Dns(DnsProtocol::Dot)
+        ));
+        assert!(matches!(
+            detector.detect(&[], 40000, 853, Transport::Tcp),
+            ProtocolDetectResult::NeedMoreData
+        ));
+        assert!(matches!(
+            detector.detect(b"SSH-2.0-OpenSSH_9.6\r\n", 40000, 853, Transport::Tcp),
+            ProtocolDetectResult::Unknown
+        ));
+        assert!(matches!(
+            detector.detect(&[], 853, 40000, Transport::Udp),
+            ProtocolDetectResult::Dns(DnsProtocol::Doq)
+        ));
+        // mDNS只走UDP
+        assert!(matches!(
+            detector.detect(&[], 5353, 5353, Transport::Udp),
+            ProtocolDetectResult::Dns(DnsProtocol::Mdns)
+        ));
+        assert!(matches!(
+            detector.detect(&[], 40000, 5353, Transport::Tcp),
+            ProtocolDetectResult::Unknown
+        ));
+        // 443端口按HTTP内容确认DoH
+        assert!(matches!(
+            detector.detect(b"POST /dns-query HTTP/1.1\r\n", 40000, 443, Transport::Tcp),
+            ProtocolDetectResult::Dns(DnsProtocol::Doh)
+        ));
+        assert!(matches!(
+            detector.detect(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n", 40000, 443, Transport::Tcp),
+            ProtocolDetectResult::Dns(DnsProtocol::Doh)
+        ));
+        assert!(matches!(
+            detector.detect(&[0x17, 0x03, 0x03, 0x00, 0x20], 40000, 443, Transport::Tcp),
+            ProtocolDetectResult::NeedMoreData
+        ));
+        assert!(matches!(
+            detector.detect(&[], 40000, 80, Transport::Tcp),
+            ProtocolDetectResult::Unknown
+        ));
+    }
+
+    #[test]
+    fn test_non_dns_payload_on_other_ports_is_unknown() {
+        let detector = ProtocolDetector::new();
+
+        // 非DNS端口上的DNS查询仍按内容识别
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        query.extend_from_slice(b"\x07example\x03com\x00\x00\x01\x00\x01");
+        assert!(matches!(
+            detector.detect(&query, 40000, 5300, Transport::Udp),
+            ProtocolDetectResult::Dns(DnsProtocol::Udp)
+        ));
+
+        let ntp = {
+            let mut packet = vec![0x23, 0x00, 0x06, 0x20];
+            packet.resize(48, 0);
+            packet
+        };
+        let tls_record = [0x17, 0x03, 0x03, 0x00, 0x20, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF, 0x00, 0x11];
+        for payload in [
+            &b""[..],
+            &b"\x00\x01"[..],
+            &b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"[..],
+            &b"M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\n\r\n"[..],
+            &ntp[..],
+            &tls_record[..],
+            &[0xFF; 64][..],
+        ] {
+            assert!(
+                matches!(detector.detect(payload, 40000, 1900, Transport::Udp), ProtocolDetectResult::Unknown),
+                "{:?} detected as DNS",
+                payload
+            );
+        }
     }
 
     #[test]
@@ -142,6 +379,7 @@ mod tests {
         assert!(detector.is_dns_related_port(53));
         assert!(detector.is_dns_related_port(853));
         assert!(detector.is_dns_related_port(443));
+        assert!(detector.is_dns_related_port(5353));
         assert!(!detector.is_dns_related_port(80));
     }
 }
\ No newline at end of file
@@ -0,0 +1,191 @@
+//! DNS-SD服务发现解析
+//! mDNS/LLMNR上的PTR记录：`_services._dns-sd._udp.<域>`枚举服务类型，
+//! `_<服务>._tcp.<域>`或`_<服务>._udp.<域>`列出服务实例，可用于局域网资产发现
+
+use std::fmt;
+
+use crate::protocols::dns::{DnsMessage, DnsRecordType};
+
+/// 服务类型枚举名（RFC 6763 第9节）
+const SERVICES_ENUMERATION: &str = "_services._dns-sd._udp";
+
+/// 从PTR记录中识别出的服务发现信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DnsSdRecord {
+    /// 枚举到的服务类型，如`_ipp._tcp`
+    ServiceType { service: String, domain: String },
+    /// 服务实例，如`Office Printer`提供`_ipp._tcp`服务
+    Instance {
+        instance: String,
+        service: String,
+        domain: String,
+    },
+}
+
+impl fmt::Display for DnsSdRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsSdRecord::ServiceType { service, domain } => write!(f, "服务类型 {}.{}", service, domain),
+            DnsSdRecord::Instance {
+                instance,
+                service,
+                domain,
+            } => write!(f, "{} ({}.{})", instance, service, domain),
+        }
+    }
+}
+
+/// 是否为服务类型枚举查询名
+pub fn is_service_enumeration(name: &str) -> bool {
+    split_enumeration(name).is_some()
+}
+
+/// 从消息的应答和附加部分提取服务发现信息，重复的条目只保留一次
+pub fn dnssd_records(message: &DnsMessage) -> Vec<DnsSdRecord> {
+    let mut records: Vec<DnsSdRecord> = Vec::new();
+    let ptrs = message
+        .answers
+        .iter()
+        .chain(&message.additionals)
+        .filter(|record| record.record_type == DnsRecordType::PTR);
+
+    for ptr in ptrs {
+        let owner = ptr.name.trim_end_matches('.');
+        let target = ptr.data_str.trim_end_matches('.');
+
+        let record = if split_enumeration(owner).is_some() {
+            split_service_type(target).map(|(service, domain)| DnsSdRecord::ServiceType {
+                service: service.to_string(),
+                domain: domain.to_string(),
+            })
+        } else if let Some((service, domain)) = split_service_type(owner) {
+            // 实例名为目标去掉`.<服务>.<域>`后缀的部分，可以包含点和空格
+            let suffix_len = owner.len() + 1;
+            let instance = target
+                .len()
+                .checked_sub(suffix_len)
+                .filter(|&end| end > 0 && target.is_char_boundary(end))
+                .filter(|&end| target[end..].eq_ignore_ascii_case(&format!(".{}", owner)))
+                .map(|end| &target[..end]);
+            instance.map(|instance| DnsSdRecord::Instance {
+                instance: instance.to_string(),
+                service: service.to_string(),
+                domain: domain.to_string(),
+            })
+        } else {
+            None
+        };
+
+        if let Some(record) = record {
+            if !records.contains(&record) {
+                records.push(record);
+            }
+        }
+    }
+    records
+}
+
+/// 拆分服务类型枚举名，返回域
+fn split_enumeration(name: &str) -> Option<&str> {
+    let name = name.trim_end_matches('.');
+    let prefix = name.get(..SERVICES_ENUMERATION.len())?;
+    if !prefix.eq_ignore_ascii_case(SERVICES_ENUMERATION) {
+        return None;
+    }
+    name[SERVICES_ENUMERATION.len()..]
+        .strip_prefix('.')
+        .filter(|domain| !domain.is_empty())
+}
+
+/// 拆分服务类型名`[<子类型>._sub.]_<服务>._tcp|_udp.<域>`，返回(服务, 域)
+fn split_service_type(name: &str) -> Option<(&str, &str)> {
+    let mut name = name.trim_end_matches('.');
+    // 子类型只是服务的一个筛选条件，归入所属服务
+    if let Some((_, rest)) = name.split_once("._sub.") {
+        name = rest;
+    }
+
+    let mut parts = name.splitn(3, '.');
+    let service = parts.next()?;
+    let proto = parts.next()?;
+    let domain = parts.next().filter(|domain| !domain.is_empty())?;
+    if service.len() < 2
+        || !service.starts_with('_')
+        || !(proto.eq_ignore_ascii_case("_tcp") || proto.eq_ignore_ascii_case("_udp"))
+    {
+        return None;
+    }
+    Some((&name[..service.len() + 1 + proto.len()], domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::stats::StatsCounter;
+    use crate::protocols::dns::{DnsParser, UdpDnsParser};
+
+    /// 未压缩的域名
+    fn wire_name(name: &str) -> Vec<u8> {
+        let mut wire = Vec::new();
+        for label in name.split('.') {
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label.as_bytes());
+        }
+        wire.push(0);
+        wire
+    }
+
+    /// PTR记录，TTL为4500秒
+    fn ptr(owner: &str, target: &str) -> Vec<u8> {
+        let rdata = wire_name(target);
+        let mut record = wire_name(owner);
+        record.extend_from_slice(&[0x00, 0x0C, 0x00, 0x01, 0x00, 0x00, 0x11, 0x94]);
+        record.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        record.extend_from_slice(&rdata);
+        record
+    }
+
+    #[test]
+    fn test_decode_dnssd_ptr_response() {
+        // mDNS响应：服务类型枚举结果和该服务的两个实例
+        let mut packet = vec![0x00, 0x00, 0x84, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00];
+        packet.extend(ptr("_services._dns-sd._udp.local", "_ipp._tcp.local"));
+        packet.extend(ptr("_ipp._tcp.local", "Office Printer._ipp._tcp.local"));
+        packet.extend(ptr("_ipp._tcp.local", "Lab.2F Printer._ipp._tcp.local"));
+        // 普通反向解析PTR不属于服务发现
+        packet.extend(ptr("10.2.0.192.in-addr.arpa", "printer.local"));
+
+        let mut parser = UdpDnsParser::new(65535);
+        let message = parser.parse(&packet, &StatsCounter::new()).unwrap();
+        let records = dnssd_records(&message);
+
+        assert_eq!(
+            records,
+            vec![
+                DnsSdRecord::ServiceType {
+                    service: "_ipp._tcp".to_string(),
+                    domain: "local".to_string(),
+                },
+                DnsSdRecord::Instance {
+                    instance: "Office Printer".to_string(),
+                    service: "_ipp._tcp".to_string(),
+                    domain: "local".to_string(),
+                },
+                DnsSdRecord::Instance {
+                    instance: "Lab.2F Printer".to_string(),
+                    service: "_ipp._tcp".to_string(),
+                    domain: "local".to_string(),
+                },
+            ]
+        );
+        let listing: Vec<String> = records.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            listing,
+            vec!["服务类型 _ipp._tcp.local", "Office Printer (_ipp._tcp.local)", "Lab.2F Printer (_ipp._tcp.local)"]
+        );
+
+        assert!(is_service_enumeration("_services._dns-sd._udp.local."));
+        assert!(!is_service_enumeration("_ipp._tcp.local"));
+        assert_eq!(split_service_type("_color._sub._ipp._tcp.local"), Some(("_ipp._tcp", "local")));
+    }
+}
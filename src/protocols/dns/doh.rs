@@ -1,28 +1,76 @@
 //! DNS over HTTPS (DoH) 协议解析实现
+//! 支持明文可见的HTTP/1.x和HTTP/2：HTTP/1.x按头部和Content-Length切分消息，
+//! HTTP/2按帧重组各流的DATA负载，HPACK不维护动态表，只解析名称在静态表中的字面量头部
+
+use std::collections::HashMap;
+use std::net::IpAddr;
 
 use crate::core::stats::StatsCounter;
 use crate::protocols::dns::{DnsMessage, DnsParser, DnsProtocol};
 
+/// HTTP头部最大长度
+const MAX_HTTP_HEADER: usize = 8192;
+/// DoH消息的Content-Type
+const DNS_MESSAGE_CONTENT_TYPE: &str = "application/dns-message";
+/// RFC 8484示例使用的请求路径
+const DNS_QUERY_PATH: &str = "/dns-query";
+/// HTTP/2客户端连接前言
+pub const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+/// HTTP/2帧头长度：长度(3) + 类型(1) + 标志(1) + 流ID(4)
+const HTTP2_FRAME_HEADER_LEN: usize = 9;
+const HTTP2_FRAME_DATA: u8 = 0x0;
+const HTTP2_FRAME_HEADERS: u8 = 0x1;
+const HTTP2_FLAG_END_STREAM: u8 = 0x1;
+const HTTP2_FLAG_PADDED: u8 = 0x8;
+const HTTP2_FLAG_PRIORITY: u8 = 0x20;
+/// 每个HTTP/2会话同时跟踪的流数上限，与RFC 9113建议的最小并发流数一致
+const MAX_HTTP2_STREAMS: usize = 100;
+/// 每个HTTP/2会话各流缓存的消息体总字节数上限
+const MAX_HTTP2_BUFFERED_BODY: usize = 256 * 1024;
+
 /// HTTP请求方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum HttpMethod {
     Get,
     Post,
 }
 
-/// HTTP会话状态
-struct HttpSession {
-    method: HttpMethod,
-    headers: std::collections::HashMap<String, String>,
+/// HTTP/2流上已收到的头部和消息体
+#[derive(Default)]
+struct Http2Stream {
+    /// `:path`，Huffman编码或引用动态表时为None
+    path: Option<String>,
+    /// `content-type`，同上
+    content_type: Option<String>,
     body: Vec<u8>,
-    state: HttpParseState,
 }
 
-/// HTTP解析状态
-enum HttpParseState {
-    RequestLine,
-    Headers,
-    Body,
-    Complete,
+/// HTTP会话状态，按会话累积尚未组成完整HTTP消息或HTTP/2帧的字节
+#[derive(Default)]
+struct HttpSession {
+    buffer: Vec<u8>,
+    /// 已收到HTTP/2连接前言，或是已知HTTP/2连接的服务器方向
+    http2: bool,
+    /// HTTP/2各流的状态
+    streams: HashMap<u32, Http2Stream>,
+    /// 各流已缓存的消息体总字节数
+    buffered_body: usize,
+    /// 请求的Host头部或`:authority`
+    authority: Option<String>,
+    /// authority是否已被取走
+    authority_taken: bool,
+    last_seen: u64,
+}
+
+/// 解析后的HTTP头部
+struct HttpHead {
+    /// 请求方法，响应为None
+    method: Option<HttpMethod>,
+    /// 请求目标（路径和查询串）
+    target: String,
+    headers: HashMap<String, String>,
+    /// 头部长度（含结尾空行）
+    len: usize,
 }
 
 /// DoH解析器
@@ -30,54 +78,603 @@ pub struct DohParser {
     // 内部UDP解析器用于解析DNS消息
     udp_parser: super::udp::UdpDnsParser,
     // HTTP会话跟踪
-    http_sessions: std::collections::HashMap<u32, HttpSession>,
+    http_sessions: HashMap<(IpAddr, IpAddr, u16, u16), HttpSession>, // (src_ip, dst_ip, src_port, dst_port)
+    max_packet_size: usize,
+    // 配置
+    max_sessions: usize,
+    session_timeout_ms: u64,
+    current_time_ms: u64,
 }
 
 impl DohParser {
     /// 创建新的DoH解析器
-    pub fn new(max_packet_size: usize) -> Self {
+    pub fn new(max_packet_size: usize, max_sessions: usize, session_timeout_ms: u64) -> Self {
         DohParser {
-            udp_parser: super::udp::UdpDnsParser::new(max_packet_size),
-            http_sessions: std::collections::HashMap::new(),
+            udp_parser: super::udp::UdpDnsParser::new(max_packet_size).with_protocol(DnsProtocol::Doh),
+            http_sessions: HashMap::with_capacity(max_sessions),
+            max_packet_size,
+            max_sessions,
+            session_timeout_ms,
+            current_time_ms: 0,
+        }
+    }
+
+    /// 更新当前时间
+    pub fn update_time(&mut self, time_ms: u64) {
+        self.current_time_ms = time_ms;
+        self.cleanup_sessions();
+    }
+
+    /// 清理过期会话
+    fn cleanup_sessions(&mut self) {
+        let expired_time = self.current_time_ms.saturating_sub(self.session_timeout_ms);
+        self.http_sessions.retain(|_, session| session.last_seen > expired_time);
+    }
+
+    /// 会话是否已被识别为DoH，后续不带HTTP特征的数据段同样属于该会话
+    pub fn has_session(&self, src_ip: IpAddr, dst_ip: IpAddr, src_port: u16, dst_port: u16) -> bool {
+        self.http_sessions.contains_key(&(src_ip, dst_ip, src_port, dst_port))
+    }
+
+    /// 会话是否为HTTP/2连接
+    pub fn is_http2(&self, src_ip: IpAddr, dst_ip: IpAddr, src_port: u16, dst_port: u16) -> bool {
+        self.http_sessions.get(&(src_ip, dst_ip, src_port, dst_port)).is_some_and(|session| session.http2)
+    }
+
+    /// 登记HTTP/2连接的服务器方向，服务器不发送连接前言，直接按帧解析
+    pub fn expect_http2(&mut self, src_ip: IpAddr, dst_ip: IpAddr, src_port: u16, dst_port: u16) {
+        let session_id = (src_ip, dst_ip, src_port, dst_port);
+        if !self.http_sessions.contains_key(&session_id) {
+            self.evict_if_full();
         }
+        let session = self.http_sessions.entry(session_id).or_default();
+        session.http2 = true;
+        session.last_seen = self.current_time_ms;
     }
 
-    /// 处理HTTP请求
-    pub fn process_http_data(&mut self, 
-                            session_id: u32,
+    /// 取出会话新发现的Host/`:authority`，每个会话只返回一次
+    pub fn take_authority(&mut self, src_ip: IpAddr, dst_ip: IpAddr, src_port: u16, dst_port: u16) -> Option<String> {
+        let session = self.http_sessions.get_mut(&(src_ip, dst_ip, src_port, dst_port))?;
+        if session.authority_taken {
+            return None;
+        }
+        let authority = session.authority.clone()?;
+        session.authority_taken = true;
+        Some(authority)
+    }
+
+    /// 会话数达到上限时先清理过期会话，仍然满则淘汰最久未活动的会话
+    fn evict_if_full(&mut self) {
+        if self.http_sessions.len() < self.max_sessions {
+            return;
+        }
+        self.cleanup_sessions();
+        if self.http_sessions.len() >= self.max_sessions {
+            let oldest = self
+                .http_sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_seen)
+                .map(|(id, _)| *id);
+            if let Some(id) = oldest {
+                self.http_sessions.remove(&id);
+            }
+        }
+    }
+
+    /// 处理HTTP数据
+    ///
+    /// 数据按四元组累积，只有收到完整的HTTP消息（头部加Content-Length指定的消息体）
+    /// 或HTTP/2流结束后才提取DNS负载；同一段数据中的多个流水线消息或帧依次处理。
+    pub fn process_http_data(&mut self,
+                            src_ip: IpAddr,
+                            dst_ip: IpAddr,
+                            src_port: u16,
+                            dst_port: u16,
                             data: &[u8],
-                            stats: &mut StatsCounter) -> Vec<DnsMessage> {
-        // 在实际实现中，这里需要解析HTTP请求/响应
-        // 这是一个简化版本，假设我们已经提取了DNS查询数据
-        
+                            stats: &StatsCounter) -> Vec<DnsMessage> {
         let mut results = Vec::new();
-        
-        // 检查是否是DoH请求
-        if let Some(dns_data) = self.extract_dns_data(data) {
-            if let Some(message) = self.udp_parser.parse(&dns_data, stats) {
-                results.push(message);
+
+        // 会话标识
+        let session_id = (src_ip, dst_ip, src_port, dst_port);
+
+        if !self.http_sessions.contains_key(&session_id) {
+            self.evict_if_full();
+        }
+        let session = self.http_sessions.entry(session_id).or_default();
+        session.buffer.extend_from_slice(data);
+        session.last_seen = self.current_time_ms;
+
+        if session.buffer.len() > MAX_HTTP_HEADER + self.max_packet_size {
+            stats.increment("dns.doh.buffer_overflow");
+            self.http_sessions.remove(&session_id);
+            return results;
+        }
+
+        if !session.http2 {
+            if session.buffer.starts_with(HTTP2_PREFACE) {
+                session.buffer.drain(..HTTP2_PREFACE.len());
+                session.http2 = true;
+                stats.increment("dns.doh.http2");
+            } else if HTTP2_PREFACE.starts_with(&session.buffer) {
+                // 连接前言未收全
+                return results;
+            }
+        }
+
+        if session.http2 {
+            let payloads = Self::drain_http2_frames(session, self.max_packet_size, stats);
+            for dns_data in payloads {
+                self.parse_dns(&dns_data, stats, &mut results);
+            }
+            return results;
+        }
+
+        while let Some(session) = self.http_sessions.get_mut(&session_id) {
+            let head = match Self::parse_head(&session.buffer) {
+                Ok(Some(head)) => head,
+                // 头部未收全，等待更多数据
+                Ok(None) => break,
+                Err(counter) => {
+                    stats.increment(counter);
+                    self.http_sessions.remove(&session_id);
+                    break;
+                }
+            };
+
+            if session.authority.is_none() {
+                session.authority = head.headers.get("host").cloned();
+            }
+
+            let body_len = match head.headers.get("content-length") {
+                Some(value) => match value.parse::<usize>() {
+                    Ok(len) => len,
+                    Err(_) => {
+                        stats.increment("dns.doh.invalid_http");
+                        self.http_sessions.remove(&session_id);
+                        break;
+                    }
+                },
+                None if head.headers.contains_key("transfer-encoding") => {
+                    stats.increment("dns.doh.unsupported_framing");
+                    self.http_sessions.remove(&session_id);
+                    break;
+                }
+                None => 0,
+            };
+
+            // 消息体未收全，等待更多数据
+            if session.buffer.len() < head.len + body_len {
+                break;
+            }
+
+            let message: Vec<u8> = session.buffer.drain(..head.len + body_len).collect();
+            let body = &message[head.len..];
+
+            if let Some(dns_data) = Self::extract_dns_data(&head, body) {
+                self.parse_dns(&dns_data, stats, &mut results);
+            } else {
+                stats.increment("dns.doh.not_dns");
             }
         }
-        
+
         results
     }
-    
-    /// 从HTTP数据中提取DNS查询
-    fn extract_dns_data(&self, data: &[u8]) -> Option<Vec<u8>> {
-        // 在实际实现中，这里需要:
-        // 1. 解析HTTP请求/响应
-        // 2. 检查Content-Type是否为application/dns-message
-        // 3. 对于GET请求，解码URL参数中的dns参数
-        // 4. 对于POST请求，直接使用请求体
-        
-        // 简化版本，假设数据已经是DNS消息
-        // 在实际实现中需要更复杂的HTTP解析
-        Some(data.to_vec())
+
+    /// 解析提取出的DNS报文
+    fn parse_dns(&mut self, dns_data: &[u8], stats: &StatsCounter, results: &mut Vec<DnsMessage>) {
+        if let Some(message) = self.udp_parser.parse(dns_data, stats) {
+            results.push(message);
+        }
+    }
+
+    /// 依次处理缓冲区中的完整HTTP/2帧，返回本次结束的流中提取出的DNS报文
+    ///
+    /// GET请求取`:path`中的`dns`参数，其余流在结束时取DATA负载；
+    /// 已知Content-Type且不是application/dns-message的流丢弃。
+    fn drain_http2_frames(session: &mut HttpSession, max_body: usize, stats: &StatsCounter) -> Vec<Vec<u8>> {
+        let mut payloads = Vec::new();
+
+        while session.buffer.len() >= HTTP2_FRAME_HEADER_LEN {
+            let header = &session.buffer[..HTTP2_FRAME_HEADER_LEN];
+            let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+            let (frame_type, flags) = (header[3], header[4]);
+            let stream_id = u32::from_be_bytes([header[5], header[6], header[7], header[8]]) & 0x7FFF_FFFF;
+            if session.buffer.len() < HTTP2_FRAME_HEADER_LEN + len {
+                break;
+            }
+
+            let frame: Vec<u8> = session.buffer.drain(..HTTP2_FRAME_HEADER_LEN + len).collect();
+            let payload = &frame[HTTP2_FRAME_HEADER_LEN..];
+            if frame_type != HTTP2_FRAME_DATA && frame_type != HTTP2_FRAME_HEADERS {
+                continue;
+            }
+
+            let payload = match strip_padding(payload, flags) {
+                Some(payload) => payload,
+                None => {
+                    stats.increment("dns.doh.invalid_http2");
+                    continue;
+                }
+            };
+            if !session.streams.contains_key(&stream_id) && session.streams.len() >= MAX_HTTP2_STREAMS {
+                stats.increment("dns.doh.too_many_streams");
+                continue;
+            }
+            let stream = session.streams.entry(stream_id).or_default();
+
+            if frame_type == HTTP2_FRAME_HEADERS {
+                // 优先级字段：依赖流(4) + 权重(1)
+                let block = if flags & HTTP2_FLAG_PRIORITY != 0 { payload.get(5..).unwrap_or(&[]) } else { payload };
+                for (name, value) in hpack_literals(block) {
+                    match name {
+                        ":authority" if session.authority.is_none() => session.authority = Some(value),
+                        ":path" => stream.path = Some(value),
+                        "content-type" => stream.content_type = Some(value),
+                        _ => {}
+                    }
+                }
+            } else {
+                stream.body.extend_from_slice(payload);
+                session.buffered_body += payload.len();
+                let overflow = if stream.body.len() > max_body {
+                    Some("dns.doh.buffer_overflow")
+                } else if session.buffered_body > MAX_HTTP2_BUFFERED_BODY {
+                    Some("dns.doh.session_buffer_overflow")
+                } else {
+                    None
+                };
+                if let Some(counter) = overflow {
+                    stats.increment(counter);
+                    Self::remove_stream(session, stream_id);
+                    continue;
+                }
+            }
+
+            if flags & HTTP2_FLAG_END_STREAM == 0 {
+                continue;
+            }
+            let stream = match Self::remove_stream(session, stream_id) {
+                Some(stream) => stream,
+                None => continue,
+            };
+            let not_dns = stream
+                .content_type
+                .as_deref()
+                .is_some_and(|content_type| !content_type.eq_ignore_ascii_case(DNS_MESSAGE_CONTENT_TYPE));
+            let dns_data = if !stream.body.is_empty() && !not_dns {
+                Some(stream.body)
+            } else {
+                stream.path.as_deref().and_then(dns_param)
+            };
+            match dns_data {
+                Some(dns_data) => payloads.push(dns_data),
+                None => stats.increment("dns.doh.not_dns"),
+            }
+        }
+
+        payloads
+    }
+
+    /// 移除流并扣除其缓存的消息体字节数
+    fn remove_stream(session: &mut HttpSession, stream_id: u32) -> Option<Http2Stream> {
+        let stream = session.streams.remove(&stream_id)?;
+        session.buffered_body -= stream.body.len();
+        Some(stream)
+    }
+
+    /// 解析HTTP头部，头部不完整时返回Ok(None)，格式错误时返回对应计数器名
+    fn parse_head(buffer: &[u8]) -> Result<Option<HttpHead>, &'static str> {
+        let end = match buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(end) => end,
+            None if buffer.len() > MAX_HTTP_HEADER => return Err("dns.doh.invalid_http"),
+            None => return Ok(None),
+        };
+
+        let text = std::str::from_utf8(&buffer[..end]).map_err(|_| "dns.doh.invalid_http")?;
+        let mut lines = text.split("\r\n");
+        let start_line = lines.next().unwrap_or("");
+        let mut parts = start_line.split(' ');
+
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(version), Some(_)) if version.starts_with("HTTP/") => (None, String::new()),
+            (Some("GET"), Some(target)) => (Some(HttpMethod::Get), target.to_string()),
+            (Some("POST"), Some(target)) => (Some(HttpMethod::Post), target.to_string()),
+            _ => return Err("dns.doh.invalid_http"),
+        };
+
+        let mut headers = HashMap::new();
+        for line in lines {
+            let (name, value) = line.split_once(':').ok_or("dns.doh.invalid_http")?;
+            headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+        }
+
+        Ok(Some(HttpHead {
+            method,
+            target,
+            headers,
+            len: end + 4,
+        }))
+    }
+
+    /// 从完整的HTTP消息中提取DNS报文
+    ///
+    /// GET请求取URL参数`dns`的base64url解码结果，POST请求和响应要求
+    /// Content-Type为application/dns-message并直接使用消息体。
+    fn extract_dns_data(head: &HttpHead, body: &[u8]) -> Option<Vec<u8>> {
+        match head.method {
+            Some(HttpMethod::Get) => dns_param(&head.target),
+            Some(HttpMethod::Post) | None => {
+                let content_type = head.headers.get("content-type")?;
+                if !content_type.eq_ignore_ascii_case(DNS_MESSAGE_CONTENT_TYPE) {
+                    return None;
+                }
+                Some(body.to_vec())
+            }
+        }
+    }
+}
+
+/// 数据是否像DoH流的开始
+///
+/// HTTP/2连接前言，或请求路径为`/dns-query`、Content-Type为application/dns-message的
+/// HTTP/1.x请求和响应。加密的HTTPS流量看不到这些特征。
+pub fn looks_like_doh(data: &[u8]) -> bool {
+    if data.starts_with(&HTTP2_PREFACE[..14]) {
+        return true;
+    }
+
+    let end = data
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .unwrap_or(data.len())
+        .min(MAX_HTTP_HEADER);
+    let text = match std::str::from_utf8(&data[..end]) {
+        Ok(text) => text,
+        Err(_) => return false,
+    };
+    let mut lines = text.split("\r\n");
+    let mut parts = lines.next().unwrap_or("").split(' ');
+
+    match (parts.next(), parts.next()) {
+        (Some("GET" | "POST"), Some(target)) if target.split('?').next() == Some(DNS_QUERY_PATH) => return true,
+        (Some("GET" | "POST"), Some(_)) => {}
+        (Some(version), Some(_)) if version.starts_with("HTTP/1.") => {}
+        _ => return false,
+    }
+
+    lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("content-type")
+                && value.trim().eq_ignore_ascii_case(DNS_MESSAGE_CONTENT_TYPE)
+        })
+    })
+}
+
+/// 取请求目标中`dns`参数的base64url解码结果
+fn dns_param(target: &str) -> Option<Vec<u8>> {
+    let (_, query) = target.split_once('?')?;
+    let encoded = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("dns="))?;
+    decode_base64url(encoded)
+}
+
+/// 去掉HTTP/2帧的填充，填充长度超过负载时返回None
+fn strip_padding(payload: &[u8], flags: u8) -> Option<&[u8]> {
+    if flags & HTTP2_FLAG_PADDED == 0 {
+        return Some(payload);
+    }
+    let (&pad_len, rest) = payload.split_first()?;
+    rest.get(..rest.len().checked_sub(pad_len as usize)?)
+}
+
+/// HPACK整数，`prefix`为首字节中的位数
+fn hpack_int(block: &[u8], pos: &mut usize, prefix: u32) -> Option<usize> {
+    let max = (1usize << prefix) - 1;
+    let mut value = (*block.get(*pos)? as usize) & max;
+    *pos += 1;
+    if value < max {
+        return Some(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let byte = *block.get(*pos)?;
+        *pos += 1;
+        value = value.checked_add(((byte & 0x7F) as usize).checked_shl(shift)?)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+        if shift > 28 {
+            return None;
+        }
+    }
+}
+
+/// HPACK字符串，值无法还原（Huffman编码错误或不是UTF-8）时返回Some(None)
+fn hpack_string(block: &[u8], pos: &mut usize) -> Option<Option<String>> {
+    let huffman = *block.get(*pos)? & 0x80 != 0;
+    let len = hpack_int(block, pos, 7)?;
+    let bytes = block.get(*pos..pos.checked_add(len)?)?;
+    *pos += len;
+    let decoded = if huffman { huffman_decode(bytes) } else { Some(bytes.to_vec()) };
+    Some(decoded.and_then(|bytes| String::from_utf8(bytes).ok()))
+}
+
+/// HPACK Huffman码长（RFC 7541附录B），按符号0-255排列，EOS为30位全1
+///
+/// 附录B的编码是规范Huffman码，码字可由码长推出。
+const HUFFMAN_CODE_LENGTHS: [u8; 256] = [
+    13, 23, 28, 28, 28, 28, 28, 28, 28, 24, 30, 28, 28, 30, 28, 28, 28, 28, 28, 28, 28, 28, 30, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 6, 10, 10, 12, 13, 6, 8, 11, 10, 10, 8, 11, 8, 6, 6, 6, 5, 5,
+    5, 6, 6, 6, 6, 6, 6, 6, 7, 8, 15, 6, 12, 10, 13, 6, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+    7, 7, 7, 7, 7, 7, 7, 7, 8, 7, 8, 13, 19, 13, 14, 6, 15, 5, 6, 5, 6, 5, 6, 6, 6, 5, 7, 7, 6, 6,
+    6, 5, 6, 7, 6, 5, 5, 6, 7, 7, 7, 7, 7, 15, 11, 14, 13, 28, 20, 22, 20, 20, 22, 22, 22, 23, 22,
+    23, 23, 23, 23, 23, 24, 23, 24, 24, 22, 23, 24, 23, 23, 23, 23, 21, 22, 23, 22, 23, 23, 24, 22,
+    21, 20, 22, 22, 23, 23, 21, 23, 22, 22, 24, 21, 22, 23, 23, 21, 21, 22, 21, 23, 22, 23, 23, 20,
+    22, 22, 22, 23, 22, 22, 23, 26, 26, 20, 19, 22, 23, 22, 25, 26, 26, 26, 27, 27, 26, 24, 25, 19,
+    21, 26, 27, 27, 26, 27, 24, 21, 21, 26, 26, 28, 27, 27, 27, 20, 24, 20, 21, 22, 21, 21, 23, 22,
+    22, 25, 25, 24, 24, 26, 23, 26, 27, 26, 26, 27, 27, 27, 27, 27, 28, 27, 27, 27, 27, 27, 26,
+];
+/// 最长码字的位数
+const HUFFMAN_MAX_LENGTH: usize = 30;
+
+/// 规范Huffman解码表：各码长的码字数、首个码字，以及按（码长，符号）排序的符号
+struct HuffmanTable {
+    count: [u32; HUFFMAN_MAX_LENGTH + 1],
+    first: [u32; HUFFMAN_MAX_LENGTH + 1],
+    offset: [usize; HUFFMAN_MAX_LENGTH + 1],
+    symbols: [u8; 256],
+}
+
+const HUFFMAN_TABLE: HuffmanTable = {
+    let mut table = HuffmanTable {
+        count: [0; HUFFMAN_MAX_LENGTH + 1],
+        first: [0; HUFFMAN_MAX_LENGTH + 1],
+        offset: [0; HUFFMAN_MAX_LENGTH + 1],
+        symbols: [0; 256],
+    };
+    let mut symbol = 0;
+    while symbol < 256 {
+        table.count[HUFFMAN_CODE_LENGTHS[symbol] as usize] += 1;
+        symbol += 1;
+    }
+    let mut len = 1;
+    while len <= HUFFMAN_MAX_LENGTH {
+        table.first[len] = (table.first[len - 1] + table.count[len - 1]) << 1;
+        table.offset[len] = table.offset[len - 1] + table.count[len - 1] as usize;
+        len += 1;
+    }
+    let mut next = table.offset;
+    let mut symbol = 0;
+    while symbol < 256 {
+        let len = HUFFMAN_CODE_LENGTHS[symbol] as usize;
+        table.symbols[next[len]] = symbol as u8;
+        next[len] += 1;
+        symbol += 1;
+    }
+    table
+};
+
+/// HPACK Huffman解码，编码错误或填充不是EOS前缀时返回None
+fn huffman_decode(bytes: &[u8]) -> Option<Vec<u8>> {
+    let table = &HUFFMAN_TABLE;
+    let mut output = Vec::with_capacity(bytes.len() * 8 / 5);
+    let mut code: u32 = 0;
+    let mut len = 0;
+
+    for &byte in bytes {
+        for shift in (0..8).rev() {
+            code = (code << 1) | ((byte >> shift) & 1) as u32;
+            len += 1;
+            let index = code.wrapping_sub(table.first[len]);
+            if index < table.count[len] {
+                output.push(table.symbols[table.offset[len] + index as usize]);
+                code = 0;
+                len = 0;
+            } else if len == HUFFMAN_MAX_LENGTH {
+                // 不是任何符号的码字，包括EOS
+                return None;
+            }
+        }
+    }
+
+    // 结尾填充最多7位，且必须是EOS码字的高位（全1）
+    if len > 7 || code != (1 << len) - 1 {
+        return None;
+    }
+    Some(output)
+}
+
+/// DoH关心的头部在HPACK静态表中的名称
+fn hpack_static_name(index: usize) -> Option<&'static str> {
+    match index {
+        1 => Some(":authority"),
+        4 | 5 => Some(":path"),
+        31 => Some("content-type"),
+        _ => None,
+    }
+}
+
+/// 从HPACK头部块中取出名称在静态表中的字面量头部，Huffman编码的值会被解码
+///
+/// 不维护动态表，引用动态表的头部被跳过：客户端通常把`:authority`、`content-type`
+/// 加入动态表，同一连接的后续请求只能取到每次都以字面量发送的`:path`。
+fn hpack_literals(block: &[u8]) -> Vec<(&'static str, String)> {
+    let mut headers = Vec::new();
+    let mut pos = 0;
+
+    while let Some(&byte) = block.get(pos) {
+        let prefix = match byte {
+            // 索引头部字段
+            b if b & 0x80 != 0 => {
+                if hpack_int(block, &mut pos, 7).is_none() {
+                    break;
+                }
+                continue;
+            }
+            // 带增量索引的字面量
+            b if b & 0xC0 == 0x40 => 6,
+            // 动态表大小更新
+            b if b & 0xE0 == 0x20 => {
+                if hpack_int(block, &mut pos, 5).is_none() {
+                    break;
+                }
+                continue;
+            }
+            // 不索引或永不索引的字面量
+            _ => 4,
+        };
+
+        let index = match hpack_int(block, &mut pos, prefix) {
+            Some(index) => index,
+            None => break,
+        };
+        // 新名称的字面量先跳过名称字符串
+        if index == 0 && hpack_string(block, &mut pos).is_none() {
+            break;
+        }
+        let value = match hpack_string(block, &mut pos) {
+            Some(value) => value,
+            None => break,
+        };
+        if let (Some(name), Some(value)) = (hpack_static_name(index), value) {
+            headers.push((name, value));
+        }
+    }
+
+    headers
+}
+
+/// 无填充base64url解码（RFC 8484 GET请求使用）
+fn decode_base64url(input: &str) -> Option<Vec<u8>> {
+    let mut output = Vec::with_capacity(input.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+
+    for byte in input.bytes().take_while(|&b| b != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'-' => 62,
+            b'_' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((acc >> bits) as u8);
+        }
     }
+
+    Some(output)
 }
 
 impl DnsParser for DohParser {
-    fn parse(&mut self, data: &[u8], stats: &mut StatsCounter) -> Option<DnsMessage> {
+    fn parse(&mut self, data: &[u8], stats: &StatsCounter) -> Option<DnsMessage> {
         // 注意：DoH解析器需要通过process_http_data方法处理HTTP数据
         // 这个方法主要用于兼容DnsParser特征
         stats.increment("dns.doh.direct_parse_attempt");
@@ -87,4 +684,193 @@ impl DnsParser for DohParser {
     fn protocol_type(&self) -> DnsProtocol {
         DnsProtocol::Doh
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53));
+
+    /// example.com A查询
+    fn query() -> Vec<u8> {
+        let mut query = vec![0xAB, 0xCD, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        query.extend_from_slice(b"\x07example\x03com\x00");
+        query.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        query
+    }
+
+    #[test]
+    fn test_post_split_across_fragments() {
+        let body = query();
+        let mut request = format!(
+            "POST /dns-query HTTP/1.1\r\nHost: doh.example\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        request.extend_from_slice(&body);
+
+        let mut parser = DohParser::new(65535, 1000, 30_000);
+        let stats = StatsCounter::new();
+
+        // 在消息体中间切分
+        let split = request.len() - 10;
+        assert!(parser.process_http_data(CLIENT, SERVER, 40007, 443, &request[..split], &stats).is_empty());
+        // 其他会话的数据不影响该会话
+        assert!(parser.process_http_data(CLIENT, SERVER, 40008, 443, &request[..20], &stats).is_empty());
+
+        let messages = parser.process_http_data(CLIENT, SERVER, 40007, 443, &request[split..], &stats);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].transaction_id, 0xABCD);
+        assert_eq!(messages[0].questions[0].name, "example.com");
+        assert!(matches!(messages[0].protocol, DnsProtocol::Doh));
+        assert_eq!(stats.get("dns.doh.parsed"), 1);
+    }
+
+    /// 构造HTTP/2帧
+    fn frame(frame_type: u8, flags: u8, stream_id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut frame = (payload.len() as u32).to_be_bytes()[1..].to_vec();
+        frame.push(frame_type);
+        frame.push(flags);
+        frame.extend_from_slice(&stream_id.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// 名称引用静态表、值不经Huffman编码且不索引的字面量头部
+    fn literal(index: u8, value: &str) -> Vec<u8> {
+        let mut field = if index < 15 { vec![index] } else { vec![0x0F, index - 15] };
+        field.push(value.len() as u8);
+        field.extend_from_slice(value.as_bytes());
+        field
+    }
+
+    #[test]
+    fn test_looks_like_doh() {
+        assert!(looks_like_doh(HTTP2_PREFACE));
+        assert!(looks_like_doh(b"GET /dns-query?dns=AAAB HTTP/1.1\r\nHost: doh.example\r\n"));
+        assert!(looks_like_doh(b"POST /resolve HTTP/1.1\r\ncontent-type: Application/DNS-Message\r\n\r\n"));
+        assert!(looks_like_doh(b"HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\n\r\n"));
+
+        assert!(!looks_like_doh(b"GET /index.html HTTP/1.1\r\nHost: www.example\r\n\r\n"));
+        assert!(!looks_like_doh(b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n"));
+        assert!(!looks_like_doh(&[0x16, 0x03, 0x01, 0x00, 0x20]));
+        assert!(!looks_like_doh(b""));
+    }
+
+    #[test]
+    fn test_http2_streams_and_authority() {
+        let mut parser = DohParser::new(65535, 1000, 30_000);
+        let stats = StatsCounter::new();
+
+        // 连接前言跨段到达
+        assert!(parser.process_http_data(CLIENT, SERVER, 40001, 443, &HTTP2_PREFACE[..10], &stats).is_empty());
+        assert!(parser.has_session(CLIENT, SERVER, 40001, 443));
+
+        // 流1：POST，消息体带填充，单独的DATA帧结束流
+        let mut headers = vec![0x83, 0x87];
+        headers.extend(literal(1, "doh.example"));
+        headers.extend(literal(4, "/dns-query"));
+        headers.extend(literal(31, "application/dns-message"));
+        let mut body = vec![2];
+        body.extend(query());
+        body.extend_from_slice(&[0, 0]);
+
+        let mut data = HTTP2_PREFACE[10..].to_vec();
+        data.extend(frame(0x4, 0, 0, &[]));
+        data.extend(frame(HTTP2_FRAME_HEADERS, 0x4, 1, &headers));
+        data.extend(frame(HTTP2_FRAME_DATA, HTTP2_FLAG_END_STREAM | HTTP2_FLAG_PADDED, 1, &body));
+
+        // 流3：GET，查询在:path中，HEADERS帧结束流；无法解码的Huffman值被跳过
+        let mut headers = vec![0x82, 0x87, 0x01, 0x83, 0xAA, 0xBB, 0xCC];
+        headers.extend(literal(4, "/dns-query?dns=AAABAAABAAAAAAAAA3d3dwdleGFtcGxlA2NvbQAAAQAB"));
+        let get = frame(HTTP2_FRAME_HEADERS, HTTP2_FLAG_END_STREAM | 0x4, 3, &headers);
+        data.extend_from_slice(&get[..12]);
+
+        let messages = parser.process_http_data(CLIENT, SERVER, 40001, 443, &data, &stats);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].transaction_id, 0xABCD);
+        assert!(parser.is_http2(CLIENT, SERVER, 40001, 443));
+        assert_eq!(stats.get("dns.doh.http2"), 1);
+
+        let messages = parser.process_http_data(CLIENT, SERVER, 40001, 443, &get[12..], &stats);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].questions[0].name, "www.example.com");
+
+        // authority只返回一次
+        assert_eq!(parser.take_authority(CLIENT, SERVER, 40001, 443), Some("doh.example".to_string()));
+        assert_eq!(parser.take_authority(CLIENT, SERVER, 40001, 443), None);
+
+        // 服务器方向不发送连接前言
+        parser.expect_http2(CLIENT, SERVER, 40002, 443);
+        let mut headers = vec![0x88];
+        headers.extend(literal(31, "application/dns-message"));
+        let mut data = frame(HTTP2_FRAME_HEADERS, 0x4, 1, &headers);
+        data.extend(frame(HTTP2_FRAME_DATA, HTTP2_FLAG_END_STREAM, 1, &query()));
+        assert_eq!(parser.process_http_data(CLIENT, SERVER, 40002, 443, &data, &stats).len(), 1);
+
+        // 会话超时后被清理
+        parser.update_time(60_000);
+        assert!(!parser.has_session(CLIENT, SERVER, 40001, 443));
+        assert!(!parser.has_session(CLIENT, SERVER, 40002, 443));
+    }
+
+    #[test]
+    fn test_huffman_values_and_stream_limits() {
+        // RFC 7541 C.4.1、C.4.2中的Huffman编码
+        assert_eq!(
+            huffman_decode(&[0xF1, 0xE3, 0xC2, 0xE5, 0xF2, 0x3A, 0x6B, 0xA0, 0xAB, 0x90, 0xF4, 0xFF]),
+            Some(b"www.example.com".to_vec())
+        );
+        assert_eq!(huffman_decode(&[0xA8, 0xEB, 0x10, 0x64, 0x9C, 0xBF]), Some(b"no-cache".to_vec()));
+        // 填充超过7位或不是全1
+        assert_eq!(huffman_decode(&[0xFF, 0xFF, 0xFF, 0xFF]), None);
+        assert_eq!(huffman_decode(&[0xF1, 0xE3, 0xC2, 0xE5, 0xF2, 0x3A, 0x6B, 0xA0, 0xAB, 0x90, 0xF4, 0xFE]), None);
+
+        let mut parser = DohParser::new(65535, 1000, 30_000);
+        let stats = StatsCounter::new();
+        parser.expect_http2(CLIENT, SERVER, 40001, 443);
+
+        // Huffman编码的:authority
+        let mut headers = vec![0x82, 0x87, 0x41, 0x8C, 0xF1, 0xE3, 0xC2, 0xE5, 0xF2, 0x3A, 0x6B, 0xA0, 0xAB, 0x90, 0xF4, 0xFF];
+        headers.extend(literal(4, "/dns-query"));
+        let mut data = frame(HTTP2_FRAME_HEADERS, 0x4, 1, &headers);
+
+        // 超出流数上限的新流被丢弃
+        for stream_id in 0..MAX_HTTP2_STREAMS as u32 {
+            data.extend(frame(HTTP2_FRAME_DATA, 0, 3 + 2 * stream_id, &[0]));
+        }
+        assert!(parser.process_http_data(CLIENT, SERVER, 40001, 443, &data, &stats).is_empty());
+        assert_eq!(parser.take_authority(CLIENT, SERVER, 40001, 443), Some("www.example.com".to_string()));
+        assert_eq!(stats.get("dns.doh.too_many_streams"), 1);
+
+        // 未结束的流缓存的消息体总量受限
+        let mut parser = DohParser::new(65535, 1000, 30_000);
+        parser.expect_http2(CLIENT, SERVER, 40001, 443);
+        let chunk = vec![0; 60 * 1024];
+        for stream_id in 0..(MAX_HTTP2_BUFFERED_BODY / chunk.len()) as u32 + 1 {
+            let data = frame(HTTP2_FRAME_DATA, 0, 1 + 2 * stream_id, &chunk);
+            assert!(parser.process_http_data(CLIENT, SERVER, 40001, 443, &data, &stats).is_empty());
+        }
+        assert_eq!(stats.get("dns.doh.session_buffer_overflow"), 1);
+
+        // 被丢弃的流释放额度，之后的流仍可解析
+        let data = frame(HTTP2_FRAME_DATA, HTTP2_FLAG_END_STREAM, 1001, &query());
+        assert_eq!(parser.process_http_data(CLIENT, SERVER, 40001, 443, &data, &stats).len(), 1);
+    }
+
+    #[test]
+    fn test_get_with_base64url_parameter() {
+        // RFC 8484示例中的www.example.com查询
+        let request = b"GET /dns-query?dns=AAABAAABAAAAAAAAA3d3dwdleGFtcGxlA2NvbQAAAQAB HTTP/1.1\r\nHost: doh.example\r\n\r\n";
+
+        let mut parser = DohParser::new(65535, 1000, 30_000);
+        let stats = StatsCounter::new();
+        let messages = parser.process_http_data(CLIENT, SERVER, 40001, 443, request, &stats);
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].questions[0].name, "www.example.com");
+    }
+}
@@ -4,6 +4,7 @@
 use crate::core::stats::StatsCounter;
 use crate::protocols::dns::{DnsMessage, DnsParser, DnsProtocol};
 use std::collections::HashMap;
+use std::net::IpAddr;
 
 /// QUIC会话状态
 struct QuicSession {
@@ -24,7 +25,7 @@ pub struct DoqParser {
     // 内部UDP解析器用于解析DNS消息
     udp_parser: super::udp::UdpDnsParser,
     // QUIC会话跟踪
-    quic_sessions: HashMap<(u32, u32, u16, u16), QuicSession>, // (src_ip, dst_ip, src_port, dst_port)
+    quic_sessions: HashMap<(IpAddr, IpAddr, u16, u16), QuicSession>, // (src_ip, dst_ip, src_port, dst_port)
     // 配置
     max_sessions: usize,
     session_timeout_ms: u64,
@@ -35,7 +36,7 @@ impl DoqParser {
     /// 创建新的DoQ解析器
     pub fn new(max_packet_size: usize, max_sessions: usize, session_timeout_ms: u64) -> Self {
         DoqParser {
-            udp_parser: super::udp::UdpDnsParser::new(max_packet_size),
+            udp_parser: super::udp::UdpDnsParser::new(max_packet_size).with_protocol(DnsProtocol::Doq),
             quic_sessions: HashMap::with_capacity(max_sessions),
             max_sessions,
             session_timeout_ms,
@@ -57,12 +58,12 @@ impl DoqParser {
 
     /// 处理QUIC数据
     pub fn process_quic_data(&mut self, 
-                           src_ip: u32, 
-                           dst_ip: u32, 
+                           src_ip: IpAddr, 
+                           dst_ip: IpAddr, 
                            src_port: u16, 
                            dst_port: u16, 
                            data: &[u8], 
-                           stats: &mut StatsCounter) -> Vec<DnsMessage> {
+                           stats: &StatsCounter) -> Vec<DnsMessage> {
         // 在实际实现中，这里需要处理QUIC协议
         // 这是一个简化版本，假设我们已经解密了QUIC数据
         
@@ -109,10 +110,7 @@ impl DoqParser {
                 // 将解密后的数据传递给UDP解析器
                 let decrypted_data = self.decrypt_quic_data(data);
                 if let Some(message) = self.udp_parser.parse(&decrypted_data, stats) {
-                    // 修改协议类型
-                    let mut dns_message = message;
-                    dns_message.protocol = DnsProtocol::Doq;
-                    results.push(dns_message);
+                    results.push(message);
                 }
             },
             QuicState::Closed => {
@@ -133,7 +131,7 @@ impl DoqParser {
 }
 
 impl DnsParser for DoqParser {
-    fn parse(&mut self, data: &[u8], stats: &mut StatsCounter) -> Option<DnsMessage> {
+    fn parse(&mut self, data: &[u8], stats: &StatsCounter) -> Option<DnsMessage> {
         // 注意：DoQ解析器需要通过process_quic_data方法处理QUIC数据
         // 这个方法主要用于兼容DnsParser特征
         stats.increment("dns.doq.direct_parse_attempt");
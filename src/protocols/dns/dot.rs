@@ -3,7 +3,12 @@
 
 use crate::core::stats::StatsCounter;
 use crate::protocols::dns::{DnsMessage, DnsParser, DnsProtocol};
+use crate::protocols::tls::{client_hello_server_name, is_client_hello};
 use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// SNI日志的最小间隔（毫秒），期间的ClientHello只计数
+const SNI_LOG_INTERVAL_MS: u64 = 1_000;
 
 /// TLS会话状态
 struct TlsSession {
@@ -24,22 +29,26 @@ pub struct DotParser {
     // 内部TCP解析器用于解析DNS消息
     tcp_parser: super::tcp::TcpDnsParser,
     // TLS会话跟踪
-    tls_sessions: HashMap<(u32, u32, u16, u16), TlsSession>, // (src_ip, dst_ip, src_port, dst_port)
+    tls_sessions: HashMap<(IpAddr, IpAddr, u16, u16), TlsSession>, // (src_ip, dst_ip, src_port, dst_port)
     // 配置
     max_sessions: usize,
     session_timeout_ms: u64,
     current_time_ms: u64,
+    // 上次输出SNI日志的时间
+    last_sni_log_ms: Option<u64>,
 }
 
 impl DotParser {
     /// 创建新的DoT解析器
     pub fn new(max_packet_size: usize, max_sessions: usize, session_timeout_ms: u64) -> Self {
         DotParser {
-            tcp_parser: super::tcp::TcpDnsParser::new(max_packet_size, max_sessions, session_timeout_ms),
+            tcp_parser: super::tcp::TcpDnsParser::new(max_packet_size, max_sessions, session_timeout_ms)
+                .with_protocol(DnsProtocol::Dot),
             tls_sessions: HashMap::with_capacity(max_sessions),
             max_sessions,
             session_timeout_ms,
             current_time_ms: 0,
+            last_sni_log_ms: None,
         }
     }
 
@@ -58,12 +67,12 @@ impl DotParser {
 
     /// 处理TLS数据
     pub fn process_tls_data(&mut self, 
-                           src_ip: u32, 
-                           dst_ip: u32, 
+                           src_ip: IpAddr, 
+                           dst_ip: IpAddr, 
                            src_port: u16, 
                            dst_port: u16, 
                            data: &[u8], 
-                           stats: &mut StatsCounter) -> Vec<DnsMessage> {
+                           stats: &StatsCounter) -> Vec<DnsMessage> {
         // 在实际实现中，这里需要处理TLS协议
         // 这是一个简化版本，假设我们已经解密了TLS数据
         
@@ -98,6 +107,29 @@ impl DotParser {
         // 处理TLS数据
         match session.state {
             TlsState::Handshake => {
+                // ClientHello是明文，不解密也能看到客户端访问的服务器名
+                if is_client_hello(data) {
+                    stats.increment("dns.dot.client_hello");
+                    if let Some(server_name) = client_hello_server_name(data) {
+                        stats.increment("dns.dot.sni");
+                        // 大量新连接时限频输出，避免刷屏
+                        let now = self.current_time_ms;
+                        if self.last_sni_log_ms.is_none_or(|last| now.saturating_sub(last) >= SNI_LOG_INTERVAL_MS) {
+                            self.last_sni_log_ms = Some(now);
+                            println!(
+                                "DoT ClientHello {}:{} -> {}:{} SNI {}",
+                                src_ip,
+                                src_port,
+                                dst_ip,
+                                dst_port,
+                                server_name
+                            );
+                        } else {
+                            stats.increment("dns.dot.sni_log_suppressed");
+                        }
+                    }
+                }
+
                 // 在实际实现中，这里需要处理TLS握手
                 // 简化版本，假设握手已完成
                 session.state = TlsState::Established;
@@ -111,7 +143,6 @@ impl DotParser {
                 let decrypted_data = self.decrypt_tls_data(data);
                 let messages = self.tcp_parser.process_tcp_segment(
                     src_ip, dst_ip, src_port, dst_port, &decrypted_data, stats);
-                
                 results.extend(messages);
             },
             TlsState::Closed => {
@@ -120,11 +151,6 @@ impl DotParser {
             },
         }
         
-        // 修改消息协议类型
-        for message in &mut results {
-            message.protocol = DnsProtocol::Dot;
-        }
-        
         results
     }
     
@@ -137,7 +163,7 @@ impl DotParser {
 }
 
 impl DnsParser for DotParser {
-    fn parse(&mut self, data: &[u8], stats: &mut StatsCounter) -> Option<DnsMessage> {
+    fn parse(&mut self, data: &[u8], stats: &StatsCounter) -> Option<DnsMessage> {
         // 注意：DoT解析器需要通过process_tls_data方法处理TLS数据
         // 这个方法主要用于兼容DnsParser特征
         stats.increment("dns.dot.direct_parse_attempt");
@@ -1,22 +1,33 @@
 //! DNS协议解析模块
-//! 支持标准DNS、DoT、DoH和DoQ协议
+//! 支持标准DNS、DoT、DoH、DoQ和mDNS协议
 
+mod dnssd;
+mod name;
+mod rdata;
 mod udp;
 mod tcp;
 mod dot;
 mod doh;
 mod doq;
 
-pub use doh::DohParser;
+pub use dnssd::{dnssd_records, is_service_enumeration, DnsSdRecord};
+pub use doh::{looks_like_doh, DohParser};
 pub use doq::DoqParser;
 pub use dot::DotParser;
+pub use name::LabelIter;
+pub use rdata::{RdataDecoder, RdataRegistry};
 pub use tcp::TcpDnsParser;
 pub use udp::UdpDnsParser;
 
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+use serde::{Serialize, Serializer};
+
 use crate::core::stats::StatsCounter;
 
 /// DNS消息类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum DnsMessageType {
     Query,
     Response,
@@ -34,6 +45,8 @@ pub enum DnsRecordType {
     SOA,
     SRV,
     TXT,
+    /// EDNS伪记录，只出现在附加部分
+    OPT,
     Other(u16),
 }
 
@@ -49,53 +62,395 @@ impl From<u16> for DnsRecordType {
             6 => DnsRecordType::SOA,
             33 => DnsRecordType::SRV,
             16 => DnsRecordType::TXT,
+            41 => DnsRecordType::OPT,
             other => DnsRecordType::Other(other),
         }
     }
 }
 
+impl From<DnsRecordType> for u16 {
+    fn from(value: DnsRecordType) -> Self {
+        match value {
+            DnsRecordType::A => 1,
+            DnsRecordType::AAAA => 28,
+            DnsRecordType::CNAME => 5,
+            DnsRecordType::MX => 15,
+            DnsRecordType::NS => 2,
+            DnsRecordType::PTR => 12,
+            DnsRecordType::SOA => 6,
+            DnsRecordType::SRV => 33,
+            DnsRecordType::TXT => 16,
+            DnsRecordType::OPT => 41,
+            DnsRecordType::Other(other) => other,
+        }
+    }
+}
+
+impl DnsRecordType {
+    /// 定长记录类型的RDLENGTH，变长类型返回None
+    pub fn fixed_rdlength(&self) -> Option<usize> {
+        match self {
+            DnsRecordType::A => Some(4),
+            DnsRecordType::AAAA => Some(16),
+            _ => None,
+        }
+    }
+
+    /// 解析逗号分隔的记录类型列表，如`A,AAAA,TXT`，空项忽略
+    pub fn parse_list(list: &str) -> Result<Vec<DnsRecordType>, String> {
+        list.split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+/// 从助记符（不区分大小写）、RFC 3597的`TYPE<n>`或数值解析
+impl std::str::FromStr for DnsRecordType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let upper = s.to_ascii_uppercase();
+        let value = match upper.as_str() {
+            "A" => 1,
+            "AAAA" => 28,
+            "CNAME" => 5,
+            "MX" => 15,
+            "NS" => 2,
+            "PTR" => 12,
+            "SOA" => 6,
+            "SRV" => 33,
+            "TXT" => 16,
+            "OPT" => 41,
+            other => other
+                .strip_prefix("TYPE")
+                .unwrap_or(other)
+                .parse::<u16>()
+                .map_err(|_| format!("unknown record type: {}", s))?,
+        };
+        Ok(DnsRecordType::from(value))
+    }
+}
+
+/// 序列化为类型助记符，与Display一致
+impl Serialize for DnsRecordType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl std::fmt::Display for DnsRecordType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsRecordType::A => write!(f, "A"),
+            DnsRecordType::AAAA => write!(f, "AAAA"),
+            DnsRecordType::CNAME => write!(f, "CNAME"),
+            DnsRecordType::MX => write!(f, "MX"),
+            DnsRecordType::NS => write!(f, "NS"),
+            DnsRecordType::PTR => write!(f, "PTR"),
+            DnsRecordType::SOA => write!(f, "SOA"),
+            DnsRecordType::SRV => write!(f, "SRV"),
+            DnsRecordType::TXT => write!(f, "TXT"),
+            DnsRecordType::OPT => write!(f, "OPT"),
+            // RFC 3597未知类型表示法
+            DnsRecordType::Other(other) => write!(f, "TYPE{}", other),
+        }
+    }
+}
+
+/// DNS类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsClass {
+    IN,
+    CH,
+    HS,
+    /// 动态更新中用于前置条件（RFC 2136）
+    NONE,
+    /// 仅用于查询，如AXFR/IXFR
+    ANY,
+    Other(u16),
+}
+
+impl From<u16> for DnsClass {
+    fn from(value: u16) -> Self {
+        match value {
+            1 => DnsClass::IN,
+            3 => DnsClass::CH,
+            4 => DnsClass::HS,
+            254 => DnsClass::NONE,
+            255 => DnsClass::ANY,
+            other => DnsClass::Other(other),
+        }
+    }
+}
+
+impl From<DnsClass> for u16 {
+    fn from(value: DnsClass) -> Self {
+        match value {
+            DnsClass::IN => 1,
+            DnsClass::CH => 3,
+            DnsClass::HS => 4,
+            DnsClass::NONE => 254,
+            DnsClass::ANY => 255,
+            DnsClass::Other(other) => other,
+        }
+    }
+}
+
+/// 序列化为数值，保持日志中class字段为整数
+impl Serialize for DnsClass {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u16(u16::from(*self))
+    }
+}
+
+impl std::fmt::Display for DnsClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsClass::IN => write!(f, "IN"),
+            DnsClass::CH => write!(f, "CH"),
+            DnsClass::HS => write!(f, "HS"),
+            DnsClass::NONE => write!(f, "NONE"),
+            DnsClass::ANY => write!(f, "ANY"),
+            // RFC 3597未知类表示法
+            DnsClass::Other(other) => write!(f, "CLASS{}", other),
+        }
+    }
+}
+
+/// 域名标签的文本编码方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LabelEncoding {
+    /// 有损UTF-8解码，非法字节替换为U+FFFD
+    #[default]
+    Lossy,
+    /// DNS表示格式（RFC 4343），不可打印字节转义为`\DDD`，保留原始字节
+    Escaped,
+}
+
+/// 消息在解析链路中的位置，依据配置的递归解析器地址判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DnsRole {
+    /// 未配置解析器地址或两端都不是已知解析器
+    #[default]
+    Unknown,
+    /// 客户端与递归解析器之间的流量
+    StubToResolver,
+    /// 递归解析器向上游（权威服务器或转发目标）发起的流量
+    ResolverToAuthority,
+}
+
 /// DNS解析结果
-#[derive(Debug)]
+///
+/// 序列化字段名与早期手写JSON保持一致，原始报文不参与序列化。
+#[derive(Debug, Clone, Serialize)]
 pub struct DnsMessage {
     pub transaction_id: u16,
     pub message_type: DnsMessageType,
     pub questions: Vec<DnsQuestion>,
     pub answers: Vec<DnsAnswer>,
+    /// 权威部分记录
+    pub authorities: Vec<DnsAnswer>,
+    /// 附加部分记录（含EDNS OPT伪记录）
+    pub additionals: Vec<DnsAnswer>,
+    /// 抓包时间戳（自纪元起的微秒数）
     pub timestamp: u64,
     pub protocol: DnsProtocol,
+    /// 原始DNS报文，仅在解析器启用保留原始数据时填充
+    #[serde(skip)]
+    pub raw: Option<Vec<u8>>,
+    /// 未观察到对应查询的响应（单向镜像或主动推送）
+    pub unsolicited: bool,
+    /// 从ICMP端口不可达报文中提取的原始查询，表示该查询未能送达服务器
+    pub unreachable: bool,
+    /// 源IP地址，无传输层上下文时为未指定地址
+    pub src_ip: IpAddr,
+    /// 目的IP地址
+    pub dst_ip: IpAddr,
+    /// 源端口
+    pub src_port: u16,
+    /// 目的端口
+    pub dst_port: u16,
+    /// 操作码（头部标志第11-14位）
+    pub opcode: u8,
+    /// 有效响应码：头部标志低4位，存在OPT记录时拼接其扩展响应码高8位
+    #[serde(serialize_with = "serialize_rcode")]
+    pub rcode: u16,
+    /// AA：权威应答
+    #[serde(rename = "aa")]
+    pub authoritative: bool,
+    /// TC：消息被截断
+    #[serde(rename = "tc")]
+    pub truncated: bool,
+    /// RD：期望递归
+    #[serde(rename = "rd")]
+    pub recursion_desired: bool,
+    /// RA：支持递归
+    #[serde(rename = "ra")]
+    pub recursion_available: bool,
+    /// EDNS OPT记录中的DO位，表示客户端需要DNSSEC记录
+    pub dnssec_ok: bool,
+    /// EDNS信息，无OPT记录时为None
+    pub edns: Option<EdnsInfo>,
+    /// 否定应答（NXDOMAIN/NODATA）的缓存时间，取权威部分SOA的TTL与MINIMUM中较小者（RFC 2308）
+    pub negative_ttl: Option<u32>,
+    /// 捕获该报文的接口（或抓包文件），无捕获上下文时为None
+    pub interface: Option<Arc<str>>,
+    /// 问题名疑似DNS隧道（标签过长且熵值过高），未启用隧道检测时恒为false
+    pub tunneling_suspected: bool,
+    /// 消息所属的解析链路，未配置解析器地址时为Unknown
+    pub role: DnsRole,
+    /// 客户端地址的反向DNS主机名，未启用反向DNS富化或尚未查到时为None
+    pub client_hostname: Option<Arc<str>>,
+}
+
+/// 空的UDP查询，地址为未指定地址，其余字段取零值，便于只设置关心的字段
+impl Default for DnsMessage {
+    fn default() -> Self {
+        DnsMessage {
+            transaction_id: 0,
+            message_type: DnsMessageType::Query,
+            questions: Vec::new(),
+            answers: Vec::new(),
+            authorities: Vec::new(),
+            additionals: Vec::new(),
+            timestamp: 0,
+            protocol: DnsProtocol::Udp,
+            raw: None,
+            unsolicited: false,
+            unreachable: false,
+            src_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            dst_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            src_port: 0,
+            dst_port: 0,
+            opcode: 0,
+            rcode: 0,
+            authoritative: false,
+            truncated: false,
+            recursion_desired: false,
+            recursion_available: false,
+            dnssec_ok: false,
+            edns: None,
+            negative_ttl: None,
+            interface: None,
+            tunneling_suspected: false,
+            role: DnsRole::Unknown,
+            client_hostname: None,
+        }
+    }
+}
+
+/// EDNS信息（OPT伪记录，RFC 6891）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EdnsInfo {
+    /// 通告的UDP负载大小（CLASS字段）
+    pub udp_payload_size: u16,
+    /// 扩展响应码高8位（TTL字段最高字节）
+    pub extended_rcode: u8,
+    /// EDNS版本
+    pub version: u8,
+    /// DO位
+    #[serde(rename = "do")]
+    pub dnssec_ok: bool,
+}
+
+/// 响应码名称（RFC 1035/2136/6891/8945/7873），未知响应码表示为`RCODE<n>`
+pub fn rcode_name(rcode: u16) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        6 => "YXDOMAIN".to_string(),
+        7 => "YXRRSET".to_string(),
+        8 => "NXRRSET".to_string(),
+        9 => "NOTAUTH".to_string(),
+        10 => "NOTZONE".to_string(),
+        16 => "BADVERS".to_string(),
+        17 => "BADKEY".to_string(),
+        18 => "BADTIME".to_string(),
+        19 => "BADMODE".to_string(),
+        20 => "BADNAME".to_string(),
+        21 => "BADALG".to_string(),
+        22 => "BADTRUNC".to_string(),
+        23 => "BADCOOKIE".to_string(),
+        other => format!("RCODE{}", other),
+    }
+}
+
+/// 响应码序列化为名称
+fn serialize_rcode<S: Serializer>(rcode: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&rcode_name(*rcode))
 }
 
 /// DNS协议类型
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub enum DnsProtocol {
     Udp,
     Tcp,
     Dot,
     Doh,
     Doq,
+    /// 组播DNS（RFC 6762），报文格式与DNS相同
+    Mdns,
+}
+
+impl DnsProtocol {
+    /// 统计键和输出中使用的小写名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            DnsProtocol::Udp => "udp",
+            DnsProtocol::Tcp => "tcp",
+            DnsProtocol::Dot => "dot",
+            DnsProtocol::Doh => "doh",
+            DnsProtocol::Doq => "doq",
+            DnsProtocol::Mdns => "mdns",
+        }
+    }
 }
 
 /// DNS问题记录
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DnsQuestion {
     pub name: String,
     pub record_type: DnsRecordType,
-    pub class: u16,
+    pub class: DnsClass,
+    /// mDNS问题class最高位（QU），请求单播响应，普通DNS恒为false
+    pub unicast_response: bool,
+    /// 问题名在原始报文中的偏移，不是从报文解析出的问题为None
+    #[serde(skip)]
+    pub offset: Option<usize>,
 }
 
 /// DNS应答记录
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DnsAnswer {
     pub name: String,
     pub record_type: DnsRecordType,
-    pub class: u16,
+    pub class: DnsClass,
+    /// mDNS记录class最高位，要求接收方刷新缓存中的同名记录，普通DNS恒为false
+    pub cache_flush: bool,
     pub ttl: u32,
+    #[serde(skip)]
     pub data: Vec<u8>,
+    /// 序列化为`data`字段
+    #[serde(rename = "data")]
     pub data_str: String,
 }
 
 /// DNS解析器特征
 pub trait DnsParser {
-    fn parse(&mut self, data: &[u8], stats: &mut StatsCounter) -> Option<DnsMessage>;
+    fn parse(&mut self, data: &[u8], stats: &StatsCounter) -> Option<DnsMessage>;
     fn protocol_type(&self) -> DnsProtocol;
+
+    /// 解析并返回失败原因，未单独实现的解析器只给出协议类型
+    fn try_parse(&mut self, data: &[u8], stats: &StatsCounter) -> crate::error::Result<DnsMessage> {
+        self.parse(data, stats).ok_or_else(|| {
+            crate::error::Error::Parse(format!("{:?} message could not be parsed", self.protocol_type()))
+        })
+    }
 }
\ No newline at end of file
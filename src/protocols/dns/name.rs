@@ -0,0 +1,145 @@
+//! 域名标签迭代
+//! 直接在报文上遍历域名标签，不构造String，用于高性能的名称匹配
+
+/// 最大压缩指针跳转次数
+const MAX_JUMPS: usize = 10;
+
+/// 域名标签迭代器
+///
+/// 按从左到右的顺序返回各标签的原始字节，遇到压缩指针时跟随跳转。
+/// 指针必须指向当前片段之前，跳转次数也有上限，畸形报文不会导致死循环。
+/// 迭代结束后可通过`is_malformed`判断是否因报文畸形而提前终止。
+pub struct LabelIter<'a> {
+    /// 报文数据
+    data: &'a [u8],
+    /// 当前位置
+    pos: usize,
+    /// 当前片段起点，压缩指针必须指向它之前
+    segment_start: usize,
+    /// 已跳转次数
+    jumps: usize,
+    /// 是否已结束
+    done: bool,
+    /// 是否因畸形报文终止
+    malformed: bool,
+}
+
+impl<'a> LabelIter<'a> {
+    /// 从报文指定偏移处开始遍历域名
+    pub fn new(data: &'a [u8], offset: usize) -> Self {
+        LabelIter {
+            data,
+            pos: offset,
+            segment_start: offset,
+            jumps: 0,
+            done: false,
+            malformed: false,
+        }
+    }
+
+    /// 是否因报文畸形而提前终止
+    pub fn is_malformed(&self) -> bool {
+        self.malformed
+    }
+
+    fn fail(&mut self) -> Option<&'a [u8]> {
+        self.done = true;
+        self.malformed = true;
+        None
+    }
+}
+
+impl<'a> Iterator for LabelIter<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let len = match self.data.get(self.pos) {
+                Some(&len) => len as usize,
+                None => return self.fail(),
+            };
+
+            if len & 0xC0 == 0xC0 {
+                let low = match self.data.get(self.pos + 1) {
+                    Some(&low) => low as usize,
+                    None => return self.fail(),
+                };
+                let pointer = ((len & 0x3F) << 8) | low;
+
+                // 只允许引用当前片段之前的数据，保证跳转目标严格递减
+                self.jumps += 1;
+                if pointer >= self.segment_start || self.jumps > MAX_JUMPS {
+                    return self.fail();
+                }
+                self.pos = pointer;
+                self.segment_start = pointer;
+                continue;
+            }
+
+            if len & 0xC0 != 0 {
+                // 保留的标签类型
+                return self.fail();
+            }
+
+            if len == 0 {
+                self.done = true;
+                return None;
+            }
+
+            let start = self.pos + 1;
+            let end = start + len;
+            if end > self.data.len() {
+                return self.fail();
+            }
+
+            self.pos = end;
+            return Some(&self.data[start..end]);
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 问题名www.example.com在偏移12，应答名mail+指针在偏移29
+    fn compressed_packet() -> Vec<u8> {
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(b"\x03www\x07example\x03com\x00");
+        data.extend_from_slice(b"\x04mail\xC0\x10");
+        data
+    }
+
+    #[test]
+    fn test_iterate_compressed_name() {
+        let data = compressed_packet();
+
+        let labels: Vec<&[u8]> = LabelIter::new(&data, 12).collect();
+        assert_eq!(labels, vec![&b"www"[..], b"example", b"com"]);
+
+        let mut iter = LabelIter::new(&data, 29);
+        let labels: Vec<&[u8]> = iter.by_ref().collect();
+        assert_eq!(labels, vec![&b"mail"[..], b"example", b"com"]);
+        assert!(!iter.is_malformed());
+    }
+
+    #[test]
+    fn test_pointer_loop_is_malformed() {
+        // 指针指向自身
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(b"\x03www\xC0\x0C");
+
+        let mut iter = LabelIter::new(&data, 12);
+        assert_eq!(iter.next(), Some(&b"www"[..]));
+        assert_eq!(iter.next(), None);
+        assert!(iter.is_malformed());
+
+        // 截断的标签
+        let data = b"\x05ab";
+        let mut iter = LabelIter::new(data, 0);
+        assert_eq!(iter.next(), None);
+        assert!(iter.is_malformed());
+    }
+}
@@ -0,0 +1,195 @@
+//! 应答RDATA解码
+//! 按记录类型注册解码器，把RDATA转换为可读文本；内置类型同样以解码器注册，
+//! 新类型只需注册解码器，不必修改应答解析流程
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::protocols::dns::udp::parse_domain_name;
+use crate::protocols::dns::{DnsRecordType, LabelEncoding};
+
+/// RDATA解码器
+pub trait RdataDecoder: Send + Sync {
+    /// 解码一条记录的RDATA
+    ///
+    /// `data`为RDATA，`packet`为完整DNS报文，`offset`为RDATA在报文中的偏移，
+    /// 用于解析其中的压缩域名。无法解码时返回None，记录按数据长度显示。
+    fn decode(&self, rtype: u16, data: &[u8], packet: &[u8], offset: usize) -> Option<String>;
+}
+
+impl<F> RdataDecoder for F
+where
+    F: Fn(u16, &[u8], &[u8], usize) -> Option<String> + Send + Sync,
+{
+    fn decode(&self, rtype: u16, data: &[u8], packet: &[u8], offset: usize) -> Option<String> {
+        self(rtype, data, packet, offset)
+    }
+}
+
+/// 按记录类型索引的解码器表
+#[derive(Clone, Default)]
+pub struct RdataRegistry {
+    decoders: HashMap<u16, Arc<dyn RdataDecoder>>,
+    /// 仍由内置解码器处理的类型，重新注册内置解码器时只替换这些类型
+    builtin: HashSet<u16>,
+}
+
+impl RdataRegistry {
+    /// 创建空的解码器表
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建注册了内置解码器的解码器表
+    pub fn builtin(encoding: LabelEncoding, max_labels: usize, max_name_length: usize) -> Self {
+        let mut registry = Self::new();
+        registry.register_builtin(encoding, max_labels, max_name_length);
+        registry
+    }
+
+    /// 注册A、AAAA、CNAME、NS、PTR、MX、SRV、TXT、SOA的内置解码器
+    ///
+    /// RDATA中的域名按给定的编码方式解码，按给定的标签数和长度上限检查。
+    /// 已注册自定义解码器的类型保持不变，可随时调整内置解码器的配置。
+    pub fn register_builtin(&mut self, encoding: LabelEncoding, max_labels: usize, max_name_length: usize) {
+        let decoder: Arc<dyn RdataDecoder> = Arc::new(BuiltinDecoder {
+            encoding,
+            max_labels,
+            max_name_length,
+        });
+        for record_type in [
+            DnsRecordType::A,
+            DnsRecordType::AAAA,
+            DnsRecordType::CNAME,
+            DnsRecordType::NS,
+            DnsRecordType::PTR,
+            DnsRecordType::MX,
+            DnsRecordType::SRV,
+            DnsRecordType::TXT,
+            DnsRecordType::SOA,
+        ] {
+            let rtype = u16::from(record_type);
+            if !self.decoders.contains_key(&rtype) || self.builtin.contains(&rtype) {
+                self.decoders.insert(rtype, Arc::clone(&decoder));
+                self.builtin.insert(rtype);
+            }
+        }
+    }
+
+    /// 注册解码器，替换同类型已有的解码器
+    pub fn register(&mut self, rtype: u16, decoder: Arc<dyn RdataDecoder>) {
+        self.decoders.insert(rtype, decoder);
+        self.builtin.remove(&rtype);
+    }
+
+    /// 解码RDATA，未注册该类型或解码器放弃时返回None
+    pub fn decode(&self, rtype: u16, data: &[u8], packet: &[u8], offset: usize) -> Option<String> {
+        self.decoders.get(&rtype)?.decode(rtype, data, packet, offset)
+    }
+}
+
+/// 内置记录类型解码器
+struct BuiltinDecoder {
+    /// RDATA中域名的标签编码方式
+    encoding: LabelEncoding,
+    /// RDATA中域名的最大标签数
+    max_labels: usize,
+    /// RDATA中域名的最大长度（线上格式字节数）
+    max_name_length: usize,
+}
+
+impl BuiltinDecoder {
+    /// 按配置的编码方式和上限解析RDATA中的域名
+    fn parse_name(&self, packet: &[u8], offset: usize) -> Option<(String, usize)> {
+        parse_domain_name(packet, offset, self.encoding, self.max_labels, self.max_name_length).ok()
+    }
+
+    /// 解析RDATA中的域名，未压缩部分超出RDATA末尾`end`时返回None
+    fn parse_rdata_name(&self, packet: &[u8], offset: usize, end: usize) -> Option<(String, usize)> {
+        self.parse_name(packet, offset).filter(|&(_, next)| next <= end)
+    }
+
+    /// 解析TXT记录数据：一个或多个`<长度><字节>`字符串，按RFC 7208拼接，长度越界时返回None
+    fn parse_txt(rdata: &[u8]) -> Option<String> {
+        let mut text = Vec::with_capacity(rdata.len());
+        let mut offset = 0;
+        while offset < rdata.len() {
+            let len = rdata[offset] as usize;
+            let chunk = rdata.get(offset + 1..offset + 1 + len)?;
+            text.extend_from_slice(chunk);
+            offset += 1 + len;
+        }
+        Some(String::from_utf8_lossy(&text).into_owned())
+    }
+
+    /// 解析SOA记录数据：mname、rname两个域名和serial、refresh、retry、expire、minimum五个32位整数
+    fn parse_soa(&self, packet: &[u8], start: usize, end: usize) -> Option<String> {
+        let (mname, offset) = self.parse_rdata_name(packet, start, end)?;
+        let (rname, offset) = self.parse_rdata_name(packet, offset, end)?;
+        if offset + 20 > end {
+            return None;
+        }
+
+        let fields: Vec<String> = packet[offset..offset + 20]
+            .chunks(4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]).to_string())
+            .collect();
+        Some(format!("{}. {}. {}", mname, rname, fields.join(" ")))
+    }
+}
+
+impl RdataDecoder for BuiltinDecoder {
+    fn decode(&self, rtype: u16, data: &[u8], packet: &[u8], offset: usize) -> Option<String> {
+        let end = offset + data.len();
+        let decoded = match DnsRecordType::from(rtype) {
+            DnsRecordType::A => {
+                if data.len() == 4 {
+                    format!("{}.{}.{}.{}", data[0], data[1], data[2], data[3])
+                } else {
+                    String::from("Invalid A record")
+                }
+            }
+            DnsRecordType::AAAA => {
+                if data.len() == 16 {
+                    let parts: Vec<String> = data
+                        .chunks(2)
+                        .map(|b| format!("{:x}", u16::from_be_bytes([b[0], b[1]])))
+                        .collect();
+                    parts.join(":")
+                } else {
+                    String::from("Invalid AAAA record")
+                }
+            }
+            DnsRecordType::CNAME | DnsRecordType::NS | DnsRecordType::PTR => {
+                match self.parse_rdata_name(packet, offset, end) {
+                    Some((domain, _)) => domain,
+                    None => String::from("Invalid domain name"),
+                }
+            }
+            DnsRecordType::MX if data.len() >= 3 => {
+                let preference = u16::from_be_bytes([data[0], data[1]]);
+                match self.parse_rdata_name(packet, offset + 2, end) {
+                    Some((exchange, _)) => format!("{} {}", preference, exchange),
+                    None => String::from("Invalid MX record"),
+                }
+            }
+            DnsRecordType::SRV if data.len() >= 7 => {
+                let priority = u16::from_be_bytes([data[0], data[1]]);
+                let weight = u16::from_be_bytes([data[2], data[3]]);
+                let port = u16::from_be_bytes([data[4], data[5]]);
+                match self.parse_rdata_name(packet, offset + 6, end) {
+                    Some((target, _)) => format!("{} {} {} {}", priority, weight, port, target),
+                    None => String::from("Invalid SRV record"),
+                }
+            }
+            DnsRecordType::TXT => {
+                Self::parse_txt(data).unwrap_or_else(|| String::from("Invalid TXT record"))
+            }
+            DnsRecordType::SOA => self
+                .parse_soa(packet, offset, offset + data.len())
+                .unwrap_or_else(|| String::from("Invalid SOA record")),
+            _ => return None,
+        };
+        Some(decoded)
+    }
+}
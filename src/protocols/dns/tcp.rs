@@ -2,13 +2,23 @@
 //! 处理TCP流重组和DNS消息提取
 
 use crate::core::stats::StatsCounter;
-use crate::protocols::dns::{DnsMessage, DnsParser, DnsProtocol};
+use crate::protocols::dns::{DnsMessage, DnsMessageType, DnsParser, DnsProtocol};
 use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// IXFR查询类型
+const QTYPE_IXFR: u16 = 251;
+/// AXFR查询类型
+const QTYPE_AXFR: u16 = 252;
+/// 单条TCP DNS消息的最大长度（含2字节长度前缀）
+const MAX_TCP_MESSAGE: usize = u16::MAX as usize + 2;
 
 /// TCP会话状态
 struct TcpSession {
     buffer: Vec<u8>,
     last_seen: u64,
+    /// 区域传送响应流，按单条消息限制缓冲而非整个会话
+    zone_transfer: bool,
 }
 
 /// TCP DNS解析器
@@ -16,7 +26,7 @@ pub struct TcpDnsParser {
     // 内部UDP解析器用于解析DNS消息
     udp_parser: super::udp::UdpDnsParser,
     // TCP会话跟踪
-    tcp_sessions: HashMap<(u32, u32, u16, u16), TcpSession>, // (src_ip, dst_ip, src_port, dst_port)
+    tcp_sessions: HashMap<(IpAddr, IpAddr, u16, u16), TcpSession>, // (src_ip, dst_ip, src_port, dst_port)
     // 配置
     max_packet_size: usize,
     max_sessions: usize,
@@ -28,7 +38,8 @@ impl TcpDnsParser {
     /// 创建新的TCP DNS解析器
     pub fn new(max_packet_size: usize, max_sessions: usize, session_timeout_ms: u64) -> Self {
         TcpDnsParser {
-            udp_parser: super::udp::UdpDnsParser::new(max_packet_size),
+            // 单条消息的长度由会话缓冲上限控制，区域传送的消息可以超过max_packet_size
+            udp_parser: super::udp::UdpDnsParser::new(u16::MAX as usize).with_protocol(DnsProtocol::Tcp),
             tcp_sessions: HashMap::with_capacity(max_sessions),
             max_packet_size,
             max_sessions,
@@ -37,6 +48,12 @@ impl TcpDnsParser {
         }
     }
 
+    /// 承载在TCP之上的协议（如DoT），解析计数记在`dns.<协议>.*`下
+    pub fn with_protocol(mut self, protocol: DnsProtocol) -> Self {
+        self.udp_parser = self.udp_parser.with_protocol(protocol);
+        self
+    }
+
     /// 更新当前时间
     pub fn update_time(&mut self, time_ms: u64) {
         self.current_time_ms = time_ms;
@@ -49,20 +66,8 @@ impl TcpDnsParser {
         self.tcp_sessions.retain(|_, session| session.last_seen > expired_time);
     }
 
-    /// 处理TCP段
-    pub fn process_tcp_segment(&mut self, 
-                              src_ip: u32, 
-                              dst_ip: u32, 
-                              src_port: u16, 
-                              dst_port: u16, 
-                              data: &[u8], 
-                              stats: &mut StatsCounter) -> Vec<DnsMessage> {
-        let mut results = Vec::new();
-        
-        // 会话标识
-        let session_id = (src_ip, dst_ip, src_port, dst_port);
-        
-        // 在闭包外先做清理
+    /// 会话表已满时先清理过期会话，仍满则淘汰最久未活动的会话
+    fn make_room(&mut self) {
         if self.tcp_sessions.len() >= self.max_sessions {
             self.cleanup_sessions();
             if self.tcp_sessions.len() >= self.max_sessions {
@@ -74,11 +79,72 @@ impl TcpDnsParser {
                 }
             }
         }
+    }
+
+    /// 当前跟踪的会话数
+    pub fn session_count(&self) -> usize {
+        self.tcp_sessions.len()
+    }
+
+    /// 收到SYN时初始化会话，丢弃同一四元组上旧连接残留的数据
+    pub fn open_session(&mut self, src_ip: IpAddr, dst_ip: IpAddr, src_port: u16, dst_port: u16, stats: &StatsCounter) {
+        self.make_room();
+        self.tcp_sessions.insert((src_ip, dst_ip, src_port, dst_port), TcpSession {
+            buffer: Vec::new(),
+            last_seen: self.current_time_ms,
+            zone_transfer: false,
+        });
+        stats.increment("dns.tcp.session_opened");
+    }
+
+    /// 收到FIN或RST时立即移除会话，不等待超时
+    ///
+    /// FIN只结束发送方方向，对端仍可继续发送；RST中止整个连接，两个方向一并移除。
+    /// 缓冲中未完成的消息随会话丢弃并计入`dns.tcp.discarded_bytes`。
+    pub fn close_session(&mut self,
+                         src_ip: IpAddr,
+                         dst_ip: IpAddr,
+                         src_port: u16,
+                         dst_port: u16,
+                         reset: bool,
+                         stats: &StatsCounter) {
+        let mut discarded = self.tcp_sessions
+            .remove(&(src_ip, dst_ip, src_port, dst_port))
+            .map_or(0, |session| session.buffer.len());
+        if reset {
+            discarded += self.tcp_sessions
+                .remove(&(dst_ip, src_ip, dst_port, src_port))
+                .map_or(0, |session| session.buffer.len());
+            stats.increment("dns.tcp.session_reset");
+        } else {
+            stats.increment("dns.tcp.session_closed");
+        }
+        if discarded > 0 {
+            stats.add("dns.tcp.discarded_bytes", discarded as u64);
+        }
+    }
+
+    /// 处理TCP段
+    pub fn process_tcp_segment(&mut self, 
+                              src_ip: IpAddr, 
+                              dst_ip: IpAddr, 
+                              src_port: u16, 
+                              dst_port: u16, 
+                              data: &[u8], 
+                              stats: &StatsCounter) -> Vec<DnsMessage> {
+        let mut results = Vec::new();
+        
+        // 会话标识
+        let session_id = (src_ip, dst_ip, src_port, dst_port);
+        
+        // 在闭包外先做清理
+        self.make_room();
 
         // 然后只在闭包里构造新会话
         let session = self.tcp_sessions.entry(session_id).or_insert_with(|| TcpSession {
             buffer: Vec::new(),
             last_seen: self.current_time_ms,
+            zone_transfer: false,
         });
         
         // 更新最后见到时间
@@ -87,14 +153,20 @@ impl TcpDnsParser {
         // 添加数据到缓冲区
         session.buffer.extend_from_slice(data);
         
-        // 检查缓冲区大小
-        if session.buffer.len() > self.max_packet_size {
+        // 检查缓冲区大小，区域传送流只需容纳一条未完成的消息
+        let buffer_limit = if session.zone_transfer {
+            MAX_TCP_MESSAGE.max(self.max_packet_size)
+        } else {
+            self.max_packet_size
+        };
+        if session.buffer.len() > buffer_limit {
             stats.increment("dns.tcp.buffer_overflow");
             session.buffer.clear();
             return results;
         }
         
-        // 处理缓冲区中的所有完整DNS消息
+        // 处理缓冲区中的所有完整DNS消息，每条完成即输出
+        let mut zone_transfer_flow = false;
         while session.buffer.len() >= 2 {
             // TCP中的DNS消息前两个字节是长度
             let message_length = u16::from_be_bytes([session.buffer[0], session.buffer[1]]) as usize;
@@ -105,9 +177,13 @@ impl TcpDnsParser {
                 let dns_data = &session.buffer[2..message_length + 2];
                 
                 // 解析DNS消息
-                if let Some(mut message) = self.udp_parser.parse(dns_data, stats) {
-                    // 修改协议类型
-                    message.protocol = DnsProtocol::Tcp;
+                if let Some(message) = self.udp_parser.parse(dns_data, stats) {
+                    if session.zone_transfer {
+                        stats.increment("dns.tcp.zone_transfer_messages");
+                    } else if Self::is_zone_transfer_query(&message) {
+                        stats.increment("dns.tcp.zone_transfer");
+                        zone_transfer_flow = true;
+                    }
                     results.push(message);
                 }
                 
@@ -119,12 +195,34 @@ impl TcpDnsParser {
             }
         }
         
+        // 标记反向流为区域传送响应
+        if zone_transfer_flow {
+            let reverse_id = (dst_ip, src_ip, dst_port, src_port);
+            let current_time_ms = self.current_time_ms;
+            self.tcp_sessions
+                .entry(reverse_id)
+                .or_insert_with(|| TcpSession {
+                    buffer: Vec::new(),
+                    last_seen: current_time_ms,
+                    zone_transfer: true,
+                })
+                .zone_transfer = true;
+        }
+
         results
     }
+
+    /// 是否为AXFR/IXFR查询
+    fn is_zone_transfer_query(message: &DnsMessage) -> bool {
+        matches!(message.message_type, DnsMessageType::Query)
+            && message.questions.first().is_some_and(|q| {
+                matches!(u16::from(q.record_type), QTYPE_AXFR | QTYPE_IXFR)
+            })
+    }
 }
 
 impl DnsParser for TcpDnsParser {
-    fn parse(&mut self, data: &[u8], stats: &mut StatsCounter) -> Option<DnsMessage> {
+    fn parse(&mut self, data: &[u8], stats: &StatsCounter) -> Option<DnsMessage> {
         // 注意：TCP解析器需要通过process_tcp_segment方法处理TCP段
         // 这个方法主要用于兼容DnsParser特征
         stats.increment("dns.tcp.direct_parse_attempt");
@@ -134,4 +232,118 @@ impl DnsParser for TcpDnsParser {
     fn protocol_type(&self) -> DnsProtocol {
         DnsProtocol::Tcp
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53));
+
+    /// 构造带长度前缀的DNS消息
+    fn frame(flags: u16, qtype: u16, answers: u16, rdata_len: usize) -> Vec<u8> {
+        let mut msg = vec![0x12, 0x34];
+        msg.extend_from_slice(&flags.to_be_bytes());
+        msg.extend_from_slice(&1u16.to_be_bytes());
+        msg.extend_from_slice(&answers.to_be_bytes());
+        msg.extend_from_slice(&[0, 0, 0, 0]);
+        msg.extend_from_slice(b"\x07example\x03com\x00");
+        msg.extend_from_slice(&qtype.to_be_bytes());
+        msg.extend_from_slice(&1u16.to_be_bytes());
+        for _ in 0..answers {
+            // 指向问题名的压缩指针，TXT记录
+            msg.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x10, 0x00, 0x01, 0, 0, 0x0E, 0x10]);
+            msg.extend_from_slice(&(rdata_len as u16).to_be_bytes());
+            msg.extend(std::iter::repeat(b'a').take(rdata_len));
+        }
+
+        let mut framed = (msg.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&msg);
+        framed
+    }
+
+    #[test]
+    fn test_axfr_response_stream() {
+        let mut parser = TcpDnsParser::new(4096, 16, 30_000);
+        let stats = StatsCounter::new();
+
+        let query = frame(0x0000, QTYPE_AXFR, 0, 0);
+        let messages = parser.process_tcp_segment(CLIENT, SERVER, 40000, 53, &query, &stats);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(stats.get("dns.tcp.zone_transfer"), 1);
+
+        // 三条响应消息，总长度超过普通会话的缓冲上限
+        let mut stream = Vec::new();
+        for _ in 0..3 {
+            stream.extend(frame(0x8400, QTYPE_AXFR, 8, 200));
+        }
+        assert!(stream.len() > 4096);
+
+        // 按小段送入，每条消息完成即输出
+        let mut emitted = 0;
+        for chunk in stream.chunks(1000) {
+            let messages = parser.process_tcp_segment(SERVER, CLIENT, 53, 40000, chunk, &stats);
+            for message in &messages {
+                assert!(matches!(message.message_type, DnsMessageType::Response));
+            }
+            emitted += messages.len();
+        }
+
+        assert_eq!(emitted, 3);
+        assert_eq!(stats.get("dns.tcp.zone_transfer_messages"), 3);
+        assert_eq!(stats.get("dns.tcp.buffer_overflow"), 0);
+    }
+
+    #[test]
+    fn test_zone_transfer_message_crosses_buffer_limit() {
+        let mut parser = TcpDnsParser::new(4096, 16, 30_000);
+        let stats = StatsCounter::new();
+
+        let query = frame(0x0000, QTYPE_AXFR, 0, 0);
+        parser.process_tcp_segment(CLIENT, SERVER, 40000, 53, &query, &stats);
+
+        // 单条响应消息本身就超过普通会话的缓冲上限
+        let response = frame(0x8400, QTYPE_AXFR, 8, 600);
+        assert!(response.len() > 4096);
+
+        let mut emitted = 0;
+        for chunk in response.chunks(1000) {
+            emitted += parser.process_tcp_segment(SERVER, CLIENT, 53, 40000, chunk, &stats).len();
+        }
+        assert_eq!(emitted, 1);
+        assert_eq!(stats.get("dns.tcp.zone_transfer_messages"), 1);
+        assert_eq!(stats.get("dns.tcp.buffer_overflow"), 0);
+
+        // 没有区域传送查询的流仍按普通上限丢弃
+        let mut emitted = 0;
+        for chunk in response.chunks(1000) {
+            emitted += parser.process_tcp_segment(SERVER, CLIENT, 53, 40001, chunk, &stats).len();
+        }
+        assert_eq!(emitted, 0);
+        assert_eq!(stats.get("dns.tcp.buffer_overflow"), 1);
+    }
+
+    #[test]
+    fn test_counts_under_carrier_protocol() {
+        let query = frame(0x0100, 1, 0, 0);
+
+        let mut parser = TcpDnsParser::new(4096, 16, 30_000);
+        let stats = StatsCounter::new();
+        let messages = parser.process_tcp_segment(CLIENT, SERVER, 40000, 53, &query, &stats);
+        assert!(matches!(messages[0].protocol, DnsProtocol::Tcp));
+        assert_eq!(stats.get("dns.tcp.parsed"), 1);
+        assert_eq!(stats.get("dns.tcp.bytes"), query.len() as u64 - 2);
+        assert_eq!(stats.get("dns.tcp.query"), 1);
+        assert_eq!(stats.get("dns.udp.parsed"), 0);
+        assert_eq!(stats.get("dns.udp.bytes"), 0);
+
+        let mut parser = TcpDnsParser::new(4096, 16, 30_000).with_protocol(DnsProtocol::Dot);
+        let stats = StatsCounter::new();
+        let messages = parser.process_tcp_segment(CLIENT, SERVER, 40000, 853, &query, &stats);
+        assert!(matches!(messages[0].protocol, DnsProtocol::Dot));
+        assert_eq!(stats.get("dns.dot.parsed"), 1);
+        assert_eq!(stats.get("dns.tcp.parsed"), 0);
+    }
+}
@@ -1,13 +1,76 @@
 //! UDP DNS协议解析实现
 //! 处理标准DNS消息解析
 
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
 use crate::core::stats::StatsCounter;
-use crate::protocols::dns::{DnsAnswer, DnsMessage, DnsMessageType, DnsParser, DnsProtocol, DnsQuestion, DnsRecordType};
+use crate::error::{Error, Result};
+use crate::protocols::dns::rdata::{RdataDecoder, RdataRegistry};
+use crate::protocols::dns::{DnsAnswer, DnsClass, DnsMessage, DnsMessageType, DnsParser, DnsProtocol, DnsQuestion, DnsRecordType, DnsRole, EdnsInfo, LabelEncoding};
+
+/// OPT记录TTL中的DO位
+const EDNS_DO_BIT: u32 = 0x8000;
+/// 单个域名的最大标签数，255字节的域名最多容纳127个标签
+pub(super) const DEFAULT_MAX_LABELS: usize = 127;
+/// 域名最大长度，按线上格式计算（含各标签的长度字节和结尾的根标签）
+pub(super) const DEFAULT_MAX_NAME_LENGTH: usize = 255;
+/// 单个标签的最大长度
+const MAX_LABEL_LENGTH: usize = 63;
+/// mDNS中class字段的最高位：问题中为QU（请求单播响应），记录中为缓存刷新
+const MDNS_CLASS_FLAG: u16 = 0x8000;
 
 /// UDP DNS解析器
 pub struct UdpDnsParser {
     // 配置
     max_packet_size: usize,
+    label_encoding: LabelEncoding,
+    parse_questions_only: bool,
+    keep_raw: bool,
+    ttl_histograms: bool,
+    rdata_decoders: RdataRegistry,
+    max_labels: usize,
+    max_name_length: usize,
+    protocol: DnsProtocol,
+    counters: ProtocolCounters,
+}
+
+/// 按协议区分的解析计数器名
+struct ProtocolCounters {
+    parsed: String,
+    bytes: String,
+    query: String,
+    response: String,
+    invalid_size: String,
+    too_many_labels: String,
+    name_too_long: String,
+    bad_rdlength: String,
+    parse_question_failed: String,
+    parse_failed: String,
+    parse_answer_failed: String,
+    parse_authority_failed: String,
+    parse_additional_failed: String,
+}
+
+impl ProtocolCounters {
+    fn new(protocol: DnsProtocol) -> Self {
+        let key = |name: &str| format!("dns.{}.{}", protocol.name(), name);
+        ProtocolCounters {
+            parsed: key("parsed"),
+            bytes: key("bytes"),
+            query: key("query"),
+            response: key("response"),
+            invalid_size: key("invalid_size"),
+            too_many_labels: key("too_many_labels"),
+            name_too_long: key("name_too_long"),
+            bad_rdlength: key("bad_rdlength"),
+            parse_question_failed: key("parse_question_failed"),
+            parse_failed: key("parse_failed"),
+            parse_answer_failed: key("parse_answer_failed"),
+            parse_authority_failed: key("parse_authority_failed"),
+            parse_additional_failed: key("parse_additional_failed"),
+        }
+    }
 }
 
 impl UdpDnsParser {
@@ -15,104 +78,169 @@ impl UdpDnsParser {
     pub fn new(max_packet_size: usize) -> Self {
         UdpDnsParser {
             max_packet_size,
+            label_encoding: LabelEncoding::default(),
+            parse_questions_only: false,
+            keep_raw: false,
+            ttl_histograms: false,
+            rdata_decoders: RdataRegistry::builtin(LabelEncoding::default(), DEFAULT_MAX_LABELS, DEFAULT_MAX_NAME_LENGTH),
+            max_labels: DEFAULT_MAX_LABELS,
+            max_name_length: DEFAULT_MAX_NAME_LENGTH,
+            protocol: DnsProtocol::Udp,
+            counters: ProtocolCounters::new(DnsProtocol::Udp),
         }
     }
 
-    /// 解析域名
-    fn parse_domain_name(&self, data: &[u8], offset: usize) -> Option<(String, usize)> {
-        let mut name = String::new();
-        let mut pos = offset;
-        let mut jumped = false;
-        let mut jump_count = 0;
-        let max_jumps = 10; // 防止无限循环
-        let mut next_pos = pos;
-
-        while pos < data.len() {
-            // 检查是否是指针
-            if (data[pos] & 0xC0) == 0xC0 {
-                if pos + 1 >= data.len() {
-                    return None; // 数据不足
-                }
+    /// 解析结果所属的协议，`dns.<协议>.parsed`、`bytes`、`query`、`response`及解析错误按该协议计数
+    ///
+    /// mDNS的class最高位不属于类值，拆为问题的`unicast_response`和记录的`cache_flush`。
+    pub fn with_protocol(mut self, protocol: DnsProtocol) -> Self {
+        self.protocol = protocol;
+        self.counters = ProtocolCounters::new(protocol);
+        self
+    }
 
-                if !jumped {
-                    next_pos = pos + 2;
-                }
+    /// mDNS时拆出class最高位，其余协议原样返回
+    fn split_class(&self, class: u16) -> (DnsClass, bool) {
+        match self.protocol {
+            DnsProtocol::Mdns => (DnsClass::from(class & !MDNS_CLASS_FLAG), class & MDNS_CLASS_FLAG != 0),
+            _ => (DnsClass::from(class), false),
+        }
+    }
 
-                // 计算指针位置
-                let pointer = ((data[pos] as usize & 0x3F) << 8) | data[pos + 1] as usize;
-                pos = pointer;
-                jumped = true;
-                jump_count += 1;
+    /// 自定义域名标签编码方式，RDATA中的域名同样按该方式编码
+    ///
+    /// 只影响内置解码器，之前注册的自定义解码器保持不变。
+    pub fn with_label_encoding(mut self, encoding: LabelEncoding) -> Self {
+        self.label_encoding = encoding;
+        self.register_builtin_decoders();
+        self
+    }
 
-                if jump_count > max_jumps {
-                    return None; // 防止无限循环
-                }
-            } else {
-                // 标准标签
-                let len = data[pos] as usize;
-                if len == 0 {
-                    break; // 域名结束
-                }
+    /// 按当前的编码方式和域名上限重新注册内置RDATA解码器
+    fn register_builtin_decoders(&mut self) {
+        self.rdata_decoders
+            .register_builtin(self.label_encoding, self.max_labels, self.max_name_length);
+    }
 
-                pos += 1;
-                if pos + len > data.len() {
-                    return None; // 数据不足
-                }
+    /// 为记录类型注册RDATA解码器，替换同类型已有的解码器
+    pub fn with_rdata_decoder(mut self, record_type: u16, decoder: Arc<dyn RdataDecoder>) -> Self {
+        self.rdata_decoders.register(record_type, decoder);
+        self
+    }
 
-                // 添加标签到域名
-                if !name.is_empty() {
-                    name.push('.');
-                }
+    /// 问题名和记录名的最大标签数，超出时拒绝整条消息并计入`dns.<协议>.too_many_labels`
+    ///
+    /// 在拼接域名前检查，过深的域名不会占用内存。内置解码器解析的RDATA域名超出上限时该记录显示为无效。
+    pub fn with_max_labels(mut self, max_labels: usize) -> Self {
+        self.max_labels = max_labels;
+        self.register_builtin_decoders();
+        self
+    }
 
-                // 将标签添加到域名
-                name.push_str(
-                    &String::from_utf8_lossy(&data[pos..pos + len]).to_string()
-                );
+    /// 问题名和记录名的最大长度（线上格式字节数），超出时拒绝整条消息并计入`dns.<协议>.name_too_long`
+    ///
+    /// 超过63字节的标签同样按此计数。内置解码器解析的RDATA域名超出上限时该记录显示为无效。
+    pub fn with_max_name_length(mut self, max_name_length: usize) -> Self {
+        self.max_name_length = max_name_length;
+        self.register_builtin_decoders();
+        self
+    }
 
-                pos += len;
-            }
-        }
+    /// 快速模式：只解析头部和第一个问题，跳过应答部分
+    pub fn with_parse_questions_only(mut self, enabled: bool) -> Self {
+        self.parse_questions_only = enabled;
+        self
+    }
+
+    /// 是否在消息中保留原始DNS报文（dnstap等输出需要）
+    pub fn with_keep_raw(mut self, enabled: bool) -> Self {
+        self.keep_raw = enabled;
+        self
+    }
 
-        // 如果没有跳转，更新下一个位置
-        if !jumped {
-            next_pos = pos + 1;
+    /// 按记录类型统计应答TTL分布（ttl.a、ttl.aaaa、ttl.cname等直方图）
+    pub fn with_ttl_histograms(mut self, enabled: bool) -> Self {
+        self.ttl_histograms = enabled;
+        self
+    }
+
+    /// TTL直方图名称，未知类型不统计
+    fn ttl_histogram_key(record_type: DnsRecordType) -> Option<&'static str> {
+        match record_type {
+            DnsRecordType::A => Some("ttl.a"),
+            DnsRecordType::AAAA => Some("ttl.aaaa"),
+            DnsRecordType::CNAME => Some("ttl.cname"),
+            DnsRecordType::MX => Some("ttl.mx"),
+            DnsRecordType::NS => Some("ttl.ns"),
+            DnsRecordType::PTR => Some("ttl.ptr"),
+            DnsRecordType::SOA => Some("ttl.soa"),
+            DnsRecordType::SRV => Some("ttl.srv"),
+            DnsRecordType::TXT => Some("ttl.txt"),
+            DnsRecordType::OPT | DnsRecordType::Other(_) => None,
         }
+    }
 
-        Some((name, next_pos))
+    /// 按配置的编码方式、标签数和长度上限解析域名
+    fn parse_name(&self, data: &[u8], offset: usize, stats: &StatsCounter) -> Result<(String, usize)> {
+        parse_domain_name(data, offset, self.label_encoding, self.max_labels, self.max_name_length).map_err(|e| {
+            match e {
+                NameError::TooManyLabels(_) => stats.increment(&self.counters.too_many_labels),
+                NameError::NameTooLong(_) | NameError::LabelTooLong(_) => stats.increment(&self.counters.name_too_long),
+                NameError::Malformed(_) => {}
+            }
+            Error::from(e)
+        })
     }
 
     /// 解析DNS问题部分
-    fn parse_question(&self, data: &[u8], offset: usize) -> Option<(DnsQuestion, usize)> {
+    fn parse_question(&self, data: &[u8], offset: usize, stats: &StatsCounter) -> Result<(DnsQuestion, usize)> {
         // 解析域名
-        let (name, offset) = self.parse_domain_name(data, offset)?;
+        let name_offset = offset;
+        let (name, offset) = self.parse_name(data, offset, stats)?;
 
         // 确保有足够的数据
         if offset + 4 > data.len() {
-            return None;
+            return Err(Error::Parse(format!("truncated question at offset {}", offset)));
         }
 
         // 解析类型和类
         let record_type = u16::from_be_bytes([data[offset], data[offset + 1]]);
-        let class = u16::from_be_bytes([data[offset + 2], data[offset + 3]]);
+        let (class, unicast_response) = self.split_class(u16::from_be_bytes([data[offset + 2], data[offset + 3]]));
 
-        Some((
+        Ok((
             DnsQuestion {
                 name,
                 record_type: DnsRecordType::from(record_type),
                 class,
+                unicast_response,
+                offset: Some(name_offset),
             },
             offset + 4,
         ))
     }
 
+    /// 否定应答的缓存时间：权威部分SOA记录的TTL与MINIMUM字段取较小者
+    fn negative_ttl(authorities: &[DnsAnswer]) -> Option<u32> {
+        let soa = authorities
+            .iter()
+            .find(|record| record.record_type == DnsRecordType::SOA)?;
+        // MINIMUM为RDATA最后4字节，前面的域名可能被压缩，不依赖其长度
+        if soa.data.len() < 22 {
+            return None;
+        }
+        let minimum = &soa.data[soa.data.len() - 4..];
+        let minimum = u32::from_be_bytes([minimum[0], minimum[1], minimum[2], minimum[3]]);
+        Some(soa.ttl.min(minimum))
+    }
+
     /// 解析DNS应答部分
-    fn parse_answer(&self, data: &[u8], offset: usize) -> Option<(DnsAnswer, usize)> {
+    fn parse_answer(&self, data: &[u8], offset: usize, stats: &StatsCounter) -> Result<(DnsAnswer, usize)> {
         // 解析域名
-        let (name, offset) = self.parse_domain_name(data, offset)?;
+        let (name, offset) = self.parse_name(data, offset, stats)?;
 
         // 确保有足够的数据
         if offset + 10 > data.len() {
-            return None;
+            return Err(Error::Parse(format!("truncated record header at offset {}", offset)));
         }
 
         // 解析类型、类、TTL和数据长度
@@ -128,48 +256,34 @@ impl UdpDnsParser {
 
         // 确保有足够的数据
         if offset + 10 + data_len > data.len() {
-            return None;
+            return Err(Error::Parse(format!(
+                "record data length {} overruns packet at offset {}",
+                data_len,
+                offset + 10
+            )));
         }
 
+        // OPT伪记录的class是UDP负载大小，不拆分
+        let (class, cache_flush) = match DnsRecordType::from(record_type) {
+            DnsRecordType::OPT => (DnsClass::from(class), false),
+            _ => self.split_class(class),
+        };
+
         // 提取数据
         let record_data = data[offset + 10..offset + 10 + data_len].to_vec();
         
-        // 尝试将数据转换为字符串表示
-        let data_str = match DnsRecordType::from(record_type) {
-            DnsRecordType::A => {
-                if record_data.len() == 4 {
-                    format!("{}.{}.{}.{}", record_data[0], record_data[1], record_data[2], record_data[3])
-                } else {
-                    String::from("Invalid A record")
-                }
-            },
-            DnsRecordType::AAAA => {
-                if record_data.len() == 16 {
-                    let mut parts = Vec::new();
-                    for i in 0..8 {
-                        let value = u16::from_be_bytes([record_data[i*2], record_data[i*2+1]]);
-                        parts.push(format!("{:x}", value));
-                    }
-                    parts.join(":")
-                } else {
-                    String::from("Invalid AAAA record")
-                }
-            },
-            DnsRecordType::CNAME | DnsRecordType::NS | DnsRecordType::PTR => {
-                if let Some((domain, _)) = self.parse_domain_name(&data, offset + 10) {
-                    domain
-                } else {
-                    String::from("Invalid domain name")
-                }
-            },
-            _ => format!("<{} bytes of data>", record_data.len()),
-        };
+        // 按记录类型交给注册的解码器，无解码器或解码器放弃时显示数据长度
+        let data_str = self
+            .rdata_decoders
+            .decode(record_type, &record_data, data, offset + 10)
+            .unwrap_or_else(|| format!("<{} bytes of data>", record_data.len()));
 
-        Some((
+        Ok((
             DnsAnswer {
                 name,
                 record_type: DnsRecordType::from(record_type),
                 class,
+                cache_flush,
                 ttl,
                 data: record_data,
                 data_str,
@@ -179,12 +293,189 @@ impl UdpDnsParser {
     }
 }
 
+impl UdpDnsParser {
+    /// 解析一个资源记录部分，offset随之前进；记录解析失败时返回错误
+    ///
+    /// 定长类型RDLENGTH不符时不保留畸形数据，仍按RDLENGTH跳过该记录。
+    fn parse_section(
+        &self,
+        data: &[u8],
+        offset: &mut usize,
+        count: usize,
+        records: &mut Vec<DnsAnswer>,
+        stats: &StatsCounter,
+    ) -> Result<()> {
+        for _ in 0..count {
+            let (record, new_offset) = self.parse_answer(data, *offset, stats)?;
+            *offset = new_offset;
+
+            match record.record_type.fixed_rdlength() {
+                Some(expected) if expected != record.data.len() => {
+                    stats.increment(&self.counters.bad_rdlength);
+                }
+                _ => records.push(record),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 按配置的编码方式追加标签
+fn push_label(encoding: LabelEncoding, name: &mut String, label: &[u8]) {
+    match encoding {
+        LabelEncoding::Lossy => name.push_str(&String::from_utf8_lossy(label)),
+        LabelEncoding::Escaped => {
+            for &byte in label {
+                match byte {
+                    b'.' | b'\\' => {
+                        name.push('\\');
+                        name.push(byte as char);
+                    }
+                    0x21..=0x7E => name.push(byte as char),
+                    _ => name.push_str(&format!("\\{:03}", byte)),
+                }
+            }
+        }
+    }
+}
+
+/// 域名解析失败原因
+#[derive(Debug)]
+pub(super) enum NameError {
+    /// 报文格式错误
+    Malformed(String),
+    /// 标签数超过上限，附带域名起始偏移
+    TooManyLabels(usize),
+    /// 域名长度超过上限，附带域名起始偏移
+    NameTooLong(usize),
+    /// 标签长度超过63字节，附带标签偏移
+    LabelTooLong(usize),
+}
+
+impl From<NameError> for Error {
+    fn from(err: NameError) -> Self {
+        match err {
+            NameError::Malformed(msg) => Error::Parse(msg),
+            NameError::TooManyLabels(offset) => Error::Parse(format!("too many labels in name at offset {}", offset)),
+            NameError::NameTooLong(offset) => Error::Parse(format!("name too long at offset {}", offset)),
+            NameError::LabelTooLong(offset) => Error::Parse(format!("label longer than 63 bytes at offset {}", offset)),
+        }
+    }
+}
+
+/// 解析域名，返回域名和其后的偏移
+///
+/// 标签数超过`max_labels`或线上格式长度超过`max_length`时在拼接前返回错误。
+pub(super) fn parse_domain_name(
+    data: &[u8],
+    offset: usize,
+    encoding: LabelEncoding,
+    max_labels: usize,
+    max_length: usize,
+) -> std::result::Result<(String, usize), NameError> {
+    let mut name = String::new();
+    let mut labels = 0;
+    // 已解析标签的线上长度，计入结尾的根标签
+    let mut length = 1;
+    let mut pos = offset;
+    // 当前片段起点，压缩指针必须指向它之前
+    let mut segment_start = offset;
+    let mut jumped = false;
+    let mut jump_count = 0;
+    let max_jumps = 10; // 防止无限循环
+    let mut next_pos = pos;
+
+    while pos < data.len() {
+        // 检查是否是指针
+        if (data[pos] & 0xC0) == 0xC0 {
+            if pos + 1 >= data.len() {
+                return Err(NameError::Malformed(format!("truncated compression pointer at offset {}", pos)));
+            }
+
+            if !jumped {
+                next_pos = pos + 2;
+            }
+
+            // 计算指针位置
+            let pointer = ((data[pos] as usize & 0x3F) << 8) | data[pos + 1] as usize;
+            // RFC 1035要求指针指向之前出现过的域名。只允许引用当前片段起点之前的数据，
+            // 跳转目标严格递减，指向片段内、自身之后或报文外的位置都视为畸形，不会形成环
+            if pointer >= segment_start {
+                return Err(NameError::Malformed(format!(
+                    "compression pointer to {} is not backward at offset {}",
+                    pointer, pos
+                )));
+            }
+            pos = pointer;
+            segment_start = pointer;
+            jumped = true;
+            jump_count += 1;
+
+            if jump_count > max_jumps {
+                return Err(NameError::Malformed(format!("compression loop exceeded at offset {}", offset)));
+            }
+        } else {
+            // 标准标签
+            let len = data[pos] as usize;
+            if len == 0 {
+                break; // 域名结束
+            }
+
+            // 0x40和0x80开头的扩展标签类型未被使用，按超长标签处理
+            if len > MAX_LABEL_LENGTH {
+                return Err(NameError::LabelTooLong(pos));
+            }
+
+            labels += 1;
+            if labels > max_labels {
+                return Err(NameError::TooManyLabels(offset));
+            }
+
+            length += 1 + len;
+            if length > max_length {
+                return Err(NameError::NameTooLong(offset));
+            }
+
+            pos += 1;
+            if pos + len > data.len() {
+                return Err(NameError::Malformed(format!("truncated label at offset {}", pos - 1)));
+            }
+
+            // 添加标签到域名
+            if !name.is_empty() {
+                name.push('.');
+            }
+
+            // 将标签添加到域名
+            push_label(encoding, &mut name, &data[pos..pos + len]);
+
+            pos += len;
+        }
+    }
+
+    if pos >= data.len() {
+        return Err(NameError::Malformed(format!("unterminated name at offset {}", offset)));
+    }
+
+    // 如果没有跳转，更新下一个位置
+    if !jumped {
+        next_pos = pos + 1;
+    }
+
+    Ok((name, next_pos))
+}
+
 impl DnsParser for UdpDnsParser {
-    fn parse(&mut self, data: &[u8], stats: &mut StatsCounter) -> Option<DnsMessage> {
+    fn parse(&mut self, data: &[u8], stats: &StatsCounter) -> Option<DnsMessage> {
+        self.try_parse(data, stats).ok()
+    }
+
+    fn try_parse(&mut self, data: &[u8], stats: &StatsCounter) -> Result<DnsMessage> {
         // 检查数据长度
         if data.len() < 12 || data.len() > self.max_packet_size {
-            stats.increment("dns.udp.invalid_size");
-            return None;
+            stats.increment(&self.counters.invalid_size);
+            return Err(Error::Parse(format!("invalid message size {}", data.len())));
         }
 
         // 解析DNS头部
@@ -202,61 +493,843 @@ impl DnsParser for UdpDnsParser {
             DnsMessageType::Query
         };
 
+        // 快速模式下只解析第一个问题
+        let questions_count = if self.parse_questions_only {
+            questions_count.min(1)
+        } else {
+            questions_count
+        };
+        let answers_count = if self.parse_questions_only { 0 } else { answers_count };
+
         // 解析问题部分
         let mut offset = 12;
         let mut questions = Vec::with_capacity(questions_count);
 
         for _ in 0..questions_count {
-            if let Some((question, new_offset)) = self.parse_question(data, offset) {
-                questions.push(question);
-                offset = new_offset;
-            } else {
-                stats.increment("dns.udp.parse_question_failed");
-                return None;
+            match self.parse_question(data, offset, stats) {
+                Ok((question, new_offset)) => {
+                    questions.push(question);
+                    offset = new_offset;
+                }
+                Err(e) => {
+                    stats.increment(&self.counters.parse_question_failed);
+                    return Err(e);
+                }
             }
         }
 
-        // 解析应答部分
+        // 快速模式下跳过权威和附加部分
+        let (authority_count, additional_count) = if self.parse_questions_only {
+            (0, 0)
+        } else {
+            (authority_count, additional_count)
+        };
+
+        // 依次解析应答、权威和附加部分，offset连续前进以便压缩指针正确解析
         let mut answers = Vec::with_capacity(answers_count);
+        let mut authorities = Vec::with_capacity(authority_count);
+        let mut additionals = Vec::with_capacity(additional_count);
 
-        for _ in 0..answers_count {
-            if let Some((answer, new_offset)) = self.parse_answer(data, offset) {
-                answers.push(answer);
-                offset = new_offset;
-            } else {
-                // 如果解析应答失败，但至少有问题部分，仍然返回消息
-                if !questions.is_empty() {
-                    stats.increment("dns.udp.parse_answer_failed");
-                    break;
-                } else {
-                    stats.increment("dns.udp.parse_failed");
-                    return None;
-                }
+        if let Err(e) = self.parse_section(data, &mut offset, answers_count, &mut answers, stats) {
+            // 如果解析应答失败，但至少有问题部分，仍然返回消息
+            if questions.is_empty() {
+                stats.increment(&self.counters.parse_failed);
+                return Err(e);
             }
+            stats.increment(&self.counters.parse_answer_failed);
+        } else if self.parse_section(data, &mut offset, authority_count, &mut authorities, stats).is_err() {
+            stats.increment(&self.counters.parse_authority_failed);
+        } else if self.parse_section(data, &mut offset, additional_count, &mut additionals, stats).is_err() {
+            stats.increment(&self.counters.parse_additional_failed);
+        }
+
+        // 附加部分的OPT伪记录：CLASS为UDP负载大小，TTL依次为扩展响应码、版本和标志位
+        let edns = additionals
+            .iter()
+            .find(|record| record.record_type == DnsRecordType::OPT)
+            .map(|opt| EdnsInfo {
+                udp_payload_size: u16::from(opt.class),
+                extended_rcode: (opt.ttl >> 24) as u8,
+                version: (opt.ttl >> 16) as u8,
+                dnssec_ok: opt.ttl & EDNS_DO_BIT != 0,
+            });
+        let dnssec_ok = edns.is_some_and(|edns| edns.dnssec_ok);
+        // 有效响应码：OPT扩展的高8位与头部低4位拼接为12位（RFC 6891 6.1.3）
+        let rcode = edns.map_or(0, |edns| (edns.extended_rcode as u16) << 4) | (flags & 0x000F);
+
+        let negative_ttl = if message_type == DnsMessageType::Response && answers.is_empty() {
+            Self::negative_ttl(&authorities)
+        } else {
+            None
+        };
+        if dnssec_ok && message_type == DnsMessageType::Query {
+            stats.increment("dns.dnssec_ok_queries");
         }
 
-        // 忽略权威和附加部分
+        if self.ttl_histograms {
+            for answer in &answers {
+                if let Some(key) = Self::ttl_histogram_key(answer.record_type) {
+                    stats.record(key, answer.ttl as u64);
+                }
+            }
+        }
 
         // 统计
-        stats.increment("dns.udp.parsed");
+        stats.increment(&self.counters.parsed);
+        stats.add(&self.counters.bytes, data.len() as u64);
         if message_type == DnsMessageType::Query {
-            stats.increment("dns.udp.query");
+            stats.increment(&self.counters.query);
         } else {
-            stats.increment("dns.udp.response");
+            stats.increment(&self.counters.response);
         }
 
         // 返回解析结果
-        Some(DnsMessage {
+        Ok(DnsMessage {
             transaction_id,
             message_type,
             questions,
             answers,
+            authorities,
+            additionals,
             timestamp: 0, // 时间戳需要在调用处设置
-            protocol: DnsProtocol::Udp,
+            protocol: self.protocol,
+            raw: if self.keep_raw { Some(data.to_vec()) } else { None },
+            unsolicited: false,
+            unreachable: false,
+            // 地址和端口需要在调用处根据传输层设置
+            src_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            dst_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            src_port: 0,
+            dst_port: 0,
+            opcode: ((flags >> 11) & 0x0F) as u8,
+            rcode,
+            authoritative: flags & 0x0400 != 0,
+            truncated: flags & 0x0200 != 0,
+            recursion_desired: flags & 0x0100 != 0,
+            recursion_available: flags & 0x0080 != 0,
+            dnssec_ok,
+            edns,
+            negative_ttl,
+            interface: None, // 接口需要在调用处根据捕获源设置
+            tunneling_suspected: false, // 隧道检测在调用处按配置进行
+            role: DnsRole::Unknown, // 角色在调用处按配置的解析器地址判断
+            client_hostname: None,
         })
     }
 
     fn protocol_type(&self) -> DnsProtocol {
-        DnsProtocol::Udp
+        self.protocol
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造只含一个问题的DNS查询
+    fn build_query(labels: &[&[u8]]) -> Vec<u8> {
+        let mut packet = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        for label in labels {
+            packet.push(label.len() as u8);
+            packet.extend_from_slice(label);
+        }
+        packet.push(0);
+        packet.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        packet
+    }
+
+    #[test]
+    fn test_parse_questions_only() {
+        // 响应：两个问题，一个A记录应答
+        let mut packet = vec![0xAB, 0xCD, 0x81, 0x80, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+        packet.extend_from_slice(&[7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0]);
+        packet.extend_from_slice(&[0x00, 0x01, 0x00, 0x01]);
+        packet.extend_from_slice(&[3, b'f', b'o', b'o', 0xC0, 0x14]);
+        packet.extend_from_slice(&[0x00, 0x1C, 0x00, 0x01]);
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3C, 0x00, 0x04, 1, 2, 3, 4]);
+
+        let stats = StatsCounter::new();
+        let mut full_parser = UdpDnsParser::new(65535);
+        let full = full_parser.parse(&packet, &stats).unwrap();
+        assert_eq!(full.questions.len(), 2);
+        assert_eq!(full.answers.len(), 1);
+
+        let mut fast_parser = UdpDnsParser::new(65535).with_parse_questions_only(true);
+        let fast = fast_parser.parse(&packet, &stats).unwrap();
+        assert_eq!(fast.transaction_id, 0xABCD);
+        assert_eq!(fast.message_type, DnsMessageType::Response);
+        assert_eq!(fast.questions.len(), 1);
+        assert_eq!(fast.questions[0].name, "example.com");
+        assert_eq!(fast.questions[0].record_type, DnsRecordType::A);
+        assert!(fast.answers.is_empty());
+        assert_eq!(stats.get("dns.udp.parsed"), 2);
+        assert_eq!(stats.get("dns.udp.response"), 2);
+    }
+
+    #[test]
+    fn test_udp_byte_counters() {
+        let short = build_query(&[b"a", b"com"]);
+        let long = build_query(&[b"example", b"com"]);
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+
+        parser.parse(&short, &stats).unwrap();
+        parser.parse(&long, &stats).unwrap();
+
+        let total = (short.len() + long.len()) as u64;
+        assert_eq!(stats.get("dns.udp.bytes"), total);
+        assert_eq!(stats.average("dns.udp.bytes", "dns.udp.parsed"), Some(total as f64 / 2.0));
+    }
+
+    #[test]
+    fn test_ttl_histograms() {
+        // 响应：1个问题，A记录TTL 60/300，AAAA记录TTL 3600，CNAME记录TTL 30
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 4;
+        let answers: [(u16, u32, &[u8]); 4] = [
+            (1, 60, &[192, 0, 2, 1]),
+            (1, 300, &[192, 0, 2, 2]),
+            (28, 3600, &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]),
+            (5, 30, &[0xC0, 0x0C]),
+        ];
+        for (record_type, ttl, rdata) in answers {
+            packet.extend_from_slice(&[0xC0, 0x0C]);
+            packet.extend_from_slice(&record_type.to_be_bytes());
+            packet.extend_from_slice(&[0x00, 0x01]);
+            packet.extend_from_slice(&ttl.to_be_bytes());
+            packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            packet.extend_from_slice(rdata);
+        }
+
+        let stats = StatsCounter::new();
+        UdpDnsParser::new(65535).parse(&packet, &stats).unwrap();
+        assert!(stats.histogram("ttl.a").is_none());
+
+        let mut parser = UdpDnsParser::new(65535).with_ttl_histograms(true);
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.answers.len(), 4);
+
+        let a = stats.histogram("ttl.a").unwrap();
+        assert_eq!(a.count(), 2);
+        assert_eq!(a.min(), Some(60));
+        assert_eq!(a.max(), Some(300));
+        let aaaa = stats.histogram("ttl.aaaa").unwrap();
+        assert_eq!((aaaa.count(), aaaa.min(), aaaa.max()), (1, Some(3600), Some(3600)));
+        let cname = stats.histogram("ttl.cname").unwrap();
+        assert_eq!((cname.count(), cname.percentile(50.0)), (1, Some(30)));
+    }
+
+    #[test]
+    fn test_bad_rdlength_for_fixed_length_types() {
+        // 响应：A记录声称8字节，随后一条正常A记录
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 2;
+        let answers: [&[u8]; 2] = [&[192, 0, 2, 1, 192, 0, 2, 2], &[192, 0, 2, 3]];
+        for rdata in answers {
+            packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x3C]);
+            packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            packet.extend_from_slice(rdata);
+        }
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(stats.get("dns.udp.bad_rdlength"), 1);
+        assert_eq!(message.answers.len(), 1);
+        assert_eq!(message.answers[0].data_str, "192.0.2.3");
+        assert!(message.answers.iter().all(|a| a.data_str != "Invalid A record"));
+    }
+
+    #[test]
+    fn test_header_flags_decoded() {
+        // 响应：QR=1, opcode=0, AA=1, RD=1, RA=1, RCODE=3 (NXDOMAIN)
+        let mut packet = build_query(&[b"missing", b"example"]);
+        packet[2] = 0x85;
+        packet[3] = 0x83;
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.message_type, DnsMessageType::Response);
+        assert_eq!(message.opcode, 0);
+        assert_eq!(message.rcode, 3);
+        assert_eq!(crate::protocols::dns::rcode_name(message.rcode), "NXDOMAIN");
+        assert!(message.authoritative);
+        assert!(!message.truncated);
+        assert!(message.recursion_desired);
+        assert!(message.recursion_available);
+
+        // 查询：opcode=5 (UPDATE), TC=1
+        packet[2] = 0x2A;
+        packet[3] = 0x00;
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.message_type, DnsMessageType::Query);
+        assert_eq!(message.opcode, 5);
+        assert!(message.truncated);
+        assert!(!message.recursion_desired);
+        assert_eq!(crate::protocols::dns::rcode_name(12), "RCODE12");
+    }
+
+    #[test]
+    fn test_dnssec_ok_query() {
+        // 查询带OPT记录：根域名、类型41、UDP负载4096、TTL中DO位置1
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[11] = 1;
+        packet.extend_from_slice(&[0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00]);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert!(message.dnssec_ok);
+        assert_eq!(stats.get("dns.dnssec_ok_queries"), 1);
+
+        // DO位清零
+        let len = packet.len();
+        packet[len - 4] = 0x00;
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert!(!message.dnssec_ok);
+
+        // 无OPT记录
+        let message = parser.parse(&build_query(&[b"example", b"com"]), &stats).unwrap();
+        assert!(!message.dnssec_ok);
+        assert_eq!(stats.get("dns.dnssec_ok_queries"), 1);
+    }
+
+    #[test]
+    fn test_edns_opt_record() {
+        // 响应带OPT记录：UDP负载1232、扩展响应码1、版本0、DO位置1
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[11] = 1;
+        packet.extend_from_slice(&[0x00, 0x00, 0x29, 0x04, 0xD0, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00]);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(message.additionals[0].record_type, DnsRecordType::OPT);
+        assert_eq!(
+            message.edns,
+            Some(EdnsInfo {
+                udp_payload_size: 1232,
+                extended_rcode: 1,
+                version: 0,
+                dnssec_ok: true,
+            })
+        );
+
+        let message = parser.parse(&build_query(&[b"example", b"com"]), &stats).unwrap();
+        assert!(message.edns.is_none());
+    }
+
+    #[test]
+    fn test_extended_rcode_badvers() {
+        // 头部响应码为0，OPT扩展响应码为1，有效响应码为16（BADVERS）
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[11] = 1;
+        packet.extend_from_slice(&[0x00, 0x00, 0x29, 0x04, 0xD0, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.rcode, 16);
+        assert_eq!(crate::protocols::dns::rcode_name(message.rcode), "BADVERS");
+
+        // 扩展位与头部低4位拼接：扩展3、头部3得到51
+        packet[3] = 0x83;
+        let ext_rcode = packet.len() - 6;
+        packet[ext_rcode] = 3;
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.rcode, 0x33);
+        assert_eq!(crate::protocols::dns::rcode_name(message.rcode), "RCODE51");
+    }
+
+    #[test]
+    fn test_authority_and_additional_sections() {
+        // 委派响应：权威部分NS记录，附加部分胶水A记录，均使用压缩指针
+        let mut packet = build_query(&[b"www", b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x00;
+        packet[9] = 1;
+        packet[11] = 1;
+        // example.com. NS ns1.example.com.（example.com位于偏移16）
+        packet.extend_from_slice(&[0xC0, 0x10, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x06]);
+        let ns_offset = packet.len();
+        packet.extend_from_slice(&[3, b'n', b's', b'1', 0xC0, 0x10]);
+        // ns1.example.com. A 192.0.2.53
+        packet.extend_from_slice(&[0xC0, ns_offset as u8, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04]);
+        packet.extend_from_slice(&[192, 0, 2, 53]);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert!(message.answers.is_empty());
+        assert_eq!(message.authorities.len(), 1);
+        assert_eq!(message.authorities[0].name, "example.com");
+        assert_eq!(message.authorities[0].record_type, DnsRecordType::NS);
+        assert_eq!(message.authorities[0].data_str, "ns1.example.com");
+        assert_eq!(message.additionals.len(), 1);
+        assert_eq!(message.additionals[0].name, "ns1.example.com");
+        assert_eq!(message.additionals[0].data_str, "192.0.2.53");
+
+        // 快速模式不解析
+        let mut fast = UdpDnsParser::new(65535).with_parse_questions_only(true);
+        let message = fast.parse(&packet, &stats).unwrap();
+        assert!(message.authorities.is_empty() && message.additionals.is_empty());
+    }
+
+    #[test]
+    fn test_mx_record_decoded() {
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 4;
+        let qtype_low = packet.len() - 3;
+        packet[qtype_low] = 0x0F;
+        // example.com. MX 10 mail.example.com.（example.com位于偏移12）
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x0F, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x09]);
+        packet.extend_from_slice(&[0x00, 0x0A, 4, b'm', b'a', b'i', b'l', 0xC0, 0x0C]);
+        // rdata过短时回退为字节数
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x0F, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x02]);
+        packet.extend_from_slice(&[0x00, 0x0A]);
+        // 域名超出RDATA末尾：CNAME的标签后紧跟下一条记录开头的压缩指针，MX的越界部分落在报文尾部
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x02]);
+        packet.extend_from_slice(&[1, b'a']);
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x0F, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04]);
+        packet.extend_from_slice(&[0x00, 0x0A, 4, b'm', b'a', b'i', b'l', 0xC0, 0x0C]);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(message.answers.len(), 4);
+        assert_eq!(message.answers[0].record_type, DnsRecordType::MX);
+        assert_eq!(message.answers[0].data_str, "10 mail.example.com");
+        assert_eq!(message.answers[1].data_str, "<2 bytes of data>");
+        assert_eq!(message.answers[2].data_str, "Invalid domain name");
+        assert_eq!(message.answers[3].data_str, "Invalid MX record");
+    }
+
+    #[test]
+    fn test_custom_rdata_decoder() {
+        // 私有类型65400：RDATA为2字节计数加一个压缩域名
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 1;
+        packet.extend_from_slice(&[0xC0, 0x0C, 0xFF, 0x78, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04]);
+        packet.extend_from_slice(&[0x00, 0x07, 0xC0, 0x0C]);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.answers[0].data_str, "<4 bytes of data>");
+
+        let decoder = |_rtype: u16, data: &[u8], packet: &[u8], offset: usize| {
+            let count = u16::from_be_bytes([*data.first()?, *data.get(1)?]);
+            let (name, _) = parse_domain_name(packet, offset + 2, LabelEncoding::Lossy, DEFAULT_MAX_LABELS, DEFAULT_MAX_NAME_LENGTH).ok()?;
+            Some(format!("{} x {}", name, count))
+        };
+        let mut parser = UdpDnsParser::new(65535).with_rdata_decoder(65400, Arc::new(decoder));
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.answers[0].record_type, DnsRecordType::Other(65400));
+        assert_eq!(message.answers[0].data_str, "example.com x 7");
+    }
+
+    #[test]
+    fn test_custom_decoder_kept_when_builtin_reconfigured() {
+        // example.com. A 192.0.2.1
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 1;
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04]);
+        packet.extend_from_slice(&[192, 0, 2, 1]);
+
+        let decoder = |_rtype: u16, data: &[u8], _packet: &[u8], _offset: usize| Some(format!("{:02x?}", data));
+        let mut parser = UdpDnsParser::new(65535)
+            .with_rdata_decoder(u16::from(DnsRecordType::A), Arc::new(decoder))
+            .with_label_encoding(LabelEncoding::Escaped)
+            .with_max_labels(16)
+            .with_max_name_length(128);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.answers[0].data_str, "[c0, 00, 02, 01]");
+    }
+
+    #[test]
+    fn test_srv_record_decoded() {
+        let mut packet = build_query(&[b"_sip", b"_udp", b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 2;
+        // _sip._udp.example.com. SRV 10 60 5060 sip.example.com.（example.com位于偏移22）
+        let rdata = [0x00, 0x0A, 0x00, 0x3C, 0x13, 0xC4, 3, b's', b'i', b'p', 0xC0, 0x16];
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x0C]);
+        packet.extend_from_slice(&rdata);
+        // 目标名超出RDATA末尾，越界部分落在报文尾部
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x21, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x08]);
+        packet.extend_from_slice(&rdata);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(message.answers[0].record_type, DnsRecordType::SRV);
+        assert_eq!(message.answers[0].data_str, "10 60 5060 sip.example.com");
+        assert_eq!(message.answers[1].data_str, "Invalid SRV record");
+    }
+
+    #[test]
+    fn test_txt_record_decoded() {
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 2;
+        // 两个字符串拼接
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x10, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x0F]);
+        packet.extend_from_slice(b"\x08v=spf1 a\x05 -all");
+        // 声明长度超出rdata
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x10, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x03]);
+        packet.extend_from_slice(b"\x05ab");
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(message.answers.len(), 2);
+        assert_eq!(message.answers[0].data_str, "v=spf1 a -all");
+        assert_eq!(message.answers[1].data_str, "Invalid TXT record");
+    }
+
+    #[test]
+    fn test_soa_record_decoded() {
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 3;
+        // example.com. SOA ns.example.com. hostmaster.example.com.（example.com位于偏移12）
+        let mut rdata = vec![2, b'n', b's', 0xC0, 0x0C];
+        rdata.extend_from_slice(b"\x0ahostmaster\xC0\x0C");
+        for value in [2024010101u32, 7200, 3600, 1209600, 3600] {
+            rdata.extend_from_slice(&value.to_be_bytes());
+        }
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x06, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, rdata.len() as u8]);
+        packet.extend_from_slice(&rdata);
+        // 截断的SOA：缺少数值字段
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x06, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x09]);
+        packet.extend_from_slice(&rdata[..9]);
+        // mname超出RDATA末尾，越界部分落在报文尾部
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x06, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x04]);
+        packet.extend_from_slice(&rdata);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(message.answers.len(), 3);
+        assert_eq!(message.answers[0].record_type, DnsRecordType::SOA);
+        assert_eq!(
+            message.answers[0].data_str,
+            "ns.example.com. hostmaster.example.com. 2024010101 7200 3600 1209600 3600"
+        );
+        assert_eq!(message.answers[1].data_str, "Invalid SOA record");
+        assert_eq!(message.answers[2].data_str, "Invalid SOA record");
+        // 有应答的响应不计算否定缓存时间
+        assert_eq!(message.negative_ttl, None);
+    }
+
+    #[test]
+    fn test_nxdomain_negative_ttl_from_authority_soa() {
+        let mut packet = build_query(&[b"missing", b"example", b"com"]);
+        // 响应，RCODE=NXDOMAIN，权威部分一条SOA
+        packet[2] = 0x81;
+        packet[3] = 0x83;
+        packet[9] = 1;
+        // example.com位于偏移20
+        let mut rdata = vec![2, b'n', b's', 0xC0, 0x14];
+        rdata.extend_from_slice(b"\x0ahostmaster\xC0\x14");
+        for value in [2024010101u32, 7200, 3600, 1209600, 300] {
+            rdata.extend_from_slice(&value.to_be_bytes());
+        }
+        // SOA的TTL为900，MINIMUM为300
+        packet.extend_from_slice(&[0xC0, 0x14, 0x00, 0x06, 0x00, 0x01, 0x00, 0x00, 0x03, 0x84, 0x00, rdata.len() as u8]);
+        packet.extend_from_slice(&rdata);
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(message.rcode, 3);
+        assert!(message.answers.is_empty());
+        assert_eq!(message.authorities.len(), 1);
+        assert_eq!(
+            message.authorities[0].data_str,
+            "ns.example.com. hostmaster.example.com. 2024010101 7200 3600 1209600 300"
+        );
+        assert_eq!(message.negative_ttl, Some(300));
+    }
+
+    #[test]
+    fn test_try_parse_reports_error_detail() {
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+
+        // 问题部分缺少类型和类
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet.truncate(packet.len() - 2);
+        let err = parser.try_parse(&packet, &stats).unwrap_err();
+        assert!(err.to_string().contains("truncated question at offset 25"));
+        assert!(parser.parse(&packet, &stats).is_none());
+        assert_eq!(stats.get("dns.udp.parse_question_failed"), 2);
+
+        // 压缩指针指向自身
+        let mut packet = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x01, 0x00, 0x01]);
+        let err = parser.try_parse(&packet, &stats).unwrap_err();
+        assert!(err.to_string().contains("compression pointer to 12 is not backward at offset 12"));
+    }
+
+    #[test]
+    fn test_error_counters_follow_protocol() {
+        let mut parser = UdpDnsParser::new(65535).with_protocol(DnsProtocol::Mdns);
+        let stats = StatsCounter::new();
+
+        assert!(parser.try_parse(&[0x00; 4], &stats).is_err());
+        let mut packet = build_query(&[b"printer", b"local"]);
+        packet.truncate(packet.len() - 2);
+        assert!(parser.try_parse(&packet, &stats).is_err());
+
+        assert_eq!(stats.get("dns.mdns.invalid_size"), 1);
+        assert_eq!(stats.get("dns.mdns.parse_question_failed"), 1);
+        assert_eq!(stats.get("dns.udp.invalid_size"), 0);
+        assert_eq!(stats.get("dns.udp.parse_question_failed"), 0);
+    }
+
+    #[test]
+    fn test_too_many_labels_rejected() {
+        let stats = StatsCounter::new();
+
+        // 200个单字符标签，超过默认的127
+        let deep: Vec<&[u8]> = std::iter::repeat(&b"a"[..]).take(200).collect();
+        let packet = build_query(&deep);
+        let mut parser = UdpDnsParser::new(65535);
+        let err = parser.try_parse(&packet, &stats).unwrap_err();
+        assert!(err.to_string().contains("too many labels in name at offset 12"));
+        assert_eq!(stats.get("dns.udp.too_many_labels"), 1);
+
+        // 上限可配置，恰好等于上限的域名仍可解析
+        let mut parser = UdpDnsParser::new(65535).with_max_labels(3);
+        let message = parser.parse(&build_query(&[b"www", b"example", b"com"]), &stats).unwrap();
+        assert_eq!(message.questions[0].name, "www.example.com");
+        assert!(parser.parse(&build_query(&[b"a", b"www", b"example", b"com"]), &stats).is_none());
+        assert_eq!(stats.get("dns.udp.too_many_labels"), 2);
+
+        // RDATA中的域名同样按配置的上限检查：example.com. CNAME a.b.example.com.
+        let mut packet = build_query(&[b"example", b"com"]);
+        packet[2] = 0x81;
+        packet[3] = 0x80;
+        packet[7] = 1;
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x05, 0x00, 0x01, 0x00, 0x00, 0x0E, 0x10, 0x00, 0x06]);
+        packet.extend_from_slice(&[1, b'a', 1, b'b', 0xC0, 0x0C]);
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.answers[0].data_str, "Invalid domain name");
+        let message = UdpDnsParser::new(65535).parse(&packet, &stats).unwrap();
+        assert_eq!(message.answers[0].data_str, "a.b.example.com");
+    }
+
+    #[test]
+    fn test_name_length_limits() {
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+
+        // 4个63字节标签加一个61字节标签恰好255字节
+        let long = [b'a'; 63];
+        let last = [b'b'; 61];
+        let packet = build_query(&[&long, &long, &long, &last]);
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.questions[0].name.len(), 253);
+
+        // 再多一个字节即超出
+        let last = [b'b'; 62];
+        let err = parser.try_parse(&build_query(&[&long, &long, &long, &last]), &stats).unwrap_err();
+        assert!(err.to_string().contains("name too long at offset 12"));
+        assert_eq!(stats.get("dns.udp.name_too_long"), 1);
+
+        // 长度字节0x40（64）不是合法标签
+        let err = parser.try_parse(&build_query(&[&[b'x'; 64]]), &stats).unwrap_err();
+        assert!(err.to_string().contains("label longer than 63 bytes at offset 12"));
+        assert_eq!(stats.get("dns.udp.name_too_long"), 2);
+
+        // 上限可配置
+        let mut parser = UdpDnsParser::new(65535).with_max_name_length(17);
+        assert!(parser.parse(&build_query(&[b"www", b"example", b"com"]), &stats).is_some());
+        assert!(parser.parse(&build_query(&[b"wwww", b"example", b"com"]), &stats).is_none());
+        assert_eq!(stats.get("dns.udp.name_too_long"), 3);
+    }
+
+    #[test]
+    fn test_malicious_compression_pointers() {
+        let header = [0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let name = |bytes: &[u8]| {
+            let mut packet = header.to_vec();
+            packet.extend_from_slice(bytes);
+            packet
+        };
+
+        // 指向自身、指向之后的数据、指向报文之外
+        for packet in [
+            name(&[0xC0, 0x0C]),
+            name(&[0x01, b'a', 0xC0, 0x0E]),
+            name(&[0x01, b'a', 0xC0, 0x10, 0x00]),
+            name(&[0xFF, 0xFF]),
+        ] {
+            assert!(parse_domain_name(&packet, 12, LabelEncoding::Lossy, DEFAULT_MAX_LABELS, DEFAULT_MAX_NAME_LENGTH).is_err());
+        }
+
+        // 合法的后向指针仍可解析
+        let packet = name(&[0x01, b'a', 0x00, 0x01, b'b', 0xC0, 0x0C]);
+        let (parsed, next) = parse_domain_name(&packet, 15, LabelEncoding::Lossy, DEFAULT_MAX_LABELS, DEFAULT_MAX_NAME_LENGTH).unwrap();
+        assert_eq!(parsed, "b.a");
+        assert_eq!(next, packet.len());
+
+        // 两个指针成环：偏移18跳到12，读完a、b后偏移16的指针又跳回片段内的14
+        let packet = name(&[0x01, b'a', 0x01, b'b', 0xC0, 0x0E, 0xC0, 0x0C]);
+        match parse_domain_name(&packet, 18, LabelEncoding::Lossy, DEFAULT_MAX_LABELS, DEFAULT_MAX_NAME_LENGTH) {
+            Err(NameError::Malformed(detail)) => assert_eq!(detail, "compression pointer to 14 is not backward at offset 16"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // 逐级后退的指针链超过跳转上限
+        let mut packet = name(&[0x00]);
+        for _ in 0..20 {
+            let target = packet.len() - 2;
+            packet.extend_from_slice(&[0xC0 | (target >> 8) as u8, target as u8]);
+        }
+        let start = packet.len() - 2;
+        assert!(parse_domain_name(&packet, start, LabelEncoding::Lossy, DEFAULT_MAX_LABELS, DEFAULT_MAX_NAME_LENGTH).is_err());
+
+        // 随机报文：不panic，返回的偏移不越界，域名长度有上限
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let mut seed: u32 = 0x1234_5678;
+        for _ in 0..2_000 {
+            let mut packet = header.to_vec();
+            for _ in 0..64 {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                // 提高指针和短标签出现的概率
+                let byte = (seed >> 16) as u8;
+                packet.push(match byte % 4 {
+                    0 => 0xC0,
+                    1 => byte % 16,
+                    _ => byte,
+                });
+            }
+            for offset in 12..packet.len() {
+                if let Ok((parsed, next)) = parse_domain_name(&packet, offset, LabelEncoding::Escaped, DEFAULT_MAX_LABELS, DEFAULT_MAX_NAME_LENGTH) {
+                    assert!(next <= packet.len());
+                    assert!(parsed.len() <= 4 * packet.len() * (10 + 1));
+                }
+            }
+            let _ = parser.parse(&packet, &stats);
+        }
+    }
+
+    #[test]
+    fn test_qclass_any_and_none() {
+        let mut packet = build_query(&[b"example", b"com"]);
+        let len = packet.len();
+        packet[len - 1] = 0xFF; // QCLASS=ANY
+        packet[len - 3] = 0xFC; // QTYPE=AXFR
+
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.questions[0].class, DnsClass::ANY);
+        assert_eq!(message.questions[0].class.to_string(), "ANY");
+        assert_eq!(message.questions[0].record_type, DnsRecordType::Other(252));
+
+        packet[len - 1] = 0xFE; // QCLASS=NONE
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.questions[0].class, DnsClass::NONE);
+        assert_eq!(u16::from(message.questions[0].class), 254);
+        assert_eq!(DnsClass::from(42).to_string(), "CLASS42");
+    }
+
+    #[test]
+    fn test_mdns_query_with_known_answer() {
+        // mDNS查询：两个问题，第一个置单播响应位，应答区为已知应答抑制记录
+        let mut packet = vec![0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+        packet.extend_from_slice(b"\x04_ipp\x04_tcp\x05local\x00\x00\x0C\x80\x01");
+        packet.extend_from_slice(b"\x05_http\xC0\x11\x00\x0C\x00\x01");
+        // _ipp._tcp.local. PTR Office._ipp._tcp.local.
+        packet.extend_from_slice(&[0xC0, 0x0C, 0x00, 0x0C, 0x00, 0x01, 0x00, 0x00, 0x11, 0x94, 0x00, 0x09]);
+        packet.extend_from_slice(b"\x06Office\xC0\x0C");
+
+        let mut parser = UdpDnsParser::new(65535).with_protocol(DnsProtocol::Mdns);
+        let stats = StatsCounter::new();
+        let message = parser.parse(&packet, &stats).unwrap();
+
+        assert_eq!(message.message_type, DnsMessageType::Query);
+        assert!(matches!(message.protocol, DnsProtocol::Mdns));
+        assert_eq!(message.questions.len(), 2);
+        assert_eq!(message.questions[0].name, "_ipp._tcp.local");
+        // class最高位是单播响应位，拆为单独的标志
+        assert_eq!(message.questions[0].class, DnsClass::IN);
+        assert!(message.questions[0].unicast_response);
+        assert_eq!(message.questions[1].name, "_http._tcp.local");
+        assert_eq!(message.questions[1].class, DnsClass::IN);
+        assert!(!message.questions[1].unicast_response);
+        assert_eq!(message.answers.len(), 1);
+        assert_eq!(message.answers[0].ttl, 4500);
+        assert_eq!(message.answers[0].data_str, "Office._ipp._tcp.local");
+        // 只按mDNS计数
+        assert_eq!(stats.get("dns.mdns.parsed"), 1);
+        assert_eq!(stats.get("dns.mdns.query"), 1);
+        assert_eq!(stats.get("dns.udp.parsed"), 0);
+
+        // 普通DNS不拆分class
+        let message = UdpDnsParser::new(65535).parse(&packet, &stats).unwrap();
+        assert_eq!(u16::from(message.questions[0].class), 0x8001);
+        assert!(!message.questions[0].unicast_response);
+    }
+
+    #[test]
+    fn test_escaped_label_encoding() {
+        let packet = build_query(&[b"a\x00b\xff", b"example"]);
+        let mut parser = UdpDnsParser::new(65535).with_label_encoding(LabelEncoding::Escaped);
+        let stats = StatsCounter::new();
+
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.questions[0].name, "a\\000b\\255.example");
+    }
+
+    #[test]
+    fn test_escaped_label_special_chars() {
+        let packet = build_query(&[b"a.b\\c", b"com"]);
+        let mut parser = UdpDnsParser::new(65535).with_label_encoding(LabelEncoding::Escaped);
+        let stats = StatsCounter::new();
+
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.questions[0].name, "a\\.b\\\\c.com");
+    }
+
+    #[test]
+    fn test_lossy_label_encoding() {
+        let packet = build_query(&[b"a\xffb", b"example"]);
+        let mut parser = UdpDnsParser::new(65535);
+        let stats = StatsCounter::new();
+
+        let message = parser.parse(&packet, &stats).unwrap();
+        assert_eq!(message.questions[0].name, "a\u{FFFD}b.example");
+    }
+}
@@ -1,2 +1,4 @@
-pub(crate) mod detect;
-pub(crate) mod dns;
\ No newline at end of file
+pub mod decode;
+pub mod detect;
+pub mod dns;
+pub mod tls;
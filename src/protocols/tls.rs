@@ -0,0 +1,171 @@
+//! TLS明文部分的识别
+//! 不解密，只识别记录层头部并从ClientHello中取出SNI，用于确认DoT流量和记录访问的服务器
+
+/// 记录层头部长度：类型(1) + 版本(2) + 长度(2)
+const RECORD_HEADER_LEN: usize = 5;
+/// 记录最大长度：明文上限2^14加密文扩展2048
+const MAX_RECORD_LEN: usize = (1 << 14) + 2048;
+/// 握手记录类型
+const CONTENT_TYPE_HANDSHAKE: u8 = 0x16;
+/// ClientHello握手消息类型
+const HANDSHAKE_CLIENT_HELLO: u8 = 0x01;
+/// server_name扩展类型
+const EXTENSION_SERVER_NAME: u16 = 0x0000;
+/// SNI中的主机名类型
+const NAME_TYPE_HOST_NAME: u8 = 0x00;
+
+/// 数据是否以TLS记录头开始
+///
+/// 记录类型为change_cipher_spec、alert、handshake或application_data，
+/// 版本为TLS 1.0-1.2（TLS 1.3的记录层版本固定为0x0303），长度不超过记录上限。
+pub fn is_tls_record(data: &[u8]) -> bool {
+    if data.len() < RECORD_HEADER_LEN {
+        return false;
+    }
+    let version = u16::from_be_bytes([data[1], data[2]]);
+    let length = u16::from_be_bytes([data[3], data[4]]) as usize;
+    matches!(data[0], 0x14..=0x17) && (0x0301..=0x0303).contains(&version) && length > 0 && length <= MAX_RECORD_LEN
+}
+
+/// 数据是否以携带ClientHello的握手记录开始
+pub fn is_client_hello(data: &[u8]) -> bool {
+    is_tls_record(data) && data[0] == CONTENT_TYPE_HANDSHAKE && data.get(RECORD_HEADER_LEN) == Some(&HANDSHAKE_CLIENT_HELLO)
+}
+
+/// 按长度前缀依次读取字段，越界时返回None
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    /// 读取1字节长度前缀的字段
+    fn vec8(&mut self) -> Option<&'a [u8]> {
+        let len = self.u8()? as usize;
+        self.take(len)
+    }
+
+    /// 读取2字节长度前缀的字段
+    fn vec16(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()? as usize;
+        self.take(len)
+    }
+}
+
+/// 从ClientHello的server_name扩展取出主机名，统一为小写
+///
+/// 只解析数据中第一个记录；ClientHello跨多个TCP段时只有首段可见，扩展被截断时返回None。
+pub fn client_hello_server_name(data: &[u8]) -> Option<String> {
+    if !is_client_hello(data) {
+        return None;
+    }
+
+    let mut reader = Reader {
+        data,
+        pos: RECORD_HEADER_LEN + 4,
+    };
+    // client_version + random
+    reader.take(2 + 32)?;
+    // session_id、cipher_suites、compression_methods
+    reader.vec8()?;
+    reader.vec16()?;
+    reader.vec8()?;
+
+    let mut extensions = Reader {
+        data: reader.vec16()?,
+        pos: 0,
+    };
+    while let Some(extension_type) = extensions.u16() {
+        let body = extensions.vec16()?;
+        if extension_type != EXTENSION_SERVER_NAME {
+            continue;
+        }
+
+        let mut names = Reader {
+            data: Reader { data: body, pos: 0 }.vec16()?,
+            pos: 0,
+        };
+        while let Some(name_type) = names.u8() {
+            let name = names.vec16()?;
+            if name_type == NAME_TYPE_HOST_NAME {
+                return std::str::from_utf8(name).ok().map(str::to_ascii_lowercase);
+            }
+        }
+        return None;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 构造带SNI扩展的ClientHello记录
+    fn client_hello(server_name: &str) -> Vec<u8> {
+        let name = server_name.as_bytes();
+        let mut sni = Vec::new();
+        sni.extend_from_slice(&((name.len() + 3) as u16).to_be_bytes());
+        sni.push(NAME_TYPE_HOST_NAME);
+        sni.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        sni.extend_from_slice(name);
+
+        let mut extensions = Vec::new();
+        // server_name之前放一个supported_versions扩展，验证跳过其他扩展
+        extensions.extend_from_slice(&[0x00, 0x2B, 0x00, 0x03, 0x02, 0x03, 0x04]);
+        extensions.extend_from_slice(&EXTENSION_SERVER_NAME.to_be_bytes());
+        extensions.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+        extensions.extend_from_slice(&sni);
+
+        let mut hello = vec![0x03, 0x03];
+        hello.extend_from_slice(&[0xAB; 32]);
+        hello.push(0);
+        hello.extend_from_slice(&[0x00, 0x02, 0x13, 0x01]);
+        hello.extend_from_slice(&[0x01, 0x00]);
+        hello.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        hello.extend_from_slice(&extensions);
+
+        let mut handshake = vec![HANDSHAKE_CLIENT_HELLO, 0];
+        handshake.extend_from_slice(&(hello.len() as u16).to_be_bytes());
+        handshake.extend_from_slice(&hello);
+
+        let mut record = vec![CONTENT_TYPE_HANDSHAKE, 0x03, 0x01];
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        record.extend_from_slice(&handshake);
+        record
+    }
+
+    #[test]
+    fn test_client_hello_server_name() {
+        let record = client_hello("Dns.Google");
+        assert!(is_tls_record(&record));
+        assert!(is_client_hello(&record));
+        assert_eq!(client_hello_server_name(&record), Some("dns.google".to_string()));
+
+        // 截断在扩展中间
+        assert_eq!(client_hello_server_name(&record[..record.len() - 4]), None);
+
+        // 加密后的应用数据是TLS记录但不是ClientHello
+        let application_data = [0x17, 0x03, 0x03, 0x00, 0x20, 0xAA, 0xBB];
+        assert!(is_tls_record(&application_data));
+        assert!(!is_client_hello(&application_data));
+
+        // 长度前缀的DNS报文和HTTP请求都不是TLS记录
+        assert!(!is_tls_record(&[0x00, 0x1D, 0x12, 0x34, 0x01, 0x00]));
+        assert!(!is_tls_record(b"GET / HTTP/1.1\r\n"));
+        assert!(!is_tls_record(&[0x16, 0x03, 0x01, 0x00, 0x00]));
+    }
+}
@@ -0,0 +1,14 @@
+//! 稳定哈希
+//! FNV-1a实现，结果与平台字节序和运行次数无关，可用于分片、分流和跨副本的指纹
+
+/// FNV-1a 64位初始值
+pub const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+/// FNV-1a 64位乘数
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+/// 在已有哈希值上继续累加字节，首次调用传入`FNV_OFFSET_BASIS`
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, &b| (hash ^ b as u64).wrapping_mul(FNV_PRIME))
+}
@@ -18,53 +18,28 @@ macro_rules! time_it {
 }
 
 /// 重试宏，用于自动重试可能失败的操作
+///
+/// 至少执行一次，全部失败时返回最后一次的错误；默认每次重试前等待100毫秒。
 #[macro_export]
 macro_rules! retry {
-    ($op:expr, $attempts:expr) => {{
-        let mut attempts_left = $attempts;
-        let mut last_err = None;
-
-        while attempts_left > 0 {
-            match $op {
-                Ok(result) => break Ok(result),
-                Err(err) => {
-                    attempts_left -= 1;
-                    last_err = Some(err);
-                    if attempts_left > 0 {
-                        std::thread::sleep(std::time::Duration::from_millis(100));
-                    }
-                }
-            }
-        }
-
-        if let Some(err) = last_err {
-            Err(err)
-        } else {
-            Err("Unknown error".into())
-        }
-    }};
+    ($op:expr, $attempts:expr) => {
+        $crate::retry!($op, $attempts, 100)
+    };
     ($op:expr, $attempts:expr, $delay_ms:expr) => {{
         let mut attempts_left = $attempts;
-        let mut last_err = None;
 
-        while attempts_left > 0 {
+        loop {
             match $op {
                 Ok(result) => break Ok(result),
                 Err(err) => {
-                    attempts_left -= 1;
-                    last_err = Some(err);
-                    if attempts_left > 0 {
-                        std::thread::sleep(std::time::Duration::from_millis($delay_ms));
+                    if attempts_left <= 1 {
+                        break Err(err);
                     }
+                    attempts_left -= 1;
+                    std::thread::sleep(std::time::Duration::from_millis($delay_ms));
                 }
             }
         }
-
-        if let Some(err) = last_err {
-            Err(err)
-        } else {
-            Err("Unknown error".into())
-        }
     }};
 }
 
@@ -112,3 +87,32 @@ macro_rules! parse_or_return {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_retry_until_success_or_attempts_exhausted() {
+        let mut calls = 0;
+        let result: Result<u32, String> = retry!(
+            {
+                calls += 1;
+                if calls < 3 { Err(format!("attempt {}", calls)) } else { Ok(calls) }
+            },
+            5,
+            0
+        );
+        assert_eq!(result, Ok(3));
+
+        let mut calls = 0;
+        let result: Result<(), String> = retry!(
+            {
+                calls += 1;
+                Err(format!("attempt {}", calls))
+            },
+            2,
+            0
+        );
+        assert_eq!(result, Err("attempt 2".to_string()));
+        assert_eq!(calls, 2);
+    }
+}
@@ -0,0 +1,7 @@
+//! 通用工具模块
+//! 时间处理、哈希、宏和SIMD加速代码
+
+pub mod hash;
+pub mod macros;
+pub mod simd;
+pub mod time;
@@ -6,7 +6,7 @@ use std::arch::x86_64::*;
 
 /// 使用SIMD加速的内存比较
 ///
-/// # 安全性
+/// # Safety
 ///
 /// 这个函数使用了不安全的SIMD指令，调用者必须确保：
 /// 1. CPU支持SSE2指令集
@@ -55,6 +55,10 @@ pub unsafe fn simd_memcmp(a: &[u8], b: &[u8]) -> bool {
 }
 
 /// 使用SIMD加速的字节查找
+///
+/// # Safety
+///
+/// 调用者必须确保CPU支持SSE2指令集
 pub unsafe fn simd_find_byte(data: &[u8], byte: u8) -> Option<usize> {
     #[cfg(target_arch = "x86_64")]
     {
@@ -72,21 +76,19 @@ pub unsafe fn simd_find_byte(data: &[u8], byte: u8) -> Option<usize> {
                 let mask = _mm_movemask_epi8(cmp);
 
                 if mask != 0 {
-                    // 找到匹配，确定具体位置
-                    let trailing_zeros = mask.trailing_zeros() as usize;
+                    // 找到匹配，确定具体位置；movemask只有低16位有效，位置必然落在本块内
+                    let trailing_zeros = (mask & 0xFFFF).trailing_zeros() as usize;
+                    debug_assert!(trailing_zeros < 16);
                     return Some(offset + trailing_zeros);
                 }
             }
 
-            // 检查剩余字节
+            // 检查不足16字节的尾部，长度为16的整数倍时尾部为空
             let remaining_start = chunks * 16;
-            for i in remaining_start..data.len() {
-                if data[i] == byte {
-                    return Some(i);
-                }
-            }
-
-            return None;
+            return data[remaining_start..]
+                .iter()
+                .position(|&b| b == byte)
+                .map(|i| remaining_start + i);
         }
     }
 
@@ -95,6 +97,10 @@ pub unsafe fn simd_find_byte(data: &[u8], byte: u8) -> Option<usize> {
 }
 
 /// 使用SIMD加速的内存复制
+///
+/// # Safety
+///
+/// 调用者必须确保CPU支持SSE2指令集
 pub unsafe fn simd_memcpy(dst: &mut [u8], src: &[u8]) -> usize {
     let len = std::cmp::min(dst.len(), src.len());
 
@@ -114,9 +120,7 @@ pub unsafe fn simd_memcpy(dst: &mut [u8], src: &[u8]) -> usize {
 
             // 复制剩余字节
             let remaining_start = chunks * 16;
-            for i in remaining_start..len {
-                dst[i] = src[i];
-            }
+            dst[remaining_start..len].copy_from_slice(&src[remaining_start..len]);
 
             return len;
         }
@@ -129,9 +133,15 @@ pub unsafe fn simd_memcpy(dst: &mut [u8], src: &[u8]) -> usize {
 
 /// 使用SIMD加速的字符串解析
 /// 快速查找分隔符并分割字符串
+///
+/// # Safety
+///
+/// 调用者必须确保CPU支持SSE2指令集
 pub unsafe fn simd_split_at_byte(data: &[u8], delimiter: u8) -> Vec<&[u8]> {
     let mut result = Vec::new();
     let mut start = 0;
+    // 标量扫描的起点，即SIMD实际处理到的位置
+    let mut scalar_from = 0;
 
     #[cfg(target_arch = "x86_64")]
     {
@@ -164,11 +174,13 @@ pub unsafe fn simd_split_at_byte(data: &[u8], delimiter: u8) -> Vec<&[u8]> {
 
                 pos += 16;
             }
+
+            scalar_from = pos;
         }
     }
 
     // 处理剩余部分或回退到标准方法
-    let mut i = start.max(data.len() / 16 * 16);
+    let mut i = scalar_from;
     while i < data.len() {
         if data[i] == delimiter {
             result.push(&data[start..i]);
@@ -184,3 +196,108 @@ pub unsafe fn simd_split_at_byte(data: &[u8], delimiter: u8) -> Vec<&[u8]> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 纯标量参考实现，语义与simd_split_at_byte一致
+    fn scalar_split(data: &[u8], delimiter: u8) -> Vec<&[u8]> {
+        let mut result = Vec::new();
+        let mut start = 0;
+        for (i, &b) in data.iter().enumerate() {
+            if b == delimiter {
+                result.push(&data[start..i]);
+                start = i + 1;
+            }
+        }
+        if start < data.len() {
+            result.push(&data[start..]);
+        }
+        result
+    }
+
+    /// 简单的xorshift伪随机数生成器，保证测试可复现
+    fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    #[test]
+    fn test_find_byte_at_chunk_boundaries_and_tail() {
+        for &len in &[15usize, 16, 17, 31, 32, 33, 47, 48, 50] {
+            for &pos in &[0usize, 15, 16, 17, 31, 32, 33, 47, 48, 49] {
+                if pos >= len {
+                    continue;
+                }
+                let mut data = vec![b'a'; len];
+                data[pos] = b'.';
+                assert_eq!(unsafe { simd_find_byte(&data, b'.') }, Some(pos), "len={} pos={}", len, pos);
+            }
+
+            // 匹配在最后一个字节
+            let mut data = vec![b'a'; len];
+            data[len - 1] = b'.';
+            assert_eq!(unsafe { simd_find_byte(&data, b'.') }, Some(len - 1), "len={} last byte", len);
+
+            // 没有匹配
+            assert_eq!(unsafe { simd_find_byte(&vec![b'a'; len], b'.') }, None, "len={}", len);
+        }
+    }
+
+    #[test]
+    fn test_find_byte_returns_first_match() {
+        let mut data = vec![b'a'; 40];
+        data[17] = b'.';
+        data[20] = b'.';
+        data[39] = b'.';
+        assert_eq!(unsafe { simd_find_byte(&data, b'.') }, Some(17));
+        // 0x80以上的字节按有符号比较时也能匹配
+        data[3] = 0xFF;
+        assert_eq!(unsafe { simd_find_byte(&data, 0xFF) }, Some(3));
+        assert_eq!(unsafe { simd_find_byte(&[], b'.') }, None);
+    }
+
+    #[test]
+    fn test_split_delimiters_around_chunk_boundary() {
+        for &len in &[15usize, 16, 17, 31, 32, 33, 48] {
+            for &pos in &[0usize, 14, 15, 16, 17, 31, 32] {
+                if pos >= len {
+                    continue;
+                }
+                let mut data = vec![b'a'; len];
+                data[pos] = b'.';
+                let expected = scalar_split(&data, b'.');
+                let actual = unsafe { simd_split_at_byte(&data, b'.') };
+                assert_eq!(actual, expected, "len={} pos={}", len, pos);
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_adjacent_delimiters_straddling_boundary() {
+        let mut data = vec![b'x'; 40];
+        data[15] = b'.';
+        data[16] = b'.';
+        data[31] = b'.';
+        data[32] = b'.';
+        data[39] = b'.';
+        let actual = unsafe { simd_split_at_byte(&data, b'.') };
+        assert_eq!(actual, scalar_split(&data, b'.'));
+    }
+
+    #[test]
+    fn test_split_matches_scalar_on_random_inputs() {
+        let mut state = 0x2545_F491_4F6C_DD1D;
+        for _ in 0..2000 {
+            let len = (xorshift(&mut state) % 100) as usize;
+            let data: Vec<u8> = (0..len)
+                .map(|_| if xorshift(&mut state).is_multiple_of(5) { b'.' } else { b'a' })
+                .collect();
+            let actual = unsafe { simd_split_at_byte(&data, b'.') };
+            assert_eq!(actual, scalar_split(&data, b'.'), "data={:?}", data);
+        }
+    }
+}
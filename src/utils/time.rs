@@ -27,6 +27,36 @@ pub fn current_time_secs() -> u64 {
         .as_secs()
 }
 
+/// 将自纪元起的秒数转换为UTC日期`YYYY-MM-DD`
+pub fn utc_date(secs: u64) -> String {
+    // 按公历400年周期由天数推算年月日（Howard Hinnant的civil_from_days算法）
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// 将自纪元起的微秒数转换为RFC 3339格式的UTC时间`YYYY-MM-DDTHH:MM:SS.ffffffZ`
+pub fn utc_timestamp(micros: u64) -> String {
+    let secs = micros / 1_000_000;
+    let secs_of_day = secs % 86_400;
+    format!(
+        "{}T{:02}:{:02}:{:02}.{:06}Z",
+        utc_date(secs),
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60,
+        micros % 1_000_000
+    )
+}
+
 /// 高精度计时器
 pub struct HighResTimer {
     /// 开始时间
@@ -35,6 +65,12 @@ pub struct HighResTimer {
     marks: Vec<(String, Duration)>,
 }
 
+impl Default for HighResTimer {
+    fn default() -> Self {
+        HighResTimer::new()
+    }
+}
+
 impl HighResTimer {
     /// 创建新的计时器
     pub fn new() -> Self {